@@ -0,0 +1,62 @@
+//! Time the JSON and HTML exporters on a workspace-sized report, streamed
+//! into a file the way `--output` writes it. Run with `cargo bench`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use craprs::crap::CrapEntry;
+use craprs::html_export;
+use craprs::json_export;
+use craprs::metadata::RunMetadata;
+
+const FUNCTIONS: usize = 50_000;
+const ROUNDS: u32 = 5;
+
+fn entries() -> Vec<CrapEntry> {
+    (0..FUNCTIONS)
+        .map(|i| CrapEntry {
+            name: format!("function_{i}"),
+            module_path: format!("crate::module_{}", i / 100),
+            complexity: (i % 20 + 1) as u32,
+            coverage: (i % 7 != 0).then_some((i % 100) as f64),
+            crap: (i % 7 != 0).then_some((i % 60) as f64 + 1.0),
+            uncovered_lines: (0..i % 5).map(|l| i + l).collect(),
+            file: PathBuf::from(format!("src/module_{}.rs", i / 100)),
+            start_line: i % 1000,
+            end_line: i % 1000 + 10,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Average wall time of `write` into a fresh buffered file, and its size.
+fn bench(label: &str, write: impl Fn(&mut BufWriter<File>) -> io::Result<()>) {
+    let path = std::env::temp_dir().join(format!("craprs_bench_{}", std::process::id()));
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut out = BufWriter::new(File::create(&path).unwrap());
+        write(&mut out).unwrap();
+        out.flush().unwrap();
+    }
+    let elapsed = start.elapsed() / ROUNDS;
+    let size = std::fs::metadata(&path).unwrap().len();
+    let _ = std::fs::remove_file(&path);
+    println!(
+        "{label}: {FUNCTIONS} functions in {:.1} ms ({:.1} MiB)",
+        elapsed.as_secs_f64() * 1000.0,
+        size as f64 / (1024.0 * 1024.0)
+    );
+}
+
+fn main() {
+    let entries = entries();
+    let metadata = RunMetadata::new();
+    bench("json", |out| {
+        json_export::write_json(out, &entries, &metadata)
+    });
+    bench("html", |out| {
+        html_export::write_html(out, &entries, &[], "CRAP", 30.0, &metadata)
+    });
+}
@@ -0,0 +1,5 @@
+fn main() {
+    // Recorded in run metadata so archived reports name the platform they came from.
+    let target = std::env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=CRAPRS_HOST={target}");
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+
+use crate::crap::{self, CrapEntry};
+
+/// Opening marker of the README region rewritten by `craprs badge --update-readme`.
+pub const START_MARKER: &str = "<!-- craprs-badge:start -->";
+/// Closing marker of the README region rewritten by `craprs badge --update-readme`.
+pub const END_MARKER: &str = "<!-- craprs-badge:end -->";
+
+/// Number of entries scoring at or above `threshold`. Unscored entries never count.
+pub fn crappy_count(entries: &[CrapEntry], threshold: f64) -> usize {
+    entries
+        .iter()
+        .filter(|e| e.crap.is_some_and(|s| s >= threshold))
+        .count()
+}
+
+/// shields.io color for a given crappy count: green when clean, yellow for a
+/// handful of offenders, red beyond that.
+pub fn badge_color(crappy: usize) -> &'static str {
+    match crappy {
+        0 => "brightgreen",
+        1..=5 => "yellow",
+        _ => "red",
+    }
+}
+
+/// What the badge message shows. The color always follows the crappy count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BadgeMetric {
+    /// Functions at or above the threshold, e.g. `3 crappy`.
+    #[default]
+    Crappy,
+    /// Sum of CRAP over scored functions, e.g. `load 131.2`.
+    Load,
+    /// Highest CRAP of any function, e.g. `worst 42.0`.
+    Worst,
+}
+
+fn badge_message(entries: &[CrapEntry], threshold: f64, metric: BadgeMetric) -> String {
+    match metric {
+        BadgeMetric::Crappy => format!("{} crappy", crappy_count(entries, threshold)),
+        BadgeMetric::Load => format!("load {:.1}", crap::total_score(entries)),
+        BadgeMetric::Worst => match entries.iter().filter_map(|e| e.crap).reduce(f64::max) {
+            Some(worst) => format!("worst {worst:.1}"),
+            None => "worst —".to_string(),
+        },
+    }
+}
+
+/// Markdown image pointing at a static shields.io badge, e.g.
+/// `![CRAP](https://img.shields.io/badge/CRAP-3%20crappy-yellow)`.
+pub fn readme_snippet(entries: &[CrapEntry], threshold: f64, metric: BadgeMetric) -> String {
+    format!(
+        "![CRAP](https://img.shields.io/badge/{}-{}-{})",
+        shields_escape("CRAP"),
+        shields_escape(&badge_message(entries, threshold, metric)),
+        badge_color(crappy_count(entries, threshold))
+    )
+}
+
+/// Hex value of a [`badge_color`], as shields.io renders it.
+fn color_hex(color: &str) -> &'static str {
+    match color {
+        "brightgreen" => "#4c1",
+        "yellow" => "#dfb317",
+        _ => "#e05d44",
+    }
+}
+
+/// Rough rendered width of `s` in 11px Verdana. Good enough to size the
+/// badge segments without shipping font metrics.
+fn text_width(s: &str) -> usize {
+    s.chars().count() * 7
+}
+
+/// Self-contained SVG in the shields.io "flat" style, for committing next to
+/// a README when the badge shouldn't depend on an external service.
+pub fn render_svg(entries: &[CrapEntry], threshold: f64, metric: BadgeMetric) -> String {
+    labelled_svg("CRAP", entries, threshold, metric)
+}
+
+/// One [`render_svg`] badge per top-level module (the first segment of the
+/// module path), labelled `CRAP <module>`, in module order.
+pub fn render_module_svgs(
+    entries: &[CrapEntry],
+    threshold: f64,
+    metric: BadgeMetric,
+) -> Vec<(String, String)> {
+    let mut modules: BTreeMap<&str, Vec<CrapEntry>> = BTreeMap::new();
+    for e in entries {
+        let module = e.module_path.split("::").next().unwrap_or_default();
+        modules.entry(module).or_default().push(e.clone());
+    }
+    modules
+        .into_iter()
+        .map(|(module, entries)| {
+            let label = format!("CRAP {module}");
+            let svg = labelled_svg(&label, &entries, threshold, metric);
+            (module.to_string(), svg)
+        })
+        .collect()
+}
+
+fn labelled_svg(label: &str, entries: &[CrapEntry], threshold: f64, metric: BadgeMetric) -> String {
+    let label = escape(label);
+    let message = escape(&badge_message(entries, threshold, metric));
+    let color = color_hex(badge_color(crappy_count(entries, threshold)));
+    let label_w = text_width(&label) + 10;
+    let message_w = text_width(&message) + 10;
+    let width = label_w + message_w;
+    let label_x = label_w as f64 / 2.0;
+    let message_x = label_w as f64 + message_w as f64 / 2.0;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+  <title>{label}: {message}</title>
+  <linearGradient id="s" x2="0" y2="100%">
+    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+  <clipPath id="r">
+    <rect width="{width}" height="20" rx="3" fill="#fff"/>
+  </clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_w}" height="20" fill="#555"/>
+    <rect x="{label_w}" width="{message_w}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text>
+    <text x="{label_x}" y="14">{label}</text>
+    <text x="{message_x}" y="15" fill="#010101" fill-opacity=".3">{message}</text>
+    <text x="{message_x}" y="14">{message}</text>
+  </g>
+</svg>
+"##
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape a label/message for a shields.io static badge path segment.
+/// Dashes and underscores are doubled (single ones are separators), spaces and
+/// other reserved characters are percent-encoded.
+fn shields_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '-' => out.push_str("--"),
+            '_' => out.push_str("__"),
+            ' ' => out.push_str("%20"),
+            c if c.is_ascii_alphanumeric() || c == '.' => out.push(c),
+            c => {
+                let mut buf = [0u8; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("%{b:02X}"));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Replace everything between [`START_MARKER`] and [`END_MARKER`] with `snippet`.
+/// The markers themselves and the rest of the document are left untouched.
+pub fn update_readme(readme: &str, snippet: &str) -> Result<String> {
+    let Some(start) = readme.find(START_MARKER) else {
+        bail!("no `{START_MARKER}` marker found — add `{START_MARKER}` and `{END_MARKER}` around the badge");
+    };
+    let body_start = start + START_MARKER.len();
+    let Some(end_rel) = readme[body_start..].find(END_MARKER) else {
+        bail!("`{START_MARKER}` has no matching `{END_MARKER}` after it");
+    };
+    let body_end = body_start + end_rel;
+    Ok(format!(
+        "{}\n{snippet}\n{}",
+        &readme[..body_start],
+        &readme[body_end..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: "f".into(),
+            complexity: 1,
+            coverage: crap.map(|_| 0.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn crappy_count_uses_inclusive_threshold_and_ignores_unscored() {
+        let entries = vec![entry(Some(30.0)), entry(Some(29.9)), entry(None), entry(Some(80.0))];
+        assert_eq!(crappy_count(&entries, 30.0), 2);
+    }
+
+    #[test]
+    fn color_tiers() {
+        assert_eq!(badge_color(0), "brightgreen");
+        assert_eq!(badge_color(5), "yellow");
+        assert_eq!(badge_color(6), "red");
+    }
+
+    #[test]
+    fn snippet_escapes_message() {
+        let entries = vec![entry(Some(40.0))];
+        assert_eq!(
+            readme_snippet(&entries, 30.0, BadgeMetric::Crappy),
+            "![CRAP](https://img.shields.io/badge/CRAP-1%20crappy-yellow)"
+        );
+        assert_eq!(
+            readme_snippet(&entries, 30.0, BadgeMetric::Load),
+            "![CRAP](https://img.shields.io/badge/CRAP-load%2040.0-yellow)"
+        );
+    }
+
+    #[test]
+    fn svg_sizes_segments_to_the_message() {
+        let svg = render_svg(&[entry(Some(12.5)), entry(None)], 30.0, BadgeMetric::Load);
+        assert!(svg.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"111\" height=\"20\" role=\"img\" \
+             aria-label=\"CRAP: load 12.5\">"
+        ));
+        assert!(svg.contains("<rect x=\"38\" width=\"73\" height=\"20\" fill=\"#4c1\"/>"));
+        assert!(svg.contains("<text x=\"74.5\" y=\"14\">load 12.5</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn module_badges_cover_each_top_level_module() {
+        let module = |module_path: &str, crap: Option<f64>| CrapEntry {
+            module_path: module_path.into(),
+            ..entry(crap)
+        };
+        let entries = vec![
+            module("parser::expr", Some(42.0)),
+            module("parser", Some(12.0)),
+            module("io", None),
+        ];
+        let badges = render_module_svgs(&entries, 30.0, BadgeMetric::Worst);
+        let modules: Vec<&str> = badges.iter().map(|(m, _)| m.as_str()).collect();
+        assert_eq!(modules, vec!["io", "parser"]);
+        let (io, parser) = (&badges[0].1, &badges[1].1);
+        assert!(io.contains("aria-label=\"CRAP io: worst —\""));
+        assert!(parser.contains("aria-label=\"CRAP parser: worst 42.0\""));
+        assert!(parser.contains("fill=\"#dfb317\""));
+        let crappy = render_module_svgs(&entries, 30.0, BadgeMetric::Crappy);
+        let parser = &crappy[1].1;
+        assert!(parser.contains("<text x=\"43.5\" y=\"14\">CRAP parser</text>"));
+    }
+
+    #[test]
+    fn shields_escape_doubles_separators() {
+        assert_eq!(shields_escape("a-b_c d"), "a--b__c%20d");
+        assert_eq!(shields_escape("≥"), "%E2%89%A5");
+    }
+
+    #[test]
+    fn update_replaces_only_marked_region() {
+        let readme = format!("# Title\n{START_MARKER}\nold badge\n{END_MARKER}\nrest\n");
+        let updated = update_readme(&readme, "NEW").unwrap();
+        assert_eq!(updated, format!("# Title\n{START_MARKER}\nNEW\n{END_MARKER}\nrest\n"));
+    }
+
+    #[test]
+    fn update_is_idempotent() {
+        let readme = format!("{START_MARKER}{END_MARKER}\n");
+        let once = update_readme(&readme, "X").unwrap();
+        let twice = update_readme(&once, "X").unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn update_without_markers_errors() {
+        assert!(update_readme("# Title\n", "X").is_err());
+        assert!(update_readme(&format!("{END_MARKER}\n{START_MARKER}\n"), "X").is_err());
+    }
+}
@@ -0,0 +1,776 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use crate::crap::{self, CrapEntry, Previous, PreviousScores};
+
+/// Which side's entry wins when both baselines score the same function.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MergeStrategy {
+    /// Keep the higher CRAP, so neither branch's allowance is lost.
+    #[default]
+    Max,
+    /// Keep the lower CRAP, holding both branches to the stricter score.
+    Min,
+}
+
+/// Merge two `--format json` reports into one, matching entries by module and
+/// name. Scored entries beat unscored ones; ties keep `ours`. Entries present
+/// on only one side are kept as is. The result is sorted like a report, worst first.
+pub fn merge(ours: &str, theirs: &str, strategy: MergeStrategy) -> Result<String> {
+    let ours = parse(ours).context("invalid first baseline")?;
+    let theirs = parse(theirs).context("invalid second baseline")?;
+
+    let mut merged: Vec<Value> = Vec::new();
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for entry in ours {
+        index.insert(key(&entry), merged.len());
+        merged.push(entry);
+    }
+    for entry in theirs {
+        match index.get(&key(&entry)) {
+            Some(&i) => {
+                if prefer_theirs(crap(&merged[i]), crap(&entry), strategy) {
+                    merged[i] = entry;
+                }
+            }
+            None => {
+                index.insert(key(&entry), merged.len());
+                merged.push(entry);
+            }
+        }
+    }
+
+    merged.sort_by(|a, b| {
+        let order = match (crap(a), crap(b)) {
+            (Some(x), Some(y)) => y.total_cmp(&x),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        };
+        order.then_with(|| key(a).cmp(&key(b)))
+    });
+    Ok(serde_json::to_string_pretty(&merged)?)
+}
+
+/// Stable content hash (64-bit FNV-1a, hex) of a source file, recorded per
+/// entry in JSON reports.
+pub fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in content.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// What moved a function's score between two baselines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// The function's source file changed.
+    Code,
+    /// Same source, so only the coverage data differs.
+    Coverage,
+    /// One side has no `file_hash` (written by an older craprs).
+    Unknown,
+    Added,
+    Removed,
+}
+
+impl Change {
+    pub fn label(self) -> &'static str {
+        match self {
+            Change::Code => "code changed",
+            Change::Coverage => "coverage changed",
+            Change::Unknown => "cause unknown",
+            Change::Added => "new",
+            Change::Removed => "removed",
+        }
+    }
+}
+
+/// One function whose score differs between two baselines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub module: String,
+    pub name: String,
+    pub old: Option<f64>,
+    pub new: Option<f64>,
+    pub change: Change,
+    /// `module::name` in the old baseline, when matched by fingerprint.
+    pub renamed_from: Option<String>,
+    /// `file`, `start_line` and `end_line` in the new baseline, when recorded.
+    pub location: Option<(String, usize, usize)>,
+    /// Commits that touched the function between the baselines, filled in
+    /// by `craprs baseline diff --blame` for regressions.
+    pub commits: Vec<String>,
+}
+
+impl Delta {
+    /// The function is still scored and its score went up.
+    pub fn is_regression(&self) -> bool {
+        matches!((self.old, self.new), (Some(old), Some(new)) if new > old)
+    }
+
+    /// The function is still scored and its score went down.
+    pub fn is_improvement(&self) -> bool {
+        matches!((self.old, self.new), (Some(old), Some(new)) if new < old)
+    }
+}
+
+/// Functions whose score moved by at least `min_delta` between `old` and
+/// `new`, or that gained or lost a score, plus added and removed ones. Largest
+/// moves first; added and removed functions last.
+///
+/// Entries are matched by module and name. Those left over are matched by
+/// `fingerprint` when exactly one entry on each side has it, so a renamed or
+/// moved function is compared with its old self instead of showing up as
+/// removed and added.
+pub fn diff(old: &str, new: &str, min_delta: f64) -> Result<Vec<Delta>> {
+    let old = parse(old).context("invalid old baseline")?;
+    let new = parse(new).context("invalid new baseline")?;
+    let mut old_by_key: HashMap<(String, String), &Value> =
+        old.iter().map(|e| (key(e), e)).collect();
+
+    let mut deltas = Vec::new();
+    let mut unmatched = Vec::new();
+    for entry in &new {
+        match old_by_key.remove(&key(entry)) {
+            Some(before) => deltas.extend(compare(before, entry, min_delta)),
+            None => unmatched.push(entry),
+        }
+    }
+    let removed: Vec<&Value> = old
+        .iter()
+        .filter(|e| old_by_key.contains_key(&key(e)))
+        .collect();
+    let old_prints = unique_fingerprints(&removed);
+    let new_prints = unique_fingerprints(&unmatched);
+    let mut renamed = HashSet::new();
+    for entry in &unmatched {
+        let before = fingerprint(entry)
+            .filter(|print| new_prints.contains_key(print))
+            .and_then(|print| old_prints.get(print));
+        if let Some(&before) = before {
+            renamed.insert(key(before));
+            if let Some(mut delta) = compare(before, entry, min_delta) {
+                // Identical bodies, so whatever moved the score, it wasn't the code.
+                delta.change = Change::Coverage;
+                let (module, name) = key(before);
+                delta.renamed_from = Some(format!("{module}::{name}"));
+                deltas.push(delta);
+            }
+        } else {
+            let (module, name) = key(entry);
+            deltas.push(Delta {
+                module,
+                name,
+                old: None,
+                new: crap(entry),
+                change: Change::Added,
+                renamed_from: None,
+                location: location(entry),
+                commits: Vec::new(),
+            });
+        }
+    }
+    for entry in removed.iter().filter(|e| !renamed.contains(&key(e))) {
+        let (module, name) = key(entry);
+        deltas.push(Delta {
+            module,
+            name,
+            old: crap(entry),
+            new: None,
+            change: Change::Removed,
+            renamed_from: None,
+            location: None,
+            commits: Vec::new(),
+        });
+    }
+
+    let rank = |d: &Delta| match d.change {
+        Change::Added | Change::Removed => -1.0,
+        _ => (d.new.unwrap_or(0.0) - d.old.unwrap_or(0.0)).abs(),
+    };
+    deltas.sort_by(|a, b| rank(b).total_cmp(&rank(a)));
+    Ok(deltas)
+}
+
+/// The delta between two versions of a function, if its score moved.
+fn compare(before: &Value, after: &Value, min_delta: f64) -> Option<Delta> {
+    let moved = match (crap(before), crap(after)) {
+        (Some(a), Some(b)) => (b - a).abs() >= min_delta,
+        (None, None) => false,
+        _ => true,
+    };
+    if !moved {
+        return None;
+    }
+    let change = match (before["file_hash"].as_str(), after["file_hash"].as_str()) {
+        (Some(a), Some(b)) if !a.is_empty() && a == b => Change::Coverage,
+        (Some(a), Some(b)) if !a.is_empty() && !b.is_empty() => Change::Code,
+        _ => Change::Unknown,
+    };
+    let (module, name) = key(after);
+    Some(Delta {
+        module,
+        name,
+        old: crap(before),
+        new: crap(after),
+        change,
+        renamed_from: None,
+        location: location(after),
+        commits: Vec::new(),
+    })
+}
+
+/// Entries by fingerprint, leaving out fingerprints shared by several entries.
+fn unique_fingerprints<'a>(entries: &[&'a Value]) -> HashMap<&'a str, &'a Value> {
+    let mut by_print: HashMap<&str, Option<&Value>> = HashMap::new();
+    for &entry in entries {
+        if let Some(print) = fingerprint(entry) {
+            by_print
+                .entry(print)
+                .and_modify(|e| *e = None)
+                .or_insert(Some(entry));
+        }
+    }
+    by_print
+        .into_iter()
+        .filter_map(|(print, entry)| entry.map(|e| (print, e)))
+        .collect()
+}
+
+/// One line per delta: score change, old → new, function and cause, with
+/// the delta's commits indented below it.
+pub fn format_diff(deltas: &[Delta]) -> String {
+    if deltas.is_empty() {
+        return "No score changes.\n".to_string();
+    }
+    let score = |s: Option<f64>| s.map_or("—".to_string(), |s| format!("{s:.1}"));
+    let mut out = String::new();
+    for d in deltas {
+        let delta = match (d.old, d.new) {
+            (Some(a), Some(b)) => format!("{:+.1}", b - a),
+            _ => String::new(),
+        };
+        let renamed = match d.renamed_from {
+            Some(ref from) => format!(", was {from}"),
+            None => String::new(),
+        };
+        out.push_str(&format!(
+            "{delta:>7}  {:>6} -> {:<6}  {}::{}  ({}{renamed})\n",
+            score(d.old),
+            score(d.new),
+            d.module,
+            d.name,
+            d.change.label()
+        ));
+        for commit in &d.commits {
+            out.push_str(&format!("           {commit}\n"));
+        }
+    }
+    out
+}
+
+/// The `n` improvements with the largest score drops, one line each with
+/// the authors of the delta's commits, for `baseline diff --most-improved`.
+pub fn format_most_improved(deltas: &[Delta], n: usize) -> String {
+    let mut improved: Vec<(&Delta, f64)> = deltas
+        .iter()
+        .filter_map(|d| match (d.old, d.new) {
+            (Some(old), Some(new)) if new < old => Some((d, new - old)),
+            _ => None,
+        })
+        .collect();
+    improved.sort_by(|a, b| a.1.total_cmp(&b.1));
+    improved.truncate(n);
+    if improved.is_empty() {
+        return "\nmost improved: none\n".to_string();
+    }
+    let mut out = "\nmost improved:\n".to_string();
+    for (d, change) in improved {
+        let mut authors: Vec<&str> = Vec::new();
+        // `<short hash> <author>: <subject>`, see `blame::commits_touching`.
+        let names = d.commits.iter().filter_map(|c| {
+            let (_, rest) = c.split_once(' ')?;
+            Some(rest.split_once(": ")?.0)
+        });
+        for name in names {
+            if !authors.contains(&name) {
+                authors.push(name);
+            }
+        }
+        let by = if authors.is_empty() {
+            String::new()
+        } else {
+            format!("  by {}", authors.join(", "))
+        };
+        out.push_str(&format!(
+            "{change:>7.1}  {:>6.1} -> {:<6.1}  {}::{}{by}\n",
+            d.old.unwrap_or_default(),
+            d.new.unwrap_or_default(),
+            d.module,
+            d.name
+        ));
+    }
+    out
+}
+
+/// The `git_commit` a `--format json` report was produced at, if recorded.
+pub fn report_commit(content: &str) -> Option<String> {
+    let report: Value = serde_json::from_str(content).ok()?;
+    let commit = report["metadata"]["git_commit"].as_str()?;
+    Some(commit.to_string())
+}
+
+/// Scores and coverage per function in an earlier report, for `--compare`.
+pub fn previous_scores(content: &str) -> Result<PreviousScores> {
+    let entries = parse(content)?;
+    Ok(entries
+        .iter()
+        .map(|e| {
+            let previous = Previous {
+                crap: crap(e),
+                coverage: e["coverage"].as_f64(),
+            };
+            (key(e), previous)
+        })
+        .collect())
+}
+
+/// One line counting how `analyzed` moved against `previous`: functions
+/// whose score rose or fell by at least 0.05, new ones, and ones no longer
+/// there. `analyzed` must be every analyzed function, before `--select`,
+/// `--top`, `--min-crap` and the other report filters; a function those
+/// filters hide would otherwise count as removed.
+pub fn format_comparison(analyzed: &[CrapEntry], previous: &PreviousScores, label: &str) -> String {
+    let (mut worse, mut better, mut new) = (0, 0, 0);
+    let mut present = HashSet::new();
+    for e in analyzed {
+        let key = (e.module_path.clone(), e.name.clone());
+        match previous.get(&key) {
+            Some(before) => {
+                if let (Some(a), Some(b)) = (before.crap, e.crap) {
+                    if b - a >= 0.05 {
+                        worse += 1;
+                    } else if a - b >= 0.05 {
+                        better += 1;
+                    }
+                }
+            }
+            None => new += 1,
+        }
+        present.insert(key);
+    }
+    let removed = previous.keys().filter(|k| !present.contains(*k)).count();
+    if worse + better + new + removed == 0 {
+        return format!("compared with {label}: no changes\n");
+    }
+    format!(
+        "compared with {label}: {worse} function(s) worse, {better} better, {new} new, {removed} removed\n"
+    )
+}
+
+/// Entries of a `--format json` report as [`CrapEntry`]s, with what the
+/// report records: location, hashes, complexity, coverage and score.
+pub fn report_entries(content: &str) -> Result<Vec<CrapEntry>> {
+    let entries = parse(content)?;
+    Ok(entries
+        .iter()
+        .map(|e| {
+            let (module_path, name) = key(e);
+            let line = |field: &str| e[field].as_u64().unwrap_or_default() as usize;
+            CrapEntry {
+                name,
+                module_path,
+                complexity: e["complexity"].as_u64().unwrap_or_default() as u32,
+                coverage: e["coverage"].as_f64(),
+                crap: crap(e),
+                file: e["file"].as_str().unwrap_or_default().into(),
+                start_line: line("start_line"),
+                end_line: line("end_line"),
+                file_hash: e["file_hash"].as_str().unwrap_or_default().to_string(),
+                fingerprint: fingerprint(e).unwrap_or_default().to_string(),
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+/// `craprs merge`: one summary line per labelled report, e.g. one per
+/// repository, then the `top` highest-scoring functions across all of them.
+pub fn format_aggregate(
+    reports: &[(String, Vec<CrapEntry>)],
+    threshold: f64,
+    top: usize,
+) -> String {
+    let mut out = String::new();
+    for (label, entries) in reports {
+        let summary = crap::summarize(entries, threshold);
+        let worst = entries
+            .iter()
+            .filter_map(|e| e.crap)
+            .max_by(f64::total_cmp)
+            .map_or("none".to_string(), |s| format!("{s:.1}"));
+        out.push_str(&format!(
+            "{label}: {} function(s), {} scored, total CRAP {:.1}, worst {worst}, \
+             {} with CRAP >= {threshold}\n",
+            summary.functions, summary.scored, summary.total, summary.crappy
+        ));
+    }
+    let mut worst: Vec<(&str, &CrapEntry, f64)> = reports
+        .iter()
+        .flat_map(|(label, entries)| {
+            entries
+                .iter()
+                .filter_map(move |e| e.crap.map(|s| (label.as_str(), e, s)))
+        })
+        .collect();
+    // Stable, so ties keep the order the reports were given in.
+    worst.sort_by(|a, b| b.2.total_cmp(&a.2));
+    worst.truncate(top);
+    if worst.is_empty() {
+        return out;
+    }
+    out.push_str(&format!(
+        "\nworst offenders across {} report(s):\n",
+        reports.len()
+    ));
+    for (label, e, score) in worst {
+        let location = if e.file.as_os_str().is_empty() {
+            String::new()
+        } else {
+            format!("  {}", e.location())
+        };
+        out.push_str(&format!(
+            "{score:>7.1}  {label}  {}::{}{location}\n",
+            e.module_path, e.name
+        ));
+    }
+    out
+}
+
+/// Entries of a `--format json` report, or of a bare entry array as written
+/// by older versions and by [`merge`].
+fn parse(content: &str) -> Result<Vec<Value>> {
+    let entries = match serde_json::from_str(content)? {
+        Value::Array(entries) => entries,
+        Value::Object(mut report) => match report.remove("entries") {
+            Some(Value::Array(entries)) => entries,
+            _ => bail!("expected an `entries` array in the report"),
+        },
+        _ => bail!("expected a JSON report or array of report entries"),
+    };
+    for entry in &entries {
+        if !entry["name"].is_string() || !entry["module"].is_string() {
+            bail!("entry without string `name` and `module`: {entry}");
+        }
+    }
+    Ok(entries)
+}
+
+fn key(entry: &Value) -> (String, String) {
+    (
+        entry["module"].as_str().unwrap_or_default().to_string(),
+        entry["name"].as_str().unwrap_or_default().to_string(),
+    )
+}
+
+fn location(entry: &Value) -> Option<(String, usize, usize)> {
+    let file = entry["file"].as_str().filter(|f| !f.is_empty())?;
+    let start = entry["start_line"].as_u64().filter(|&l| l > 0)?;
+    let end = entry["end_line"].as_u64().filter(|&l| l >= start)?;
+    Some((file.to_string(), start as usize, end as usize))
+}
+
+fn crap(entry: &Value) -> Option<f64> {
+    entry["crap"].as_f64()
+}
+
+fn fingerprint(entry: &Value) -> Option<&str> {
+    entry["fingerprint"].as_str().filter(|f| !f.is_empty())
+}
+
+fn prefer_theirs(ours: Option<f64>, theirs: Option<f64>, strategy: MergeStrategy) -> bool {
+    match (ours, theirs) {
+        (Some(o), Some(t)) => match strategy {
+            MergeStrategy::Max => t > o,
+            MergeStrategy::Min => t < o,
+        },
+        (None, Some(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OURS: &str = r#"[
+        {"name": "parse", "module": "core", "crap": 12.0, "complexity": 4},
+        {"name": "load", "module": "io", "crap": null, "complexity": 2},
+        {"name": "old", "module": "core", "crap": 3.0, "complexity": 1}
+    ]"#;
+    const THEIRS: &str = r#"[
+        {"name": "parse", "module": "core", "crap": 20.0, "complexity": 5},
+        {"name": "load", "module": "io", "crap": 6.0, "complexity": 2},
+        {"name": "new", "module": "core", "crap": null, "complexity": 1}
+    ]"#;
+
+    fn summary(json: &str) -> Vec<(String, Option<f64>)> {
+        let entries: Vec<Value> = serde_json::from_str(json).unwrap();
+        entries
+            .iter()
+            .map(|e| (e["name"].as_str().unwrap().to_string(), e["crap"].as_f64()))
+            .collect()
+    }
+
+    #[test]
+    fn max_keeps_the_higher_score_and_every_function() {
+        let merged = merge(OURS, THEIRS, MergeStrategy::Max).unwrap();
+        assert_eq!(
+            summary(&merged),
+            vec![
+                ("parse".to_string(), Some(20.0)),
+                ("load".to_string(), Some(6.0)),
+                ("old".to_string(), Some(3.0)),
+                ("new".to_string(), None),
+            ]
+        );
+        // The winning side's entry is taken whole.
+        assert!(merged.contains("\"complexity\": 5"));
+    }
+
+    #[test]
+    fn min_keeps_the_lower_score_but_never_an_unscored_one() {
+        let merged = merge(OURS, THEIRS, MergeStrategy::Min).unwrap();
+        let summary = summary(&merged);
+        assert_eq!(summary[0], ("parse".to_string(), Some(12.0)));
+        assert!(summary.contains(&("load".to_string(), Some(6.0))));
+    }
+
+    #[test]
+    fn content_hash_is_stable_and_content_sensitive() {
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("fn a() {}"), content_hash("fn a() {}"));
+        assert_ne!(content_hash("fn a() {}"), content_hash("fn b() {}"));
+    }
+
+    #[test]
+    fn diff_labels_code_and_coverage_changes() {
+        let old = r#"[
+            {"name": "parse", "module": "core", "crap": 12.0, "file_hash": "aa"},
+            {"name": "load", "module": "io", "crap": 6.0, "file_hash": "bb"},
+            {"name": "same", "module": "io", "crap": 2.0, "file_hash": "bb"},
+            {"name": "legacy", "module": "io", "crap": 1.0},
+            {"name": "gone", "module": "core", "crap": 3.0, "file_hash": "aa"}
+        ]"#;
+        let new = r#"[
+            {"name": "parse", "module": "core", "crap": 20.0, "file_hash": "cc"},
+            {"name": "load", "module": "io", "crap": 4.0, "file_hash": "bb"},
+            {"name": "same", "module": "io", "crap": 2.0, "file_hash": "bb"},
+            {"name": "legacy", "module": "io", "crap": 5.0, "file_hash": "bb"},
+            {"name": "fresh", "module": "core", "crap": null, "file_hash": "cc"}
+        ]"#;
+        let deltas = diff(old, new, 0.1).unwrap();
+        let summary: Vec<(&str, Change)> =
+            deltas.iter().map(|d| (d.name.as_str(), d.change)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("parse", Change::Code),
+                ("legacy", Change::Unknown),
+                ("load", Change::Coverage),
+                ("fresh", Change::Added),
+                ("gone", Change::Removed),
+            ]
+        );
+        let out = format_diff(&deltas);
+        assert!(out.contains("   +8.0    12.0 -> 20.0    core::parse  (code changed)\n"));
+        assert!(out.contains("core::gone  (removed)"));
+        assert_eq!(format_diff(&[]), "No score changes.\n");
+    }
+
+    #[test]
+    fn regressions_carry_their_location_and_commits() {
+        let old = r#"{"metadata": {"git_commit": "abc123"}, "entries": [
+            {"name": "parse", "module": "core", "crap": 12.0},
+            {"name": "load", "module": "io", "crap": 6.0}
+        ]}"#;
+        let new = r#"[
+            {"name": "parse", "module": "core", "crap": 20.0, "file": "src/core.rs", "start_line": 12, "end_line": 40},
+            {"name": "load", "module": "io", "crap": 4.0, "file": "src/io.rs", "start_line": 0, "end_line": 0}
+        ]"#;
+        assert_eq!(report_commit(old).as_deref(), Some("abc123"));
+        assert_eq!(report_commit(new), None);
+        let mut deltas = diff(old, new, 0.1).unwrap();
+        assert!(deltas[0].is_regression());
+        assert!(!deltas[1].is_regression());
+        assert_eq!(
+            deltas[0].location,
+            Some(("src/core.rs".to_string(), 12, 40))
+        );
+        assert_eq!(deltas[1].location, None);
+        deltas[0].commits = vec!["7fb4c50 Bob: inline the lexer".to_string()];
+        assert!(format_diff(&deltas).starts_with(
+            "   +8.0    12.0 -> 20.0    core::parse  (cause unknown)\n\
+             \x20          7fb4c50 Bob: inline the lexer\n"
+        ));
+    }
+
+    #[test]
+    fn most_improved_lists_the_largest_drops_with_their_authors() {
+        let old = r#"[
+            {"name": "parse", "module": "core", "crap": 40.0},
+            {"name": "load", "module": "io", "crap": 6.0},
+            {"name": "lex", "module": "core", "crap": 30.0},
+            {"name": "save", "module": "io", "crap": 3.0}
+        ]"#;
+        let new = r#"[
+            {"name": "parse", "module": "core", "crap": 10.0},
+            {"name": "load", "module": "io", "crap": 4.0},
+            {"name": "lex", "module": "core", "crap": 20.0},
+            {"name": "save", "module": "io", "crap": 9.0}
+        ]"#;
+        let mut deltas = diff(old, new, 0.1).unwrap();
+        assert!(deltas[0].is_improvement());
+        assert!(deltas[2].is_regression() && !deltas[2].is_improvement());
+        deltas[0].commits = vec![
+            "7fb4c50 Ann Lee: split parse".to_string(),
+            "b2afc1a Bob: test parse".to_string(),
+            "c0ffee1 Ann Lee: tidy parse".to_string(),
+        ];
+        assert_eq!(
+            format_most_improved(&deltas, 2),
+            "\nmost improved:\n\
+             \x20 -30.0    40.0 -> 10.0    core::parse  by Ann Lee, Bob\n\
+             \x20 -10.0    30.0 -> 20.0    core::lex\n"
+        );
+        assert_eq!(
+            format_most_improved(&deltas[2..3], 2),
+            "\nmost improved: none\n"
+        );
+    }
+
+    #[test]
+    fn diff_follows_renames_by_fingerprint() {
+        let old = r#"[
+            {"name": "parse", "module": "core", "crap": 12.0, "fingerprint": "f1"},
+            {"name": "tidy", "module": "core", "crap": 2.0, "fingerprint": "f2"},
+            {"name": "get_a", "module": "core", "crap": 1.0, "fingerprint": "f3"},
+            {"name": "get_b", "module": "core", "crap": 1.0, "fingerprint": "f3"}
+        ]"#;
+        let new = r#"[
+            {"name": "parse_header", "module": "io::header", "crap": 6.0, "fingerprint": "f1"},
+            {"name": "clean", "module": "core", "crap": 2.0, "fingerprint": "f2"},
+            {"name": "a", "module": "core", "crap": 1.0, "fingerprint": "f3"},
+            {"name": "b", "module": "core", "crap": 1.0, "fingerprint": "f3"}
+        ]"#;
+        let deltas = diff(old, new, 0.1).unwrap();
+        let summary: Vec<(&str, Change)> =
+            deltas.iter().map(|d| (d.name.as_str(), d.change)).collect();
+        // `tidy` -> `clean` kept its score, so it isn't listed; the shared
+        // getter fingerprint is ambiguous, so those stay added and removed.
+        assert_eq!(
+            summary,
+            vec![
+                ("parse_header", Change::Coverage),
+                ("a", Change::Added),
+                ("b", Change::Added),
+                ("get_a", Change::Removed),
+                ("get_b", Change::Removed),
+            ]
+        );
+        assert!(format_diff(&deltas).contains(
+            "   -6.0    12.0 -> 6.0     io::header::parse_header  (coverage changed, was core::parse)\n"
+        ));
+    }
+
+    #[test]
+    fn reads_entries_from_full_reports() {
+        let report =
+            format!(r#"{{"metadata": {{"craprs_version": "0.2.0"}}, "entries": {THEIRS}}}"#);
+        let merged = merge(OURS, &report, MergeStrategy::Max).unwrap();
+        assert_eq!(summary(&merged).len(), 4);
+    }
+
+    #[test]
+    fn aggregates_reports_with_a_global_worst_list() {
+        let service = r#"{"metadata": {}, "entries": [
+            {"name": "route", "module": "http", "crap": 42.0, "complexity": 6, "coverage": 10.0, "file": "src/http.rs", "start_line": 8, "end_line": 30},
+            {"name": "boot", "module": "main", "crap": null, "complexity": 1}
+        ]}"#;
+        let service = report_entries(service).unwrap();
+        assert_eq!(service[0].location(), "src/http.rs:8");
+        assert_eq!(
+            (service[0].complexity, service[0].coverage),
+            (6, Some(10.0))
+        );
+        let reports = vec![
+            ("service.json".to_string(), service),
+            ("lib.json".to_string(), report_entries(THEIRS).unwrap()),
+        ];
+        assert_eq!(
+            format_aggregate(&reports, 30.0, 2),
+            "service.json: 2 function(s), 1 scored, total CRAP 42.0, worst 42.0, 1 with CRAP >= 30\n\
+             lib.json: 3 function(s), 2 scored, total CRAP 26.0, worst 20.0, 0 with CRAP >= 30\n\
+             \n\
+             worst offenders across 2 report(s):\n\
+             \x20  42.0  service.json  http::route  src/http.rs:8\n\
+             \x20  20.0  lib.json  core::parse\n"
+        );
+    }
+
+    #[test]
+    fn rejects_non_report_json() {
+        assert!(merge("{}", THEIRS, MergeStrategy::Max).is_err());
+        assert!(merge("3", THEIRS, MergeStrategy::Max).is_err());
+        assert!(merge(OURS, r#"[{"crap": 1.0}]"#, MergeStrategy::Max).is_err());
+    }
+
+    #[test]
+    fn compares_entries_with_a_previous_report() {
+        let previous = previous_scores(OURS).unwrap();
+        assert_eq!(
+            previous[&("core".to_string(), "parse".to_string())],
+            Previous {
+                crap: Some(12.0),
+                coverage: None
+            }
+        );
+        let entry = |module: &str, name: &str, crap: Option<f64>| CrapEntry {
+            name: name.into(),
+            module_path: module.into(),
+            complexity: 1,
+            coverage: crap.map(|_| 50.0),
+            crap,
+            file: std::path::PathBuf::new(),
+            ..Default::default()
+        };
+        let entries = vec![
+            entry("core", "parse", Some(20.0)),
+            entry("io", "load", Some(6.0)),
+            entry("core", "new", None),
+        ];
+        assert_eq!(
+            format_comparison(&entries, &previous, "main.json"),
+            "compared with main.json: 1 function(s) worse, 0 better, 1 new, 1 removed\n"
+        );
+        // Functions a report filter such as `--top 1` hides are still
+        // analyzed, not removed.
+        let mut analyzed = entries.clone();
+        analyzed.push(entry("core", "old", Some(3.0)));
+        assert_eq!(
+            format_comparison(&analyzed, &previous, "main.json"),
+            "compared with main.json: 1 function(s) worse, 0 better, 1 new, 0 removed\n"
+        );
+        let same = vec![entry("core", "parse", Some(12.0))];
+        let previous = previous_scores(r#"[{"name": "parse", "module": "core", "crap": 12.0}]"#);
+        assert_eq!(
+            format_comparison(&same, &previous.unwrap(), "main.json"),
+            "compared with main.json: no changes\n"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+/// Commits in `since..until` that touched lines `start..=end` of `file`, newest
+/// first, as `<short hash> <author>: <subject>`. `git log -L` follows the range
+/// back through earlier edits, so lines added above the function don't hide it.
+/// `file` is relative to `dir`, and the range is taken at `until`.
+pub fn commits_touching(
+    dir: &Path,
+    since: &str,
+    until: &str,
+    file: &str,
+    start: usize,
+    end: usize,
+) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .args([
+            "log",
+            "--no-patch",
+            "--format=%h %an: %s",
+            &format!("{since}..{until}"),
+            &format!("-L{start},{end}:{file}"),
+        ])
+        .current_dir(dir)
+        .output()
+        .context("failed to run git log")?;
+    if !output.status.success() {
+        bail!(
+            "git log -L{start},{end}:{file} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The non-empty lines of `git log --no-patch` output.
+fn parse_log(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_one_line_per_commit() {
+        let out = "7fb4c50 Bob: tighten parser\n\nb2afc1a Ann Lee: add parser \n";
+        assert_eq!(
+            parse_log(out),
+            vec!["7fb4c50 Bob: tighten parser", "b2afc1a Ann Lee: add parser"]
+        );
+        assert!(parse_log("").is_empty());
+    }
+}
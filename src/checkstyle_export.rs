@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use crate::crap::CrapEntry;
+use crate::metadata::RunMetadata;
+use crate::tiers::Tiers;
+
+/// Checkstyle XML with one `<file>` per source file that has a function at or
+/// above `threshold`, and one `<error>` per such function at its first line.
+/// With risk `tiers`, every function above the lowest tier is reported
+/// instead: the highest tier as `error`, the ones between as `warning`.
+/// Checkstyle has no place for run metadata, so it goes in a comment.
+pub fn to_checkstyle(
+    entries: &[CrapEntry],
+    score_label: &str,
+    threshold: f64,
+    tiers: Option<&Tiers>,
+    metadata: &RunMetadata,
+) -> String {
+    let mut files: BTreeMap<String, Vec<&CrapEntry>> = BTreeMap::new();
+    let tier = |score: f64| tiers.map(|t| t.classify(score));
+    for e in entries {
+        let reported = match e.crap {
+            Some(score) => tier(score).map_or(score >= threshold, |i| i > 0),
+            None => false,
+        };
+        if reported {
+            files
+                .entry(e.file.to_string_lossy().into_owned())
+                .or_default()
+                .push(e);
+        }
+    }
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let facts: Vec<String> = metadata
+        .pairs()
+        .iter()
+        // `--` may not appear inside an XML comment.
+        .map(|(key, value)| format!("{key}={}", value.replace("--", "- -")))
+        .collect();
+    out.push_str(&format!("<!-- craprs run: {} -->\n", facts.join(", ")));
+    out.push_str("<checkstyle version=\"4.3\">\n");
+    for (file, crappy) in &mut files {
+        crappy.sort_by_key(|e| e.start_line);
+        out.push_str(&format!("  <file name=\"{}\">\n", escape(file)));
+        for e in crappy.iter() {
+            let score = e.crap.unwrap_or(0.0);
+            let (rank, severity) = match (tiers, tier(score)) {
+                (Some(t), Some(i)) => {
+                    let severity = if i == t.tiers.len() - 1 {
+                        "error"
+                    } else {
+                        "warning"
+                    };
+                    (format!("in tier {}", t.tiers[i].name), severity)
+                }
+                _ => (format!(">= {threshold}"), "error"),
+            };
+            let message = format!(
+                "{}::{} has {score_label} {score:.1} {rank} (CC {}, {:.1}% covered)",
+                e.module_path,
+                e.name,
+                e.complexity,
+                e.coverage.unwrap_or(0.0)
+            );
+            out.push_str(&format!(
+                "    <error line=\"{}\" column=\"1\" severity=\"{severity}\" message=\"{}\" source=\"craprs.{}\"/>\n",
+                e.start_line,
+                escape(&message),
+                score_label.to_lowercase()
+            ));
+        }
+        out.push_str("  </file>\n");
+    }
+    out.push_str("</checkstyle>\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(file: &str, name: &str, line: usize, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "core".into(),
+            complexity: 7,
+            coverage: crap.map(|_| 20.0),
+            crap,
+            file: PathBuf::from(file),
+            start_line: line,
+            end_line: line + 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_crappy_functions_are_reported_per_file() {
+        let entries = vec![
+            entry("src/core.rs", "Vec<u8>::parse", 40, Some(45.0)),
+            entry("src/core.rs", "load", 4, Some(31.0)),
+            entry("src/core.rs", "fine", 60, Some(8.0)),
+            entry("src/io.rs", "idle", 1, None),
+        ];
+        let xml = to_checkstyle(&entries, "CRAP", 30.0, None, &RunMetadata::default());
+        assert!(xml.contains("<checkstyle version=\"4.3\">\n  <file name=\"src/core.rs\">\n"));
+        assert!(xml.contains(
+            "<!-- craprs run: craprs_version=, timestamp=1970-01-01T00:00:00Z, host= -->\n"
+        ));
+        let load = xml.find("line=\"4\"").unwrap();
+        let parse = xml.find(
+            "<error line=\"40\" column=\"1\" severity=\"error\" \
+             message=\"core::Vec&lt;u8&gt;::parse has CRAP 45.0 &gt;= 30 (CC 7, 20.0% covered)\" \
+             source=\"craprs.crap\"/>",
+        );
+        assert!(load < parse.unwrap());
+        assert!(!xml.contains("fine"));
+        assert!(!xml.contains("src/io.rs"));
+        assert!(xml.ends_with("</checkstyle>\n"));
+    }
+
+    #[test]
+    fn tiers_set_what_is_reported_and_its_severity() {
+        let tiers = Tiers::parse(&[
+            "ok < 5".to_string(),
+            "warn < 40".to_string(),
+            "fail >= 40".to_string(),
+        ])
+        .unwrap();
+        let entries = vec![
+            entry("src/core.rs", "parse", 40, Some(45.0)),
+            entry("src/core.rs", "fine", 60, Some(8.0)),
+            entry("src/core.rs", "tiny", 70, Some(2.0)),
+        ];
+        let xml = to_checkstyle(
+            &entries,
+            "CRAP",
+            30.0,
+            Some(&tiers),
+            &RunMetadata::default(),
+        );
+        assert!(xml.contains(
+            "severity=\"error\" message=\"core::parse has CRAP 45.0 in tier fail (CC 7, 20.0% covered)\""
+        ));
+        assert!(xml.contains(
+            "severity=\"warning\" message=\"core::fine has CRAP 8.0 in tier warn (CC 7, 20.0% covered)\""
+        ));
+        assert!(!xml.contains("tiny"));
+    }
+}
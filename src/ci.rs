@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::crap::CrapEntry;
+
+/// `--ci`: which CI system's defaults to apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CiMode {
+    /// Detect the CI system from its environment variables.
+    #[default]
+    Auto,
+    /// Behave as on a developer machine.
+    Off,
+    /// GitHub Actions: annotations and a job summary.
+    Github,
+    /// GitLab CI: no color.
+    Gitlab,
+    /// Jenkins: no color.
+    Jenkins,
+    /// Buildkite: a build annotation.
+    Buildkite,
+}
+
+/// A CI system craprs adapts its defaults to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    GithubActions,
+    GitlabCi,
+    Jenkins,
+    Buildkite,
+}
+
+impl Provider {
+    /// The CI system `env` (a variable lookup) belongs to, from the variables
+    /// each one documents as always set.
+    pub fn detect(env: impl Fn(&str) -> Option<String>) -> Option<Provider> {
+        let is_true = |name: &str| env(name).is_some_and(|v| v == "true");
+        if is_true("GITHUB_ACTIONS") {
+            Some(Provider::GithubActions)
+        } else if is_true("GITLAB_CI") {
+            Some(Provider::GitlabCi)
+        } else if is_true("BUILDKITE") {
+            Some(Provider::Buildkite)
+        } else if env("JENKINS_URL").is_some() {
+            Some(Provider::Jenkins)
+        } else {
+            None
+        }
+    }
+
+    /// The provider `mode` picks, detecting it from `env` for `auto`.
+    pub fn resolve(mode: CiMode, env: impl Fn(&str) -> Option<String>) -> Option<Provider> {
+        match mode {
+            CiMode::Auto => Provider::detect(env),
+            CiMode::Off => None,
+            CiMode::Github => Some(Provider::GithubActions),
+            CiMode::Gitlab => Some(Provider::GitlabCi),
+            CiMode::Jenkins => Some(Provider::Jenkins),
+            CiMode::Buildkite => Some(Provider::Buildkite),
+        }
+    }
+
+    /// The `--ci` value selecting this provider.
+    pub fn mode(self) -> CiMode {
+        match self {
+            Provider::GithubActions => CiMode::Github,
+            Provider::GitlabCi => CiMode::Gitlab,
+            Provider::Jenkins => CiMode::Jenkins,
+            Provider::Buildkite => CiMode::Buildkite,
+        }
+    }
+
+    /// The environment variable [`Provider::detect`] recognizes this provider by.
+    pub fn variable(self) -> &'static str {
+        match self {
+            Provider::GithubActions => "GITHUB_ACTIONS",
+            Provider::GitlabCi => "GITLAB_CI",
+            Provider::Jenkins => "JENKINS_URL",
+            Provider::Buildkite => "BUILDKITE",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Provider::GithubActions => "GitHub Actions",
+            Provider::GitlabCi => "GitLab CI",
+            Provider::Jenkins => "Jenkins",
+            Provider::Buildkite => "Buildkite",
+        }
+    }
+}
+
+/// A GitHub Actions `::warning` workflow command for every entry scoring at
+/// or above `threshold`, so they show up on the pull request's diff. Paths
+/// are made relative to `root` (the checkout, `GITHUB_WORKSPACE`) when the
+/// project dir is below it.
+pub fn github_annotations(
+    entries: &[CrapEntry],
+    threshold: f64,
+    score_label: &str,
+    root: Option<&Path>,
+) -> String {
+    let mut out = String::new();
+    for e in entries {
+        let Some(score) = e.crap.filter(|&s| s >= threshold) else {
+            continue;
+        };
+        let file = root
+            .and_then(|root| {
+                let absolute = std::path::absolute(&e.file).ok()?;
+                Some(absolute.strip_prefix(root).ok()?.to_path_buf())
+            })
+            .unwrap_or_else(|| e.file.strip_prefix(".").unwrap_or(&e.file).to_path_buf());
+        let coverage = e
+            .coverage
+            .map_or(String::new(), |c| format!(", {c:.1}% covered"));
+        out.push_str(&format!(
+            "::warning file={},line={},endLine={},title={}::{}\n",
+            escape_property(&file.to_string_lossy()),
+            e.start_line,
+            e.end_line,
+            escape_property(&format!("{score_label} {score:.1}")),
+            escape_data(&format!(
+                "{} ({}) has {score_label} {score:.1} (CC {}{coverage}), at or above {threshold}",
+                e.name, e.module_path, e.complexity
+            ))
+        ));
+    }
+    out
+}
+
+/// Line ranges each file's changes cover on the new side of a diff, keyed by
+/// path as git prints it.
+pub type ChangedLines = HashMap<PathBuf, Vec<RangeInclusive<usize>>>;
+
+/// The lines `HEAD` changed since it branched off `base`, for
+/// `--annotate-changed-only`. Paths are relative to `dir`, like report
+/// entries are to the project dir, and files outside it are left out.
+/// Renames without edits have no hunks, so they change no lines.
+pub fn changed_lines(dir: &Path, base: &str) -> Result<ChangedLines> {
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "-U0",
+            "-M",
+            "--no-color",
+            "--relative",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+            &format!("{base}...HEAD"),
+        ])
+        .current_dir(dir)
+        .output()
+        .context("failed to run git diff")?;
+    if !output.status.success() {
+        bail!(
+            "git diff {base}...HEAD failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(parse_hunks(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The new-side ranges of the `@@ -a,b +c,d @@` hunk headers in `git diff
+/// -U0` output. A hunk that only deletes lines covers the two lines either
+/// side of the gap.
+fn parse_hunks(diff: &str) -> ChangedLines {
+    let mut changed = ChangedLines::new();
+    let mut file = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // `/dev/null` for deleted files, which no entry points into.
+            file = path.strip_prefix("b/").map(PathBuf::from);
+            continue;
+        }
+        let (Some(file), Some(hunk)) = (&file, line.strip_prefix("@@ ")) else {
+            continue;
+        };
+        let Some(new) = hunk.split(' ').find_map(|s| s.strip_prefix('+')) else {
+            continue;
+        };
+        let (start, count) = new.split_once(',').unwrap_or((new, "1"));
+        let (Ok(start), Ok(count)) = (start.parse::<usize>(), count.parse::<usize>()) else {
+            continue;
+        };
+        let range = match count {
+            0 => start..=start + 1,
+            _ => start..=start + count - 1,
+        };
+        changed.entry(file.clone()).or_default().push(range);
+    }
+    changed
+}
+
+/// Whether the lines `e` spans, `start_line..=end_line`, overlap `changed`.
+pub fn touches_changes(e: &CrapEntry, changed: &ChangedLines) -> bool {
+    let file = e.file.strip_prefix(".").unwrap_or(&e.file);
+    changed.get(file).is_some_and(|ranges| {
+        ranges
+            .iter()
+            .any(|r| *r.start() <= e.end_line && e.start_line <= *r.end())
+    })
+}
+
+/// Escape a workflow command message.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a workflow command property value, which also ends at `:` and `,`.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        move |name| vars.get(name).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn detects_providers_from_their_variables() {
+        let detect = |vars| Provider::detect(env(vars));
+        assert_eq!(
+            detect(&[("GITHUB_ACTIONS", "true"), ("CI", "true")]),
+            Some(Provider::GithubActions)
+        );
+        assert_eq!(detect(&[("GITLAB_CI", "true")]), Some(Provider::GitlabCi));
+        assert_eq!(detect(&[("BUILDKITE", "true")]), Some(Provider::Buildkite));
+        assert_eq!(
+            detect(&[("JENKINS_URL", "https://ci.example.com/")]),
+            Some(Provider::Jenkins)
+        );
+        assert_eq!(detect(&[("CI", "true")]), None);
+        assert_eq!(detect(&[("GITHUB_ACTIONS", "false")]), None);
+        assert_eq!(
+            Provider::resolve(CiMode::Off, env(&[("GITHUB_ACTIONS", "true")])),
+            None
+        );
+        assert_eq!(
+            Provider::resolve(CiMode::Gitlab, env(&[])),
+            Some(Provider::GitlabCi)
+        );
+    }
+
+    #[test]
+    fn annotates_entries_at_or_above_the_threshold() {
+        let entry = |name: &str, crap: Option<f64>| CrapEntry {
+            name: name.into(),
+            module_path: "core".into(),
+            complexity: 8,
+            coverage: crap.map(|_| 45.0),
+            crap,
+            file: PathBuf::from("./src/core.rs"),
+            start_line: 12,
+            end_line: 30,
+            ..Default::default()
+        };
+        let entries = vec![
+            entry("Map<K, V>::get", Some(35.9)),
+            entry("fine", Some(4.0)),
+            entry("idle", None),
+        ];
+        assert_eq!(
+            github_annotations(&entries, 30.0, "CRAP", None),
+            "::warning file=src/core.rs,line=12,endLine=30,title=CRAP 35.9::Map<K, V>::get (core) \
+             has CRAP 35.9 (CC 8, 45.0%25 covered), at or above 30\n"
+        );
+        assert_eq!(escape_property("a,b:c%"), "a%2Cb%3Ac%25");
+    }
+
+    #[test]
+    fn changed_only_keeps_entries_overlapping_the_diff() {
+        let diff = "\
+diff --git a/src/core.rs b/src/core.rs
+--- a/src/core.rs
++++ b/src/core.rs
+@@ -14,2 +14,3 @@ fn parse() {
+@@ -80 +81 @@ fn tidy() {
+@@ -99,4 +100,0 @@ fn main() {
+diff --git a/src/gone.rs b/src/gone.rs
+--- a/src/gone.rs
++++ /dev/null
+@@ -1,9 +0,0 @@
+";
+        let changed = parse_hunks(diff);
+        assert_eq!(
+            changed[Path::new("src/core.rs")],
+            vec![14..=16, 81..=81, 100..=101]
+        );
+        assert_eq!(changed.len(), 1);
+        let entry = |start_line, end_line| CrapEntry {
+            file: PathBuf::from("./src/core.rs"),
+            start_line,
+            end_line,
+            ..Default::default()
+        };
+        assert!(touches_changes(&entry(10, 14), &changed));
+        assert!(touches_changes(&entry(81, 90), &changed));
+        assert!(touches_changes(&entry(101, 120), &changed));
+        assert!(!touches_changes(&entry(17, 80), &changed));
+        assert!(!touches_changes(
+            &CrapEntry {
+                file: PathBuf::from("src/other.rs"),
+                ..entry(10, 14)
+            },
+            &changed
+        ));
+    }
+}
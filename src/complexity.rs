@@ -1,33 +1,299 @@
+use std::collections::HashMap;
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::visit::Visit;
 use syn::{
-    Arm, Attribute, BinOp, ExprBinary, ExprForLoop, ExprIf, ExprLoop, ExprTry, ExprWhile, File,
-    ImplItem, Item, TraitItem,
+    Arm, Attribute, BinOp, Expr, ExprBinary, ExprForLoop, ExprIf, ExprLoop, ExprMatch, ExprTry,
+    ExprWhile, File, ImplItem, Item, LitStr, Meta, PatOr, Token, TraitItem, Visibility,
 };
 
+use crate::baseline::content_hash;
+use crate::dispatch::dyn_calls;
+
 #[derive(Debug, Clone)]
 pub struct FunctionInfo {
     pub name: String,
     pub start_line: usize,
     pub end_line: usize,
     pub complexity: u32,
+    pub shape: FunctionShape,
+    /// Every construct that added to `complexity`, in source order. With a
+    /// `try_weight` above 1, each `try` point adds that weight.
+    pub decision_points: Vec<DecisionPoint>,
+    /// Body is a single plain expression, see [`is_trivial`].
+    pub trivial: bool,
+    /// Hash of the body, see [`fingerprint`].
+    pub fingerprint: String,
+    /// Calls through trait objects, see [`dyn_calls`](crate::dispatch::dyn_calls).
+    pub dyn_calls: u32,
+    /// Attribute macro wrapping the body, e.g. `tokio::main`, see [`WRAPPERS`].
+    pub wrapper: Option<String>,
+    /// Line of the first statement in the body (or its opening brace when
+    /// empty). The lines before it hold the signature and any prologue code
+    /// a `wrapper` generates there.
+    pub body_line: usize,
+    /// Tags from `craprs(tag = "...")` attributes on the function or its
+    /// impl, see [`attr_tags`].
+    pub tags: Vec<String>,
+    /// Declared `pub`, and not inside a function body. Trait impl methods
+    /// count as public, default methods of a trait when the trait is `pub`.
+    pub public: bool,
+    /// Inside a non-`pub` inline `mod { }`, so only reachable through a
+    /// re-export.
+    pub in_private_mod: bool,
+}
+
+/// One construct that adds a path through a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecisionPoint {
+    pub kind: DecisionKind,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecisionKind {
+    If,
+    While,
+    For,
+    Loop,
+    MatchArm,
+    And,
+    Or,
+    Try,
+    OrPattern,
+    Guard,
+}
+
+impl DecisionKind {
+    /// Stable lowercase name used in exports.
+    pub fn label(self) -> &'static str {
+        match self {
+            DecisionKind::If => "if",
+            DecisionKind::While => "while",
+            DecisionKind::For => "for",
+            DecisionKind::Loop => "loop",
+            DecisionKind::MatchArm => "match_arm",
+            DecisionKind::And => "and",
+            DecisionKind::Or => "or",
+            DecisionKind::Try => "try",
+            DecisionKind::OrPattern => "or_pattern",
+            DecisionKind::Guard => "guard",
+        }
+    }
+}
+
+/// Structural measurements gathered while computing complexity, used for
+/// refactoring hints.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionShape {
+    /// Arms in the largest `match`.
+    pub max_match_arms: usize,
+    /// Deepest nesting of `if` / `match` / loops (an `else if` ladder counts once).
+    pub max_nesting: u32,
+    /// Branches in the longest `if / else if` ladder.
+    pub longest_if_chain: u32,
+    /// Most `&&` / `||` operators in a single `if` or `while` condition.
+    pub max_condition_ops: u32,
+}
+
+/// Knobs for what counts toward cyclomatic complexity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComplexityOptions {
+    /// Complexity added per `?` operator; 0 ignores error propagation entirely.
+    pub try_weight: u32,
+    /// Count each extra alternative of an or-pattern (`A | B | C` adds 2) and
+    /// each match guard as a decision point of its own.
+    pub weigh_patterns: bool,
+}
+
+/// What to do with trivial functions (constructors, getters, setters; see
+/// [`is_trivial`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TrivialMode {
+    /// Report them like any other function.
+    #[default]
+    Show,
+    /// Report them after every non-trivial function.
+    Demote,
+    /// Leave them out of the report.
+    Hide,
+}
+
+impl Default for ComplexityOptions {
+    fn default() -> Self {
+        ComplexityOptions {
+            try_weight: 1,
+            weigh_patterns: false,
+        }
+    }
 }
 
 /// Extract all functions from Rust source code with their cyclomatic complexity.
+/// Names that would collide within the file are disambiguated, see [`disambiguate`].
 pub fn extract_functions(source: &str) -> Vec<FunctionInfo> {
-    let syntax: File = syn::parse_file(source).expect("failed to parse Rust source");
+    extract_functions_with(source, ComplexityOptions::default())
+}
+
+/// [`extract_functions`] with non-default complexity rules.
+pub fn extract_functions_with(source: &str, opts: ComplexityOptions) -> Vec<FunctionInfo> {
+    try_extract_functions_with(source, opts).expect("failed to parse Rust source")
+}
+
+/// [`extract_functions_with`] for sources that may not parse, e.g. files
+/// with syntax only a newer compiler accepts.
+pub fn try_extract_functions_with(
+    source: &str,
+    opts: ComplexityOptions,
+) -> syn::Result<Vec<FunctionInfo>> {
+    let syntax: File = syn::parse_file(source)?;
     let mut extractor = FunctionExtractor {
+        opts,
         functions: Vec::new(),
+        qualified: Vec::new(),
         impl_name: None,
+        impl_qualifier: None,
+        impl_cfg: Vec::new(),
+        impl_tags: Vec::new(),
+        impl_trait: false,
+        private_mods: 0,
+        fn_depth: 0,
     };
     extractor.visit_file(&syntax);
-    extractor.functions
+    disambiguate(&mut extractor.functions, &extractor.qualified);
+    Ok(extractor.functions)
+}
+
+/// Give functions that share a name (`Foo::serialize` in `impl<T: Write> Foo<T>`
+/// and `impl Foo<Vec<u8>>`, or `fmt` in `Display` and `Debug` impls, or
+/// cfg-gated variants) distinct names, so baselines and allowlists can tell
+/// them apart. Colliding names are replaced by their `qualified` form; any
+/// that still collide get a `#n` ordinal in source order. Unique names are
+/// left alone.
+fn disambiguate(functions: &mut [FunctionInfo], qualified: &[String]) {
+    fn counts<'a>(names: impl Iterator<Item = &'a str>) -> HashMap<&'a str, usize> {
+        let mut counts = HashMap::new();
+        for name in names {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    let plain = counts(functions.iter().map(|f| f.name.as_str()));
+    let names: Vec<String> = functions
+        .iter()
+        .zip(qualified)
+        .map(|(f, q)| {
+            if plain[f.name.as_str()] > 1 {
+                q.clone()
+            } else {
+                f.name.clone()
+            }
+        })
+        .collect();
+
+    let remaining = counts(names.iter().map(String::as_str));
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut renamed = Vec::with_capacity(names.len());
+    for name in &names {
+        if remaining[name.as_str()] > 1 {
+            let n = seen.entry(name).or_insert(0);
+            *n += 1;
+            renamed.push(format!("{name}#{n}"));
+        } else {
+            renamed.push(name.clone());
+        }
+    }
+    for (f, name) in functions.iter_mut().zip(renamed) {
+        f.name = name;
+    }
+}
+
+/// A report name reduced to a plain path, the form symbol names from
+/// coverage and API tools reduce to as well, so they can be matched up:
+/// without the cfg suffix and `#n` ordinal [`disambiguate`] adds, without
+/// generics, and `<Foo<T> as Trait>::fmt` as `Foo::fmt`.
+pub fn plain_name(name: &str) -> String {
+    let name = name.split(" [").next().unwrap_or(name);
+    let name = match name.rsplit_once('#') {
+        Some((base, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => name,
+    };
+    let name = match name.strip_prefix('<').and_then(|n| n.split_once(" as ")) {
+        Some((ty, rest)) => match rest.rsplit_once(">::") {
+            Some((_, method)) => format!("{ty}::{method}"),
+            None => name.to_string(),
+        },
+        None => name.to_string(),
+    };
+    let mut plain = String::new();
+    let mut depth = 0usize;
+    let mut prev = ' ';
+    for c in name.chars() {
+        match c {
+            '<' => depth += 1,
+            // The arrow of a `fn(A) -> B` argument doesn't close anything.
+            '>' if prev != '-' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => plain.push(c),
+            _ => {}
+        }
+        prev = c;
+    }
+    plain
+}
+
+/// Attribute macros that turn a function into a test, like `#[test]` does.
+const TEST_WRAPPERS: [&str; 10] = [
+    "tokio::test",
+    "async_std::test",
+    "actix_rt::test",
+    "actix_web::test",
+    "test_log::test",
+    "sqlx::test",
+    "test_case",
+    "test_case::test_case",
+    "rstest",
+    "rstest::rstest",
+];
+
+/// Attribute macros that wrap a function's body in generated setup code
+/// (a runtime, a tracing span) spanned to the attribute or signature.
+pub const WRAPPERS: [&str; 6] = [
+    "tokio::main",
+    "async_std::main",
+    "actix_web::main",
+    "actix_rt::main",
+    "tracing::instrument",
+    "instrument",
+];
+
+/// An attribute's path as written, e.g. `tokio::main`.
+fn attr_path(attr: &Attribute) -> String {
+    attr.path()
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+pub(crate) fn has_test_attr(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|a| {
+        let path = attr_path(a);
+        path == "test" || TEST_WRAPPERS.contains(&path.as_str())
+    })
 }
 
-fn has_test_attr(attrs: &[Attribute]) -> bool {
-    attrs.iter().any(|a| a.path().is_ident("test"))
+/// The first of `attrs` that is one of [`WRAPPERS`].
+fn wrapper(attrs: &[Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .map(attr_path)
+        .find(|path| WRAPPERS.contains(&path.as_str()))
 }
 
-fn has_cfg_test_attr(attrs: &[Attribute]) -> bool {
+pub(crate) fn has_cfg_test_attr(attrs: &[Attribute]) -> bool {
     attrs.iter().any(|a| {
         if !a.path().is_ident("cfg") {
             return false;
@@ -43,20 +309,109 @@ fn has_cfg_test_attr(attrs: &[Attribute]) -> bool {
     })
 }
 
+/// `#[cfg(...)]` predicates on an item, e.g. `cfg(unix)`.
+fn cfg_predicates(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|a| a.path().is_ident("cfg"))
+        .filter_map(|a| match &a.meta {
+            syn::Meta::List(list) => {
+                let pred: String = list.tokens.to_string().split_whitespace().collect();
+                Some(format!("cfg({pred})"))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Tags from `#[cfg_attr(any(), craprs(tag = "parser"))]` attributes, in
+/// order. rustc rejects attributes it doesn't know, so the `craprs(...)` part
+/// sits behind a `cfg_attr` that never applies; a bare `#[craprs(tag = ...)]`
+/// (from a no-op attribute macro) counts too. Several tags can be listed in
+/// one attribute: `craprs(tag = "parser", tag = "hot")`.
+pub fn attr_tags(attrs: &[Attribute]) -> Vec<String> {
+    let mut tags = Vec::new();
+    for attr in attrs {
+        let metas = if attr.path().is_ident("craprs") {
+            vec![attr.meta.clone()]
+        } else if attr.path().is_ident("cfg_attr") {
+            // The predicate comes first, then the attributes it guards.
+            attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .map(|metas| metas.into_iter().skip(1).collect())
+                .unwrap_or_default()
+        } else {
+            continue;
+        };
+        for meta in metas {
+            let Meta::List(list) = meta else { continue };
+            if !list.path.is_ident("craprs") {
+                continue;
+            }
+            let _ = list.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    let tag: LitStr = meta.value()?.parse()?;
+                    tags.push(tag.value());
+                }
+                Ok(())
+            });
+        }
+    }
+    tags
+}
+
+/// Plain `pub`: `pub(crate)` and narrower aren't part of the public API.
+fn is_pub(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+/// Append cfg predicates to a qualified name: `run [cfg(unix)]`.
+fn with_cfg(name: String, cfgs: &[String]) -> String {
+    if cfgs.is_empty() {
+        name
+    } else {
+        format!("{name} [{}]", cfgs.join(", "))
+    }
+}
+
 struct FunctionExtractor {
+    opts: ComplexityOptions,
     functions: Vec<FunctionInfo>,
+    /// Fully qualified name for each entry in `functions`, used on collisions.
+    qualified: Vec<String>,
     impl_name: Option<String>,
+    /// `Foo<T>` or `<Foo<T> as Trait>` for the impl being visited.
+    impl_qualifier: Option<String>,
+    /// cfg predicates on the impl being visited.
+    impl_cfg: Vec<String>,
+    /// Tags on the impl being visited, inherited by its methods.
+    impl_tags: Vec<String>,
+    /// The impl being visited is a trait impl.
+    impl_trait: bool,
+    /// Non-`pub` inline modules around the item being visited.
+    private_mods: usize,
+    /// Function bodies around the item being visited.
+    fn_depth: usize,
+}
+
+impl FunctionExtractor {
+    fn push(&mut self, info: FunctionInfo, qualified: String) {
+        self.functions.push(info);
+        self.qualified.push(qualified);
+    }
 }
 
 impl<'ast> Visit<'ast> for FunctionExtractor {
     fn visit_item(&mut self, node: &'ast Item) {
         // Skip #[cfg(test)] modules entirely
-        if let Item::Mod(m) = node {
-            if has_cfg_test_attr(&m.attrs) {
-                return;
-            }
+        if let Item::Mod(m) = node
+            && has_cfg_test_attr(&m.attrs)
+        {
+            return;
         }
+        let private_mod = matches!(node, Item::Mod(m) if !is_pub(&m.vis));
+        self.private_mods += usize::from(private_mod);
         syn::visit::visit_item(self, node);
+        self.private_mods -= usize::from(private_mod);
     }
 
     fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
@@ -64,71 +419,137 @@ impl<'ast> Visit<'ast> for FunctionExtractor {
             let name = node.sig.ident.to_string();
             let start = node.sig.ident.span().start().line;
             let end = span_end_line(&node.block);
-            let complexity = compute_complexity(&node.block);
-            self.functions.push(FunctionInfo {
-                name,
-                start_line: start,
-                end_line: end,
-                complexity,
-            });
+            let (complexity, shape, decision_points) = compute_complexity(&node.block, self.opts);
+            let qualified = with_cfg(name.clone(), &cfg_predicates(&node.attrs));
+            self.push(
+                FunctionInfo {
+                    name,
+                    start_line: start,
+                    end_line: end,
+                    complexity,
+                    shape,
+                    decision_points,
+                    trivial: is_trivial(&node.block),
+                    fingerprint: fingerprint(&node.block),
+                    dyn_calls: dyn_calls(&node.sig, &node.block),
+                    wrapper: wrapper(&node.attrs),
+                    body_line: body_start_line(&node.block),
+                    tags: attr_tags(&node.attrs),
+                    public: self.fn_depth == 0 && is_pub(&node.vis),
+                    in_private_mod: self.private_mods > 0,
+                },
+                qualified,
+            );
         }
         // Visit statements to find nested fn items (they're extracted separately)
+        self.fn_depth += 1;
         for stmt in &node.block.stmts {
             if let syn::Stmt::Item(item) = stmt {
                 self.visit_item(item);
             }
         }
+        self.fn_depth -= 1;
     }
 
     fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
         let name = type_name(&node.self_ty);
-        let prev = self.impl_name.take();
-        self.impl_name = Some(name);
+        let self_ty = full_type_name(&node.self_ty);
+        let qualifier = match node.trait_ {
+            Some((_, ref path, _)) => format!("<{self_ty} as {}>", path_name(path)),
+            None => self_ty,
+        };
+        let prev = self.impl_name.replace(name);
+        let prev_qualifier = self.impl_qualifier.replace(qualifier);
+        let prev_cfg = std::mem::replace(&mut self.impl_cfg, cfg_predicates(&node.attrs));
+        let prev_tags = std::mem::replace(&mut self.impl_tags, attr_tags(&node.attrs));
+        let prev_trait = std::mem::replace(&mut self.impl_trait, node.trait_.is_some());
         for item in &node.items {
             self.visit_impl_item(item);
         }
         self.impl_name = prev;
+        self.impl_qualifier = prev_qualifier;
+        self.impl_cfg = prev_cfg;
+        self.impl_tags = prev_tags;
+        self.impl_trait = prev_trait;
     }
 
     fn visit_impl_item(&mut self, node: &'ast ImplItem) {
-        if let ImplItem::Fn(method) = node {
-            if !has_test_attr(&method.attrs) {
-                let base = method.sig.ident.to_string();
-                let name = if let Some(ref impl_name) = self.impl_name {
-                    format!("{impl_name}::{base}")
-                } else {
-                    base
-                };
-                let start = method.sig.ident.span().start().line;
-                let end = span_end_line(&method.block);
-                let complexity = compute_complexity(&method.block);
-                self.functions.push(FunctionInfo {
+        if let ImplItem::Fn(method) = node
+            && !has_test_attr(&method.attrs)
+        {
+            let base = method.sig.ident.to_string();
+            let name = if let Some(ref impl_name) = self.impl_name {
+                format!("{impl_name}::{base}")
+            } else {
+                base.clone()
+            };
+            let qualified = match self.impl_qualifier {
+                Some(ref q) => format!("{q}::{base}"),
+                None => base,
+            };
+            let mut cfgs = self.impl_cfg.clone();
+            cfgs.extend(cfg_predicates(&method.attrs));
+            let start = method.sig.ident.span().start().line;
+            let end = span_end_line(&method.block);
+            let (complexity, shape, decision_points) = compute_complexity(&method.block, self.opts);
+            let mut tags = self.impl_tags.clone();
+            tags.extend(attr_tags(&method.attrs));
+            self.push(
+                FunctionInfo {
                     name,
                     start_line: start,
                     end_line: end,
                     complexity,
-                });
-            }
+                    shape,
+                    decision_points,
+                    trivial: is_trivial(&method.block),
+                    fingerprint: fingerprint(&method.block),
+                    dyn_calls: dyn_calls(&method.sig, &method.block),
+                    wrapper: wrapper(&method.attrs),
+                    body_line: body_start_line(&method.block),
+                    tags,
+                    public: self.fn_depth == 0 && (self.impl_trait || is_pub(&method.vis)),
+                    in_private_mod: self.private_mods > 0,
+                },
+                with_cfg(qualified, &cfgs),
+            );
         }
     }
 
     fn visit_item_trait(&mut self, node: &'ast syn::ItemTrait) {
+        let trait_tags = attr_tags(&node.attrs);
+        let public = self.fn_depth == 0 && is_pub(&node.vis);
         for item in &node.items {
-            if let TraitItem::Fn(method) = item {
-                if let Some(ref block) = method.default {
-                    if !has_test_attr(&method.attrs) {
-                        let name = method.sig.ident.to_string();
-                        let start = method.sig.ident.span().start().line;
-                        let end = span_end_line(block);
-                        let complexity = compute_complexity(block);
-                        self.functions.push(FunctionInfo {
-                            name,
-                            start_line: start,
-                            end_line: end,
-                            complexity,
-                        });
-                    }
-                }
+            if let TraitItem::Fn(method) = item
+                && let Some(ref block) = method.default
+                && !has_test_attr(&method.attrs)
+            {
+                let name = method.sig.ident.to_string();
+                let start = method.sig.ident.span().start().line;
+                let end = span_end_line(block);
+                let (complexity, shape, decision_points) = compute_complexity(block, self.opts);
+                let qualified = with_cfg(name.clone(), &cfg_predicates(&method.attrs));
+                let mut tags = trait_tags.clone();
+                tags.extend(attr_tags(&method.attrs));
+                self.push(
+                    FunctionInfo {
+                        name,
+                        start_line: start,
+                        end_line: end,
+                        complexity,
+                        shape,
+                        decision_points,
+                        trivial: is_trivial(block),
+                        fingerprint: fingerprint(block),
+                        dyn_calls: dyn_calls(&method.sig, block),
+                        wrapper: wrapper(&method.attrs),
+                        body_line: body_start_line(block),
+                        tags,
+                        public,
+                        in_private_mod: self.private_mods > 0,
+                    },
+                    qualified,
+                );
             }
         }
     }
@@ -147,65 +568,261 @@ fn type_name(ty: &syn::Type) -> String {
     }
 }
 
+/// Like [`type_name`], but keeps generic arguments (`Foo<Vec<u8>>`) and
+/// renders references, slices and tuples.
+fn full_type_name(ty: &syn::Type) -> String {
+    match ty {
+        syn::Type::Path(tp) => path_name(&tp.path),
+        syn::Type::Reference(r) => {
+            let m = if r.mutability.is_some() { "mut " } else { "" };
+            format!("&{m}{}", full_type_name(&r.elem))
+        }
+        syn::Type::Slice(s) => format!("[{}]", full_type_name(&s.elem)),
+        syn::Type::Array(a) => format!("[{}; _]", full_type_name(&a.elem)),
+        syn::Type::Tuple(t) => {
+            let elems: Vec<String> = t.elems.iter().map(full_type_name).collect();
+            format!("({})", elems.join(", "))
+        }
+        _ => "_".to_string(),
+    }
+}
+
+fn path_name(path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|s| match &s.arguments {
+            syn::PathArguments::AngleBracketed(args) => {
+                let args: Vec<String> = args.args.iter().map(generic_arg_name).collect();
+                format!("{}<{}>", s.ident, args.join(", "))
+            }
+            _ => s.ident.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn generic_arg_name(arg: &syn::GenericArgument) -> String {
+    match arg {
+        syn::GenericArgument::Type(ty) => full_type_name(ty),
+        syn::GenericArgument::Lifetime(l) => l.to_string(),
+        syn::GenericArgument::Const(Expr::Lit(lit)) => match &lit.lit {
+            syn::Lit::Int(i) => i.base10_digits().to_string(),
+            syn::Lit::Bool(b) => b.value.to_string(),
+            _ => "_".to_string(),
+        },
+        syn::GenericArgument::Const(Expr::Path(p)) => path_name(&p.path),
+        _ => "_".to_string(),
+    }
+}
+
+/// Boilerplate with a single path through it: the body is one expression
+/// (or statement) built only from paths, literals, field accesses, struct and
+/// tuple literals, argument-free method calls, calls whose arguments are
+/// themselves plain, and assignments between those. Covers `Self { .. }`
+/// constructors, `Default` impls, getters and setters.
+pub fn is_trivial(block: &syn::Block) -> bool {
+    match block.stmts.as_slice() {
+        [] => true,
+        [syn::Stmt::Expr(expr, _)] => is_plain(expr),
+        _ => false,
+    }
+}
+
+fn is_plain(expr: &Expr) -> bool {
+    match expr {
+        Expr::Path(_) | Expr::Lit(_) => true,
+        Expr::Field(f) => is_plain(&f.base),
+        Expr::Reference(r) => is_plain(&r.expr),
+        Expr::Paren(p) => is_plain(&p.expr),
+        Expr::Cast(c) => is_plain(&c.expr),
+        Expr::Unary(u) => matches!(u.op, syn::UnOp::Deref(_)) && is_plain(&u.expr),
+        Expr::Assign(a) => is_plain(&a.left) && is_plain(&a.right),
+        Expr::Struct(s) => {
+            s.fields.iter().all(|f| is_plain(&f.expr)) && s.rest.as_deref().is_none_or(is_plain)
+        }
+        Expr::Tuple(t) => t.elems.iter().all(is_plain),
+        Expr::Array(a) => a.elems.iter().all(is_plain),
+        Expr::Call(c) => matches!(*c.func, Expr::Path(_)) && c.args.iter().all(is_plain),
+        Expr::MethodCall(m) => m.args.is_empty() && is_plain(&m.receiver),
+        _ => false,
+    }
+}
+
+/// Structural fingerprint of a function body: a [`content_hash`] of its
+/// tokens, so formatting and comments don't matter and neither do the
+/// function's name, signature or location.
+pub fn fingerprint(block: &syn::Block) -> String {
+    content_hash(&block.to_token_stream().to_string())
+}
+
 fn span_end_line(block: &syn::Block) -> usize {
     block.brace_token.span.close().end().line
 }
 
-fn compute_complexity(block: &syn::Block) -> u32 {
-    let mut visitor = ComplexityVisitor { complexity: 1 };
+fn body_start_line(block: &syn::Block) -> usize {
+    match block.stmts.first() {
+        Some(stmt) => stmt.span().start().line,
+        None => block.brace_token.span.open().start().line,
+    }
+}
+
+fn compute_complexity(
+    block: &syn::Block,
+    opts: ComplexityOptions,
+) -> (u32, FunctionShape, Vec<DecisionPoint>) {
+    let mut visitor = ComplexityVisitor {
+        opts,
+        complexity: 1,
+        depth: 0,
+        shape: FunctionShape::default(),
+        points: Vec::new(),
+    };
     visitor.visit_block(block);
-    visitor.complexity
+    (visitor.complexity, visitor.shape, visitor.points)
+}
+
+/// Number of `&&` / `||` operators in a boolean expression.
+fn condition_ops(expr: &Expr) -> u32 {
+    match expr {
+        Expr::Binary(b) if matches!(b.op, BinOp::And(_) | BinOp::Or(_)) => {
+            1 + condition_ops(&b.left) + condition_ops(&b.right)
+        }
+        Expr::Paren(p) => condition_ops(&p.expr),
+        Expr::Unary(u) => condition_ops(&u.expr),
+        _ => 0,
+    }
 }
 
 struct ComplexityVisitor {
+    opts: ComplexityOptions,
     complexity: u32,
+    depth: u32,
+    shape: FunctionShape,
+    points: Vec<DecisionPoint>,
+}
+
+impl ComplexityVisitor {
+    fn decision(&mut self, kind: DecisionKind, span: proc_macro2::Span) {
+        self.weighted_decision(kind, span, 1);
+    }
+
+    /// Record a decision point that adds `weight` to complexity; weight 0
+    /// means the construct isn't counted at all.
+    fn weighted_decision(&mut self, kind: DecisionKind, span: proc_macro2::Span, weight: u32) {
+        if weight == 0 {
+            return;
+        }
+        self.complexity += weight;
+        self.points.push(DecisionPoint {
+            kind,
+            line: span.start().line,
+        });
+    }
+
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.shape.max_nesting = self.shape.max_nesting.max(self.depth);
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn note_condition(&mut self, cond: &Expr) {
+        self.shape.max_condition_ops = self.shape.max_condition_ops.max(condition_ops(cond));
+    }
 }
 
 impl<'ast> Visit<'ast> for ComplexityVisitor {
+    // Walk the whole `if / else if / else` ladder here so it is one nesting level.
     fn visit_expr_if(&mut self, node: &'ast ExprIf) {
-        self.complexity += 1;
-        self.visit_expr(&node.cond);
-        self.visit_block(&node.then_branch);
-        if let Some((_, ref else_branch)) = node.else_branch {
-            self.visit_expr(else_branch);
+        self.enter();
+        let mut branches = 0;
+        let mut current = node;
+        loop {
+            self.decision(DecisionKind::If, current.if_token.span);
+            branches += 1;
+            self.note_condition(&current.cond);
+            self.visit_expr(&current.cond);
+            self.visit_block(&current.then_branch);
+            match current.else_branch {
+                Some((_, ref else_branch)) => match &**else_branch {
+                    Expr::If(next) => current = next,
+                    other => {
+                        self.visit_expr(other);
+                        break;
+                    }
+                },
+                None => break,
+            }
         }
+        self.shape.longest_if_chain = self.shape.longest_if_chain.max(branches);
+        self.leave();
+    }
+
+    fn visit_expr_match(&mut self, node: &'ast ExprMatch) {
+        self.shape.max_match_arms = self.shape.max_match_arms.max(node.arms.len());
+        self.enter();
+        syn::visit::visit_expr_match(self, node);
+        self.leave();
     }
 
     fn visit_expr_while(&mut self, node: &'ast ExprWhile) {
-        self.complexity += 1;
+        self.decision(DecisionKind::While, node.while_token.span);
+        self.note_condition(&node.cond);
+        self.enter();
         syn::visit::visit_expr_while(self, node);
+        self.leave();
     }
 
     fn visit_expr_for_loop(&mut self, node: &'ast ExprForLoop) {
-        self.complexity += 1;
+        self.decision(DecisionKind::For, node.for_token.span);
+        self.enter();
         syn::visit::visit_expr_for_loop(self, node);
+        self.leave();
     }
 
     fn visit_expr_loop(&mut self, node: &'ast ExprLoop) {
-        self.complexity += 1;
+        self.decision(DecisionKind::Loop, node.loop_token.span);
+        self.enter();
         syn::visit::visit_expr_loop(self, node);
+        self.leave();
     }
 
     fn visit_arm(&mut self, node: &'ast Arm) {
-        self.complexity += 1;
+        self.decision(DecisionKind::MatchArm, node.pat.span());
+        if self.opts.weigh_patterns
+            && let Some((ref if_token, _)) = node.guard
+        {
+            self.decision(DecisionKind::Guard, if_token.span);
+        }
         syn::visit::visit_arm(self, node);
     }
 
     fn visit_expr_binary(&mut self, node: &'ast ExprBinary) {
         match node.op {
-            BinOp::And(_) | BinOp::Or(_) => {
-                self.complexity += 1;
-            }
+            BinOp::And(_) => self.decision(DecisionKind::And, node.op.span()),
+            BinOp::Or(_) => self.decision(DecisionKind::Or, node.op.span()),
             _ => {}
         }
         syn::visit::visit_expr_binary(self, node);
     }
 
     fn visit_expr_try(&mut self, node: &'ast ExprTry) {
-        self.complexity += 1;
+        let weight = self.opts.try_weight;
+        self.weighted_decision(DecisionKind::Try, node.question_token.span, weight);
         syn::visit::visit_expr_try(self, node);
     }
 
+    fn visit_pat_or(&mut self, node: &'ast PatOr) {
+        if self.opts.weigh_patterns {
+            for case in node.cases.iter().skip(1) {
+                self.decision(DecisionKind::OrPattern, case.span());
+            }
+        }
+        syn::visit::visit_pat_or(self, node);
+    }
+
     // Don't recurse into nested fn items — they have their own complexity
     fn visit_item_fn(&mut self, _node: &'ast syn::ItemFn) {}
 
@@ -386,6 +1003,123 @@ fn test_fn() {
         assert_eq!(fns[0].name, "real_fn");
     }
 
+    #[test]
+    fn attribute_macro_wrappers() {
+        let src = r#"
+#[tokio::main]
+async fn main() -> Result<()> {
+    serve().await
+}
+
+#[tracing::instrument(skip(db))]
+fn load(db: &Db) { db.get() }
+
+#[tokio::test]
+async fn serves() {}
+
+#[test_case(1 ; "one")]
+fn doubles(n: u8) {}
+"#;
+        let fns = extract_functions(src);
+        let names: Vec<&str> = fns.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "load"]);
+        assert_eq!(fns[0].wrapper.as_deref(), Some("tokio::main"));
+        assert_eq!((fns[0].start_line, fns[0].body_line), (3, 4));
+        assert_eq!(fns[1].wrapper.as_deref(), Some("tracing::instrument"));
+        assert_eq!((fns[1].start_line, fns[1].body_line), (8, 8));
+    }
+
+    #[test]
+    fn tags_come_from_craprs_attributes() {
+        let src = r#"
+#[cfg_attr(any(), craprs(tag = "parser", tag = "hot"))]
+fn parse() {}
+
+#[craprs(tag = "ffi")]
+#[cfg_attr(unix, inline)]
+fn call() {}
+
+#[cfg_attr(any(), craprs(tag = "io"))]
+impl Reader {
+    fn read(&self) {}
+    #[cfg_attr(any(), craprs(tag = "hot"))]
+    fn peek(&self) {}
+}
+
+fn plain() {}
+"#;
+        let fns = extract_functions(src);
+        let tags: Vec<(&str, Vec<&str>)> = fns
+            .iter()
+            .map(|f| (f.name.as_str(), f.tags.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("parse", vec!["parser", "hot"]),
+                ("call", vec!["ffi"]),
+                ("Reader::read", vec!["io"]),
+                ("Reader::peek", vec!["io", "hot"]),
+                ("plain", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_which_functions_are_declared_public() {
+        let src = r#"
+pub fn api() {
+    pub fn local() {}
+}
+pub(crate) fn crate_only() {}
+impl Parser {
+    pub fn parse(&self) {}
+    fn step(&self) {}
+}
+impl Display for Parser {
+    fn fmt(&self) {}
+}
+pub trait Visit {
+    fn visit(&self) {}
+}
+mod detail {
+    pub fn helper() {}
+}
+"#;
+        let fns = extract_functions(src);
+        let flags: Vec<(&str, bool, bool)> = fns
+            .iter()
+            .map(|f| (f.name.as_str(), f.public, f.in_private_mod))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("api", true, false),
+                ("local", false, false),
+                ("crate_only", false, false),
+                ("Parser::parse", true, false),
+                ("Parser::step", false, false),
+                ("Parser::fmt", true, false),
+                ("visit", true, false),
+                ("helper", true, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_names_drop_generics_and_disambiguation() {
+        assert_eq!(plain_name("Map<K, V>::get"), "Map::get");
+        assert_eq!(plain_name("<Map<K, V> as Display>::fmt#2"), "Map::fmt");
+        assert_eq!(plain_name("run [cfg(unix)]"), "run");
+        assert_eq!(plain_name("run [cfg(unix)]#2"), "run");
+        assert_eq!(plain_name("helper#12"), "helper");
+        assert_eq!(
+            plain_name("m::apply<fn(u8) -> Vec<u8>>::inner"),
+            "m::apply::inner"
+        );
+        assert_eq!(plain_name("m::main::{closure#0}"), "m::main::{closure#0}");
+    }
+
     #[test]
     fn skips_cfg_test_modules() {
         let src = r#"
@@ -442,4 +1176,241 @@ fn second(x: bool) -> i32 {
         assert_eq!(fns[1].start_line, 6);
         assert_eq!(fns[1].end_line, 8);
     }
+
+    fn shape(source: &str) -> FunctionShape {
+        let fns = extract_functions(source);
+        assert_eq!(fns.len(), 1, "expected exactly 1 function, got: {fns:?}");
+        fns[0].shape.clone()
+    }
+
+    #[test]
+    fn shape_of_match_and_nesting() {
+        let src = r#"
+fn foo(xs: Vec<i32>) {
+    for x in xs {
+        if x > 0 {
+            match x { 1 => {}, 2 => {}, _ => loop { break; } }
+        }
+    }
+}
+"#;
+        let s = shape(src);
+        assert_eq!(s.max_match_arms, 3);
+        assert_eq!(s.max_nesting, 4);
+    }
+
+    #[test]
+    fn else_if_ladder_is_one_level() {
+        let src = r#"
+fn foo(x: i32, a: bool, b: bool, c: bool) -> i32 {
+    if x == 1 { 1 } else if x == 2 { 2 } else if a && (b || !c) { 3 } else { 0 }
+}
+"#;
+        assert_eq!(cc(src), 6);
+        let s = shape(src);
+        assert_eq!(s.longest_if_chain, 3);
+        assert_eq!(s.max_nesting, 1);
+        assert_eq!(s.max_condition_ops, 2);
+    }
+
+    fn names(source: &str) -> Vec<String> {
+        extract_functions(source)
+            .into_iter()
+            .map(|f| f.name)
+            .collect()
+    }
+
+    #[test]
+    fn generic_impls_are_told_apart() {
+        let src = r#"
+impl<T: Write> Foo<T> { fn serialize(&self) {} fn flush(&self) {} }
+impl Foo<Vec<u8>> { fn serialize(&self) {} }
+impl Bar<3> { fn len(&self) {} }
+impl Bar<4> { fn len(&self) {} }
+"#;
+        assert_eq!(
+            names(src),
+            vec![
+                "Foo<T>::serialize",
+                "Foo::flush",
+                "Foo<Vec<u8>>::serialize",
+                "Bar<3>::len",
+                "Bar<4>::len",
+            ]
+        );
+    }
+
+    #[test]
+    fn trait_impls_and_cfg_variants_are_told_apart() {
+        let src = r#"
+impl Display for Foo { fn fmt(&self) {} }
+impl Debug for Foo { fn fmt(&self) {} }
+#[cfg(unix)]
+fn run() {}
+#[cfg(not(unix))]
+fn run() {}
+"#;
+        assert_eq!(
+            names(src),
+            vec![
+                "<Foo as Display>::fmt",
+                "<Foo as Debug>::fmt",
+                "run [cfg(unix)]",
+                "run [cfg(not(unix))]",
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_qualified_names_get_ordinals() {
+        let src = r#"
+mod a { pub fn helper() {} }
+mod b { pub fn helper() {} }
+fn unique() {}
+"#;
+        assert_eq!(names(src), vec!["helper#1", "helper#2", "unique"]);
+    }
+
+    #[test]
+    fn decision_points_match_complexity() {
+        let src = r#"
+fn foo(xs: &[Option<i32>], a: bool, b: bool) -> Result<i32, E> {
+    for x in xs {
+        if a && b {
+            match x { Some(v) => return Ok(*v), None => {} }
+        }
+    }
+    parse()?;
+    Ok(0)
+}
+"#;
+        let fns = extract_functions(src);
+        let kinds: Vec<(&str, usize)> = fns[0]
+            .decision_points
+            .iter()
+            .map(|p| (p.kind.label(), p.line))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("for", 3),
+                ("if", 4),
+                ("and", 4),
+                ("match_arm", 5),
+                ("match_arm", 5),
+                ("try", 8),
+            ]
+        );
+        assert_eq!(fns[0].complexity as usize, 1 + kinds.len());
+    }
+
+    #[test]
+    fn try_weight_is_configurable() {
+        let src = "fn foo() -> Result<(), E> { a()?; b()?; Ok(()) }";
+        assert_eq!(cc(src), 3);
+        let with = |try_weight| ComplexityOptions {
+            try_weight,
+            ..ComplexityOptions::default()
+        };
+        let cc_with = |try_weight| extract_functions_with(src, with(try_weight))[0].complexity;
+        assert_eq!(cc_with(0), 1);
+        assert_eq!(cc_with(2), 5);
+        let ignored = extract_functions_with(src, with(0));
+        assert!(ignored[0].decision_points.is_empty());
+    }
+
+    #[test]
+    fn pattern_weighting_counts_alternatives_and_guards() {
+        let src = r#"
+fn foo(x: Option<u8>) -> u8 {
+    match x {
+        Some(1 | 2 | 3) => 1,
+        Some(n) if n > 10 => 2,
+        _ => 0,
+    }
+}
+"#;
+        assert_eq!(cc(src), 4);
+        let opts = ComplexityOptions {
+            weigh_patterns: true,
+            ..ComplexityOptions::default()
+        };
+        let f = &extract_functions_with(src, opts)[0];
+        assert_eq!(f.complexity, 7);
+        let kinds: Vec<&str> = f.decision_points.iter().map(|p| p.kind.label()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "match_arm",
+                "or_pattern",
+                "or_pattern",
+                "match_arm",
+                "guard",
+                "match_arm"
+            ]
+        );
+    }
+
+    #[test]
+    fn trivial_constructors_getters_and_setters() {
+        let src = r#"
+struct Foo { a: u8, b: Vec<u8> }
+impl Foo {
+    fn new(a: u8) -> Self { Self { a, b: Vec::new() } }
+    fn a(&self) -> u8 { self.a }
+    fn b(&self) -> &[u8] { &self.b }
+    fn len(&self) -> usize { self.b.len() }
+    fn set_a(&mut self, a: u8) { self.a = a; }
+    fn push(&mut self, x: u8) { self.b.push(x) }
+    fn checked(&self) -> Option<u8> { self.a.checked_add(1) }
+    fn two(&self) -> u8 { let x = self.a; x }
+    fn pick(&self) -> u8 { if self.a > 0 { self.a } else { 1 } }
+}
+impl Default for Foo {
+    fn default() -> Self { Foo::new(0) }
+}
+"#;
+        let trivial: Vec<(String, bool)> = extract_functions(src)
+            .into_iter()
+            .map(|f| (f.name, f.trivial))
+            .collect();
+        let expected = [
+            ("Foo::new", true),
+            ("Foo::a", true),
+            ("Foo::b", true),
+            ("Foo::len", true),
+            ("Foo::set_a", true),
+            ("Foo::push", false),
+            ("Foo::checked", false),
+            ("Foo::two", false),
+            ("Foo::pick", false),
+            ("Foo::default", true),
+        ];
+        let expected: Vec<(String, bool)> =
+            expected.iter().map(|&(n, t)| (n.to_string(), t)).collect();
+        assert_eq!(trivial, expected);
+    }
+
+    #[test]
+    fn fingerprints_ignore_name_formatting_and_comments() {
+        let fns = extract_functions(
+            r#"
+fn parse(x: u8) -> u8 { if x > 1 { x } else { 0 } }
+mod m {
+    // moved and renamed
+    fn parse_byte(y: u8) -> u8 {
+        if x > 1 {
+            x
+        } else {
+            0
+        }
+    }
+}
+fn other(x: u8) -> u8 { if x > 2 { x } else { 0 } }
+"#,
+        );
+        assert_eq!(fns[0].fingerprint, fns[1].fingerprint);
+        assert_ne!(fns[0].fingerprint, fns[2].fingerprint);
+        assert_eq!(fns[0].fingerprint.len(), 16);
+    }
 }
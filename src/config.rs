@@ -0,0 +1,256 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::ci::{CiMode, Provider};
+
+/// Where an effective setting came from, for `craprs config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    /// Given as a flag.
+    CommandLine,
+    /// Set by an environment variable.
+    Environment,
+    /// The project's `craprs.toml`.
+    ProjectPolicy,
+    /// The `--policy` file.
+    Policy,
+    /// Nothing set it.
+    Default,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::CommandLine => "command line",
+            Source::Environment => "environment",
+            Source::ProjectPolicy => "project policy",
+            Source::Policy => "--policy",
+            Source::Default => "default",
+        }
+    }
+}
+
+/// One effective setting: a report flag or a policy key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Setting {
+    /// Flag name without `--`, or the policy key.
+    pub name: String,
+    /// A string, a bool for switches, a list for repeatable settings, or
+    /// null when unset.
+    pub value: Value,
+    pub source: Source,
+    /// The variable, policy file or setting behind `source`, when there's
+    /// more than one it could be.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+}
+
+/// The settings a report run ends up with, after layering the `--policy`
+/// file, the project's `craprs.toml`, the environment and the command line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Resolved {
+    /// Policy files that were read, lowest precedence first.
+    pub policy_files: Vec<String>,
+    pub settings: Vec<Setting>,
+}
+
+impl Resolved {
+    pub fn setting(&self, name: &str) -> Option<&Setting> {
+        self.settings.iter().find(|s| s.name == name)
+    }
+
+    /// Resolve what `--ci auto` and `--color auto` turn into in the
+    /// environment `env` (a variable lookup). Color stays `auto` when only
+    /// stdout being a terminal decides it.
+    pub fn apply_environment(&mut self, env: impl Fn(&str) -> Option<String>) {
+        let mut provider = None;
+        if let Some(ci) = self.settings.iter_mut().find(|s| s.name == "ci") {
+            let mode = ci
+                .value
+                .as_str()
+                .and_then(|v| CiMode::from_str(v, true).ok())
+                .unwrap_or_default();
+            provider = Provider::resolve(mode, &env);
+            if mode == CiMode::Auto {
+                match provider {
+                    Some(p) => {
+                        ci.value = mode_name(p.mode());
+                        ci.source = Source::Environment;
+                        ci.origin = Some(p.variable().to_string());
+                    }
+                    None => ci.value = mode_name(CiMode::Off),
+                }
+            }
+        }
+        let ci_source = self.setting("ci").map_or(Source::Default, |s| s.source);
+        let Some(color) = self.settings.iter_mut().find(|s| s.name == "color") else {
+            return;
+        };
+        if color.value != "auto" {
+            return;
+        }
+        if env("NO_COLOR").is_some() {
+            color.value = "never".into();
+            color.source = Source::Environment;
+            color.origin = Some("NO_COLOR".to_string());
+        } else if provider.is_some() {
+            color.value = "never".into();
+            color.source = ci_source;
+            color.origin = Some("--ci".to_string());
+        }
+    }
+
+    /// Settings that something other than a default set.
+    pub fn overrides(&self) -> Resolved {
+        Resolved {
+            policy_files: self.policy_files.clone(),
+            settings: self
+                .settings
+                .iter()
+                .filter(|s| s.source != Source::Default)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+fn mode_name(mode: CiMode) -> Value {
+    mode.to_possible_value()
+        .map_or(Value::Null, |v| v.get_name().into())
+}
+
+/// A setting's value as text: lists comma-separated, `—` when unset or empty.
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => "—".to_string(),
+        Value::Array(items) if items.is_empty() => "—".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(value_text).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// `name  value  source` per setting, aligned, after the policy files read.
+pub fn format_text(resolved: &Resolved) -> String {
+    let mut out = String::new();
+    if resolved.policy_files.is_empty() {
+        out.push_str("policy files: none\n");
+    } else {
+        out.push_str(&format!(
+            "policy files: {}\n",
+            resolved.policy_files.join(", ")
+        ));
+    }
+    let rows: Vec<(&str, String, String)> = resolved
+        .settings
+        .iter()
+        .map(|s| {
+            let source = match s.origin {
+                Some(ref origin) => format!("{} ({origin})", s.source.label()),
+                None => s.source.label().to_string(),
+            };
+            (s.name.as_str(), value_text(&s.value), source)
+        })
+        .collect();
+    let name_width = rows.iter().map(|r| r.0.chars().count()).max().unwrap_or(0);
+    let value_width = rows.iter().map(|r| r.1.chars().count()).max().unwrap_or(0);
+    for (name, value, source) in rows {
+        out.push_str(&format!(
+            "{name:<name_width$}  {value:<value_width$}  {source}\n"
+        ));
+    }
+    out
+}
+
+/// The settings as a JSON document: `policy_files` and a `settings` array of
+/// `{name, value, source, origin?}`.
+pub fn to_json(resolved: &Resolved) -> String {
+    serde_json::to_string_pretty(resolved).expect("settings serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn setting(name: &str, value: Value, source: Source) -> Setting {
+        Setting {
+            name: name.into(),
+            value,
+            source,
+            origin: None,
+        }
+    }
+
+    fn resolved() -> Resolved {
+        Resolved {
+            policy_files: vec!["./craprs.toml".into()],
+            settings: vec![
+                Setting {
+                    origin: Some("./craprs.toml".into()),
+                    ..setting("threshold", "40".into(), Source::ProjectPolicy)
+                },
+                setting("ci", "auto".into(), Source::Default),
+                setting("color", "auto".into(), Source::Default),
+                setting("policy", Value::Null, Source::Default),
+                setting("quiet", true.into(), Source::CommandLine),
+                setting(
+                    "coverage-floor",
+                    vec!["core::** >= 80%", "io >= 50%"].into(),
+                    Source::CommandLine,
+                ),
+            ],
+        }
+    }
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        move |name| vars.get(name).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn environment_resolves_ci_and_color() {
+        let mut config = resolved();
+        config.apply_environment(env(&[("GITHUB_ACTIONS", "true")]));
+        let ci = config.setting("ci").unwrap();
+        assert_eq!(ci.value, "github");
+        assert_eq!(ci.source, Source::Environment);
+        assert_eq!(ci.origin.as_deref(), Some("GITHUB_ACTIONS"));
+        let color = config.setting("color").unwrap();
+        assert_eq!(color.value, "never");
+        assert_eq!(color.source, Source::Environment);
+        assert_eq!(color.origin.as_deref(), Some("--ci"));
+
+        let mut config = resolved();
+        config.apply_environment(env(&[("NO_COLOR", "1")]));
+        assert_eq!(config.setting("ci").unwrap().value, "off");
+        assert_eq!(config.setting("ci").unwrap().source, Source::Default);
+        let color = config.setting("color").unwrap();
+        assert_eq!(color.value, "never");
+        assert_eq!(color.origin.as_deref(), Some("NO_COLOR"));
+
+        let mut config = resolved();
+        config.apply_environment(env(&[]));
+        assert_eq!(config.setting("color").unwrap().value, "auto");
+    }
+
+    #[test]
+    fn formats_settings_with_their_sources() {
+        assert_eq!(
+            format_text(&resolved().overrides()),
+            "policy files: ./craprs.toml\n\
+             threshold       40                          project policy (./craprs.toml)\n\
+             quiet           true                        command line\n\
+             coverage-floor  core::** >= 80%, io >= 50%  command line\n"
+        );
+        let json: Value = serde_json::from_str(&to_json(&resolved())).unwrap();
+        assert_eq!(json["policy_files"][0], "./craprs.toml");
+        assert_eq!(json["settings"][0]["source"], "project-policy");
+        assert_eq!(json["settings"][0]["origin"], "./craprs.toml");
+        assert_eq!(json["settings"][1].get("origin"), None);
+        assert_eq!(json["settings"][3]["value"], Value::Null);
+        assert_eq!(json["settings"][5]["value"][1], "io >= 50%");
+    }
+}
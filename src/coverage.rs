@@ -1,10 +1,41 @@
 use std::collections::HashMap;
 use std::path::Path;
 
+use crate::complexity::plain_name;
+use crate::crap::CrapEntry;
+use crate::demangle::demangle;
+
 /// Per-file line coverage: line_number -> hit_count
 pub type LineCoverage = HashMap<usize, u64>;
 
-/// Parse LCOV content into file -> line coverage map.
+/// Coverage in the JSON form `--coverage-format exec:<CMD>` converters print:
+/// `{"files": {"src/lib.rs": {"12": 3, "13": 0}}}`, mapping each source
+/// path to hit counts per 1-based line. Lines left out aren't instrumented.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExternalCoverage {
+    files: HashMap<String, LineCoverage>,
+}
+
+/// Parse the JSON coverage of an external converter into the same map
+/// [`parse_lcov`] builds.
+pub fn parse_coverage_json(content: &str) -> Result<HashMap<String, LineCoverage>, String> {
+    let parsed: ExternalCoverage = serde_json::from_str(content).map_err(|err| {
+        format!("expected {{\"files\": {{\"<path>\": {{\"<line>\": <hits>}}}}}}: {err}")
+    })?;
+    if let Some(path) = parsed
+        .files
+        .iter()
+        .find_map(|(path, lines)| lines.contains_key(&0).then_some(path))
+    {
+        return Err(format!("line numbers start at 1, got line 0 for {path}"));
+    }
+    Ok(parsed.files)
+}
+
+/// Parse LCOV content into file -> line coverage map. Files recorded more
+/// than once, e.g. by several test binaries, get their hits summed; see
+/// [`duplicate_records`].
 pub fn parse_lcov(content: &str) -> HashMap<String, LineCoverage> {
     let mut result: HashMap<String, LineCoverage> = HashMap::new();
     let mut current_file = String::new();
@@ -18,20 +49,520 @@ pub fn parse_lcov(content: &str) -> HashMap<String, LineCoverage> {
         } else if let Some(rest) = line.strip_prefix("DA:") {
             // DA:line_number,hit_count
             let mut parts = rest.splitn(2, ',');
-            if let (Some(ln_str), Some(hits_str)) = (parts.next(), parts.next()) {
-                if let (Ok(ln), Ok(hits)) = (ln_str.parse::<usize>(), hits_str.parse::<u64>()) {
-                    current_lines.insert(ln, hits);
-                }
+            if let (Some(ln_str), Some(hits_str)) = (parts.next(), parts.next())
+                && let (Ok(ln), Ok(hits)) = (ln_str.parse::<usize>(), hits_str.parse::<u64>())
+            {
+                current_lines.insert(ln, hits);
+            }
+        } else if line == "end_of_record" && !current_file.is_empty() {
+            let merged = result.entry(current_file.clone()).or_default();
+            for (ln, hits) in std::mem::take(&mut current_lines) {
+                *merged.entry(ln).or_default() += hits;
+            }
+        }
+    }
+    result
+}
+
+/// Hits per branch from LCOV `BRDA:<line>,<block>,<branch>,<taken>` records,
+/// keyed by `(line, block, branch)`. A `-` (the block never ran) counts as 0.
+pub type BranchCoverage = HashMap<(usize, u32, u32), u64>;
+
+/// Parse the branch records of LCOV content, per file, summing files
+/// recorded more than once like [`parse_lcov`]. `BRF:`/`BRH:` only total a
+/// file's `BRDA:` records, so per-function coverage is computed from those
+/// and the totals aren't needed. Malformed records are skipped, as in
+/// `parse_lcov`; files without branch records are left out.
+pub fn parse_lcov_branches(content: &str) -> HashMap<String, BranchCoverage> {
+    let mut result: HashMap<String, BranchCoverage> = HashMap::new();
+    let mut current_file = String::new();
+    let mut current = BranchCoverage::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(path) = line.strip_prefix("SF:") {
+            current_file = path.to_string();
+            current = BranchCoverage::new();
+        } else if let Some(rest) = line.strip_prefix("BRDA:") {
+            if let Some((key, hits)) = parse_brda(rest) {
+                *current.entry(key).or_default() += hits;
             }
-        } else if line == "end_of_record" {
-            if !current_file.is_empty() {
-                result.insert(current_file.clone(), std::mem::take(&mut current_lines));
+        } else if line == "end_of_record" && !current_file.is_empty() && !current.is_empty() {
+            let merged = result.entry(current_file.clone()).or_default();
+            for (key, hits) in std::mem::take(&mut current) {
+                *merged.entry(key).or_default() += hits;
             }
         }
     }
     result
 }
 
+/// `<line>,<block>,<branch>,<taken>` of a `BRDA:` record.
+fn parse_brda(rest: &str) -> Option<((usize, u32, u32), u64)> {
+    let mut fields = rest.splitn(4, ',');
+    let line = fields.next()?.parse().ok()?;
+    let block = fields.next()?.parse().ok()?;
+    let branch = fields.next()?.parse().ok()?;
+    let hits = match fields.next()? {
+        "-" => 0,
+        taken => taken.parse().ok()?,
+    };
+    Some(((line, block, branch), hits))
+}
+
+/// Percentage (0.0-100.0) of the branches on lines `start..=end` taken at
+/// least once, or `None` when the range has no branches.
+pub fn branch_coverage_for_range(
+    branches: &BranchCoverage,
+    start: usize,
+    end: usize,
+) -> Option<f64> {
+    let (taken, total) = branches
+        .iter()
+        .filter(|((line, _, _), _)| (start..=end).contains(line))
+        .fold((0usize, 0usize), |(taken, total), (_, &hits)| {
+            (taken + usize::from(hits > 0), total + 1)
+        });
+    (total > 0).then(|| 100.0 * taken as f64 / total as f64)
+}
+
+/// A function from LCOV `FN:` records, with its `FNDA:` execution count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionRecord {
+    /// Demangled symbol reduced to a [`plain_name`], e.g. `mycrate::Map::get`.
+    pub name: String,
+    /// Line the function started on when coverage was recorded.
+    pub line: usize,
+    /// Times the function was entered, over all its instantiations.
+    pub hits: u64,
+}
+
+/// Parse the `FN:<line>[,<end line>],<symbol>` and `FNDA:<count>,<symbol>`
+/// records of LCOV content, per file, sorted by line. Symbols are demangled,
+/// and records of one function at one line (generic instantiations, files
+/// recorded more than once) are merged with their counts summed. Functions
+/// without an `FNDA:` record are left out, their count being unknown.
+pub fn parse_lcov_functions(content: &str) -> HashMap<String, Vec<FunctionRecord>> {
+    let mut merged: HashMap<String, HashMap<(String, usize), u64>> = HashMap::new();
+    let mut current_file = String::new();
+    let mut starts: Vec<(&str, usize)> = Vec::new();
+    let mut counts: HashMap<&str, u64> = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(path) = line.strip_prefix("SF:") {
+            current_file = path.to_string();
+            starts.clear();
+            counts.clear();
+        } else if let Some(rest) = line.strip_prefix("FN:") {
+            if let Some((start, rest)) = rest.split_once(',')
+                && let Ok(start) = start.parse()
+            {
+                // LCOV 2 adds the end line: `FN:<line>,<end line>,<symbol>`.
+                let symbol = match rest.split_once(',') {
+                    Some((end, symbol)) if end.parse::<usize>().is_ok() => symbol,
+                    _ => rest,
+                };
+                starts.push((symbol, start));
+            }
+        } else if let Some(rest) = line.strip_prefix("FNDA:") {
+            if let Some((count, symbol)) = rest.split_once(',')
+                && let Ok(count) = count.parse::<u64>()
+            {
+                *counts.entry(symbol).or_default() += count;
+            }
+        } else if line == "end_of_record" && !current_file.is_empty() {
+            let file = merged.entry(current_file.clone()).or_default();
+            for &(symbol, start) in &starts {
+                if let Some(&count) = counts.get(symbol) {
+                    let name = plain_name(&demangle(symbol));
+                    *file.entry((name, start)).or_default() += count;
+                }
+            }
+        }
+    }
+    merged
+        .into_iter()
+        .filter(|(_, functions)| !functions.is_empty())
+        .map(|(file, functions)| {
+            let mut records: Vec<FunctionRecord> = functions
+                .into_iter()
+                .map(|((name, line), hits)| FunctionRecord { name, line, hits })
+                .collect();
+            records.sort_by(|a, b| a.line.cmp(&b.line).then_with(|| a.name.cmp(&b.name)));
+            (file, records)
+        })
+        .collect()
+}
+
+/// The record each of `functions` (report name, start line) was recorded
+/// as. Record names carry the crate and module path, so they match when they
+/// end with the function's [`plain_name`]. A record goes to the matching
+/// function nearest its line, and a function takes the nearest record it was
+/// given, so `cfg` variants of one function don't share a record.
+pub fn match_functions<'a>(
+    records: &'a [FunctionRecord],
+    functions: &[(&str, usize)],
+) -> Vec<Option<&'a FunctionRecord>> {
+    let suffixes: Vec<(String, String)> = functions
+        .iter()
+        .map(|(name, _)| {
+            let name = plain_name(name);
+            let suffix = format!("::{name}");
+            (name, suffix)
+        })
+        .collect();
+    let mut matched: Vec<Option<&FunctionRecord>> = vec![None; functions.len()];
+    for record in records {
+        let owner = functions
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| {
+                let (name, suffix) = &suffixes[i];
+                record.name == *name || record.name.ends_with(suffix.as_str())
+            })
+            .min_by_key(|&(_, &(_, line))| record.line.abs_diff(line));
+        if let Some((i, &(_, line))) = owner
+            && matched[i].is_none_or(|m| record.line.abs_diff(line) < m.line.abs_diff(line))
+        {
+            matched[i] = Some(record);
+        }
+    }
+    matched
+}
+
+/// Line coverage of `start..=end` as recorded `shift` lines further down,
+/// numbered as the lines are now: coverage from before an edit moved the
+/// function.
+pub fn shift_lines(
+    line_cov: &LineCoverage,
+    start: usize,
+    end: usize,
+    shift: isize,
+) -> LineCoverage {
+    (start..=end)
+        .filter_map(|ln| {
+            let then = ln.checked_add_signed(shift)?;
+            Some((ln, *line_cov.get(&then)?))
+        })
+        .collect()
+}
+
+/// [`shift_lines`] for branch coverage.
+pub fn shift_branches(
+    branches: &BranchCoverage,
+    start: usize,
+    end: usize,
+    shift: isize,
+) -> BranchCoverage {
+    branches
+        .iter()
+        .filter_map(|(&(line, block, branch), &taken)| {
+            let now = line.checked_add_signed(-shift)?;
+            (start..=end)
+                .contains(&now)
+                .then_some(((now, block, branch), taken))
+        })
+        .collect()
+}
+
+/// Where each line of `then` is in `now`, both 1-based, for the lines an edit
+/// in between left alone. Like `git diff --patience`: after the common start
+/// and end, lines that occur once on each side anchor the match, and the gaps
+/// between anchors are matched the same way. Edited, added and removed lines
+/// have no entry.
+pub fn line_mapping(then: &str, now: &str) -> HashMap<usize, usize> {
+    let then: Vec<&str> = then.lines().collect();
+    let now: Vec<&str> = now.lines().collect();
+    let mut mapping = HashMap::new();
+    match_lines(&then, &now, (0, 0), &mut mapping);
+    mapping
+}
+
+/// [`line_mapping`] of the slices starting `offset` lines into each side.
+fn match_lines(
+    then: &[&str],
+    now: &[&str],
+    offset: (usize, usize),
+    mapping: &mut HashMap<usize, usize>,
+) {
+    let prefix = then.iter().zip(now).take_while(|(a, b)| a == b).count();
+    let suffix = then[prefix..]
+        .iter()
+        .rev()
+        .zip(now[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    for i in 0..prefix {
+        mapping.insert(offset.0 + i + 1, offset.1 + i + 1);
+    }
+    for i in 0..suffix {
+        mapping.insert(offset.0 + then.len() - i, offset.1 + now.len() - i);
+    }
+    let then = &then[prefix..then.len() - suffix];
+    let now = &now[prefix..now.len() - suffix];
+    let offset = (offset.0 + prefix, offset.1 + prefix);
+
+    // Per line: occurrences and last index in `then`, then the same in `now`.
+    let mut seen: HashMap<&str, (usize, usize, usize, usize)> = HashMap::new();
+    for (i, line) in then.iter().enumerate() {
+        let e = seen.entry(line).or_default();
+        (e.0, e.1) = (e.0 + 1, i);
+    }
+    for (j, line) in now.iter().enumerate() {
+        let e = seen.entry(line).or_default();
+        (e.2, e.3) = (e.2 + 1, j);
+    }
+    let mut unique: Vec<(usize, usize)> = seen
+        .into_values()
+        .filter(|e| e.0 == 1 && e.2 == 1)
+        .map(|e| (e.1, e.3))
+        .collect();
+    unique.sort_unstable();
+    let (mut i, mut j) = (0, 0);
+    for (a, b) in longest_increasing(&unique) {
+        match_lines(
+            &then[i..a],
+            &now[j..b],
+            (offset.0 + i, offset.1 + j),
+            mapping,
+        );
+        mapping.insert(offset.0 + a + 1, offset.1 + b + 1);
+        (i, j) = (a + 1, b + 1);
+    }
+    if i > 0 {
+        match_lines(&then[i..], &now[j..], (offset.0 + i, offset.1 + j), mapping);
+    }
+}
+
+/// The longest run of `pairs` (sorted by their first index) whose second
+/// index increases too, by patience sorting.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // `tails[k]`: the pair ending the run of length k + 1 with the smallest end.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; pairs.len()];
+    for (p, &(_, j)) in pairs.iter().enumerate() {
+        let k = tails.partition_point(|&t| pairs[t].1 < j);
+        if k > 0 {
+            prev[p] = Some(tails[k - 1]);
+        }
+        if k == tails.len() {
+            tails.push(p);
+        } else {
+            tails[k] = p;
+        }
+    }
+    let mut run = Vec::new();
+    let mut at = tails.last().copied();
+    while let Some(p) = at {
+        run.push(pairs[p]);
+        at = prev[p];
+    }
+    run.reverse();
+    run
+}
+
+/// Line coverage recorded against an earlier version of a file, renumbered
+/// by a [`line_mapping`] to match it now. Lines edited since lose their hits.
+pub fn remap_lines(line_cov: &LineCoverage, mapping: &HashMap<usize, usize>) -> LineCoverage {
+    line_cov
+        .iter()
+        .filter_map(|(line, &hits)| Some((*mapping.get(line)?, hits)))
+        .collect()
+}
+
+/// [`remap_lines`] for branch coverage.
+pub fn remap_branches(
+    branches: &BranchCoverage,
+    mapping: &HashMap<usize, usize>,
+) -> BranchCoverage {
+    branches
+        .iter()
+        .filter_map(|(&(line, block, branch), &taken)| {
+            Some(((*mapping.get(&line)?, block, branch), taken))
+        })
+        .collect()
+}
+
+/// [`remap_lines`] for function records. Functions whose first line was
+/// edited are dropped, so they fall back to plain line coverage.
+pub fn remap_functions(
+    records: &[FunctionRecord],
+    mapping: &HashMap<usize, usize>,
+) -> Vec<FunctionRecord> {
+    records
+        .iter()
+        .filter_map(|r| {
+            Some(FunctionRecord {
+                line: *mapping.get(&r.line)?,
+                ..r.clone()
+            })
+        })
+        .collect()
+}
+
+/// Source files with more than one `SF:` record in LCOV `content`, sorted.
+pub fn duplicate_records(content: &str) -> Vec<String> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for line in content.lines() {
+        if let Some(path) = line.trim().strip_prefix("SF:") {
+            *seen.entry(path).or_default() += 1;
+        }
+    }
+    let mut duplicates: Vec<String> = seen
+        .into_iter()
+        .filter(|&(_, n)| n > 1)
+        .map(|(path, _)| path.to_string())
+        .collect();
+    duplicates.sort();
+    duplicates
+}
+
+/// Coverage records for files that don't exist (relative paths are taken
+/// from the working dir), sorted. Usually coverage from another checkout or
+/// for files deleted since it was collected.
+pub fn missing_files(file_coverage: &HashMap<String, LineCoverage>) -> Vec<&str> {
+    let mut missing: Vec<&str> = file_coverage
+        .keys()
+        .map(String::as_str)
+        .filter(|path| !Path::new(path).exists())
+        .collect();
+    missing.sort();
+    missing
+}
+
+/// grcov's `-t coveralls` JSON: per source file, one hit count per line from
+/// line 1, `null` where nothing is instrumented. Everything else in the
+/// report (git info, branches, digests) is ignored.
+#[derive(serde::Deserialize)]
+struct CoverallsReport {
+    source_files: Vec<CoverallsFile>,
+}
+
+#[derive(serde::Deserialize)]
+struct CoverallsFile {
+    name: String,
+    coverage: Vec<Option<u64>>,
+}
+
+/// Parse a Coveralls JSON report (`grcov -t coveralls`) into the same map
+/// [`parse_lcov`] builds. Files listed more than once get their hits summed.
+pub fn parse_coveralls(content: &str) -> Result<HashMap<String, LineCoverage>, String> {
+    let report: CoverallsReport = serde_json::from_str(content).map_err(|err| err.to_string())?;
+    let mut result: HashMap<String, LineCoverage> = HashMap::new();
+    for file in report.source_files {
+        let lines = result.entry(file.name).or_default();
+        for (i, hits) in file.coverage.into_iter().enumerate() {
+            if let Some(hits) = hits {
+                *lines.entry(i + 1).or_default() += hits;
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Parse a Cobertura XML report (`cargo tarpaulin --out Xml`,
+/// `cargo llvm-cov --cobertura`) into the same map [`parse_lcov`] builds.
+/// Relative `filename`s are joined to the first `<source>` dir. Only each
+/// class's own `<lines>` count; the per-method lists repeat them.
+pub fn parse_cobertura(content: &str) -> Result<HashMap<String, LineCoverage>, String> {
+    let doc = roxmltree::Document::parse(content).map_err(|err| err.to_string())?;
+    let root = doc.root_element();
+    if !root.has_tag_name("coverage") {
+        return Err(format!(
+            "expected a <coverage> root element, got <{}>",
+            root.tag_name().name()
+        ));
+    }
+    let source = root
+        .descendants()
+        .find(|n| n.has_tag_name("source"))
+        .and_then(|n| n.text())
+        .map(str::trim);
+    let mut result: HashMap<String, LineCoverage> = HashMap::new();
+    for class in root.descendants().filter(|n| n.has_tag_name("class")) {
+        let Some(filename) = class.attribute("filename") else {
+            return Err("<class> without a filename".into());
+        };
+        let path = match source {
+            Some(dir) if !dir.is_empty() && Path::new(filename).is_relative() => {
+                Path::new(dir).join(filename).to_string_lossy().into_owned()
+            }
+            _ => filename.to_string(),
+        };
+        let lines = result.entry(path).or_default();
+        let line_elements = class
+            .children()
+            .filter(|n| n.has_tag_name("lines"))
+            .flat_map(|n| n.children())
+            .filter(|n| n.has_tag_name("line"));
+        for line in line_elements {
+            let number = line
+                .attribute("number")
+                .and_then(|n| n.parse::<usize>().ok());
+            let hits = line.attribute("hits").and_then(|h| h.parse::<u64>().ok());
+            let (Some(number @ 1..), Some(hits)) = (number, hits) else {
+                return Err(format!(
+                    "invalid <line> in {filename}: expected number >= 1 and hits, got \
+                     number={:?} hits={:?}",
+                    line.attribute("number").unwrap_or_default(),
+                    line.attribute("hits").unwrap_or_default()
+                ));
+            };
+            *lines.entry(number).or_default() += hits;
+        }
+    }
+    Ok(result)
+}
+
+/// Rewrite the `SF:` paths of a `bazel coverage --combined_report=lcov`
+/// report to absolute paths under `workspace_root`, the directory holding
+/// `MODULE.bazel` or `WORKSPACE`. Bazel records sources relative to its
+/// workspace, sometimes behind the sandbox's `/proc/self/cwd/` or an
+/// `.../execroot/<name>/` prefix; without remapping, the suffix match in
+/// [`find_coverage_for_file`] can't tell `a/src/lib.rs` from `b/src/lib.rs`.
+pub fn remap_bazel_paths<T>(
+    file_coverage: HashMap<String, T>,
+    workspace_root: &Path,
+) -> HashMap<String, T> {
+    file_coverage
+        .into_iter()
+        .map(|(path, lines)| {
+            let rel = match path.find("/execroot/") {
+                Some(i) => {
+                    let after = &path[i + "/execroot/".len()..];
+                    after.split_once('/').map_or(after, |(_, rest)| rest)
+                }
+                None => path.strip_prefix("/proc/self/cwd/").unwrap_or(&path),
+            };
+            let remapped = if Path::new(rel).is_absolute() {
+                rel.to_string()
+            } else {
+                workspace_root.join(rel).to_string_lossy().into_owned()
+            };
+            (remapped, lines)
+        })
+        .collect()
+}
+
+/// Rewrite `SF:` paths under `from` (a directory as seen where coverage ran,
+/// e.g. inside a container) to the same path under `to`. Other paths are kept.
+pub fn remap_prefix<T>(
+    file_coverage: HashMap<String, T>,
+    from: &str,
+    to: &Path,
+) -> HashMap<String, T> {
+    let from = from.trim_end_matches('/');
+    file_coverage
+        .into_iter()
+        .map(|(path, lines)| match path.strip_prefix(from) {
+            Some(rest) if rest.starts_with('/') => {
+                let remapped = to.join(&rest[1..]).to_string_lossy().into_owned();
+                (remapped, lines)
+            }
+            _ => (path, lines),
+        })
+        .collect()
+}
+
 /// Compute coverage percentage (0.0-100.0) for a line range.
 pub fn coverage_for_range(line_cov: &LineCoverage, start: usize, end: usize) -> f64 {
     let mut instrumented = 0u64;
@@ -51,6 +582,126 @@ pub fn coverage_for_range(line_cov: &LineCoverage, start: usize, end: usize) ->
     }
 }
 
+/// Instrumented lines in `start..=end` with zero hits, ascending.
+pub fn uncovered_lines(line_cov: &LineCoverage, start: usize, end: usize) -> Vec<usize> {
+    (start..=end)
+        .filter(|ln| line_cov.get(ln) == Some(&0))
+        .collect()
+}
+
+/// Collapse ascending line numbers into `3, 5-7, 12`.
+pub fn line_ranges(lines: &[usize]) -> String {
+    let mut parts = Vec::new();
+    let mut iter = lines.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end += 1;
+            iter.next();
+        }
+        if start == end {
+            parts.push(start.to_string());
+        } else {
+            parts.push(format!("{start}-{end}"));
+        }
+    }
+    parts.join(", ")
+}
+
+/// The uncovered lines of each entry that has any, in report order, e.g.
+/// `  parse (core::lexer) src/lexer.rs:12 — lines 17-19, 24`.
+pub fn format_uncovered(entries: &[CrapEntry]) -> String {
+    let mut out = String::new();
+    for e in entries.iter().filter(|e| !e.uncovered_lines.is_empty()) {
+        if out.is_empty() {
+            out.push_str("Uncovered lines:\n");
+        }
+        out.push_str(&format!(
+            "  {} ({}) {} — lines {}\n",
+            e.name,
+            e.module_path,
+            e.location(),
+            line_ranges(&e.uncovered_lines)
+        ));
+    }
+    out
+}
+
+/// Which lines of `source` hold code, indexed by line number - 1. Blank
+/// lines and lines that are only comments don't. Approximate: comment
+/// markers inside string literals aren't told apart.
+pub fn code_lines(source: &str) -> Vec<bool> {
+    let mut in_block = false;
+    source
+        .lines()
+        .map(|line| {
+            let mut rest = line.trim_start();
+            loop {
+                if in_block {
+                    match rest.find("*/") {
+                        Some(i) => {
+                            in_block = false;
+                            rest = rest[i + 2..].trim_start();
+                        }
+                        None => return false,
+                    }
+                } else if rest.is_empty() || rest.starts_with("//") {
+                    return false;
+                } else if let Some(after) = rest.strip_prefix("/*") {
+                    in_block = true;
+                    rest = after;
+                } else {
+                    return true;
+                }
+            }
+        })
+        .collect()
+}
+
+/// Whether the coverage of `start..=end` doesn't line up with the source:
+/// most of its instrumented lines are blank or comments, so the counts
+/// belong to code that has since moved (stale lcov.info) or was generated
+/// at another span (macro expansion). Needs at least two instrumented lines.
+pub fn span_mismatch(line_cov: &LineCoverage, code: &[bool], start: usize, end: usize) -> bool {
+    let instrumented: Vec<usize> = (start..=end)
+        .filter(|ln| line_cov.contains_key(ln))
+        .collect();
+    let misplaced = instrumented
+        .iter()
+        .filter(|&&ln| !code.get(ln.wrapping_sub(1)).copied().unwrap_or(false))
+        .count();
+    instrumented.len() >= 2 && misplaced * 2 > instrumented.len()
+}
+
+/// Data-quality warning listing functions whose coverage spans don't match
+/// their source spans. Empty when there are none.
+pub fn format_span_mismatches(entries: &[CrapEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    format!("warning: {}", span_mismatch_message(entries))
+}
+
+/// [`format_span_mismatches`] without the `warning: ` prefix, for `--strict`
+/// to fail with.
+pub fn span_mismatch_message(entries: &[CrapEntry]) -> String {
+    let mut out = format!(
+        "coverage for {} function(s) doesn't line up with the source (most \
+         instrumented lines are blank or comments); lcov.info may be stale or the \
+         code macro-generated, so their coverage is unreliable:\n",
+        entries.len()
+    );
+    for e in entries {
+        out.push_str(&format!(
+            "  {} ({}) {}\n",
+            e.name,
+            e.module_path,
+            e.location()
+        ));
+    }
+    out
+}
+
 /// Convert a source path to a module path.
 /// e.g. "src/foo/bar.rs" -> "foo::bar", "src/foo/mod.rs" -> "foo"
 pub fn source_to_module_path(path: &Path, src_dir: &Path) -> String {
@@ -69,6 +720,43 @@ pub fn source_to_module_path(path: &Path, src_dir: &Path) -> String {
     }
 }
 
+/// Find coverage data for a source file. Tries canonical match first, then
+/// literal match, then suffix match against a normalized form that strips any
+/// leading `./`. Returns `None` when the file has no entry in lcov.info —
+/// distinct from an entry that exists but has zero hits.
+pub fn find_coverage_for_file<T: Clone>(
+    source_path: &Path,
+    file_coverage: &HashMap<String, T>,
+) -> Option<T> {
+    // Best signal: canonical absolute path (tarpaulin emits absolutes).
+    let canonical = source_path
+        .canonicalize()
+        .ok()
+        .map(|p| p.to_string_lossy().into_owned());
+    if let Some(ref c) = canonical
+        && let Some(cov) = file_coverage.get(c)
+    {
+        return Some(cov.clone());
+    }
+
+    // Fall back to the literal string as we were given it.
+    let source_str = source_path.to_string_lossy();
+    if let Some(cov) = file_coverage.get(source_str.as_ref()) {
+        return Some(cov.clone());
+    }
+
+    // Suffix match using a normalized form that strips leading `./`. Without
+    // this, `./src/foo.rs` never matches `/abs/path/src/foo.rs` in lcov.
+    let normalized = source_str.strip_prefix("./").unwrap_or(&source_str);
+    for (lcov_path, cov) in file_coverage {
+        if lcov_path.ends_with(normalized) || normalized.ends_with(lcov_path.as_str()) {
+            return Some(cov.clone());
+        }
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,6 +782,38 @@ end_of_record
         assert_eq!(main_cov[&3], 5);
         let lib_cov = &result["src/lib.rs"];
         assert_eq!(lib_cov[&1], 2);
+        assert!(duplicate_records(lcov).is_empty());
+    }
+
+    #[test]
+    fn parse_lcov_sums_duplicate_records() {
+        let lcov = "\
+SF:src/lib.rs
+DA:1,2
+DA:2,0
+end_of_record
+SF:src/lib.rs
+DA:2,1
+DA:3,0
+end_of_record
+";
+        assert_eq!(
+            parse_lcov(lcov)["src/lib.rs"],
+            LineCoverage::from([(1, 2), (2, 1), (3, 0)])
+        );
+        assert_eq!(duplicate_records(lcov), vec!["src/lib.rs"]);
+    }
+
+    #[test]
+    fn missing_files_lists_records_without_a_file() {
+        let file_coverage = HashMap::from([
+            ("Cargo.toml".to_string(), LineCoverage::new()),
+            ("/nonexistent/src/lib.rs".to_string(), LineCoverage::new()),
+        ]);
+        assert_eq!(
+            missing_files(&file_coverage),
+            vec!["/nonexistent/src/lib.rs"]
+        );
     }
 
     #[test]
@@ -121,6 +841,44 @@ end_of_record
         assert_eq!(coverage_for_range(&cov, 1, 2), 100.0);
     }
 
+    #[test]
+    fn uncovered_lines_skips_hit_and_uninstrumented() {
+        let mut cov = LineCoverage::new();
+        cov.insert(3, 1);
+        cov.insert(4, 0);
+        cov.insert(6, 0);
+        cov.insert(9, 0);
+        assert_eq!(uncovered_lines(&cov, 3, 8), vec![4, 6]);
+    }
+
+    #[test]
+    fn code_lines_skip_blank_and_comment_lines() {
+        let source = "fn f() {\n\n    // note\n    /* a\n       b */\n    /* c */ g();\n}\n";
+        assert_eq!(
+            code_lines(source),
+            vec![true, false, false, false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn flags_coverage_mostly_on_non_code_lines() {
+        let code = code_lines("fn f() {\n\n    // x\n\n    g();\n}\n");
+        let mut cov = LineCoverage::new();
+        cov.insert(2, 1);
+        cov.insert(3, 0);
+        cov.insert(5, 1);
+        assert!(span_mismatch(&cov, &code, 1, 6));
+        cov.insert(1, 1);
+        cov.insert(6, 1);
+        assert!(!span_mismatch(&cov, &code, 1, 6));
+        // Past the end of the file counts as misplaced too.
+        let mut cov = LineCoverage::new();
+        cov.insert(9, 1);
+        cov.insert(10, 1);
+        assert!(span_mismatch(&cov, &code, 1, 10));
+        assert!(!span_mismatch(&cov, &code, 9, 9));
+    }
+
     #[test]
     fn source_to_module_basic() {
         let src = PathBuf::from("src");
@@ -147,4 +905,320 @@ end_of_record
             "main"
         );
     }
+
+    #[test]
+    fn find_coverage_exact_match() {
+        let mut file_cov = HashMap::new();
+        let mut line_cov = LineCoverage::new();
+        line_cov.insert(1, 5);
+        file_cov.insert("src/main.rs".to_string(), line_cov);
+
+        let result = find_coverage_for_file(Path::new("src/main.rs"), &file_cov);
+        let cov = result.expect("expected Some");
+        assert_eq!(cov.get(&1), Some(&5));
+    }
+
+    #[test]
+    fn find_coverage_suffix_match() {
+        let mut file_cov = HashMap::new();
+        let mut line_cov = LineCoverage::new();
+        line_cov.insert(1, 3);
+        file_cov.insert("/home/user/project/src/main.rs".to_string(), line_cov);
+
+        let result = find_coverage_for_file(Path::new("src/main.rs"), &file_cov);
+        let cov = result.expect("expected Some via suffix match");
+        assert_eq!(cov.get(&1), Some(&3));
+    }
+
+    #[test]
+    fn find_coverage_no_match_returns_none() {
+        let file_cov: HashMap<String, LineCoverage> = HashMap::new();
+        let result = find_coverage_for_file(Path::new("src/main.rs"), &file_cov);
+        assert!(
+            result.is_none(),
+            "absent file must be None, not Some(empty)"
+        );
+    }
+
+    #[test]
+    fn find_coverage_present_but_empty_returns_some_empty() {
+        // Regression: tarpaulin emits SF: for files with no executable lines.
+        // That is instrumented-but-empty — distinct from "not in the build at all".
+        let mut file_cov = HashMap::new();
+        file_cov.insert("src/empty.rs".to_string(), LineCoverage::new());
+
+        let result = find_coverage_for_file(Path::new("src/empty.rs"), &file_cov);
+        let cov = result.expect("entry exists, must be Some");
+        assert!(cov.is_empty());
+    }
+
+    #[test]
+    fn find_coverage_dot_slash_prefix_matches_absolute() {
+        // Regression: `resolve_targets(Path::new("."), ...)` produces source paths
+        // like `./src/foo.rs`, while tarpaulin writes absolute paths into lcov.info.
+        // The suffix-match fallback must strip the leading `./` so the two forms align.
+        let mut file_cov = HashMap::new();
+        let mut line_cov = LineCoverage::new();
+        line_cov.insert(1, 7);
+        file_cov.insert("/Users/dev/project/src/foo.rs".to_string(), line_cov);
+
+        let result = find_coverage_for_file(Path::new("./src/foo.rs"), &file_cov);
+        let cov = result.expect("dot-slash prefix must still suffix-match");
+        assert_eq!(cov.get(&1), Some(&7));
+    }
+
+    #[test]
+    fn bazel_paths_are_anchored_at_the_workspace_root() {
+        let mut report = HashMap::new();
+        for path in [
+            "crates/a/src/lib.rs",
+            "/proc/self/cwd/crates/b/src/lib.rs",
+            "/home/ci/.cache/bazel/_bazel_ci/1f2e/execroot/_main/crates/c/src/lib.rs",
+            "/abs/elsewhere.rs",
+        ] {
+            report.insert(path.to_string(), LineCoverage::from([(1, 1)]));
+        }
+        let remapped = remap_bazel_paths(report, Path::new("/repo"));
+        let mut paths: Vec<&str> = remapped.keys().map(String::as_str).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "/abs/elsewhere.rs",
+                "/repo/crates/a/src/lib.rs",
+                "/repo/crates/b/src/lib.rs",
+                "/repo/crates/c/src/lib.rs",
+            ]
+        );
+    }
+
+    #[test]
+    fn prefix_remap_moves_only_paths_under_the_prefix() {
+        let mut report = HashMap::new();
+        for path in [
+            "/workspace/src/lib.rs",
+            "/workspacex/src/lib.rs",
+            "src/main.rs",
+        ] {
+            report.insert(path.to_string(), LineCoverage::from([(1, 1)]));
+        }
+        let remapped = remap_prefix(report, "/workspace/", Path::new("/home/me/app"));
+        let mut paths: Vec<&str> = remapped.keys().map(String::as_str).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "/home/me/app/src/lib.rs",
+                "/workspacex/src/lib.rs",
+                "src/main.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn line_ranges_collapse_runs() {
+        assert_eq!(line_ranges(&[3, 5, 6, 7, 12]), "3, 5-7, 12");
+        assert_eq!(line_ranges(&[]), "");
+    }
+
+    #[test]
+    fn lists_uncovered_lines_of_partly_covered_functions() {
+        let entry = |name: &str, uncovered: &[usize]| CrapEntry {
+            name: name.into(),
+            module_path: "lexer".into(),
+            complexity: 3,
+            coverage: Some(50.0),
+            crap: Some(4.1),
+            uncovered_lines: uncovered.to_vec(),
+            file: PathBuf::from("src/lexer.rs"),
+            start_line: 12,
+            end_line: 30,
+            ..Default::default()
+        };
+        let entries = vec![entry("next", &[17, 18, 19, 24]), entry("peek", &[])];
+        assert_eq!(
+            format_uncovered(&entries),
+            "Uncovered lines:\n  next (lexer) src/lexer.rs:12 — lines 17-19, 24\n"
+        );
+        assert_eq!(format_uncovered(&entries[1..]), "");
+    }
+
+    #[test]
+    fn parses_external_json_coverage() {
+        let json = r#"{"files": {"src/lib.rs": {"12": 3, "13": 0}, "src/empty.rs": {}}}"#;
+        let files = parse_coverage_json(json).unwrap();
+        assert_eq!(files["src/lib.rs"], LineCoverage::from([(12, 3), (13, 0)]));
+        assert!(files["src/empty.rs"].is_empty());
+        for bad in [
+            "[]",
+            r#"{"files": {"src/lib.rs": {"x": 1}}}"#,
+            r#"{"files": {"src/lib.rs": {"1": -1}}}"#,
+            r#"{"files": {"src/lib.rs": {"0": 1}}}"#,
+            r#"{"files": {}, "version": 2}"#,
+        ] {
+            assert!(parse_coverage_json(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parses_cobertura_class_lines() {
+        let xml = r#"<?xml version="1.0"?>
+<coverage line-rate="0.5">
+  <sources><source>/work/app</source></sources>
+  <packages><package name="app"><classes>
+    <class name="lib" filename="src/lib.rs">
+      <methods><method name="f"><lines><line number="12" hits="3"/></lines></method></methods>
+      <lines><line number="12" hits="3"/><line number="13" hits="0"/></lines>
+    </class>
+    <class name="gen" filename="/abs/gen.rs"><lines/></class>
+  </classes></package></packages>
+</coverage>"#;
+        let files = parse_cobertura(xml).unwrap();
+        assert_eq!(
+            files["/work/app/src/lib.rs"],
+            LineCoverage::from([(12, 3), (13, 0)])
+        );
+        assert!(files["/abs/gen.rs"].is_empty());
+        for bad in [
+            "<report/>",
+            "<coverage><class/></coverage>",
+            r#"<coverage><class filename="a.rs"><lines><line number="0" hits="1"/></lines></class></coverage>"#,
+            r#"<coverage><class filename="a.rs"><lines><line number="1"/></lines></class></coverage>"#,
+            "<coverage>",
+        ] {
+            assert!(parse_cobertura(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parses_coveralls_line_arrays() {
+        let json = r#"{
+            "repo_token": "x",
+            "git": {"head": {"id": "abc"}},
+            "source_files": [
+                {"name": "src/lib.rs", "source_digest": "d", "coverage": [null, 2, 0, null], "branches": []},
+                {"name": "src/lib.rs", "coverage": [null, 1]},
+                {"name": "src/empty.rs", "coverage": []}
+            ]
+        }"#;
+        let files = parse_coveralls(json).unwrap();
+        assert_eq!(files["src/lib.rs"], LineCoverage::from([(2, 3), (3, 0)]));
+        assert!(files["src/empty.rs"].is_empty());
+        for bad in [
+            "{}",
+            r#"{"source_files": [{"name": "a.rs"}]}"#,
+            r#"{"source_files": [{"name": "a.rs", "coverage": [-1]}]}"#,
+        ] {
+            assert!(parse_coveralls(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parses_branch_records_per_range() {
+        let lcov = "\
+SF:src/lib.rs
+DA:3,1
+BRDA:3,0,0,2
+BRDA:3,0,1,0
+BRDA:7,1,0,-
+BRDA:7,1,1,x
+BRF:4
+BRH:1
+end_of_record
+SF:src/lib.rs
+BRDA:3,0,1,1
+end_of_record
+SF:src/plain.rs
+DA:1,1
+end_of_record
+";
+        let files = parse_lcov_branches(lcov);
+        assert_eq!(files.len(), 1);
+        let branches = &files["src/lib.rs"];
+        assert_eq!(branches.len(), 3);
+        assert_eq!(branch_coverage_for_range(branches, 1, 5), Some(100.0));
+        assert_eq!(branch_coverage_for_range(branches, 1, 9), Some(200.0 / 3.0));
+        assert_eq!(branch_coverage_for_range(branches, 8, 9), None);
+    }
+
+    #[test]
+    fn matches_function_records_by_name_and_line() {
+        let lcov = "\
+SF:src/lib.rs
+FN:4,_ZN7mycrate3Map3get17h0123456789abcdefE
+FN:4,_ZN7mycrate3Map3get17hfedcba9876543210E
+FN:12,20,_ZN7mycrate3run17h0123456789abcdefE
+FN:30,_ZN7mycrate4idle17h0123456789abcdefE
+FNDA:2,_ZN7mycrate3Map3get17h0123456789abcdefE
+FNDA:1,_ZN7mycrate3Map3get17hfedcba9876543210E
+FNDA:0,_ZN7mycrate3run17h0123456789abcdefE
+end_of_record
+";
+        let files = parse_lcov_functions(lcov);
+        let records = &files["src/lib.rs"];
+        assert_eq!(
+            records,
+            &vec![
+                FunctionRecord {
+                    name: "mycrate::Map::get".into(),
+                    line: 4,
+                    hits: 3,
+                },
+                FunctionRecord {
+                    name: "mycrate::run".into(),
+                    line: 12,
+                    hits: 0,
+                },
+            ]
+        );
+        // Moved down two lines since; `run` has a cfg twin the record isn't for.
+        let matched = match_functions(
+            records,
+            &[
+                ("Map<K, V>::get", 6),
+                ("run [cfg(unix)]", 14),
+                ("run [cfg(windows)]", 24),
+                ("idle", 32),
+            ],
+        );
+        let lines: Vec<Option<usize>> = matched.iter().map(|m| m.map(|r| r.line)).collect();
+        assert_eq!(lines, vec![Some(4), Some(12), None, None]);
+
+        let lc: LineCoverage = [(4, 1), (5, 0), (9, 3)].into_iter().collect();
+        let shifted = shift_lines(&lc, 6, 8, -2);
+        assert_eq!(shifted, [(6, 1), (7, 0)].into_iter().collect());
+    }
+
+    #[test]
+    fn line_mapping_follows_unchanged_lines_across_edits() {
+        let then = "// header\nfn a() {\n    one();\n}\n\nfn b() {\n    two();\n}\n";
+        let now =
+            "// header\n// added\n// added\nfn b() {\n    two();\n}\n\nfn a() {\n    uno();\n}\n";
+        let mapping = line_mapping(then, now);
+        // `a` moved below `b` and had its body edited; `b` moved up two lines.
+        assert_eq!(mapping[&1], 1);
+        assert_eq!((mapping[&6], mapping[&7]), (4, 5));
+        assert!(!mapping.contains_key(&3));
+        assert_eq!(line_mapping(then, then).len(), 8);
+
+        let lc: LineCoverage = [(2, 1), (3, 1), (6, 0), (7, 0)].into_iter().collect();
+        assert_eq!(
+            remap_lines(&lc, &mapping),
+            [(4, 0), (5, 0)].into_iter().collect()
+        );
+        let branches: BranchCoverage = [((7, 0, 1), 2)].into_iter().collect();
+        assert_eq!(
+            remap_branches(&branches, &mapping),
+            [((5, 0, 1), 2)].into_iter().collect()
+        );
+        let record = |line| FunctionRecord {
+            name: "b".into(),
+            line,
+            hits: 0,
+        };
+        assert_eq!(
+            remap_functions(&[record(6), record(3)], &mapping),
+            vec![record(4)]
+        );
+    }
 }
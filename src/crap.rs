@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::complexity::DecisionPoint;
+
+#[derive(Debug, Clone, Default)]
 pub struct CrapEntry {
     pub name: String,
     pub module_path: String,
@@ -7,6 +13,57 @@ pub struct CrapEntry {
     pub coverage: Option<f64>,
     /// `None` whenever `coverage` is `None` — we can't score without data.
     pub crap: Option<f64>,
+    /// Instrumented lines inside the function that were never hit, ascending.
+    /// Empty when fully covered or uninstrumented.
+    pub uncovered_lines: Vec<usize>,
+    /// Refactoring suggestions derived from the function's AST shape.
+    pub hints: Vec<String>,
+    /// The constructs that make up `complexity`, for machine-readable exports.
+    pub decision_points: Vec<DecisionPoint>,
+    /// Source file the function was found in, as discovered.
+    pub file: PathBuf,
+    /// Line span of the function, from its name to its closing brace.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// [`content_hash`](crate::baseline::content_hash) of `file` when it was
+    /// analyzed, so baseline diffs can tell code changes from coverage changes.
+    pub file_hash: String,
+    /// Hash of the function body's tokens, see
+    /// [`fingerprint`](crate::complexity::fingerprint). Survives renames and
+    /// moves, so baseline diffs can match the function up again.
+    pub fingerprint: String,
+    /// Calls made through trait objects, see
+    /// [`dyn_calls`](crate::dispatch::dyn_calls).
+    pub dyn_calls: u32,
+    /// Share of the branches in the function's span taken at least once,
+    /// 0.0–100.0, from LCOV `BRDA:` records. `None` without branch data.
+    pub branch_coverage: Option<f64>,
+    /// Tags from `craprs(tag = "...")` attributes and the project's tag
+    /// rules, see [`tags_for`](crate::tags::tags_for).
+    pub tags: Vec<String>,
+    /// Part of the crate's public API, see [`public_api`](crate::public_api).
+    pub public: bool,
+}
+
+impl CrapEntry {
+    /// `file:line` of the function's first line, the form editors and
+    /// terminals accept for jumping to a location.
+    pub fn location(&self) -> String {
+        let file = self.file.strip_prefix(".").unwrap_or(&self.file);
+        format!("{}:{}", file.display(), self.start_line)
+    }
+}
+
+/// Score at or above which a function is considered "crappy" (high risk).
+pub const CRAPPY_THRESHOLD: f64 = 30.0;
+
+/// Sum of the scores of the scored entries; 0.0 when there are none.
+pub fn total_score(entries: &[CrapEntry]) -> f64 {
+    // Not `sum()`: that yields -0.0 for an empty iterator.
+    entries
+        .iter()
+        .filter_map(|e| e.crap)
+        .fold(0.0, |sum, s| sum + s)
 }
 
 /// CRAP = CC^2 * (1 - coverage)^3 + CC. Returns `None` when coverage is unknown.
@@ -17,6 +74,172 @@ pub fn crap_score(complexity: u32, coverage_pct: Option<f64>) -> Option<f64> {
     Some(cc * cc * uncov * uncov * uncov + cc)
 }
 
+/// Uncovered instrumented lines inside functions at or above a score threshold —
+/// the lines someone would have to cover to pay the debt down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoverageDebt {
+    pub lines: usize,
+    pub functions: usize,
+}
+
+pub fn coverage_debt(entries: &[CrapEntry], threshold: f64) -> CoverageDebt {
+    let mut debt = CoverageDebt::default();
+    for e in entries {
+        if e.crap.is_some_and(|s| s >= threshold) {
+            debt.functions += 1;
+            debt.lines += e.uncovered_lines.len();
+        }
+    }
+    debt
+}
+
+/// Project-level figures printed at the end of the text report.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReportSummary {
+    pub functions: usize,
+    /// Functions with coverage data; the score figures cover only these.
+    pub scored: usize,
+    pub total: f64,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    /// Scored functions at or above the threshold.
+    pub crappy: usize,
+    /// Line coverage of scored functions weighted by their complexity, so
+    /// untested branchy code weighs more than untested one-liners.
+    pub weighted_coverage: Option<f64>,
+}
+
+pub fn summarize(entries: &[CrapEntry], threshold: f64) -> ReportSummary {
+    let mut scores: Vec<f64> = entries.iter().filter_map(|e| e.crap).collect();
+    scores.sort_by(f64::total_cmp);
+    let n = scores.len();
+    let total = total_score(entries);
+    let median = match n {
+        0 => None,
+        _ if n % 2 == 1 => Some(scores[n / 2]),
+        _ => Some((scores[n / 2 - 1] + scores[n / 2]) / 2.0),
+    };
+    let (cc, covered_cc) = entries
+        .iter()
+        .filter(|e| e.crap.is_some())
+        .filter_map(|e| e.coverage.map(|c| (f64::from(e.complexity), c)))
+        .fold((0.0, 0.0), |(all, covered), (cc, c)| {
+            (all + cc, covered + cc * c)
+        });
+    ReportSummary {
+        functions: entries.len(),
+        scored: n,
+        total,
+        mean: (n > 0).then(|| total / n as f64),
+        median,
+        crappy: scores.iter().filter(|&&s| s >= threshold).count(),
+        weighted_coverage: (cc > 0.0).then(|| covered_cc / cc),
+    }
+}
+
+/// One line, e.g. `summary: 42 function(s), 40 scored — total CRAP 512.3,
+/// mean 12.8, median 6.0, 3 with CRAP >= 30, complexity-weighted coverage 71.2%`.
+pub fn format_summary(
+    summary: &ReportSummary,
+    score_label: &str,
+    threshold: f64,
+    numbers: NumberFormat,
+) -> String {
+    let mut out = format!(
+        "summary: {} function(s), {} scored",
+        summary.functions, summary.scored
+    );
+    if let (Some(mean), Some(median), Some(coverage)) =
+        (summary.mean, summary.median, summary.weighted_coverage)
+    {
+        out.push_str(&format!(
+            " — total {score_label} {}, mean {}, median {}, {} with {score_label} >= \
+             {threshold}, complexity-weighted coverage {}%",
+            numbers.format(summary.total),
+            numbers.format(mean),
+            numbers.format(median),
+            summary.crappy,
+            numbers.format(coverage)
+        ));
+    }
+    out.push('\n');
+    out
+}
+
+/// The final log line, in a fixed greppable shape whatever the output format:
+/// `craprs: 12 crappy, worst=84.2 (core::parser::parse), load=310.5`, with
+/// `worst=none` when nothing has coverage data.
+pub fn verdict_line(entries: &[CrapEntry], threshold: f64) -> String {
+    let crappy = entries
+        .iter()
+        .filter(|e| e.crap.is_some_and(|s| s >= threshold))
+        .count();
+    let load = total_score(entries);
+    let worst = entries
+        .iter()
+        .filter_map(|e| e.crap.map(|s| (s, e)))
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    let worst = match worst {
+        Some((score, e)) if e.module_path.is_empty() => format!("{score:.1} ({})", e.name),
+        Some((score, e)) => format!("{score:.1} ({}::{})", e.module_path, e.name),
+        None => "none".to_string(),
+    };
+    format!("craprs: {crappy} crappy, worst={worst}, load={load:.1}")
+}
+
+/// The whole report as one `key=value` line for scripts and commit statuses:
+/// `functions=42 crap_total=412.3 worst=87.2 over_threshold=5`, with
+/// `worst=none` when nothing has coverage data. The keys stay the same
+/// whatever `--score` ranks by.
+pub fn quiet_line(entries: &[CrapEntry], threshold: f64) -> String {
+    let summary = summarize(entries, threshold);
+    let worst = entries
+        .iter()
+        .filter_map(|e| e.crap)
+        .max_by(f64::total_cmp)
+        .map_or("none".to_string(), |s| format!("{s:.1}"));
+    format!(
+        "functions={} crap_total={:.1} worst={worst} over_threshold={}",
+        summary.functions, summary.total, summary.crappy
+    )
+}
+
+/// Linear alternative to CRAP: CC * (1 - coverage). Easier to explain than the
+/// cubic formula — a fully uncovered function scores its CC, a covered one 0.
+/// Returns `None` when coverage is unknown.
+pub fn uncovered_cc_score(complexity: u32, coverage_pct: Option<f64>) -> Option<f64> {
+    let pct = coverage_pct?;
+    Some(complexity as f64 * (1.0 - pct / 100.0))
+}
+
+/// Risk score entries are ranked by. The result is stored in `CrapEntry::crap`
+/// and everything downstream (sorting, filters, report) works on it unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ScoreKind {
+    /// CC² × (1 − coverage)³ + CC
+    #[default]
+    Crap,
+    /// CC × (1 − coverage)
+    UncoveredCc,
+}
+
+impl ScoreKind {
+    pub fn score(self, complexity: u32, coverage_pct: Option<f64>) -> Option<f64> {
+        match self {
+            ScoreKind::Crap => crap_score(complexity, coverage_pct),
+            ScoreKind::UncoveredCc => uncovered_cc_score(complexity, coverage_pct),
+        }
+    }
+
+    /// Column header / label for the score in reports.
+    pub fn label(self) -> &'static str {
+        match self {
+            ScoreKind::Crap => "CRAP",
+            ScoreKind::UncoveredCc => "UncovCC",
+        }
+    }
+}
+
 /// Sort descending by CRAP. `None` CRAP entries sink to the bottom, preserving
 /// their input order (stable — `sort_by` is Rust's stable sort).
 pub fn sort_entries(entries: &mut [CrapEntry]) {
@@ -33,26 +256,560 @@ pub fn sort_entries(entries: &mut [CrapEntry]) {
 
 const DASH: &str = "—";
 
-pub fn format_report(entries: &[CrapEntry]) -> String {
-    let header = format!(
-        "{:<30} {:<45} {:>4} {:>6} {:>8}",
-        "Function", "Module", "CC", "Cov%", "CRAP"
-    );
-    let sep = "-".repeat(header.len());
-    let mut lines = vec!["CRAP Report".to_string(), "===========".to_string(), header, sep];
-    for e in entries {
-        let cov_cell = match e.coverage {
-            Some(pct) => format!("{pct:>5.1}%"),
-            None => format!("{DASH:>6}"),
+/// Layout of the text report table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TableStyle {
+    /// Fixed-width columns with a `===` title underline and `---` header rule.
+    #[default]
+    Ascii,
+    /// Fixed-width columns framed with box-drawing characters.
+    Unicode,
+    /// GitHub-flavored markdown table (no padding, no title).
+    Markdown,
+    /// No title or rules; columns sized to their content, two spaces apart.
+    Compact,
+}
+
+/// Which part of a long name or module path `--width` cuts out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TruncateStyle {
+    /// Keep the end: `…Visitor>::visit_map`.
+    Head,
+    /// Keep both ends: `<HashMap<K…>::visit_map`.
+    Middle,
+    /// Keep the start: `<HashMap<K, V> as Vis…`.
+    #[default]
+    Tail,
+}
+
+/// When to colorize the text report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorMode {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How scores and percentages are written in the text report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    /// Digits after the separator.
+    pub precision: usize,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            decimal_separator: '.',
+            precision: 1,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Parse a sample number: `0.0` is the default, `0,00` uses a decimal
+    /// comma and two digits, `0` rounds to whole numbers.
+    pub fn parse(s: &str) -> Result<NumberFormat, String> {
+        let invalid = || format!("expected a sample like `0.0`, `0,00` or `0`, got `{s}`");
+        let Some(rest) = s.strip_prefix('0') else {
+            return Err(invalid());
         };
-        let crap_cell = match e.crap {
-            Some(s) => format!("{s:>8.1}"),
-            None => format!("{DASH:>8}"),
+        let mut chars = rest.chars();
+        let decimal_separator = match chars.next() {
+            None => '.',
+            Some(c @ ('.' | ',')) => c,
+            Some(_) => return Err(invalid()),
         };
-        lines.push(format!(
-            "{:<30} {:<45} {:>4} {cov_cell} {crap_cell}",
-            e.name, e.module_path, e.complexity
-        ));
+        let digits = chars.as_str();
+        if !digits.chars().all(|c| c == '0') || (!rest.is_empty() && digits.is_empty()) {
+            return Err(invalid());
+        }
+        if digits.len() > 6 {
+            return Err(format!(
+                "at most 6 decimal places, got {} in `{s}`",
+                digits.len()
+            ));
+        }
+        Ok(NumberFormat {
+            decimal_separator,
+            precision: digits.len(),
+        })
+    }
+
+    pub fn format(self, value: f64) -> String {
+        let out = format!("{value:.*}", self.precision);
+        if self.decimal_separator == '.' {
+            out
+        } else {
+            out.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+}
+
+/// A function's score and coverage in an earlier report, for `--compare`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Previous {
+    pub crap: Option<f64>,
+    pub coverage: Option<f64>,
+}
+
+/// Earlier scores keyed by module path and function name.
+pub type PreviousScores = HashMap<(String, String), Previous>;
+
+/// Whether `e` is new since `previous` or scores higher than it did there,
+/// for `--only-regressions`. Functions that gained or lost a score are not.
+pub fn is_regression(e: &CrapEntry, previous: &PreviousScores) -> bool {
+    match previous.get(&(e.module_path.clone(), e.name.clone())) {
+        Some(before) => matches!((before.crap, e.crap), (Some(a), Some(b)) if b > a),
+        None => true,
+    }
+}
+
+/// Rendering options for the text report.
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    pub style: TableStyle,
+    /// Header of the score column.
+    pub score_label: &'static str,
+    /// Color rows by risk relative to this threshold: red at or above it,
+    /// yellow from half of it, green below. `None` renders plain text;
+    /// markdown tables are never colored.
+    pub highlight: Option<f64>,
+    pub numbers: NumberFormat,
+    /// Columns to show, in order.
+    pub columns: Vec<Column>,
+    /// Scores from an earlier report for the delta columns.
+    pub previous: Option<PreviousScores>,
+    /// Fit rows into this many characters by narrowing the name and module
+    /// columns, cutting their cells short with `…`. `None` never truncates.
+    pub max_width: Option<usize>,
+    /// Where cells cut to fit `max_width` lose characters.
+    pub truncate: TruncateStyle,
+    /// Never narrow the name column; only module paths are cut to fit.
+    pub full_names: bool,
+    /// In the ascii and unicode layouts, keep text columns at exactly
+    /// [`Column::width`] whatever they hold, cutting longer cells with `…`.
+    /// Only the last column still grows to fit, as nothing follows it.
+    pub fixed_widths: bool,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        TableOptions {
+            style: TableStyle::Ascii,
+            score_label: ScoreKind::Crap.label(),
+            highlight: None,
+            numbers: NumberFormat::default(),
+            columns: DEFAULT_COLUMNS.to_vec(),
+            previous: None,
+            max_width: None,
+            truncate: TruncateStyle::default(),
+            full_names: false,
+            fixed_widths: false,
+        }
+    }
+}
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Wrap a rendered row in the ANSI color for its risk; unscored rows stay plain.
+fn paint(line: String, crap: Option<f64>, highlight: Option<f64>) -> String {
+    let (Some(score), Some(threshold)) = (crap, highlight) else {
+        return line;
+    };
+    let color = if score >= threshold {
+        RED
+    } else if score >= threshold / 2.0 {
+        YELLOW
+    } else {
+        GREEN
+    };
+    format!("{color}{line}{RESET}")
+}
+
+/// A column of the text report, chosen with `--columns`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Column {
+    /// Function name, with its impl type
+    Name,
+    Module,
+    /// `pub` for functions in the crate's public API
+    Api,
+    /// Cyclomatic complexity
+    Cc,
+    /// Line coverage
+    Cov,
+    /// Branch coverage, from LCOV `BRDA:` records
+    Branch,
+    /// The score, CRAP or UncovCC
+    Crap,
+    /// Lines of code: the function's span from name to closing brace
+    Loc,
+    /// `file:line` of the function, see [`CrapEntry::location`]
+    File,
+    /// Score change since the `--compare` report
+    DeltaCrap,
+    /// Coverage change since the `--compare` report
+    DeltaCov,
+}
+
+/// The report's columns when `--columns` isn't given.
+pub const DEFAULT_COLUMNS: [Column; 6] = [
+    Column::Name,
+    Column::Module,
+    Column::Cc,
+    Column::Cov,
+    Column::Crap,
+    Column::File,
+];
+
+/// The default columns with `--compare`: the deltas follow the score.
+pub const COMPARE_COLUMNS: [Column; 8] = [
+    Column::Name,
+    Column::Module,
+    Column::Cc,
+    Column::Cov,
+    Column::Crap,
+    Column::DeltaCrap,
+    Column::DeltaCov,
+    Column::File,
+];
+
+/// The default columns, plus branch coverage after line coverage when
+/// `branches` (the report has branch data), the public API marker after the
+/// name with `api`, and the deltas after the score with `compare`.
+pub fn default_columns(branches: bool, api: bool, compare: bool) -> Vec<Column> {
+    let base: &[Column] = if compare {
+        &COMPARE_COLUMNS
+    } else {
+        &DEFAULT_COLUMNS
+    };
+    let mut columns = base.to_vec();
+    if branches {
+        let cov = columns.iter().position(|&c| c == Column::Cov).unwrap_or(0);
+        columns.insert(cov + 1, Column::Branch);
+    }
+    if api {
+        columns.insert(1, Column::Api);
+    }
+    columns
+}
+
+/// `▲1.5` / `▼0.3` for a change at the chosen precision, `=` when it rounds
+/// away.
+fn delta(change: f64, numbers: NumberFormat) -> String {
+    let magnitude = numbers.format(change.abs());
+    if magnitude == numbers.format(0.0) {
+        "=".to_string()
+    } else if change > 0.0 {
+        format!("▲{magnitude}")
+    } else {
+        format!("▼{magnitude}")
+    }
+}
+
+impl Column {
+    fn header(self, score_label: &str) -> String {
+        match self {
+            Column::Name => "Function",
+            Column::Module => "Module",
+            Column::Api => "API",
+            Column::Cc => "CC",
+            Column::Cov => "Cov%",
+            Column::Branch => "Br%",
+            Column::Crap => score_label,
+            Column::Loc => "LOC",
+            Column::File => "Location",
+            Column::DeltaCrap => return format!("Δ{score_label}"),
+            Column::DeltaCov => "ΔCov",
+        }
+        .to_string()
+    }
+
+    /// Minimum width in the fixed-width layouts; columns grow to fit their
+    /// content, see [`widths`].
+    fn width(self) -> usize {
+        match self {
+            Column::Name => 30,
+            Column::Module => 45,
+            Column::Api => 3,
+            Column::Cc => 4,
+            Column::Cov | Column::Branch => 6,
+            Column::Crap => 8,
+            Column::Loc => 5,
+            Column::File => 8,
+            Column::DeltaCrap => 8,
+            Column::DeltaCov => 7,
+        }
+    }
+
+    /// Whether the column is right-aligned (numbers) or left-aligned (text).
+    fn right(self) -> bool {
+        !matches!(
+            self,
+            Column::Name | Column::Module | Column::Api | Column::File
+        )
+    }
+
+    fn cell(self, e: &CrapEntry, opts: &TableOptions) -> String {
+        let numbers = opts.numbers;
+        let previous = opts
+            .previous
+            .as_ref()
+            .map(|p| p.get(&(e.module_path.clone(), e.name.clone())));
+        match self {
+            Column::Name => e.name.clone(),
+            Column::Module => e.module_path.clone(),
+            Column::Api => if e.public { "pub" } else { "" }.to_string(),
+            Column::Cc => e.complexity.to_string(),
+            Column::Cov | Column::Branch => {
+                let pct = match self {
+                    Column::Cov => e.coverage,
+                    _ => e.branch_coverage,
+                };
+                match pct {
+                    Some(pct) => format!("{}%", numbers.format(pct)),
+                    None => DASH.to_string(),
+                }
+            }
+            Column::Crap => match e.crap {
+                Some(s) => numbers.format(s),
+                None => DASH.to_string(),
+            },
+            Column::Loc => (e.end_line + 1).saturating_sub(e.start_line).to_string(),
+            Column::File => e.location(),
+            Column::DeltaCrap | Column::DeltaCov => {
+                let Some(previous) = previous else {
+                    return DASH.to_string();
+                };
+                let Some(previous) = previous else {
+                    return "new".to_string();
+                };
+                let (before, after) = match self {
+                    Column::DeltaCrap => (previous.crap, e.crap),
+                    _ => (previous.coverage, e.coverage),
+                };
+                match (before, after) {
+                    (Some(before), Some(after)) => delta(after - before, numbers),
+                    _ => DASH.to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Narrowest the name and module columns get when fitting `max_width`.
+const MIN_TEXT_WIDTH: usize = 8;
+
+/// Width of each column: wide enough for its header and every cell in `rows`,
+/// and in the fixed-width layouts at least [`Column::width`] (the coverage
+/// column at least `100%` at the chosen precision), so tables line up across
+/// runs and groups unless something doesn't fit. With `exact`, text columns
+/// other than the last are held at that minimum instead of growing.
+fn widths(
+    columns: &[Column],
+    headers: &[String],
+    rows: &[Vec<String>],
+    fixed: Option<NumberFormat>,
+    exact: bool,
+) -> Vec<usize> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(i, &column)| {
+            let content = rows
+                .iter()
+                .map(|r| r[i].chars().count())
+                .fold(headers[i].chars().count(), usize::max);
+            let Some(numbers) = fixed else {
+                return content;
+            };
+            let floor = match column {
+                Column::Cov | Column::Branch if numbers.precision > 0 => {
+                    column.width().max(5 + numbers.precision)
+                }
+                _ => column.width(),
+            };
+            if exact && !column.right() && i + 1 < columns.len() {
+                return floor;
+            }
+            content.max(floor)
+        })
+        .collect()
+}
+
+/// Narrow the name and module columns, widest first, until rows with
+/// `overhead` characters of separators fit in `max_width`, or both are down
+/// to [`MIN_TEXT_WIDTH`]. With `full_names` only the module column narrows.
+fn fit(
+    widths: &mut [usize],
+    columns: &[Column],
+    overhead: usize,
+    max_width: usize,
+    full_names: bool,
+) {
+    let mut total: usize = widths.iter().sum::<usize>() + overhead;
+    while total > max_width {
+        let widest = columns
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| {
+                let narrowable = match c {
+                    Column::Name => !full_names,
+                    Column::Module => true,
+                    _ => false,
+                };
+                narrowable && widths[*i] > MIN_TEXT_WIDTH
+            })
+            .max_by_key(|(i, _)| widths[*i]);
+        let Some((i, _)) = widest else {
+            return;
+        };
+        widths[i] -= 1;
+        total -= 1;
+    }
+}
+
+/// `s` cut to `width` characters, with `…` where `style` took some out.
+fn truncate(s: &str, width: usize, style: TruncateStyle) -> String {
+    let len = s.chars().count();
+    if len <= width {
+        return s.to_string();
+    }
+    let keep = width.saturating_sub(1);
+    let (head, tail) = match style {
+        TruncateStyle::Head => (0, keep),
+        TruncateStyle::Middle => (keep.div_ceil(2), keep / 2),
+        TruncateStyle::Tail => (keep, 0),
+    };
+    let mut out: String = s.chars().take(head).collect();
+    out.push('…');
+    out.extend(s.chars().skip(len - tail));
+    out
+}
+
+fn pad(s: &str, width: usize, right: bool) -> String {
+    if right {
+        format!("{s:>width$}")
+    } else {
+        format!("{s:<width$}")
+    }
+}
+
+fn row(cells: &[String], columns: &[Column], widths: &[usize], sep: &str) -> String {
+    cells
+        .iter()
+        .zip(widths)
+        .zip(columns)
+        .map(|((c, &w), column)| pad(c, w, column.right()))
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+pub fn format_report(entries: &[CrapEntry]) -> String {
+    format_report_with(entries, &TableOptions::default())
+}
+
+pub fn format_report_with(entries: &[CrapEntry], opts: &TableOptions) -> String {
+    let mut out = match opts.style {
+        TableStyle::Ascii => "CRAP Report\n===========\n".to_string(),
+        TableStyle::Unicode => "CRAP Report\n".to_string(),
+        TableStyle::Markdown | TableStyle::Compact => String::new(),
+    };
+    out.push_str(&format_table(entries, opts));
+    out
+}
+
+/// Render just the table (header, rules and rows) without the report title.
+pub fn format_table(entries: &[CrapEntry], opts: &TableOptions) -> String {
+    let columns = &opts.columns;
+    let headers: Vec<String> = columns.iter().map(|c| c.header(opts.score_label)).collect();
+    let mut rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|e| columns.iter().map(|c| c.cell(e, opts)).collect())
+        .collect();
+    let fixed = matches!(opts.style, TableStyle::Ascii | TableStyle::Unicode);
+    let exact = fixed && opts.fixed_widths;
+    let mut widths = widths(
+        columns,
+        &headers,
+        &rows,
+        fixed.then_some(opts.numbers),
+        exact,
+    );
+    let gaps = columns.len().saturating_sub(1);
+    let overhead = match opts.style {
+        TableStyle::Ascii => gaps,
+        TableStyle::Unicode => 3 * gaps + 4,
+        TableStyle::Compact => 2 * gaps,
+        TableStyle::Markdown => 0,
+    };
+    let fits = opts
+        .max_width
+        .filter(|_| opts.style != TableStyle::Markdown);
+    if let Some(max_width) = fits {
+        fit(&mut widths, columns, overhead, max_width, opts.full_names);
+    }
+    if fits.is_some() || exact {
+        for row in &mut rows {
+            for (cell, &width) in row.iter_mut().zip(&widths) {
+                *cell = truncate(cell, width, opts.truncate);
+            }
+        }
+    }
+    let mut lines = Vec::new();
+    match opts.style {
+        TableStyle::Ascii => {
+            let header = row(&headers, columns, &widths, " ");
+            let sep = "-".repeat(header.chars().count());
+            lines.extend([header.trim_end().to_string(), sep]);
+            lines.extend(rows.iter().zip(entries).map(|(r, e)| {
+                let line = row(r, columns, &widths, " ").trim_end().to_string();
+                paint(line, e.crap, opts.highlight)
+            }));
+        }
+        TableStyle::Unicode => {
+            let rule = |l: &str, m: &str, r: &str| {
+                let segs: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+                format!("{l}{}{r}", segs.join(m))
+            };
+            let framed = |cells: &[String]| format!("│ {} │", row(cells, columns, &widths, " │ "));
+            lines.push(rule("┌", "┬", "┐"));
+            lines.push(framed(&headers));
+            lines.push(rule("├", "┼", "┤"));
+            lines.extend(
+                rows.iter()
+                    .zip(entries)
+                    .map(|(r, e)| paint(framed(r), e.crap, opts.highlight)),
+            );
+            lines.push(rule("└", "┴", "┘"));
+        }
+        TableStyle::Markdown => {
+            let md = |cells: &[String]| {
+                let escaped: Vec<String> = cells.iter().map(|c| c.replace('|', "\\|")).collect();
+                format!("| {} |", escaped.join(" | "))
+            };
+            lines.push(md(&headers));
+            let align: Vec<&str> = columns
+                .iter()
+                .map(|c| if c.right() { "---:" } else { "---" })
+                .collect();
+            lines.push(format!("| {} |", align.join(" | ")));
+            lines.extend(rows.iter().map(|r| md(r)));
+        }
+        TableStyle::Compact => {
+            lines.push(row(&headers, columns, &widths, "  ").trim_end().to_string());
+            lines.extend(rows.iter().zip(entries).map(|(r, e)| {
+                let line = row(r, columns, &widths, "  ").trim_end().to_string();
+                paint(line, e.crap, opts.highlight)
+            }));
+        }
     }
     lines.push(String::new());
     lines.join("\n")
@@ -67,6 +824,16 @@ mod tests {
         assert_eq!(crap_score(5, Some(100.0)), Some(5.0));
     }
 
+    #[test]
+    fn total_score_skips_unscored_and_is_positive_zero_when_empty() {
+        let entries = [Some(1.5), None, Some(2.0)].map(|crap| CrapEntry {
+            crap,
+            ..Default::default()
+        });
+        assert_eq!(total_score(&entries), 3.5);
+        assert!(total_score(&[]).is_sign_positive());
+    }
+
     #[test]
     fn score_zero_coverage() {
         assert_eq!(crap_score(5, Some(0.0)), Some(30.0));
@@ -90,13 +857,130 @@ mod tests {
         assert_eq!(crap_score(1, None), None);
     }
 
+    #[test]
+    fn coverage_debt_counts_lines_only_above_threshold() {
+        let mut hot = entry("hot", Some(45.0));
+        hot.uncovered_lines = vec![3, 4, 9];
+        let mut edge = entry("edge", Some(30.0));
+        edge.uncovered_lines = vec![12];
+        let mut cool = entry("cool", Some(12.0));
+        cool.uncovered_lines = vec![20, 21];
+        let unscored = entry("unscored", None);
+        let debt = coverage_debt(&[hot, edge, cool, unscored], 30.0);
+        assert_eq!(
+            debt,
+            CoverageDebt {
+                lines: 4,
+                functions: 2
+            }
+        );
+    }
+
+    #[test]
+    fn summary_statistics_cover_scored_entries() {
+        let mut branchy = entry("branchy", Some(40.0));
+        branchy.complexity = 6;
+        branchy.coverage = Some(20.0);
+        let mut simple = entry("simple", Some(2.0));
+        simple.complexity = 2;
+        simple.coverage = Some(100.0);
+        let entries = vec![
+            branchy,
+            entry("mid", Some(10.0)),
+            simple,
+            entry("unscored", None),
+        ];
+        let summary = summarize(&entries, 30.0);
+        assert_eq!(summary.functions, 4);
+        assert_eq!(summary.scored, 3);
+        assert_eq!(summary.total, 52.0);
+        assert_eq!(summary.median, Some(10.0));
+        assert_eq!(summary.crappy, 1);
+        // (6 × 20% + 1 × 0% + 2 × 100%) / 9
+        assert!((summary.weighted_coverage.unwrap() - 320.0 / 9.0).abs() < 1e-9);
+        assert_eq!(
+            format_summary(&summary, "CRAP", 30.0, NumberFormat::default()),
+            "summary: 4 function(s), 3 scored — total CRAP 52.0, mean 17.3, median 10.0, \
+             1 with CRAP >= 30, complexity-weighted coverage 35.6%\n"
+        );
+
+        let even = summarize(&[entry("a", Some(4.0)), entry("b", Some(8.0))], 30.0);
+        assert_eq!(even.median, Some(6.0));
+        assert_eq!(
+            format_summary(&summarize(&[], 30.0), "CRAP", 30.0, NumberFormat::default()),
+            "summary: 0 function(s), 0 scored\n"
+        );
+    }
+
+    #[test]
+    fn verdict_line_names_the_worst_function() {
+        let mut worst = entry("parse", Some(84.2));
+        worst.module_path = "core::parser".into();
+        let entries = vec![entry("load", Some(31.0)), worst, entry("idle", None)];
+        assert_eq!(
+            verdict_line(&entries, 30.0),
+            "craprs: 2 crappy, worst=84.2 (core::parser::parse), load=115.2"
+        );
+        assert_eq!(
+            verdict_line(&[entry("idle", None)], 30.0),
+            "craprs: 0 crappy, worst=none, load=0.0"
+        );
+    }
+
+    #[test]
+    fn quiet_line_is_key_value_pairs() {
+        let entries = vec![
+            entry("load", Some(31.0)),
+            entry("parse", Some(84.2)),
+            entry("idle", None),
+        ];
+        assert_eq!(
+            quiet_line(&entries, 30.0),
+            "functions=3 crap_total=115.2 worst=84.2 over_threshold=2"
+        );
+        assert_eq!(
+            quiet_line(&[], 30.0),
+            "functions=0 crap_total=0.0 worst=none over_threshold=0"
+        );
+    }
+
+    #[test]
+    fn uncovered_cc_is_linear_in_coverage() {
+        assert_eq!(uncovered_cc_score(8, Some(0.0)), Some(8.0));
+        assert_eq!(uncovered_cc_score(8, Some(25.0)), Some(6.0));
+        assert_eq!(uncovered_cc_score(8, Some(100.0)), Some(0.0));
+        assert_eq!(uncovered_cc_score(8, None), None);
+    }
+
+    #[test]
+    fn score_kind_dispatches() {
+        assert_eq!(ScoreKind::Crap.score(5, Some(0.0)), Some(30.0));
+        assert_eq!(ScoreKind::UncoveredCc.score(5, Some(0.0)), Some(5.0));
+    }
+
+    #[test]
+    fn score_label_renames_score_column() {
+        let opts = TableOptions {
+            score_label: ScoreKind::UncoveredCc.label(),
+            ..Default::default()
+        };
+        let report = format_report_with(&[], &opts);
+        assert!(
+            report
+                .lines()
+                .nth(2)
+                .unwrap()
+                .ends_with(" UncovCC Location")
+        );
+    }
+
     fn entry(name: &str, crap: Option<f64>) -> CrapEntry {
         CrapEntry {
             name: name.into(),
-            module_path: String::new(),
             complexity: 1,
             coverage: crap.map(|_| 0.0),
             crap,
+            ..Default::default()
         }
     }
 
@@ -142,6 +1026,7 @@ mod tests {
             complexity: 3,
             coverage: Some(85.0),
             crap: Some(4.5),
+            ..Default::default()
         }];
         let report = format_report(&entries);
         assert!(report.contains("foo"));
@@ -156,8 +1041,7 @@ mod tests {
             name: "uncovered".into(),
             module_path: "mod::x".into(),
             complexity: 4,
-            coverage: None,
-            crap: None,
+            ..Default::default()
         }];
         let report = format_report(&entries);
         assert!(report.contains("uncovered"));
@@ -165,4 +1049,371 @@ mod tests {
         // Make sure we didn't print a spurious 0.0%.
         assert!(!report.contains("0.0%"));
     }
+
+    fn sample() -> Vec<CrapEntry> {
+        vec![
+            CrapEntry {
+                name: "parse".into(),
+                module_path: "core::parser".into(),
+                complexity: 12,
+                coverage: Some(45.0),
+                crap: Some(35.9),
+                file: PathBuf::from("./src/parser.rs"),
+                start_line: 42,
+                ..Default::default()
+            },
+            CrapEntry {
+                name: "a|b".into(),
+                module_path: "io".into(),
+                complexity: 2,
+                file: PathBuf::from("./src/io.rs"),
+                start_line: 7,
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn styled(style: TableStyle) -> TableOptions {
+        TableOptions {
+            style,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ascii_style_is_the_default_layout() {
+        assert_eq!(
+            format_report(&sample()),
+            format_report_with(&sample(), &styled(TableStyle::Ascii))
+        );
+    }
+
+    #[test]
+    fn ascii_keeps_fixed_columns() {
+        let report = format_report(&sample());
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "CRAP Report");
+        assert!(lines[4].starts_with(&format!("{:<30} {:<45}", "parse", "core::parser")));
+        assert!(lines[4].ends_with("  12  45.0%     35.9 src/parser.rs:42"));
+        assert!(lines[5].ends_with(&format!("{DASH} src/io.rs:7")));
+    }
+
+    #[test]
+    fn fixed_widths_cut_long_names_instead_of_growing() {
+        let mut entries = sample();
+        entries[0].name = "n".repeat(40);
+        entries[0].module_path = format!("core::{}", "m".repeat(50));
+        let opts = TableOptions {
+            fixed_widths: true,
+            ..Default::default()
+        };
+        let table = format_table(&entries, &opts);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines[0],
+            format!(
+                "{:<30} {:<45}   CC   Cov%     CRAP Location",
+                "Function", "Module"
+            )
+        );
+        assert_eq!(
+            lines[2],
+            format!(
+                "{}… core::{}…   12  45.0%     35.9 src/parser.rs:42",
+                "n".repeat(29),
+                "m".repeat(38)
+            )
+        );
+        assert_eq!(
+            lines[3],
+            format!(
+                "{:<30} {:<45}    2      {DASH}        {DASH} src/io.rs:7",
+                "a|b", "io"
+            )
+        );
+        // Without them the columns grow to fit instead.
+        assert!(format_table(&entries, &TableOptions::default()).contains(&"n".repeat(40)));
+    }
+
+    #[test]
+    fn unicode_style_frames_rows() {
+        let report = format_report_with(&sample(), &styled(TableStyle::Unicode));
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[1].starts_with('┌') && lines[1].ends_with('┐'));
+        assert!(lines[2].starts_with("│ Function"));
+        assert!(lines.last().unwrap().starts_with('└'));
+        // Every framed line has the same display width.
+        let widths: Vec<usize> = lines[1..].iter().map(|l| l.chars().count()).collect();
+        assert!(widths.iter().all(|&w| w == widths[0]), "{widths:?}");
+    }
+
+    #[test]
+    fn markdown_style_escapes_pipes_and_aligns_numbers() {
+        let report = format_report_with(&sample(), &styled(TableStyle::Markdown));
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(
+            lines[0],
+            "| Function | Module | CC | Cov% | CRAP | Location |"
+        );
+        assert_eq!(lines[1], "| --- | --- | ---: | ---: | ---: | --- |");
+        assert_eq!(
+            lines[2],
+            "| parse | core::parser | 12 | 45.0% | 35.9 | src/parser.rs:42 |"
+        );
+        assert_eq!(
+            lines[3],
+            format!("| a\\|b | io | 2 | {DASH} | {DASH} | src/io.rs:7 |")
+        );
+    }
+
+    #[test]
+    fn compact_style_sizes_columns_to_content() {
+        let report = format_report_with(&sample(), &styled(TableStyle::Compact));
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Function  Module        CC   Cov%  CRAP  Location"
+        );
+        assert_eq!(
+            lines[1],
+            "parse     core::parser  12  45.0%  35.9  src/parser.rs:42"
+        );
+    }
+
+    #[test]
+    fn columns_pick_and_order_the_table() {
+        let mut entries = sample();
+        entries[0].end_line = 50;
+        let opts = TableOptions {
+            style: TableStyle::Compact,
+            columns: vec![Column::File, Column::Loc, Column::Crap],
+            ..Default::default()
+        };
+        let report = format_report_with(&entries, &opts);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Location          LOC  CRAP");
+        assert_eq!(lines[1], "src/parser.rs:42    9  35.9");
+        assert_eq!(lines[2], format!("src/io.rs:7         0     {DASH}"));
+    }
+
+    #[test]
+    fn branch_column_follows_line_coverage_when_there_is_branch_data() {
+        assert_eq!(
+            default_columns(false, false, false),
+            DEFAULT_COLUMNS.to_vec()
+        );
+        let columns = default_columns(true, false, true);
+        assert_eq!(&columns[3..5], &[Column::Cov, Column::Branch]);
+        assert_eq!(columns.len(), COMPARE_COLUMNS.len() + 1);
+
+        let mut entries = sample();
+        entries[0].branch_coverage = Some(62.5);
+        let opts = TableOptions {
+            style: TableStyle::Compact,
+            columns: vec![Column::Name, Column::Cov, Column::Branch],
+            ..Default::default()
+        };
+        let report = format_report_with(&entries, &opts);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[0].ends_with("Cov%    Br%"), "{}", lines[0]);
+        assert!(lines[1].ends_with("62.5%"), "{}", lines[1]);
+        assert!(lines[2].ends_with(DASH), "{}", lines[2]);
+    }
+
+    #[test]
+    fn api_column_marks_public_functions() {
+        let mut entries = sample();
+        entries[0].public = true;
+        let opts = TableOptions {
+            style: TableStyle::Compact,
+            columns: default_columns(false, true, false)[..3].to_vec(),
+            ..Default::default()
+        };
+        let report = format_report_with(&entries, &opts);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Function  API  Module");
+        assert_eq!(lines[1], "parse     pub  core::parser");
+        assert_eq!(lines[2], "a|b            io");
+    }
+
+    #[test]
+    fn long_names_widen_their_column_or_are_cut_to_fit() {
+        let mut entries = sample();
+        entries[0].name = "HashMapVisitor::visit_map_with_capacity".into();
+        let report = format_table(&entries, &TableOptions::default());
+        let lines: Vec<&str> = report.lines().collect();
+        // Every row keeps the module column where the header put it.
+        assert_eq!(lines[0].find("Module"), Some(40));
+        assert_eq!(lines[2].find("core::parser"), Some(40));
+        assert_eq!(lines[3].find("io"), Some(40));
+
+        let opts = TableOptions {
+            style: TableStyle::Compact,
+            max_width: Some(60),
+            ..Default::default()
+        };
+        let report = format_table(&entries, &opts);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines.iter().all(|l| l.chars().count() <= 60), "{report}");
+        assert!(
+            lines[1].starts_with("HashMapVisi…  core::pars…  12"),
+            "{report}"
+        );
+        assert_eq!(truncate("parser", 4, TruncateStyle::Tail), "par…");
+        assert_eq!(truncate("io", 4, TruncateStyle::Tail), "io");
+    }
+
+    #[test]
+    fn truncation_style_picks_what_survives() {
+        let name = "<Map as Visitor>::visit_map";
+        assert_eq!(truncate(name, 12, TruncateStyle::Head), "…::visit_map");
+        assert_eq!(truncate(name, 12, TruncateStyle::Middle), "<Map a…t_map");
+        assert_eq!(truncate(name, 12, TruncateStyle::Tail), "<Map as Vis…");
+
+        let mut entries = sample();
+        entries[0].name = "HashMapVisitor::visit_map_with_capacity".into();
+        let opts = TableOptions {
+            style: TableStyle::Compact,
+            max_width: Some(60),
+            full_names: true,
+            ..Default::default()
+        };
+        let report = format_table(&entries, &opts);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(
+            lines[1].starts_with("HashMapVisitor::visit_map_with_capacity  core::p…  12"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn delta_columns_compare_with_previous_scores() {
+        let mut entries = sample();
+        entries.push(CrapEntry {
+            name: "fresh".into(),
+            ..entries[0].clone()
+        });
+        let previous = Previous {
+            crap: Some(30.0),
+            coverage: Some(45.0),
+        };
+        let opts = TableOptions {
+            style: TableStyle::Compact,
+            columns: vec![Column::Name, Column::DeltaCrap, Column::DeltaCov],
+            previous: Some(HashMap::from([
+                (("core::parser".into(), "parse".into()), previous),
+                (("io".into(), "a|b".into()), previous),
+            ])),
+            ..Default::default()
+        };
+        let report = format_table(&entries, &opts);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Function  ΔCRAP  ΔCov");
+        assert_eq!(lines[1], "parse      ▲5.9     =");
+        assert_eq!(lines[2], format!("a|b           {DASH}     {DASH}"));
+        assert_eq!(lines[3], "fresh       new   new");
+
+        // The Δ headers are multi-byte; the rule matches them in characters.
+        let ascii = format_table(
+            &entries,
+            &TableOptions {
+                style: TableStyle::Ascii,
+                ..opts
+            },
+        );
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines[0].chars().count(), 47);
+        assert_eq!(lines[1], "-".repeat(47));
+    }
+
+    #[test]
+    fn regressions_are_new_or_higher_scoring() {
+        let mut entries = sample();
+        for name in ["fresh", "tidied"] {
+            entries.push(CrapEntry {
+                name: name.into(),
+                ..entries[0].clone()
+            });
+        }
+        let previous = |crap| Previous {
+            crap,
+            coverage: None,
+        };
+        // `a|b` lost its score and `tidied` improved, so only `parse` (up
+        // from 30.0) and the new `fresh` are left.
+        let previous = HashMap::from([
+            (
+                ("core::parser".into(), "parse".into()),
+                previous(Some(30.0)),
+            ),
+            (("io".into(), "a|b".into()), previous(Some(2.0))),
+            (
+                ("core::parser".into(), "tidied".into()),
+                previous(Some(50.0)),
+            ),
+        ]);
+        let kept: Vec<&str> = entries
+            .iter()
+            .filter(|e| is_regression(e, &previous))
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(kept, ["parse", "fresh"]);
+    }
+
+    #[test]
+    fn number_format_controls_separator_and_precision() {
+        let comma = NumberFormat::parse("0,00").unwrap();
+        assert_eq!(comma.format(35.8765), "35,88");
+        assert_eq!(NumberFormat::parse("0").unwrap().format(35.8765), "36");
+        assert_eq!(NumberFormat::parse("0.0").unwrap(), NumberFormat::default());
+        for bad in ["", "0.", "0;0", "0.5", "1.0", "0.0000000"] {
+            assert!(NumberFormat::parse(bad).is_err(), "{bad}");
+        }
+        let opts = TableOptions {
+            style: TableStyle::Compact,
+            numbers: comma,
+            ..Default::default()
+        };
+        let report = format_report_with(&sample(), &opts);
+        assert!(report.contains("parse     core::parser  12  45,00%  35,90  src/parser.rs:42\n"));
+        let ascii = format_report_with(
+            &sample(),
+            &TableOptions {
+                numbers: comma,
+                ..Default::default()
+            },
+        );
+        assert!(
+            ascii
+                .lines()
+                .nth(4)
+                .unwrap()
+                .ends_with("  12  45,00%    35,90 src/parser.rs:42")
+        );
+    }
+
+    #[test]
+    fn highlight_colors_rows_by_risk() {
+        let mut entries = sample();
+        entries.push(entry("calm", Some(5.0)));
+        entries.push(entry("warm", Some(20.0)));
+        let opts = TableOptions {
+            highlight: Some(30.0),
+            ..Default::default()
+        };
+        let report = format_report_with(&entries, &opts);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[2].starts_with("Function"));
+        assert!(lines[4].starts_with(RED) && lines[4].ends_with(RESET));
+        // Unscored rows stay plain.
+        assert!(lines[5].starts_with("a|b"));
+        assert!(lines[6].starts_with(GREEN));
+        assert!(lines[7].starts_with(YELLOW));
+
+        let md = TableOptions {
+            style: TableStyle::Markdown,
+            ..opts
+        };
+        assert!(!format_report_with(&entries, &md).contains('\x1b'));
+    }
 }
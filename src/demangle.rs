@@ -0,0 +1,446 @@
+/// Demangle a Rust symbol as found in LCOV `FN:` records, e.g.
+/// `_ZN7mycrate6parser5parse17h0123456789abcdefE` (legacy) or
+/// `_RNvNtCs1234_7mycrate6parser5parse` (v0) to `mycrate::parser::parse`.
+/// Hashes and crate disambiguators are dropped, trait impls read
+/// `<mycrate::Foo as core::fmt::Display>::fmt`. Anything that isn't a
+/// mangled Rust symbol (tools like tarpaulin write plain names) is returned
+/// unchanged.
+pub fn demangle(symbol: &str) -> String {
+    // macOS adds a second leading underscore.
+    let Some(stripped) = symbol.strip_prefix("__").or(symbol.strip_prefix('_')) else {
+        return symbol.to_string();
+    };
+    if let Some(rest) = stripped.strip_prefix("ZN")
+        && let Some(name) = legacy(rest)
+    {
+        return name;
+    }
+    if let Some(rest) = stripped.strip_prefix('R') {
+        let rest = rest.split('.').next().unwrap_or(rest);
+        let mut parser = V0 {
+            bytes: rest.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        if let Some(name) = parser.path() {
+            return name;
+        }
+    }
+    symbol.to_string()
+}
+
+/// `<len><ident>...E` with `$..$` escapes and a trailing `h<hash>` segment.
+fn legacy(mut rest: &str) -> Option<String> {
+    let mut segments = Vec::new();
+    while !rest.starts_with('E') {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let len: usize = rest[..digits].parse().ok()?;
+        let ident = rest.get(digits..digits + len)?;
+        rest = &rest[digits + len..];
+        segments.push(ident);
+    }
+    if let Some(last) = segments.last()
+        && last.len() == 17
+        && last.starts_with('h')
+        && last[1..].bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        segments.pop();
+    }
+    let segments: Vec<String> = segments.into_iter().map(unescape).collect();
+    Some(segments.join("::"))
+}
+
+/// Decode the `$LT$`-style escapes and `..` separators of a legacy segment.
+fn unescape(segment: &str) -> String {
+    // A segment can't start with `$`, so an escape there gets a `_` in front.
+    let segment = segment
+        .strip_prefix('_')
+        .filter(|s| s.starts_with('$'))
+        .unwrap_or(segment);
+    let mut out = String::new();
+    let mut rest = segment;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("..") {
+            out.push_str("::");
+            rest = after;
+            continue;
+        }
+        if c == '$'
+            && let Some(end) = rest[1..].find('$')
+        {
+            let code = &rest[1..end + 1];
+            let decoded = match code {
+                "SP" => Some('@'),
+                "BP" => Some('*'),
+                "RF" => Some('&'),
+                "LT" => Some('<'),
+                "GT" => Some('>'),
+                "LP" => Some('('),
+                "RP" => Some(')'),
+                "C" => Some(','),
+                _ => code
+                    .strip_prefix('u')
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32),
+            };
+            if let Some(decoded) = decoded {
+                out.push(decoded);
+                rest = &rest[end + 2..];
+                continue;
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Recursive-descent reader for the v0 mangling scheme (RFC 2603), rendering
+/// paths and types the way they'd be written in source.
+struct V0<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Nesting through backrefs, bounded so a malformed symbol can't loop.
+    depth: usize,
+}
+
+impl V0<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        let found = self.peek() == Some(b);
+        self.pos += usize::from(found);
+        found
+    }
+
+    /// `_` for 0, otherwise base 62 digits then `_` for the value plus one.
+    fn base62(&mut self) -> Option<u64> {
+        if self.eat(b'_') {
+            return Some(0);
+        }
+        let mut value: u64 = 0;
+        loop {
+            let digit = match self.next()? {
+                b @ b'0'..=b'9' => b - b'0',
+                b @ b'a'..=b'z' => b - b'a' + 10,
+                b @ b'A'..=b'Z' => b - b'A' + 36,
+                b'_' => return value.checked_add(1),
+                _ => return None,
+            };
+            value = value.checked_mul(62)?.checked_add(u64::from(digit))?;
+        }
+    }
+
+    fn decimal(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn disambiguator(&mut self) -> Option<u64> {
+        if self.eat(b's') {
+            return self.base62();
+        }
+        Some(0)
+    }
+
+    /// `[u] <len> [_] <bytes>`; punycode (`u`) identifiers are kept encoded.
+    fn ident(&mut self) -> Option<String> {
+        self.eat(b'u');
+        let len = self.decimal()?;
+        self.eat(b'_');
+        let bytes = self.bytes.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Re-read the path or type at a backref's target, then come back.
+    fn backref<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let target = usize::try_from(self.base62()?).ok()?;
+        if target >= self.pos || self.depth > 64 {
+            return None;
+        }
+        let saved = self.pos;
+        self.pos = target;
+        self.depth += 1;
+        let value = read(self);
+        self.depth -= 1;
+        self.pos = saved;
+        value
+    }
+
+    fn path(&mut self) -> Option<String> {
+        match self.next()? {
+            b'C' => {
+                self.disambiguator()?;
+                self.ident()
+            }
+            b'M' => {
+                self.disambiguator()?;
+                self.path()?;
+                self.ty()
+            }
+            b'X' => {
+                self.disambiguator()?;
+                self.path()?;
+                let ty = self.ty()?;
+                let tr = self.path()?;
+                Some(format!("<{ty} as {tr}>"))
+            }
+            b'Y' => {
+                let ty = self.ty()?;
+                let tr = self.path()?;
+                Some(format!("<{ty} as {tr}>"))
+            }
+            b'N' => {
+                let ns = self.next()?;
+                let parent = self.path()?;
+                let disambiguator = self.disambiguator()?;
+                let ident = self.ident()?;
+                Some(match ns {
+                    b'C' => format!("{parent}::{{closure#{disambiguator}}}"),
+                    b'S' => format!("{parent}::{{shim:{ident}#{disambiguator}}}"),
+                    b'A'..=b'Z' => format!("{parent}::{{{}#{disambiguator}}}", ns as char),
+                    _ if ident.is_empty() => parent,
+                    _ => format!("{parent}::{ident}"),
+                })
+            }
+            b'I' => {
+                let path = self.path()?;
+                let args = self.generic_args()?;
+                Some(format!("{path}<{args}>"))
+            }
+            b'B' => self.backref(Self::path),
+            _ => None,
+        }
+    }
+
+    /// Generic arguments up to `E`, lifetimes left out.
+    fn generic_args(&mut self) -> Option<String> {
+        let mut args = Vec::new();
+        while !self.eat(b'E') {
+            if self.eat(b'L') {
+                self.base62()?;
+            } else if self.eat(b'K') {
+                args.push(self.constant()?);
+            } else {
+                args.push(self.ty()?);
+            }
+        }
+        Some(args.join(", "))
+    }
+
+    fn constant(&mut self) -> Option<String> {
+        if self.eat(b'p') {
+            return Some("_".into());
+        }
+        if self.eat(b'B') {
+            return self.backref(Self::constant);
+        }
+        let ty = self.next()?;
+        let negative = self.eat(b'n');
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_hexdigit()) {
+            self.pos += 1;
+        }
+        let hex = std::str::from_utf8(&self.bytes[start..self.pos]).ok()?;
+        if !self.eat(b'_') {
+            return None;
+        }
+        let value = if hex.is_empty() {
+            0
+        } else {
+            u128::from_str_radix(hex, 16).ok()?
+        };
+        Some(match ty {
+            b'b' => (value != 0).to_string(),
+            b'c' => char::from_u32(u32::try_from(value).ok()?)
+                .map_or_else(|| value.to_string(), |c| format!("{c:?}")),
+            _ if negative => format!("-{value}"),
+            _ => value.to_string(),
+        })
+    }
+
+    fn ty(&mut self) -> Option<String> {
+        let basic = match self.peek()? {
+            b'a' => "i8",
+            b'b' => "bool",
+            b'c' => "char",
+            b'd' => "f64",
+            b'e' => "str",
+            b'f' => "f32",
+            b'h' => "u8",
+            b'i' => "isize",
+            b'j' => "usize",
+            b'l' => "i32",
+            b'm' => "u32",
+            b'n' => "i128",
+            b'o' => "u128",
+            b's' => "i16",
+            b't' => "u16",
+            b'u' => "()",
+            b'v' => "...",
+            b'x' => "i64",
+            b'y' => "u64",
+            b'z' => "!",
+            b'p' => "_",
+            _ => "",
+        };
+        if !basic.is_empty() {
+            self.pos += 1;
+            return Some(basic.to_string());
+        }
+        match self.peek()? {
+            b'C' | b'M' | b'X' | b'Y' | b'N' | b'I' => return self.path(),
+            _ => {}
+        }
+        Some(match self.next()? {
+            b'A' => {
+                let ty = self.ty()?;
+                let len = self.constant()?;
+                format!("[{ty}; {len}]")
+            }
+            b'S' => format!("[{}]", self.ty()?),
+            b'T' => {
+                let mut items = Vec::new();
+                while !self.eat(b'E') {
+                    items.push(self.ty()?);
+                }
+                match items.len() {
+                    1 => format!("({},)", items[0]),
+                    _ => format!("({})", items.join(", ")),
+                }
+            }
+            b'R' | b'Q' => {
+                let mutable = self.bytes[self.pos - 1] == b'Q';
+                if self.eat(b'L') {
+                    self.base62()?;
+                }
+                let ty = self.ty()?;
+                if mutable {
+                    format!("&mut {ty}")
+                } else {
+                    format!("&{ty}")
+                }
+            }
+            b'P' => format!("*const {}", self.ty()?),
+            b'O' => format!("*mut {}", self.ty()?),
+            b'F' => {
+                if self.eat(b'G') {
+                    self.base62()?;
+                }
+                let unsafety = if self.eat(b'U') { "unsafe " } else { "" };
+                let abi = if self.eat(b'K') {
+                    let abi = match self.eat(b'C') {
+                        true => "C".to_string(),
+                        false => self.ident()?.replace('_', "-"),
+                    };
+                    format!("extern \"{abi}\" ")
+                } else {
+                    String::new()
+                };
+                let mut params = Vec::new();
+                while !self.eat(b'E') {
+                    params.push(self.ty()?);
+                }
+                let ret = self.ty()?;
+                let ret = match ret.as_str() {
+                    "()" => String::new(),
+                    _ => format!(" -> {ret}"),
+                };
+                format!("{unsafety}{abi}fn({}){ret}", params.join(", "))
+            }
+            b'D' => {
+                if self.eat(b'G') {
+                    self.base62()?;
+                }
+                let mut bounds = Vec::new();
+                while !self.eat(b'E') {
+                    let mut bound = self.path()?;
+                    while self.eat(b'p') {
+                        let name = self.ident()?;
+                        let ty = self.ty()?;
+                        bound.push_str(&format!("<{name} = {ty}>"));
+                    }
+                    bounds.push(bound);
+                }
+                if self.eat(b'L') {
+                    self.base62()?;
+                }
+                format!("dyn {}", bounds.join(" + "))
+            }
+            b'B' => self.backref(Self::ty)?,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangles_legacy_symbols() {
+        assert_eq!(
+            demangle("_ZN7mycrate6parser5parse17h0123456789abcdefE"),
+            "mycrate::parser::parse"
+        );
+        assert_eq!(
+            demangle(
+                "_ZN62_$LT$mycrate..Map$LT$K$C$V$GT$$u20$as$u20$core..fmt..Debug$GT$3fmt17h0123456789abcdefE"
+            ),
+            "<mycrate::Map<K,V> as core::fmt::Debug>::fmt"
+        );
+        assert_eq!(
+            demangle("__ZN7mycrate4main28_$u7b$$u7b$closure$u7d$$u7d$17h0123456789abcdefE"),
+            "mycrate::main::{{closure}}"
+        );
+    }
+
+    #[test]
+    fn demangles_v0_symbols() {
+        assert_eq!(
+            demangle("_RNvNtCs1234_7mycrate6parser5parse"),
+            "mycrate::parser::parse"
+        );
+        // <mycrate::Foo as core::fmt::Display>::fmt
+        assert_eq!(
+            demangle("_RNvXs_Cs1234_7mycrateNtB4_3FooNtNtCs9_4core3fmt7Display3fmt"),
+            "<mycrate::Foo as core::fmt::Display>::fmt"
+        );
+        // Inherent method of a generic type, and a closure inside it.
+        assert_eq!(
+            demangle("_RNvMCs1234_7mycrateINtB2_3MapjjE3get"),
+            "mycrate::Map<usize, usize>::get"
+        );
+        assert_eq!(
+            demangle("_RNCNvCs1234_7mycrate4main0B3_"),
+            "mycrate::main::{closure#0}"
+        );
+        assert_eq!(
+            demangle("_RINvCs1234_7mycrate5applyFRhEuE"),
+            "mycrate::apply<fn(&u8)>"
+        );
+    }
+
+    #[test]
+    fn leaves_plain_names_alone() {
+        assert_eq!(demangle("parse"), "parse");
+        assert_eq!(demangle("Parser::parse"), "Parser::parse");
+        assert_eq!(demangle("_ZN3foo"), "_ZN3foo");
+        assert_eq!(demangle("_RNvB9_3foo"), "_RNvB9_3foo");
+    }
+}
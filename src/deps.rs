@@ -0,0 +1,218 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::crap::CrapEntry;
+
+/// A registry package pinned in `Cargo.lock`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedCrate {
+    pub name: String,
+    pub version: String,
+}
+
+/// Packages in `Cargo.lock` that come from a registry (crates.io or another
+/// index), in lock-file order. Path and git dependencies are left out.
+pub fn registry_crates(lock: &str) -> Result<Vec<LockedCrate>> {
+    let doc: toml::Value = lock.parse().context("failed to parse Cargo.lock")?;
+    let Some(packages) = doc.get("package").and_then(|p| p.as_array()) else {
+        return Ok(Vec::new());
+    };
+    let crates = packages
+        .iter()
+        .filter(|p| {
+            p.get("source")
+                .and_then(|s| s.as_str())
+                .is_some_and(|s| s.starts_with("registry+") || s.starts_with("sparse+"))
+        })
+        .filter_map(|p| {
+            Some(LockedCrate {
+                name: p.get("name")?.as_str()?.to_string(),
+                version: p.get("version")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+    Ok(crates)
+}
+
+/// The unpacked-source directories of every registry under `cargo_home`,
+/// e.g. `~/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f`.
+pub fn registry_src_dirs(cargo_home: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(cargo_home.join("registry/src")) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Where `krate` is unpacked in one of `registry_dirs`, if cargo has fetched it.
+pub fn locate(registry_dirs: &[PathBuf], krate: &LockedCrate) -> Option<PathBuf> {
+    registry_dirs
+        .iter()
+        .map(|dir| dir.join(format!("{}-{}", krate.name, krate.version)))
+        .find(|root| root.join("Cargo.toml").is_file())
+}
+
+/// Complexity summary of one dependency.
+#[derive(Debug, Clone)]
+pub struct DepRisk {
+    pub krate: LockedCrate,
+    pub functions: usize,
+    pub max_cc: u32,
+    /// Functions with complexity at or above the hotspot threshold, most
+    /// complex first.
+    pub hotspots: Vec<CrapEntry>,
+}
+
+pub fn dep_risk(krate: LockedCrate, entries: Vec<CrapEntry>, min_cc: u32) -> DepRisk {
+    let functions = entries.len();
+    let max_cc = entries.iter().map(|e| e.complexity).max().unwrap_or(0);
+    let mut hotspots: Vec<CrapEntry> = entries
+        .into_iter()
+        .filter(|e| e.complexity >= min_cc)
+        .collect();
+    hotspots.sort_by_key(|e| std::cmp::Reverse(e.complexity));
+    DepRisk {
+        krate,
+        functions,
+        max_cc,
+        hotspots,
+    }
+}
+
+/// Riskiest first: most hotspots, then highest single complexity, then name.
+pub fn rank(risks: &mut [DepRisk]) {
+    risks.sort_by(|a, b| {
+        b.hotspots
+            .len()
+            .cmp(&a.hotspots.len())
+            .then(b.max_cc.cmp(&a.max_cc))
+            .then_with(|| a.krate.name.cmp(&b.krate.name))
+    });
+}
+
+/// One block per dependency with its `top` most complex hotspots, then the
+/// locked crates that aren't in the registry cache.
+pub fn format_deps(risks: &[DepRisk], min_cc: u32, top: usize, missing: &[LockedCrate]) -> String {
+    let mut out = format!("Dependency risk (hotspots: CC >= {min_cc}):\n");
+    for r in risks {
+        out.push_str(&format!(
+            "  {} {} — {} function(s), {} hotspot(s), max CC {}\n",
+            r.krate.name,
+            r.krate.version,
+            r.functions,
+            r.hotspots.len(),
+            r.max_cc
+        ));
+        for e in r.hotspots.iter().take(top) {
+            let path = if e.module_path.is_empty() {
+                e.name.clone()
+            } else {
+                format!("{}::{}", e.module_path, e.name)
+            };
+            out.push_str(&format!(
+                "    - {path} (CC {}) {}\n",
+                e.complexity,
+                e.location()
+            ));
+        }
+    }
+    if !missing.is_empty() {
+        let names: Vec<String> = missing
+            .iter()
+            .map(|c| format!("{} {}", c.name, c.version))
+            .collect();
+        out.push_str(&format!(
+            "not in the local registry cache (run `cargo fetch`): {}\n",
+            names.join(", ")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked(name: &str) -> LockedCrate {
+        LockedCrate {
+            name: name.into(),
+            version: "1.0.0".into(),
+        }
+    }
+
+    fn entry(name: &str, complexity: u32) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "de".into(),
+            complexity,
+            file: PathBuf::from("src/de.rs"),
+            start_line: 12,
+            end_line: 40,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keeps_only_registry_packages() {
+        let lock = r#"
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+
+[[package]]
+name = "serde"
+version = "1.0.210"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "patched"
+version = "0.3.0"
+source = "git+https://example.com/patched.git#abc123"
+
+[[package]]
+name = "internal"
+version = "2.1.0"
+source = "sparse+https://crates.example.com/index/"
+"#;
+        let crates = registry_crates(lock).unwrap();
+        let names: Vec<&str> = crates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["serde", "internal"]);
+        assert_eq!(crates[0].version, "1.0.210");
+    }
+
+    #[test]
+    fn ranks_by_hotspots_and_formats_the_worst() {
+        let mut risks = vec![
+            dep_risk(locked("calm"), vec![entry("new", 1), entry("get", 3)], 10),
+            dep_risk(
+                locked("gnarly"),
+                vec![
+                    entry("parse", 14),
+                    entry("peek", 2),
+                    entry("deserialize_any", 31),
+                ],
+                10,
+            ),
+        ];
+        rank(&mut risks);
+        assert_eq!(risks[0].krate.name, "gnarly");
+        assert_eq!(risks[0].max_cc, 31);
+        let out = format_deps(&risks, 10, 1, &[locked("absent")]);
+        assert_eq!(
+            out,
+            "Dependency risk (hotspots: CC >= 10):\n\
+             \x20 gnarly 1.0.0 — 3 function(s), 2 hotspot(s), max CC 31\n\
+             \x20   - de::deserialize_any (CC 31) src/de.rs:12\n\
+             \x20 calm 1.0.0 — 2 function(s), 0 hotspot(s), max CC 3\n\
+             not in the local registry cache (run `cargo fetch`): absent 1.0.0\n"
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::crap::CrapEntry;
+
+/// One function scored from two different coverage sources.
+#[derive(Debug, Clone)]
+pub struct Discrepancy {
+    pub name: String,
+    pub module_path: String,
+    pub complexity: u32,
+    pub a_coverage: Option<f64>,
+    pub b_coverage: Option<f64>,
+    pub a_crap: Option<f64>,
+    pub b_crap: Option<f64>,
+}
+
+impl Discrepancy {
+    /// `b - a`, or `None` unless both sides were scored.
+    pub fn delta(&self) -> Option<f64> {
+        Some(self.b_crap? - self.a_crap?)
+    }
+}
+
+/// Pair up entries from two runs by module + name and keep those that differ
+/// materially: both scored with `|Δ| >= min_delta`, or scored on one side only
+/// (one tool instrumented the file, the other didn't). Largest differences
+/// first, one-sided pairs last.
+///
+/// Functions sharing a name within a module (e.g. the same method on several
+/// impls) are paired in source order.
+pub fn discrepancies(a: &[CrapEntry], b: &[CrapEntry], min_delta: f64) -> Vec<Discrepancy> {
+    let mut b_by_key: HashMap<(&str, &str), Vec<&CrapEntry>> = HashMap::new();
+    for e in b.iter().rev() {
+        b_by_key
+            .entry((e.module_path.as_str(), e.name.as_str()))
+            .or_default()
+            .push(e);
+    }
+
+    let mut out: Vec<Discrepancy> = Vec::new();
+    for ea in a {
+        let eb = b_by_key
+            .get_mut(&(ea.module_path.as_str(), ea.name.as_str()))
+            .and_then(|v| v.pop());
+        let d = Discrepancy {
+            name: ea.name.clone(),
+            module_path: ea.module_path.clone(),
+            complexity: ea.complexity,
+            a_coverage: ea.coverage,
+            b_coverage: eb.and_then(|e| e.coverage),
+            a_crap: ea.crap,
+            b_crap: eb.and_then(|e| e.crap),
+        };
+        let material = match d.delta() {
+            Some(delta) => delta.abs() >= min_delta,
+            None => d.a_crap.is_some() != d.b_crap.is_some(),
+        };
+        if material {
+            out.push(d);
+        }
+    }
+    // Anything left in `b` had no counterpart in `a`.
+    for eb in b_by_key.into_values().flatten() {
+        if eb.crap.is_some() {
+            out.push(Discrepancy {
+                name: eb.name.clone(),
+                module_path: eb.module_path.clone(),
+                complexity: eb.complexity,
+                a_coverage: None,
+                b_coverage: eb.coverage,
+                a_crap: None,
+                b_crap: eb.crap,
+            });
+        }
+    }
+
+    out.sort_by(|x, y| match (x.delta(), y.delta()) {
+        (Some(dx), Some(dy)) => dy.abs().partial_cmp(&dx.abs()).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => (&x.module_path, &x.name).cmp(&(&y.module_path, &y.name)),
+    });
+    out
+}
+
+const DASH: &str = "—";
+
+fn opt(v: Option<f64>, suffix: &str) -> String {
+    match v {
+        Some(x) => format!("{x:.1}{suffix}"),
+        None => DASH.to_string(),
+    }
+}
+
+/// Render discrepancies as a table with one coverage and CRAP column per source.
+pub fn format_discrepancies(
+    found: &[Discrepancy],
+    a_label: &str,
+    b_label: &str,
+    min_delta: f64,
+    total: usize,
+) -> String {
+    let title = format!("Coverage tool discrepancies: {a_label} vs {b_label} (|ΔCRAP| >= {min_delta})");
+    let mut lines = vec![title.clone(), "=".repeat(title.chars().count())];
+    let header = format!(
+        "{:<30} {:<35} {:>4} {:>14} {:>14} {:>14} {:>14} {:>8}",
+        "Function",
+        "Module",
+        "CC",
+        format!("{a_label} Cov%"),
+        format!("{b_label} Cov%"),
+        format!("{a_label} CRAP"),
+        format!("{b_label} CRAP"),
+        "ΔCRAP"
+    );
+    lines.push(header.clone());
+    lines.push("-".repeat(header.chars().count()));
+    for d in found {
+        let delta = match d.delta() {
+            Some(x) => format!("{x:+.1}"),
+            None => DASH.to_string(),
+        };
+        lines.push(format!(
+            "{:<30} {:<35} {:>4} {:>14} {:>14} {:>14} {:>14} {:>8}",
+            d.name,
+            d.module_path,
+            d.complexity,
+            opt(d.a_coverage, "%"),
+            opt(d.b_coverage, "%"),
+            opt(d.a_crap, ""),
+            opt(d.b_crap, ""),
+            delta
+        ));
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "{} of {total} function(s) differ materially",
+        found.len()
+    ));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "m".into(),
+            complexity: 4,
+            coverage: crap.map(|_| 50.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    fn names(found: &[Discrepancy]) -> Vec<&str> {
+        found.iter().map(|d| d.name.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_only_material_differences_largest_first() {
+        let a = vec![
+            entry("same", Some(10.0)),
+            entry("small", Some(10.0)),
+            entry("big", Some(10.0)),
+            entry("medium", Some(10.0)),
+        ];
+        let b = vec![
+            entry("same", Some(10.0)),
+            entry("small", Some(12.0)),
+            entry("big", Some(40.0)),
+            entry("medium", Some(3.0)),
+        ];
+        let found = discrepancies(&a, &b, 5.0);
+        assert_eq!(names(&found), vec!["big", "medium"]);
+        assert_eq!(found[0].delta(), Some(30.0));
+        assert_eq!(found[1].delta(), Some(-7.0));
+    }
+
+    #[test]
+    fn one_sided_instrumentation_is_always_reported_last() {
+        let a = vec![entry("only_a", Some(2.0)), entry("big", Some(1.0))];
+        let b = vec![
+            entry("only_a", None),
+            entry("big", Some(50.0)),
+            entry("only_b", Some(3.0)),
+        ];
+        let found = discrepancies(&a, &b, 5.0);
+        assert_eq!(names(&found), vec!["big", "only_a", "only_b"]);
+    }
+
+    #[test]
+    fn both_unscored_is_not_a_discrepancy() {
+        let found = discrepancies(&[entry("u", None)], &[entry("u", None)], 0.0);
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn duplicate_names_pair_in_order() {
+        let a = vec![entry("new", Some(1.0)), entry("new", Some(20.0))];
+        let b = vec![entry("new", Some(1.0)), entry("new", Some(20.0))];
+        assert!(discrepancies(&a, &b, 0.5).is_empty());
+    }
+
+    #[test]
+    fn format_labels_columns_by_tool() {
+        let found = discrepancies(&[entry("f", Some(1.0))], &[entry("f", Some(9.0))], 5.0);
+        let out = format_discrepancies(&found, "tarpaulin", "llvm-cov", 5.0, 1);
+        assert!(out.contains("tarpaulin CRAP"));
+        assert!(out.contains("llvm-cov Cov%"));
+        assert!(out.contains("+8.0"));
+        assert!(out.contains("1 of 1 function(s) differ materially"));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use syn::visit::Visit;
+use syn::{Expr, FnArg, Pat, Type};
+
+use crate::crap::CrapEntry;
+
+/// Smart pointers whose pointee is called through, so `Box<dyn Trait>`
+/// receivers dispatch dynamically just like `&dyn Trait` ones.
+const POINTERS: [&str; 3] = ["Box", "Rc", "Arc"];
+
+/// Calls in `block` made through a trait object, detected syntactically: a
+/// method call on, or a call of, a parameter or `let` binding whose declared
+/// type is `dyn Trait`, a reference to one, or a `Box` / `Rc` / `Arc` of one.
+/// Bindings without a type annotation and trait objects reached through
+/// fields or collections aren't seen.
+pub fn dyn_calls(sig: &syn::Signature, block: &syn::Block) -> u32 {
+    let mut visitor = DynCallVisitor {
+        receivers: HashSet::new(),
+        calls: 0,
+    };
+    for input in &sig.inputs {
+        if let FnArg::Typed(arg) = input {
+            visitor.bind(&arg.pat, &arg.ty);
+        }
+    }
+    visitor.visit_block(block);
+    visitor.calls
+}
+
+fn is_trait_object(ty: &Type) -> bool {
+    match ty {
+        Type::TraitObject(t) => t.dyn_token.is_some(),
+        Type::Reference(r) => is_trait_object(&r.elem),
+        Type::Paren(p) => is_trait_object(&p.elem),
+        Type::Path(tp) => {
+            let Some(last) = tp.path.segments.last() else {
+                return false;
+            };
+            let syn::PathArguments::AngleBracketed(ref args) = last.arguments else {
+                return false;
+            };
+            POINTERS.contains(&last.ident.to_string().as_str())
+                && args
+                    .args
+                    .iter()
+                    .any(|arg| matches!(arg, syn::GenericArgument::Type(ty) if is_trait_object(ty)))
+        }
+        _ => false,
+    }
+}
+
+struct DynCallVisitor {
+    /// Bindings declared with a trait object type.
+    receivers: HashSet<String>,
+    calls: u32,
+}
+
+impl DynCallVisitor {
+    fn bind(&mut self, pat: &Pat, ty: &Type) {
+        if let Pat::Ident(ident) = pat
+            && is_trait_object(ty)
+        {
+            self.receivers.insert(ident.ident.to_string());
+        }
+    }
+
+    fn is_receiver(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Path(p) => p
+                .path
+                .get_ident()
+                .is_some_and(|ident| self.receivers.contains(&ident.to_string())),
+            Expr::Paren(p) => self.is_receiver(&p.expr),
+            Expr::Reference(r) => self.is_receiver(&r.expr),
+            Expr::Unary(u) => matches!(u.op, syn::UnOp::Deref(_)) && self.is_receiver(&u.expr),
+            _ => false,
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for DynCallVisitor {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        if let Pat::Type(ref typed) = node.pat {
+            self.bind(&typed.pat, &typed.ty);
+        }
+        syn::visit::visit_local(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        if self.is_receiver(&node.receiver) {
+            self.calls += 1;
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        // `f(x)` with `f: &dyn Fn(X)`.
+        if self.is_receiver(&node.func) {
+            self.calls += 1;
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    // Nested fn items are extracted and counted separately.
+    fn visit_item_fn(&mut self, _node: &'ast syn::ItemFn) {}
+}
+
+/// Section listing reported functions that call through trait objects, in
+/// report order. Empty when none do.
+pub fn format_dispatch(entries: &[CrapEntry]) -> String {
+    let mut out = String::new();
+    for e in entries.iter().filter(|e| e.dyn_calls > 0) {
+        if out.is_empty() {
+            out.push_str("Trait object dispatch:\n");
+        }
+        out.push_str(&format!(
+            "  {} ({}): {} call(s) through dyn receivers\n",
+            e.name, e.module_path, e.dyn_calls
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(source: &str) -> u32 {
+        let f: syn::ItemFn = syn::parse_str(source).unwrap();
+        dyn_calls(&f.sig, &f.block)
+    }
+
+    #[test]
+    fn counts_calls_through_trait_object_bindings() {
+        let source = r#"
+            fn run(sink: &mut dyn Write, handlers: Vec<Box<dyn Handler>>, cb: &dyn Fn(u8), n: u8) {
+                let store: Arc<dyn Store> = open();
+                sink.write(b"x");
+                (*sink).flush();
+                store.get(n);
+                cb(n);
+                n.count_ones();
+                for h in handlers {
+                    h.handle();
+                }
+            }
+        "#;
+        assert_eq!(count(source), 4);
+    }
+
+    #[test]
+    fn ignores_generics_and_untyped_bindings() {
+        let source = r#"
+            fn run<W: Write>(sink: &mut W, boxed: Box<u8>) {
+                let other = make_dyn();
+                sink.write(b"x");
+                boxed.count_ones();
+                other.call();
+                fn nested(d: &dyn Debug) { d.fmt(); }
+            }
+        "#;
+        assert_eq!(count(source), 0);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Item, Meta, Path, Token, Type};
+
+use crate::complexity::{FunctionInfo, plain_name};
+
+/// Functions that proc-macros added to one consumer crate.
+pub struct Expansion {
+    /// The macro the generated code is attributed to, e.g.
+    /// `app_derive::Parse`, or the proc-macro crate(s), joined with `+`, when
+    /// no single workspace macro is applied to the type it was generated for.
+    pub origin: String,
+    /// The crate whose `cargo expand` output the functions were found in.
+    pub consumer: String,
+    pub functions: Vec<FunctionInfo>,
+}
+
+impl Expansion {
+    pub fn total_complexity(&self) -> u32 {
+        self.functions.iter().map(|f| f.complexity).sum()
+    }
+}
+
+/// Functions in `expanded` (a whole crate as printed by `cargo expand`) that
+/// have no counterpart among the `written` ones extracted from the crate's
+/// own files. Names are compared as [`plain_name`]s, since the ordinals and
+/// qualified forms that tell functions apart depend on what else is in the
+/// same file. Matching is by name and count, so a generated `fmt` next to a
+/// hand-written one is still picked up.
+pub fn generated_functions(
+    written: &[FunctionInfo],
+    expanded: Vec<FunctionInfo>,
+) -> Vec<FunctionInfo> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for f in written {
+        *counts.entry(plain_name(&f.name)).or_default() += 1;
+    }
+    expanded
+        .into_iter()
+        .filter(|f| match counts.get_mut(&plain_name(&f.name)) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// Names of the macros a proc-macro crate's `source` defines: the derive
+/// name of `#[proc_macro_derive(Name)]` functions and the function name of
+/// `#[proc_macro_attribute]` and `#[proc_macro]` ones.
+pub fn defined_macros(source: &syn::File) -> Vec<String> {
+    let mut names = Vec::new();
+    for item in &source.items {
+        let Item::Fn(f) = item else { continue };
+        for attr in &f.attrs {
+            if attr.path().is_ident("proc_macro_derive") {
+                // `Name` first, then `attributes(...)` for its helper attributes.
+                if let Ok(metas) =
+                    attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                    && let Some(name) = metas.first().and_then(|m| m.path().get_ident())
+                {
+                    names.push(name.to_string());
+                }
+            } else if attr.path().is_ident("proc_macro_attribute")
+                || attr.path().is_ident("proc_macro")
+            {
+                names.push(f.sig.ident.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// The `macros` (name to qualified path, e.g. `Parse` to
+/// `app_derive::Parse`) applied to each item of `source` that code can be
+/// generated for, by item name: derives and attributes on structs, enums,
+/// unions, traits and functions, and attributes on impls, keyed by their
+/// type. Inline modules are searched too.
+pub fn macro_uses(
+    source: &syn::File,
+    macros: &HashMap<String, String>,
+) -> HashMap<String, Vec<String>> {
+    let mut uses = HashMap::new();
+    collect_uses(&source.items, macros, &mut uses);
+    uses
+}
+
+fn collect_uses(
+    items: &[Item],
+    macros: &HashMap<String, String>,
+    uses: &mut HashMap<String, Vec<String>>,
+) {
+    for item in items {
+        let (name, attrs) = match item {
+            Item::Struct(s) => (s.ident.to_string(), &s.attrs),
+            Item::Enum(e) => (e.ident.to_string(), &e.attrs),
+            Item::Union(u) => (u.ident.to_string(), &u.attrs),
+            Item::Trait(t) => (t.ident.to_string(), &t.attrs),
+            Item::Fn(f) => (f.sig.ident.to_string(), &f.attrs),
+            Item::Impl(i) => match &*i.self_ty {
+                Type::Path(p) => match p.path.segments.last() {
+                    Some(segment) => (segment.ident.to_string(), &i.attrs),
+                    None => continue,
+                },
+                _ => continue,
+            },
+            Item::Mod(m) => {
+                if let Some((_, ref items)) = m.content {
+                    collect_uses(items, macros, uses);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        for used in applied_macros(attrs) {
+            if let Some(path) = macros.get(&used) {
+                let entry: &mut Vec<String> = uses.entry(name.clone()).or_default();
+                if !entry.contains(path) {
+                    entry.push(path.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Last path segments of the derives and attributes in `attrs`.
+fn applied_macros(attrs: &[Attribute]) -> Vec<String> {
+    let last = |path: &Path| path.segments.last().map(|s| s.ident.to_string());
+    let mut names = Vec::new();
+    for attr in attrs {
+        if attr.path().is_ident("derive") {
+            if let Ok(paths) = attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+            {
+                names.extend(paths.iter().filter_map(last));
+            }
+        } else {
+            names.extend(last(attr.path()));
+        }
+    }
+    names
+}
+
+/// Group generated `functions` by the macro that produced them: the
+/// workspace macros applied to the type a method's impl is for, or to the
+/// function of the same name, per `uses` from [`macro_uses`]. Functions no
+/// workspace macro can be found for go to `fallback`, the proc-macro crates
+/// the consumer depends on. Groups are in order of first appearance.
+pub fn attribute(
+    functions: Vec<FunctionInfo>,
+    uses: &HashMap<String, Vec<String>>,
+    fallback: &str,
+) -> Vec<(String, Vec<FunctionInfo>)> {
+    let mut groups: Vec<(String, Vec<FunctionInfo>)> = Vec::new();
+    for f in functions {
+        let plain = plain_name(&f.name);
+        let owner = plain.rsplit_once("::").map_or(plain.as_str(), |(ty, _)| {
+            ty.rsplit_once("::").map_or(ty, |(_, last)| last)
+        });
+        let origin = match uses.get(owner) {
+            Some(macros) if !macros.is_empty() => macros.join("+"),
+            _ => fallback.to_string(),
+        };
+        match groups.iter_mut().find(|(o, _)| *o == origin) {
+            Some((_, fns)) => fns.push(f),
+            None => groups.push((origin, vec![f])),
+        }
+    }
+    groups
+}
+
+/// Note summarizing generated complexity per macro, largest first; empty
+/// when nothing was generated.
+pub fn format_expansions(expansions: &[Expansion]) -> String {
+    let mut expansions: Vec<&Expansion> = expansions
+        .iter()
+        .filter(|e| !e.functions.is_empty())
+        .collect();
+    if expansions.is_empty() {
+        return String::new();
+    }
+    expansions.sort_by(|a, b| {
+        b.total_complexity()
+            .cmp(&a.total_complexity())
+            .then_with(|| a.origin.cmp(&b.origin))
+    });
+    let mut out = String::from("note: complexity generated by proc-macros (not covered above):\n");
+    for e in expansions {
+        out.push_str(&format!(
+            "  {} in {}: {} function(s), total CC {}\n",
+            e.origin,
+            e.consumer,
+            e.functions.len(),
+            e.total_complexity()
+        ));
+        let mut worst: Vec<&FunctionInfo> = e.functions.iter().collect();
+        worst.sort_by_key(|f| std::cmp::Reverse(f.complexity));
+        for f in worst.iter().take(3).filter(|f| f.complexity > 1) {
+            out.push_str(&format!("    {} (CC {})\n", f.name, f.complexity));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complexity;
+
+    const SOURCE: &str = r#"
+#[derive(Debug, MyParse)]
+pub struct Config { port: u16 }
+
+impl Config {
+    pub fn port(&self) -> u16 { self.port }
+}
+"#;
+
+    const EXPANDED: &str = r#"
+pub struct Config { port: u16 }
+impl ::core::fmt::Debug for Config {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+        f.write_str("Config")
+    }
+}
+impl Config {
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.is_empty() { return None; }
+        let port = s.parse().ok()?;
+        Some(Config { port })
+    }
+}
+impl Config {
+    pub fn port(&self) -> u16 { self.port }
+}
+"#;
+
+    const MACROS: &str = r#"
+#[proc_macro_derive(MyParse, attributes(parse))]
+pub fn derive_parse(input: TokenStream) -> TokenStream { input }
+
+#[proc_macro_attribute]
+pub fn traced(_: TokenStream, item: TokenStream) -> TokenStream { item }
+
+pub fn helper() {}
+"#;
+
+    fn extract(source: &str) -> Vec<FunctionInfo> {
+        complexity::extract_functions(source)
+    }
+
+    #[test]
+    fn only_functions_missing_from_the_sources_are_generated() {
+        let found = generated_functions(&extract(SOURCE), extract(EXPANDED));
+        let names: Vec<&str> = found.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["Config::fmt", "Config::parse"]);
+        assert_eq!(found[1].complexity, 3);
+    }
+
+    #[test]
+    fn functions_sharing_a_name_across_files_are_not_generated() {
+        // One file each, but a single crate-wide expansion where they collide
+        // and get ordinals.
+        let mut written = extract("fn helper() {}\nimpl A { fn new() -> A { A } }");
+        written.extend(extract("fn helper() {}\nimpl B { fn new() -> B { B } }"));
+        let expanded = extract(
+            "mod a { fn helper() {} impl A { fn new() -> A { A } } }\n\
+             mod b { fn helper() {} impl B { fn new() -> B { B } } }\n\
+             fn helper() {}",
+        );
+        let found = generated_functions(&written, expanded);
+        let names: Vec<&str> = found.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["helper#3"]);
+    }
+
+    #[test]
+    fn attributes_functions_to_the_macro_applied_to_their_type() {
+        let macros: HashMap<String, String> = defined_macros(&syn::parse_file(MACROS).unwrap())
+            .into_iter()
+            .map(|name| (name.clone(), format!("parse_derive::{name}")))
+            .collect();
+        assert_eq!(macros.len(), 2);
+        let uses = macro_uses(
+            &syn::parse_file(&format!("{SOURCE}\n#[traced]\nfn serve() {{}}")).unwrap(),
+            &macros,
+        );
+        assert_eq!(uses["Config"], vec!["parse_derive::MyParse"]);
+        assert_eq!(uses["serve"], vec!["parse_derive::traced"]);
+        let mut generated = generated_functions(&extract(SOURCE), extract(EXPANDED));
+        generated.extend(extract("fn __serve_inner() {}"));
+        let groups = attribute(generated, &uses, "parse_derive");
+        let summary: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|(origin, fns)| {
+                (
+                    origin.as_str(),
+                    fns.iter().map(|f| f.name.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "parse_derive::MyParse",
+                    vec!["Config::fmt", "Config::parse"]
+                ),
+                ("parse_derive", vec!["__serve_inner"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn format_ranks_macros_by_generated_complexity() {
+        let functions = generated_functions(&extract(SOURCE), extract(EXPANDED));
+        let expansions = vec![
+            Expansion {
+                origin: "idle_derive".into(),
+                consumer: "app".into(),
+                functions: Vec::new(),
+            },
+            Expansion {
+                origin: "parse_derive::MyParse".into(),
+                consumer: "app".into(),
+                functions,
+            },
+        ];
+        let out = format_expansions(&expansions);
+        assert!(out.contains("  parse_derive::MyParse in app: 2 function(s), total CC 4\n"));
+        assert!(out.contains("    Config::parse (CC 3)\n"));
+        assert!(!out.contains("Config::fmt (CC"));
+        assert!(!out.contains("idle_derive"));
+        assert!(format_expansions(&[]).is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+/// A published crate version, written `name@version` on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateSpec {
+    pub name: String,
+    pub version: String,
+}
+
+impl CrateSpec {
+    pub fn parse(s: &str) -> Result<CrateSpec, String> {
+        let Some((name, version)) = s.split_once('@') else {
+            return Err(format!("expected `<crate>@<version>`, got `{s}`"));
+        };
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(format!("invalid crate name `{name}`"));
+        }
+        let valid_version = version.starts_with(|c: char| c.is_ascii_digit())
+            && version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+        if !valid_version {
+            return Err(format!(
+                "invalid version `{version}`, expected e.g. `1.0.2`"
+            ));
+        }
+        Ok(CrateSpec {
+            name: name.to_string(),
+            version: version.to_string(),
+        })
+    }
+
+    /// crates.io download endpoint; it redirects to the static file host.
+    pub fn download_url(&self) -> String {
+        format!(
+            "https://crates.io/api/v1/crates/{}/{}/download",
+            self.name, self.version
+        )
+    }
+
+    /// Directory the `.crate` archive unpacks to.
+    pub fn dir_name(&self) -> String {
+        format!("{}-{}", self.name, self.version)
+    }
+}
+
+/// Download `spec` from crates.io (with `curl`) and unpack it (with `tar`)
+/// under `cache`, returning the crate's root directory. A crate already
+/// unpacked there is reused; published versions never change.
+pub fn fetch_crate(spec: &CrateSpec, cache: &Path) -> Result<PathBuf> {
+    let root = cache.join(spec.dir_name());
+    if root.join("Cargo.toml").exists() {
+        return Ok(root);
+    }
+    std::fs::create_dir_all(cache)
+        .with_context(|| format!("failed to create {}", cache.display()))?;
+    let archive = cache.join(format!("{}.crate", spec.dir_name()));
+    let url = spec.download_url();
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args([
+            "--user-agent",
+            concat!("craprs/", env!("CARGO_PKG_VERSION")),
+        ])
+        .arg("--output")
+        .arg(&archive)
+        .arg(&url)
+        .output()
+        .context("failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "failed to download {}@{} from {url}: {}",
+            spec.name,
+            spec.version,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    let output = Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(cache)
+        .output()
+        .context("failed to run tar")?;
+    if !output.status.success() {
+        bail!(
+            "failed to unpack {}: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    let _ = std::fs::remove_file(&archive);
+    if !root.join("Cargo.toml").exists() {
+        bail!("{} has no Cargo.toml after unpacking", root.display());
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_name_and_version() {
+        let spec = CrateSpec::parse("serde_json@1.0.128").unwrap();
+        assert_eq!(spec.name, "serde_json");
+        assert_eq!(spec.version, "1.0.128");
+        assert_eq!(
+            spec.download_url(),
+            "https://crates.io/api/v1/crates/serde_json/1.0.128/download"
+        );
+        assert_eq!(spec.dir_name(), "serde_json-1.0.128");
+        assert!(CrateSpec::parse("tokio@1.40.0-rc.1").is_ok());
+        for bad in [
+            "serde",
+            "@1.0.0",
+            "serde@",
+            "serde@latest",
+            "../x@1.0",
+            "a@1.0/..",
+        ] {
+            assert!(CrateSpec::parse(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+
+use glob::{MatchOptions, Pattern};
+
+use crate::coverage::LineCoverage;
+
+/// Minimum line coverage for the modules matching a pattern, e.g.
+/// `core::** >= 80%`. `*` matches one path segment, `**` any number
+/// (including none, so `core::**` covers `core` itself).
+#[derive(Debug, Clone)]
+pub struct CoverageFloor {
+    pub rule: String,
+    pattern: Pattern,
+    pub min_pct: f64,
+}
+
+impl CoverageFloor {
+    pub fn parse(s: &str) -> Result<CoverageFloor, String> {
+        let Some((pattern, pct)) = s.split_once(">=") else {
+            return Err(format!("expected `<module pattern> >= <percent>`, got `{s}`"));
+        };
+        let pattern = pattern.trim();
+        let pct = pct.trim().trim_end_matches('%').trim();
+        let min_pct: f64 = pct
+            .parse()
+            .map_err(|_| format!("invalid percentage `{pct}` in `{s}`"))?;
+        if !(0.0..=100.0).contains(&min_pct) {
+            return Err(format!("percentage must be between 0 and 100 in `{s}`"));
+        }
+        let compiled = Pattern::new(&pattern.replace("::", "/"))
+            .map_err(|err| format!("invalid module pattern `{pattern}`: {err}"))?;
+        Ok(CoverageFloor {
+            rule: format!("{pattern} >= {min_pct}%"),
+            pattern: compiled,
+            min_pct,
+        })
+    }
+
+    pub fn matches(&self, module: &str) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let path = module.replace("::", "/");
+        self.pattern.matches_with(&path, options)
+            || self.pattern.matches_with(&format!("{path}/"), options)
+    }
+}
+
+/// Instrumented and executed line counts per module, over whole files rather
+/// than just function bodies.
+#[derive(Debug, Default)]
+pub struct ModuleLines {
+    counts: BTreeMap<String, (usize, usize)>,
+}
+
+impl ModuleLines {
+    pub fn add_file(&mut self, module: &str, line_cov: &LineCoverage) {
+        let (instrumented, hit) = self.counts.entry(module.to_string()).or_default();
+        *instrumented += line_cov.len();
+        *hit += line_cov.values().filter(|&&n| n > 0).count();
+    }
+
+    pub fn extend(&mut self, other: ModuleLines) {
+        for (module, (instrumented, hit)) in other.counts {
+            let counts = self.counts.entry(module).or_default();
+            counts.0 += instrumented;
+            counts.1 += hit;
+        }
+    }
+}
+
+/// A floor whose matching modules fall short, with their combined coverage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub rule: String,
+    pub coverage: f64,
+    pub modules: usize,
+}
+
+/// Check every floor against the combined line coverage of the modules it
+/// matches. Floors matching no instrumented module pass.
+pub fn check_floors(floors: &[CoverageFloor], lines: &ModuleLines) -> Vec<Violation> {
+    floors
+        .iter()
+        .filter_map(|floor| {
+            let matched: Vec<&(usize, usize)> = lines
+                .counts
+                .iter()
+                .filter(|(module, _)| floor.matches(module))
+                .map(|(_, counts)| counts)
+                .collect();
+            let instrumented: usize = matched.iter().map(|c| c.0).sum();
+            let hit: usize = matched.iter().map(|c| c.1).sum();
+            if instrumented == 0 {
+                return None;
+            }
+            let coverage = hit as f64 / instrumented as f64 * 100.0;
+            (coverage < floor.min_pct).then(|| Violation {
+                rule: floor.rule.clone(),
+                coverage,
+                modules: matched.len(),
+            })
+        })
+        .collect()
+}
+
+pub fn format_violations(violations: &[Violation]) -> String {
+    let mut out = String::new();
+    for v in violations {
+        out.push_str(&format!(
+            "coverage floor not met: {} — {:.1}% across {} module(s)\n",
+            v.rule, v.coverage, v.modules
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(hits: &[u64]) -> LineCoverage {
+        hits.iter().enumerate().map(|(i, &n)| (i + 1, n)).collect()
+    }
+
+    #[test]
+    fn parses_rules() {
+        let floor = CoverageFloor::parse("core::** >= 80%").unwrap();
+        assert_eq!(floor.min_pct, 80.0);
+        assert_eq!(floor.rule, "core::** >= 80%");
+        assert_eq!(CoverageFloor::parse("io>=65.5").unwrap().min_pct, 65.5);
+        assert!(CoverageFloor::parse("core 80%").is_err());
+        assert!(CoverageFloor::parse("core >= lots").is_err());
+        assert!(CoverageFloor::parse("core >= 120%").is_err());
+    }
+
+    #[test]
+    fn patterns_match_module_paths() {
+        let deep = CoverageFloor::parse("core::** >= 80").unwrap();
+        assert!(deep.matches("core"));
+        assert!(deep.matches("core::parser::lexer"));
+        assert!(!deep.matches("corex"));
+        let shallow = CoverageFloor::parse("core::* >= 80").unwrap();
+        assert!(shallow.matches("core::parser"));
+        assert!(!shallow.matches("core::parser::lexer"));
+    }
+
+    #[test]
+    fn floors_apply_to_combined_module_coverage() {
+        let mut modules = ModuleLines::default();
+        modules.add_file("core", &lines(&[1, 1, 1, 0]));
+        modules.add_file("core::parser", &lines(&[1, 0, 0, 0]));
+        modules.add_file("io", &lines(&[1, 1]));
+        let floors = vec![
+            CoverageFloor::parse("core::** >= 60%").unwrap(),
+            CoverageFloor::parse("io >= 90%").unwrap(),
+            CoverageFloor::parse("missing >= 90%").unwrap(),
+        ];
+        let violations = check_floors(&floors, &modules);
+        assert_eq!(
+            violations,
+            vec![Violation {
+                rule: "core::** >= 60%".into(),
+                coverage: 50.0,
+                modules: 2,
+            }]
+        );
+        assert_eq!(
+            format_violations(&violations),
+            "coverage floor not met: core::** >= 60% — 50.0% across 2 module(s)\n"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::crap::{self, CrapEntry, TableOptions, TableStyle};
+
+/// What to group report entries by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    Module,
+    /// Source file, as discovered (relative to the project dir).
+    File,
+    /// Tag, see [`CrapEntry::tags`]: functions with several tags show up in
+    /// each of their groups, untagged ones under [`UNTAGGED`].
+    Tag,
+}
+
+impl GroupBy {
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupBy::Module => "module",
+            GroupBy::File => "file",
+            GroupBy::Tag => "tag",
+        }
+    }
+}
+
+/// How a group's CRAP scores are combined into the single number groups are ranked by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Aggregate {
+    /// Total CRAP load of the group.
+    #[default]
+    Sum,
+    /// Worst single function in the group.
+    Max,
+    /// Average CRAP of the group's scored functions.
+    Mean,
+}
+
+impl Aggregate {
+    pub fn label(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Max => "max",
+            Aggregate::Mean => "mean",
+        }
+    }
+
+    /// Combine the scored entries; `None` if the group has no scored entries.
+    pub fn apply(self, entries: &[CrapEntry]) -> Option<f64> {
+        let scores: Vec<f64> = entries.iter().filter_map(|e| e.crap).collect();
+        if scores.is_empty() {
+            return None;
+        }
+        Some(match self {
+            Aggregate::Sum => scores.iter().sum(),
+            Aggregate::Max => scores.iter().copied().fold(f64::MIN, f64::max),
+            Aggregate::Mean => scores.iter().sum::<f64>() / scores.len() as f64,
+        })
+    }
+}
+
+pub struct Group {
+    pub key: String,
+    /// Entries in the group, in the order they were passed in (normally CRAP-sorted).
+    pub entries: Vec<CrapEntry>,
+    /// Aggregate CRAP; `None` when no entry in the group has coverage data.
+    pub score: Option<f64>,
+    /// Function coverage of all the group's functions, including those the
+    /// report filters left out of `entries`, see [`function_coverage`].
+    pub function_coverage: Option<f64>,
+}
+
+/// Percentage of instrumented functions with at least one executed line;
+/// `None` when none of the entries has coverage data.
+pub fn function_coverage(entries: &[CrapEntry]) -> Option<f64> {
+    let instrumented: Vec<f64> = entries.iter().filter_map(|e| e.coverage).collect();
+    if instrumented.is_empty() {
+        return None;
+    }
+    let executed = instrumented.iter().filter(|&&c| c > 0.0).count();
+    Some(100.0 * executed as f64 / instrumented.len() as f64)
+}
+
+/// Group key for functions without tags under `--group-by tag`.
+pub const UNTAGGED: &str = "(untagged)";
+
+fn group_keys(e: &CrapEntry, by: GroupBy) -> Vec<String> {
+    match by {
+        GroupBy::Module => vec![e.module_path.clone()],
+        GroupBy::File => {
+            let file = e.file.strip_prefix(".").unwrap_or(&e.file);
+            vec![file.to_string_lossy().into_owned()]
+        }
+        GroupBy::Tag if e.tags.is_empty() => vec![UNTAGGED.to_string()],
+        GroupBy::Tag => e.tags.clone(),
+    }
+}
+
+fn bucket(entries: Vec<CrapEntry>, by: GroupBy) -> BTreeMap<String, Vec<CrapEntry>> {
+    let mut buckets: BTreeMap<String, Vec<CrapEntry>> = BTreeMap::new();
+    for e in entries {
+        let mut keys = group_keys(&e, by);
+        let last = keys.pop().expect("every entry has a group key");
+        for key in keys {
+            buckets.entry(key).or_default().push(e.clone());
+        }
+        buckets.entry(last).or_default().push(e);
+    }
+    buckets
+}
+
+/// [`function_coverage`] of every group `all` has functions in, by key.
+pub fn coverage_by(all: &[CrapEntry], by: GroupBy) -> HashMap<String, f64> {
+    bucket(all.to_vec(), by)
+        .into_iter()
+        .filter_map(|(key, entries)| Some((key, function_coverage(&entries)?)))
+        .collect()
+}
+
+/// Bucket entries and rank the buckets by aggregate CRAP, riskiest first.
+/// Groups without any scored entry sink to the bottom; ties sort by key.
+/// Function coverage comes from `all`, the entries before report filters
+/// like `--top` picked `entries` from.
+pub fn group_entries(
+    entries: Vec<CrapEntry>,
+    all: &[CrapEntry],
+    by: GroupBy,
+    agg: Aggregate,
+) -> Vec<Group> {
+    let coverage = coverage_by(all, by);
+    rank_groups(bucket(entries, by).into_iter().collect(), &coverage, agg)
+}
+
+/// Rank pre-bucketed entries the same way as [`group_entries`], with the
+/// function `coverage` of each group by key.
+pub fn rank_groups(
+    mut buckets: Vec<(String, Vec<CrapEntry>)>,
+    coverage: &HashMap<String, f64>,
+    agg: Aggregate,
+) -> Vec<Group> {
+    buckets.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut groups: Vec<Group> = buckets
+        .into_iter()
+        .map(|(key, entries)| {
+            let score = agg.apply(&entries);
+            let function_coverage = coverage.get(&key).copied();
+            Group {
+                key,
+                entries,
+                score,
+                function_coverage,
+            }
+        })
+        .collect();
+    // Buckets are in key order, and sort_by is stable, so equal scores keep
+    // alphabetical order.
+    groups.sort_by(|a, b| match (a.score, b.score) {
+        (Some(x), Some(y)) => y.partial_cmp(&x).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    groups
+}
+
+/// Render groups one after another: a heading with the aggregate, then the
+/// group's table in the chosen style. `by_label` names what the groups are
+/// (`module`, `workspace`) in the title.
+pub fn format_grouped(
+    groups: &[Group],
+    by_label: &str,
+    agg: Aggregate,
+    opts: &TableOptions,
+) -> String {
+    let score = opts.score_label;
+    let title = format!("CRAP Report — grouped by {by_label}, ranked by {} {score}", agg.label());
+    let mut out = match opts.style {
+        TableStyle::Markdown => format!("## {title}\n"),
+        TableStyle::Ascii => format!("{title}\n{}\n", "=".repeat(title.chars().count())),
+        TableStyle::Unicode | TableStyle::Compact => format!("{title}\n"),
+    };
+    for g in groups {
+        let key = if g.key.is_empty() { "(root)" } else { g.key.as_str() };
+        let value = match g.score {
+            Some(s) => format!("{} {score} {}", agg.label(), opts.numbers.format(s)),
+            None => format!("{} {score} —", agg.label()),
+        };
+        let mut heading = format!("{key} — {value}, {} function(s)", g.entries.len());
+        if let Some(pct) = g.function_coverage {
+            heading.push_str(&format!(", {pct:.0}% executed"));
+        }
+        out.push('\n');
+        match opts.style {
+            TableStyle::Markdown => out.push_str(&format!("### {heading}\n\n")),
+            _ => out.push_str(&format!("{heading}\n")),
+        }
+        out.push_str(&crap::format_table(&g.entries, opts));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, module: &str, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: module.into(),
+            complexity: 1,
+            coverage: crap.map(|_| 0.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    fn sample() -> Vec<CrapEntry> {
+        vec![
+            entry("a1", "a", Some(40.0)),
+            entry("b1", "b", Some(20.0)),
+            entry("b2", "b", Some(25.0)),
+            entry("b3", "b", Some(1.0)),
+            entry("c1", "c", None),
+        ]
+    }
+
+    fn grouped(entries: Vec<CrapEntry>, by: GroupBy, agg: Aggregate) -> Vec<Group> {
+        group_entries(entries.clone(), &entries, by, agg)
+    }
+
+    fn keys(groups: &[Group]) -> Vec<&str> {
+        groups.iter().map(|g| g.key.as_str()).collect()
+    }
+
+    #[test]
+    fn sum_ranks_by_total_load() {
+        let groups = grouped(sample(), GroupBy::Module, Aggregate::Sum);
+        assert_eq!(keys(&groups), vec!["b", "a", "c"]);
+        assert_eq!(groups[0].score, Some(46.0));
+        assert_eq!(groups[2].score, None);
+    }
+
+    #[test]
+    fn max_ranks_by_worst_function() {
+        let groups = grouped(sample(), GroupBy::Module, Aggregate::Max);
+        assert_eq!(keys(&groups), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn mean_ranks_by_average() {
+        let groups = grouped(sample(), GroupBy::Module, Aggregate::Mean);
+        assert_eq!(keys(&groups), vec!["a", "b", "c"]);
+        let b = &groups[1];
+        assert!((b.score.unwrap() - 46.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ties_fall_back_to_key_order() {
+        let entries = vec![entry("z", "z", Some(5.0)), entry("y", "y", Some(5.0))];
+        let groups = grouped(entries, GroupBy::Module, Aggregate::Sum);
+        assert_eq!(keys(&groups), vec!["y", "z"]);
+    }
+
+    #[test]
+    fn rank_groups_orders_prebucketed_groups() {
+        let buckets = vec![
+            ("svc".to_string(), vec![entry("s", "m", Some(10.0))]),
+            ("lib".to_string(), vec![entry("l", "m", Some(10.0))]),
+            ("api".to_string(), vec![entry("a", "m", Some(50.0))]),
+        ];
+        let groups = rank_groups(buckets, &HashMap::new(), Aggregate::Sum);
+        assert_eq!(keys(&groups), vec!["api", "lib", "svc"]);
+    }
+
+    #[test]
+    fn tagged_functions_join_every_tag_group() {
+        let mut entries = sample();
+        entries[0].tags = vec!["parser".into(), "hot".into()];
+        entries[1].tags = vec!["parser".into()];
+        let groups = grouped(entries, GroupBy::Tag, Aggregate::Sum);
+        assert_eq!(keys(&groups), vec!["parser", "hot", UNTAGGED]);
+        assert_eq!(groups[0].score, Some(60.0));
+        let untagged: Vec<&str> = groups[2].entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(untagged, vec!["b2", "b3", "c1"]);
+    }
+
+    #[test]
+    fn entries_keep_input_order_within_group() {
+        let groups = grouped(sample(), GroupBy::Module, Aggregate::Sum);
+        let names: Vec<&str> = groups[0].entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["b1", "b2", "b3"]);
+    }
+
+    #[test]
+    fn function_coverage_counts_functions_with_any_executed_line() {
+        let mut entries = sample();
+        entries[1].coverage = Some(12.5);
+        entries[2].coverage = Some(100.0);
+        let groups = grouped(entries, GroupBy::Module, Aggregate::Sum);
+        let b = groups.iter().find(|g| g.key == "b").unwrap();
+        assert!((b.function_coverage.unwrap() - 200.0 / 3.0).abs() < 1e-9);
+        let c = groups.iter().find(|g| g.key == "c").unwrap();
+        assert_eq!(c.function_coverage, None);
+    }
+
+    #[test]
+    fn function_coverage_includes_filtered_out_functions() {
+        let mut all = sample();
+        all[2].coverage = Some(100.0);
+        // As with `--top 1`: only the worst `b` function is shown.
+        let groups = group_entries(vec![all[1].clone()], &all, GroupBy::Module, Aggregate::Sum);
+        assert_eq!(groups[0].entries.len(), 1);
+        assert!((groups[0].function_coverage.unwrap() - 100.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn file_groups_split_modules_by_source_file() {
+        let mut entries = sample();
+        entries[1].file = PathBuf::from("src/b/mod.rs");
+        entries[2].file = PathBuf::from("src/b/parse.rs");
+        entries[3].file = PathBuf::from("./src/b/parse.rs");
+        let groups = grouped(entries, GroupBy::File, Aggregate::Sum);
+        assert_eq!(keys(&groups), vec!["", "src/b/parse.rs", "src/b/mod.rs"]);
+        assert_eq!(groups[1].score, Some(26.0));
+    }
+
+    #[test]
+    fn format_lists_groups_with_aggregate_headings() {
+        let groups = grouped(sample(), GroupBy::Module, Aggregate::Sum);
+        let out = format_grouped(
+            &groups,
+            GroupBy::Module.label(),
+            Aggregate::Sum,
+            &TableOptions::default(),
+        );
+        let b = out.find("b — sum CRAP 46.0, 3 function(s), 0% executed\n").unwrap();
+        let a = out.find("a — sum CRAP 40.0, 1 function(s), 0% executed\n").unwrap();
+        let c = out.find("c — sum CRAP —, 1 function(s)\n").unwrap();
+        assert!(b < a && a < c);
+    }
+}
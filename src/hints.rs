@@ -0,0 +1,116 @@
+use crate::complexity::FunctionShape;
+use crate::crap::CrapEntry;
+
+/// A `match` with at least this many arms gets a lookup-table hint.
+const MATCH_ARMS: usize = 8;
+/// Nesting at or beyond this depth gets an extract-function hint.
+const NESTING: u32 = 4;
+/// An `else if` ladder with at least this many branches gets a match hint.
+const IF_CHAIN: u32 = 4;
+/// A condition with at least this many `&&` / `||` gets a named-predicate hint.
+const CONDITION_OPS: u32 = 3;
+
+/// Heuristic refactoring suggestions for a function's shape, most structural first.
+pub fn refactoring_hints(shape: &FunctionShape) -> Vec<String> {
+    let mut hints = Vec::new();
+    if shape.max_match_arms >= MATCH_ARMS {
+        hints.push(format!(
+            "match with {} arms — consider a lookup table or splitting by category",
+            shape.max_match_arms
+        ));
+    }
+    if shape.longest_if_chain >= IF_CHAIN {
+        hints.push(format!(
+            "if/else-if chain with {} branches — consider a match",
+            shape.longest_if_chain
+        ));
+    }
+    if shape.max_nesting >= NESTING {
+        hints.push(format!(
+            "nesting depth {} — extract the inner block into a function",
+            shape.max_nesting
+        ));
+    }
+    if shape.max_condition_ops >= CONDITION_OPS {
+        hints.push(format!(
+            "condition with {} boolean operators — extract a named predicate",
+            shape.max_condition_ops
+        ));
+    }
+    hints
+}
+
+/// Hint section for entries with CC at or above `min_cc`, in report order.
+/// Empty when no such entry has hints.
+pub fn format_hints(entries: &[CrapEntry], min_cc: u32) -> String {
+    let mut out = String::new();
+    for e in entries
+        .iter()
+        .filter(|e| e.complexity >= min_cc && !e.hints.is_empty())
+    {
+        if out.is_empty() {
+            out.push_str(&format!("Refactoring hints (CC >= {min_cc}):\n"));
+        }
+        out.push_str(&format!("  {} ({})\n", e.name, e.module_path));
+        for hint in &e.hints {
+            out.push_str(&format!("    - {hint}\n"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, complexity: u32, hints: &[&str]) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "m".into(),
+            complexity,
+            coverage: Some(0.0),
+            hints: hints.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn simple_shape_has_no_hints() {
+        let shape = FunctionShape {
+            max_match_arms: 3,
+            max_nesting: 2,
+            longest_if_chain: 2,
+            max_condition_ops: 1,
+        };
+        assert!(refactoring_hints(&shape).is_empty());
+    }
+
+    #[test]
+    fn each_heuristic_fires_at_its_threshold() {
+        let shape = FunctionShape {
+            max_match_arms: 14,
+            max_nesting: 6,
+            longest_if_chain: 4,
+            max_condition_ops: 3,
+        };
+        let hints = refactoring_hints(&shape);
+        assert_eq!(hints.len(), 4);
+        assert!(hints[0].starts_with("match with 14 arms"));
+        assert!(hints[2].starts_with("nesting depth 6"));
+    }
+
+    #[test]
+    fn format_respects_cc_cutoff() {
+        let entries = vec![
+            entry("big", 15, &["nesting depth 5 — extract"]),
+            entry("small", 4, &["nesting depth 4 — extract"]),
+            entry("clean", 20, &[]),
+        ];
+        let out = format_hints(&entries, 10);
+        assert!(out.starts_with("Refactoring hints (CC >= 10):"));
+        assert!(out.contains("big (m)"));
+        assert!(!out.contains("small"));
+        assert!(!out.contains("clean"));
+        assert!(format_hints(&entries, 50).is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::crap::CrapEntry;
+
+/// Lower bound and label of each bucket; a bucket runs up to the next bound.
+const BUCKETS: [(f64, &str); 4] = [
+    (f64::NEG_INFINITY, "1-5"),
+    (5.0, "5-15"),
+    (15.0, "15-30"),
+    (30.0, "30+"),
+];
+/// Length of the longest bar.
+const BAR_WIDTH: usize = 40;
+
+/// Scored functions per bucket, in [`BUCKETS`] order.
+pub fn bucket_counts(entries: &[CrapEntry]) -> [usize; 4] {
+    let mut counts = [0; 4];
+    for score in entries.iter().filter_map(|e| e.crap) {
+        let bucket = BUCKETS.iter().rposition(|&(low, _)| score >= low);
+        counts[bucket.unwrap_or(0)] += 1;
+    }
+    counts
+}
+
+/// Bar chart of how scored functions spread over the score buckets. Bars are
+/// scaled to the fullest bucket; any non-empty bucket gets at least one `#`.
+pub fn format_histogram(entries: &[CrapEntry], score_label: &str) -> String {
+    let counts = bucket_counts(entries);
+    let scored: usize = counts.iter().sum();
+    let mut out = format!("{score_label} distribution over {scored} scored function(s):\n");
+    let fullest = counts.iter().copied().max().unwrap_or(0).max(1);
+    let count_width = fullest.to_string().len();
+    for (&(_, label), &n) in BUCKETS.iter().zip(&counts) {
+        let bar = if n == 0 {
+            0
+        } else {
+            (n * BAR_WIDTH).div_ceil(fullest)
+        };
+        out.push_str(&format!(
+            "  {label:>5}  {:<BAR_WIDTH$}  {n:>count_width$}\n",
+            "#".repeat(bar)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: "f".into(),
+            complexity: 1,
+            coverage: crap.map(|_| 0.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn buckets_are_lower_inclusive() {
+        let entries: Vec<CrapEntry> = [0.0, 1.0, 4.9, 5.0, 14.9, 15.0, 30.0, 250.0]
+            .into_iter()
+            .map(|s| entry(Some(s)))
+            .chain([entry(None)])
+            .collect();
+        assert_eq!(bucket_counts(&entries), [3, 2, 1, 2]);
+    }
+
+    #[test]
+    fn bars_scale_to_the_fullest_bucket() {
+        let mut entries: Vec<CrapEntry> = (0..20).map(|_| entry(Some(2.0))).collect();
+        entries.push(entry(Some(40.0)));
+        let out = format_histogram(&entries, "CRAP");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "CRAP distribution over 21 scored function(s):");
+        assert_eq!(lines[1], format!("    1-5  {}  20", "#".repeat(40)));
+        assert_eq!(lines[2], format!("   5-15  {}   0", " ".repeat(40)));
+        assert_eq!(lines[4], format!("    30+  ##{}   1", " ".repeat(38)));
+    }
+}
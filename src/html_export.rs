@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::baseline::Delta;
+use crate::coverage::LineCoverage;
+use crate::crap::CrapEntry;
+use crate::metadata::RunMetadata;
+use crate::render::render_chunks;
+
+const STYLE: &str = "\
+body { font: 14px/1.4 system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; }
+th, td { padding: 4px 10px; border-bottom: 1px solid #ddd; text-align: left; }
+th { cursor: pointer; user-select: none; background: #f4f4f4; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+tr.crappy td { background: #fde8e8; }
+tr.worse td { background: #fde8e8; }
+tr.better td { background: #e6ffec; }
+a { color: #0550ae; }
+pre { font: 12px/1.5 ui-monospace, monospace; border: 1px solid #ddd; padding: 0; }
+pre > span { display: block; padding: 0 8px; white-space: pre; }
+pre .ln { display: inline-block; width: 4em; margin-right: 1em; color: #999; text-align: right; user-select: none; }
+pre .hit { background: #e6ffec; }
+pre .miss { background: #ffebe9; }
+pre .fn { background: #f4f4f4; font-weight: bold; border-top: 1px solid #ddd; }
+pre .fn.crappy { background: #fde8e8; }
+nav { margin-top: 1em; }
+footer { margin-top: 2em; color: #666; font-size: 12px; }
+";
+
+// Sorts on `data-sort` when present, so `—` cells and formatted numbers order
+// correctly. Only one page of rows is shown at a time: browsers crawl laying
+// out tables with tens of thousands of rows.
+const SCRIPT: &str = "\
+const PAGE_ROWS = 500;
+const body = document.querySelector('table').tBodies[0];
+const nav = document.getElementById('pages');
+let page = 0;
+const show = () => {
+  const pages = Math.max(Math.ceil(body.rows.length / PAGE_ROWS), 1);
+  page = Math.min(Math.max(page, 0), pages - 1);
+  Array.from(body.rows).forEach((tr, i) => { tr.hidden = Math.floor(i / PAGE_ROWS) !== page; });
+  nav.hidden = pages < 2;
+  nav.querySelector('span').textContent = `page ${page + 1} of ${pages}`;
+};
+nav.querySelector('.prev').addEventListener('click', () => { page -= 1; show(); });
+nav.querySelector('.next').addEventListener('click', () => { page += 1; show(); });
+document.querySelectorAll('th').forEach((th, col) => {
+  let asc = false;
+  th.addEventListener('click', () => {
+    asc = !asc;
+    const key = tr => {
+      const cell = tr.cells[col];
+      return cell.dataset.sort !== undefined ? parseFloat(cell.dataset.sort) : cell.textContent;
+    };
+    const rows = Array.from(body.rows).sort((a, b) => {
+      const x = key(a), y = key(b);
+      const order = typeof x === 'number' ? x - y : x.localeCompare(y);
+      return asc ? order : -order;
+    });
+    rows.forEach(tr => body.appendChild(tr));
+    page = 0;
+    show();
+  });
+});
+show();
+";
+
+/// An analyzed source file to render below the table.
+pub struct SourceFile {
+    /// As in [`CrapEntry::file`].
+    pub path: PathBuf,
+    pub content: String,
+    /// `None` when the file has no coverage data.
+    pub coverage: Option<LineCoverage>,
+}
+
+/// Standalone HTML page with a click-to-sort table of `entries`, followed by
+/// each of `sources` with executed lines in green, missed ones in red and a
+/// header above every reported function. Rows scoring at or above `threshold`
+/// are highlighted. Locations link to the rendered source when it's there,
+/// otherwise to the file relative to the project dir, so the page works when
+/// saved there. A footer records the run `metadata`.
+pub fn to_html(
+    entries: &[CrapEntry],
+    sources: &[SourceFile],
+    score_label: &str,
+    threshold: f64,
+    metadata: &RunMetadata,
+) -> String {
+    let mut out = Vec::new();
+    write_html(&mut out, entries, sources, score_label, threshold, metadata)
+        .expect("writing to a Vec can't fail");
+    String::from_utf8(out).expect("the page is built from strings")
+}
+
+/// [`to_html`] straight into `out`. Table rows and source files are rendered
+/// in parallel chunks and written one chunk at a time.
+pub fn write_html(
+    out: &mut impl Write,
+    entries: &[CrapEntry],
+    sources: &[SourceFile],
+    score_label: &str,
+    threshold: f64,
+    metadata: &RunMetadata,
+) -> io::Result<()> {
+    let rendered: HashSet<&PathBuf> = sources.iter().map(|s| &s.path).collect();
+    write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>CRAP Report</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n\
+         <h1>CRAP Report</h1>\n<table>\n<thead>\n<tr>"
+    )?;
+    for header in [
+        "Function",
+        "Module",
+        "Location",
+        "CC",
+        "Coverage",
+        score_label,
+    ] {
+        write!(out, "<th>{}</th>", escape(header))?;
+    }
+    out.write_all(b"</tr>\n</thead>\n<tbody>\n")?;
+    let rows = render_chunks(entries, 512, |e, out| {
+        let crappy = e.crap.is_some_and(|c| c >= threshold);
+        out.push_str(if crappy {
+            "<tr class=\"crappy\">"
+        } else {
+            "<tr>"
+        });
+        let file = e.file.to_string_lossy();
+        let href = if rendered.contains(&e.file) {
+            format!("#{}", line_id(&file, e.start_line))
+        } else {
+            file.to_string()
+        };
+        out.push_str(&format!(
+            "<td>{}</td><td>{}</td><td><a href=\"{}\">{}:{}</a></td>",
+            escape(&e.name),
+            escape(&e.module_path),
+            escape(&href),
+            escape(&file),
+            e.start_line
+        ));
+        out.push_str(&format!(
+            "<td class=\"num\" data-sort=\"{0}\">{0}</td>",
+            e.complexity
+        ));
+        out.push_str(&number_cell(e.coverage, |c| format!("{c:.1}%")));
+        out.push_str(&number_cell(e.crap, |c| format!("{c:.1}")));
+        out.push_str("</tr>\n");
+    });
+    for chunk in rows {
+        out.write_all(chunk.as_bytes())?;
+    }
+    out.write_all(
+        "</tbody>\n</table>\n<nav id=\"pages\" hidden><button class=\"prev\">‹ prev</button> \
+         <span></span> <button class=\"next\">next ›</button></nav>\n"
+            .as_bytes(),
+    )?;
+    // Grouped once up front: scanning every entry per file is quadratic in
+    // the size of the workspace.
+    let mut headers: HashMap<&PathBuf, BTreeMap<usize, Vec<&CrapEntry>>> = HashMap::new();
+    for e in entries {
+        headers
+            .entry(&e.file)
+            .or_default()
+            .entry(e.start_line)
+            .or_default()
+            .push(e);
+    }
+    let no_headers = BTreeMap::new();
+    let sections = render_chunks(sources, 4, |source, out| {
+        let headers = headers.get(&source.path).unwrap_or(&no_headers);
+        source_section(out, source, headers, score_label, threshold);
+    });
+    for chunk in sections {
+        out.write_all(chunk.as_bytes())?;
+    }
+    let facts: Vec<String> = metadata
+        .pairs()
+        .iter()
+        .map(|(key, value)| format!("{key}: {}", escape(value)))
+        .collect();
+    writeln!(out, "<footer>{}</footer>", facts.join(" · "))?;
+    write!(out, "<script>\n{SCRIPT}</script>\n</body>\n</html>\n")
+}
+
+/// Standalone HTML page for `craprs baseline diff`: a click-to-sort table of
+/// `deltas` with the score before and after side by side, regressions in red
+/// and improvements in green. `old` and `new` name the two baselines.
+pub fn diff_html(deltas: &[Delta], old: &str, new: &str) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>CRAP Diff</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n\
+         <h1>CRAP Diff</h1>\n<p>{} → {}</p>\n<table>\n<thead>\n<tr>",
+        escape(old),
+        escape(new)
+    );
+    for header in ["Function", "Module", "Before", "After", "Change", "Cause"] {
+        out.push_str(&format!("<th>{header}</th>"));
+    }
+    out.push_str("</tr>\n</thead>\n<tbody>\n");
+    for d in deltas {
+        let change = match (d.old, d.new) {
+            (Some(a), Some(b)) => Some(b - a),
+            _ => None,
+        };
+        out.push_str(match change {
+            Some(c) if c > 0.0 => "<tr class=\"worse\">",
+            Some(c) if c < 0.0 => "<tr class=\"better\">",
+            _ => "<tr>",
+        });
+        out.push_str(&format!(
+            "<td>{}</td><td>{}</td>",
+            escape(&d.name),
+            escape(&d.module)
+        ));
+        out.push_str(&number_cell(d.old, |s| format!("{s:.1}")));
+        out.push_str(&number_cell(d.new, |s| format!("{s:.1}")));
+        // Changes go negative, so unknown ones sort below every one of them.
+        out.push_str(&match change {
+            Some(c) => number_cell(Some(c), |c| format!("{c:+.1}")),
+            None => "<td class=\"num\" data-sort=\"-Infinity\">—</td>".to_string(),
+        });
+        let mut cause = escape(d.change.label());
+        if let Some(ref from) = d.renamed_from {
+            cause.push_str(&format!(", was {}", escape(from)));
+        }
+        for commit in &d.commits {
+            cause.push_str(&format!("<br><small>{}</small>", escape(commit)));
+        }
+        out.push_str(&format!("<td>{cause}</td></tr>\n"));
+    }
+    out.push_str(
+        "</tbody>\n</table>\n<nav id=\"pages\" hidden><button class=\"prev\">‹ prev</button> \
+         <span></span> <button class=\"next\">next ›</button></nav>\n",
+    );
+    out.push_str(&format!("<script>\n{SCRIPT}</script>\n</body>\n</html>\n"));
+    out
+}
+
+fn line_id(file: &str, line: usize) -> String {
+    format!("{file}:{line}")
+}
+
+/// One source file as numbered, coverage-colored lines, with a header line
+/// above each function in `headers`, keyed by start line.
+fn source_section(
+    out: &mut String,
+    source: &SourceFile,
+    headers: &BTreeMap<usize, Vec<&CrapEntry>>,
+    score_label: &str,
+    threshold: f64,
+) {
+    let file = source.path.to_string_lossy();
+    out.push_str(&format!(
+        "<section id=\"{0}\">\n<h2>{0}</h2>\n<pre>",
+        escape(&file)
+    ));
+    for (i, code) in source.content.lines().enumerate() {
+        let line = i + 1;
+        for e in headers.get(&line).into_iter().flatten() {
+            let crappy = e.crap.is_some_and(|c| c >= threshold);
+            let mut summary = format!("{} — CC {}", e.name, e.complexity);
+            if let (Some(cov), Some(score)) = (e.coverage, e.crap) {
+                summary.push_str(&format!(", {cov:.1}% covered, {score_label} {score:.1}"));
+            }
+            out.push_str(&format!(
+                "<span class=\"fn{}\">{}</span>",
+                if crappy { " crappy" } else { "" },
+                escape(&summary)
+            ));
+        }
+        let class = match source.coverage.as_ref().and_then(|c| c.get(&line)) {
+            Some(0) => " class=\"miss\"",
+            Some(_) => " class=\"hit\"",
+            None => "",
+        };
+        out.push_str(&format!(
+            "<span id=\"{}\"{class}><span class=\"ln\">{line}</span>{}</span>",
+            escape(&line_id(&file, line)),
+            escape(code)
+        ));
+    }
+    out.push_str("</pre>\n</section>\n");
+}
+
+/// Numeric cell; unknown values show `—` and sort below every real value.
+fn number_cell(value: Option<f64>, fmt: impl Fn(f64) -> String) -> String {
+    match value {
+        Some(v) => format!("<td class=\"num\" data-sort=\"{v}\">{}</td>", fmt(v)),
+        None => "<td class=\"num\" data-sort=\"-1\">—</td>".to_string(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "core".into(),
+            complexity: 4,
+            coverage: crap.map(|_| 50.0),
+            crap,
+            file: PathBuf::from("src/core.rs"),
+            start_line: 12,
+            end_line: 20,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn renders_rows_with_links_and_highlights() {
+        let html = to_html(
+            &[entry("Vec<T>::push", Some(42.0)), entry("idle", None)],
+            &[],
+            "CRAP",
+            30.0,
+            &RunMetadata {
+                craprs_version: "0.2.0".into(),
+                host: "x86_64-unknown-linux-gnu".into(),
+                ..RunMetadata::default()
+            },
+        );
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<th>CRAP</th>"));
+        assert!(html.contains("<tr class=\"crappy\"><td>Vec&lt;T&gt;::push</td>"));
+        assert!(html.contains("<a href=\"src/core.rs\">src/core.rs:12</a>"));
+        assert!(html.contains("data-sort=\"42\">42.0</td>"));
+        assert!(html.contains("<tr><td>idle</td>"));
+        assert!(html.contains("data-sort=\"-1\">—</td>"));
+        assert!(html.contains("</table>\n<nav id=\"pages\" hidden>"));
+        assert!(html.contains(
+            "<footer>craprs_version: 0.2.0 · timestamp: 1970-01-01T00:00:00Z · \
+             host: x86_64-unknown-linux-gnu</footer>"
+        ));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn diff_marks_regressions_red_and_improvements_green() {
+        let old = r#"[
+            {"name": "parse", "module": "core", "crap": 12.0},
+            {"name": "load", "module": "io", "crap": 6.0}
+        ]"#;
+        let new = r#"[
+            {"name": "parse", "module": "core", "crap": 20.0},
+            {"name": "load", "module": "io", "crap": 4.0},
+            {"name": "Vec<T>::push", "module": "core", "crap": 2.0}
+        ]"#;
+        let deltas = crate::baseline::diff(old, new, 0.1).unwrap();
+        let html = diff_html(&deltas, "main.json", "feature.json");
+        assert!(html.contains("<p>main.json → feature.json</p>"));
+        assert!(html.contains(
+            "<tr class=\"worse\"><td>parse</td><td>core</td>\
+             <td class=\"num\" data-sort=\"12\">12.0</td>\
+             <td class=\"num\" data-sort=\"20\">20.0</td>\
+             <td class=\"num\" data-sort=\"8\">+8.0</td><td>cause unknown</td></tr>"
+        ));
+        assert!(html.contains("<tr class=\"better\"><td>load</td>"));
+        assert!(html.contains(
+            "<tr><td>Vec&lt;T&gt;::push</td><td>core</td>\
+             <td class=\"num\" data-sort=\"-1\">—</td>"
+        ));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn renders_sources_as_a_heatmap_with_function_headers() {
+        let source = SourceFile {
+            path: PathBuf::from("src/core.rs"),
+            content: (1..=13).map(|i| format!("line {i} <\n")).collect(),
+            coverage: Some(LineCoverage::from([(12, 3), (13, 0)])),
+        };
+        let html = to_html(
+            &[entry("push", Some(42.0))],
+            &[source],
+            "CRAP",
+            30.0,
+            &RunMetadata::default(),
+        );
+        assert!(html.contains("<a href=\"#src/core.rs:12\">src/core.rs:12</a>"));
+        assert!(html.contains("<section id=\"src/core.rs\">\n<h2>src/core.rs</h2>\n<pre>"));
+        assert!(html.contains(
+            "<span id=\"src/core.rs:11\"><span class=\"ln\">11</span>line 11 &lt;</span>\
+             <span class=\"fn crappy\">push — CC 4, 50.0% covered, CRAP 42.0</span>\
+             <span id=\"src/core.rs:12\" class=\"hit\"><span class=\"ln\">12</span>line 12 &lt;</span>\
+             <span id=\"src/core.rs:13\" class=\"miss\">"
+        ));
+    }
+}
@@ -0,0 +1,196 @@
+use std::io::{self, Write};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::crap::CrapEntry;
+use crate::metadata::RunMetadata;
+use crate::render::render_chunks;
+
+/// One exported entry. `coverage` and `crap` are `null` for uninstrumented
+/// entries; `crap` holds whichever score `--score` selected.
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    name: &'a str,
+    module: &'a str,
+    file: String,
+    start_line: usize,
+    end_line: usize,
+    file_hash: &'a str,
+    fingerprint: &'a str,
+    complexity: u32,
+    coverage: Option<f64>,
+    crap: Option<f64>,
+    uncovered_lines: &'a [usize],
+    decision_points: Vec<JsonDecisionPoint>,
+}
+
+#[derive(Serialize)]
+struct JsonDecisionPoint {
+    kind: &'static str,
+    line: usize,
+}
+
+/// Encode entries as a pretty-printed JSON object with the run `metadata`
+/// and an `entries` array in report order.
+pub fn to_json(entries: &[CrapEntry], metadata: &RunMetadata) -> Result<String> {
+    let mut out = Vec::new();
+    write_json(&mut out, entries, metadata)?;
+    Ok(String::from_utf8(out)?)
+}
+
+/// [`to_json`] straight into `out`. Entries are serialized in parallel chunks
+/// and written one chunk at a time, byte-for-byte what `to_json` would
+/// produce as a single pretty-printed document.
+pub fn write_json(
+    out: &mut impl Write,
+    entries: &[CrapEntry],
+    metadata: &RunMetadata,
+) -> io::Result<()> {
+    write!(
+        out,
+        "{{\n  \"metadata\": {},\n  \"entries\": ",
+        indent(&serde_json::to_string_pretty(metadata)?, "  ")
+    )?;
+    if entries.is_empty() {
+        return write!(out, "[]\n}}");
+    }
+    out.write_all(b"[\n")?;
+    let last = entries.len() - 1;
+    let indexed: Vec<(usize, &CrapEntry)> = entries.iter().enumerate().collect();
+    let chunks = render_chunks(&indexed, 256, |&(i, e), out| {
+        let json =
+            serde_json::to_string_pretty(&json_entry(e)).expect("report entries always serialize");
+        out.push_str("    ");
+        out.push_str(&indent(&json, "    "));
+        out.push_str(if i == last { "\n" } else { ",\n" });
+    });
+    for chunk in chunks {
+        out.write_all(chunk.as_bytes())?;
+    }
+    write!(out, "  ]\n}}")
+}
+
+fn json_entry(e: &CrapEntry) -> JsonEntry<'_> {
+    JsonEntry {
+        name: &e.name,
+        module: &e.module_path,
+        file: e.file.to_string_lossy().into_owned(),
+        start_line: e.start_line,
+        end_line: e.end_line,
+        file_hash: &e.file_hash,
+        fingerprint: &e.fingerprint,
+        complexity: e.complexity,
+        coverage: e.coverage,
+        crap: e.crap,
+        uncovered_lines: &e.uncovered_lines,
+        decision_points: e
+            .decision_points
+            .iter()
+            .map(|p| JsonDecisionPoint {
+                kind: p.kind.label(),
+                line: p.line,
+            })
+            .collect(),
+    }
+}
+
+/// Shift every line after the first right by `prefix`, to nest a
+/// pretty-printed value. JSON strings never hold a raw newline, so this
+/// can't touch string contents.
+fn indent(json: &str, prefix: &str) -> String {
+    json.replace('\n', &format!("\n{prefix}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complexity::{DecisionKind, DecisionPoint};
+    use std::path::PathBuf;
+
+    #[test]
+    fn encodes_entries_with_nulls_for_uninstrumented() {
+        let entries = vec![
+            CrapEntry {
+                name: "parse".into(),
+                module_path: "core::parser".into(),
+                complexity: 2,
+                coverage: Some(50.0),
+                crap: Some(2.5),
+                uncovered_lines: vec![14],
+                decision_points: vec![DecisionPoint {
+                    kind: DecisionKind::If,
+                    line: 13,
+                }],
+                file: PathBuf::from("src/core/parser.rs"),
+                start_line: 12,
+                end_line: 18,
+                ..Default::default()
+            },
+            CrapEntry {
+                name: "untouched".into(),
+                module_path: "core::io".into(),
+                complexity: 1,
+                file: PathBuf::from("src/core/io.rs"),
+                start_line: 3,
+                end_line: 5,
+                ..Default::default()
+            },
+        ];
+        let meta = RunMetadata {
+            craprs_version: "0.2.0".into(),
+            ..RunMetadata::default()
+        };
+        let report: serde_json::Value =
+            serde_json::from_str(&to_json(&entries, &meta).unwrap()).unwrap();
+        assert_eq!(report["metadata"]["craprs_version"], "0.2.0");
+        let json = &report["entries"];
+        let first = &json[0];
+        assert_eq!(first["name"], "parse");
+        assert_eq!(first["module"], "core::parser");
+        assert_eq!(first["file"], "src/core/parser.rs");
+        assert_eq!(first["start_line"], 12);
+        assert_eq!(first["end_line"], 18);
+        assert_eq!(first["crap"], 2.5);
+        assert_eq!(first["decision_points"][0]["kind"], "if");
+        assert_eq!(first["decision_points"][0]["line"], 13);
+        assert!(json[1]["coverage"].is_null());
+        assert!(json[1]["crap"].is_null());
+        let empty: serde_json::Value = serde_json::from_str(&to_json(&[], &meta).unwrap()).unwrap();
+        assert_eq!(empty["entries"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn chunked_output_matches_a_single_pretty_document() {
+        #[derive(Serialize)]
+        struct Whole<'a> {
+            metadata: &'a RunMetadata,
+            entries: Vec<JsonEntry<'a>>,
+        }
+        let entries: Vec<CrapEntry> = (0..2000)
+            .map(|i| CrapEntry {
+                name: format!("f{i}"),
+                module_path: "m".into(),
+                complexity: 1,
+                coverage: (i % 3 != 0).then_some(50.0),
+                crap: (i % 3 != 0).then_some(1.1),
+                uncovered_lines: vec![i + 1],
+                file: PathBuf::from("src/m.rs"),
+                start_line: i,
+                end_line: i + 2,
+                ..Default::default()
+            })
+            .collect();
+        let meta = RunMetadata::default();
+        for entries in [&entries[..], &entries[..1], &[]] {
+            let whole = Whole {
+                metadata: &meta,
+                entries: entries.iter().map(json_entry).collect(),
+            };
+            assert_eq!(
+                to_json(entries, &meta).unwrap(),
+                serde_json::to_string_pretty(&whole).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use crate::crap::CrapEntry;
+use crate::metadata::RunMetadata;
+
+/// JUnit XML with one `<testsuite>` per module and one `<testcase>` per
+/// function. A case fails when its score is at or above `threshold` and is
+/// skipped when the function has no coverage data. Every suite carries the
+/// run `metadata` as `craprs.*` properties.
+pub fn to_junit(
+    entries: &[CrapEntry],
+    score_label: &str,
+    threshold: f64,
+    metadata: &RunMetadata,
+) -> String {
+    let mut modules: BTreeMap<&str, Vec<&CrapEntry>> = BTreeMap::new();
+    for e in entries {
+        modules.entry(&e.module_path).or_default().push(e);
+    }
+    let failed = |e: &CrapEntry| e.crap.is_some_and(|c| c >= threshold);
+    let skipped = |e: &CrapEntry| e.crap.is_none();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites name=\"craprs\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+        entries.len(),
+        entries.iter().filter(|e| failed(e)).count(),
+        entries.iter().filter(|e| skipped(e)).count()
+    ));
+    for (module, cases) in &modules {
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+            escape(module),
+            cases.len(),
+            cases.iter().filter(|e| failed(e)).count(),
+            cases.iter().filter(|e| skipped(e)).count()
+        ));
+        out.push_str("    <properties>\n");
+        for (key, value) in metadata.pairs() {
+            out.push_str(&format!(
+                "      <property name=\"craprs.{key}\" value=\"{}\"/>\n",
+                escape(&value)
+            ));
+        }
+        out.push_str("    </properties>\n");
+        for e in cases {
+            out.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\" file=\"{}\" line=\"{}\"",
+                escape(module),
+                escape(&e.name),
+                escape(&e.file.to_string_lossy()),
+                e.start_line
+            ));
+            match (e.crap, e.coverage) {
+                (Some(score), Some(cov)) if score >= threshold => {
+                    let message = format!(
+                        "{score_label} {score:.1} >= {threshold} (CC {}, {cov:.1}% covered)",
+                        e.complexity
+                    );
+                    out.push_str(&format!(
+                        ">\n      <failure message=\"{0}\" type=\"crap\">{0}</failure>\n    </testcase>\n",
+                        escape(&message)
+                    ));
+                }
+                (None, _) => {
+                    out.push_str(
+                        ">\n      <skipped message=\"no coverage data\"/>\n    </testcase>\n",
+                    );
+                }
+                _ => out.push_str("/>\n"),
+            }
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(module: &str, name: &str, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: module.into(),
+            complexity: 7,
+            coverage: crap.map(|_| 20.0),
+            crap,
+            file: PathBuf::from("src/core.rs"),
+            start_line: 4,
+            end_line: 30,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn crappy_functions_fail_and_uninstrumented_are_skipped() {
+        let entries = vec![
+            entry("core", "Vec<u8>::parse", Some(45.0)),
+            entry("core", "load", Some(8.0)),
+            entry("io", "idle", None),
+        ];
+        let meta = RunMetadata {
+            craprs_version: "0.2.0".into(),
+            git_commit: Some("abc123".into()),
+            ..RunMetadata::default()
+        };
+        let xml = to_junit(&entries, "CRAP", 30.0, &meta);
+        assert!(
+            xml.contains("<testsuites name=\"craprs\" tests=\"3\" failures=\"1\" skipped=\"1\">")
+        );
+        assert!(xml.contains("<testsuite name=\"core\" tests=\"2\" failures=\"1\" skipped=\"0\">"));
+        assert!(xml.contains(
+            "    <properties>\n      <property name=\"craprs.craprs_version\" value=\"0.2.0\"/>\n"
+        ));
+        assert!(xml.contains("<property name=\"craprs.git_commit\" value=\"abc123\"/>"));
+        assert!(xml.contains(
+            "<testcase classname=\"core\" name=\"Vec&lt;u8&gt;::parse\" file=\"src/core.rs\" line=\"4\">\n      \
+             <failure message=\"CRAP 45.0 &gt;= 30 (CC 7, 20.0% covered)\" type=\"crap\">"
+        ));
+        assert!(xml.contains("name=\"load\" file=\"src/core.rs\" line=\"4\"/>"));
+        assert!(xml.contains("<skipped message=\"no coverage data\"/>"));
+        assert!(xml.ends_with("</testsuites>\n"));
+    }
+}
@@ -1,4 +1,42 @@
+pub mod badge;
+pub mod baseline;
+pub mod blame;
+pub mod checkstyle_export;
+pub mod ci;
 pub mod complexity;
+pub mod config;
 pub mod coverage;
 pub mod crap;
+pub mod demangle;
+pub mod deps;
+pub mod discrepancy;
+pub mod dispatch;
+pub mod expand;
+pub mod fetch;
+pub mod floors;
+pub mod group;
+pub mod hints;
+pub mod histogram;
+pub mod html_export;
+pub mod json_export;
+pub mod junit_export;
+pub mod markdown;
+pub mod metadata;
+pub mod module_map;
+pub mod parquet_export;
+pub mod policy;
+pub mod prometheus_export;
+pub mod public_api;
+pub mod render;
+pub mod scaffold;
+pub mod select;
+pub mod sqlite_export;
+pub mod suggest;
+pub mod tags;
+pub mod test_only;
+#[cfg(feature = "test-support")]
+pub mod testing;
+pub mod tiers;
+pub mod untested;
+pub mod validate;
 pub mod workspace;
@@ -1,18 +1,414 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{BufWriter, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result, bail};
-use clap::Parser;
+use anyhow::{Context, Result, anyhow, bail};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use serde_json::Value;
 
-use craprs::complexity;
-use craprs::coverage::{self, LineCoverage};
-use craprs::crap::{self, CrapEntry};
+use craprs::badge::{self, BadgeMetric};
+use craprs::baseline::{self, Delta, MergeStrategy};
+use craprs::blame;
+use craprs::checkstyle_export;
+use craprs::ci::{self, CiMode, Provider};
+use craprs::complexity::{self, TrivialMode};
+use craprs::config::{self, Setting, Source};
+use craprs::coverage::{self, BranchCoverage, FunctionRecord, LineCoverage};
+use craprs::crap::{
+    self, ColorMode, Column, CrapEntry, NumberFormat, ScoreKind, TableOptions, TableStyle,
+    TruncateStyle,
+};
+use craprs::deps;
+use craprs::discrepancy;
+use craprs::dispatch;
+use craprs::expand::{self, Expansion};
+use craprs::fetch::{self, CrateSpec};
+use craprs::floors::{self, CoverageFloor, ModuleLines};
+use craprs::group::{self, Aggregate, GroupBy};
+use craprs::hints;
+use craprs::histogram;
+use craprs::html_export;
+use craprs::json_export;
+use craprs::junit_export;
+use craprs::markdown;
+use craprs::metadata::{self, RunMetadata};
+use craprs::module_map;
+use craprs::parquet_export;
+use craprs::policy::{self, Policy};
+use craprs::prometheus_export;
+use craprs::public_api;
+use craprs::scaffold;
+use craprs::select::Selector;
+use craprs::sqlite_export;
+use craprs::suggest;
+use craprs::tags;
+use craprs::test_only;
+use craprs::tiers;
+use craprs::untested;
+use craprs::validate::{self, FileKind};
 use craprs::workspace;
 
 #[derive(Parser)]
 #[command(name = "craprs", version, about = "CRAP metric for Rust")]
+#[command(group(clap::ArgGroup::new("grouping").args(["group_by", "scan_root"])))]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
+    /// Hide entries with CRAP below this threshold.
+    /// Entries with no coverage data (uninstrumented files) are unaffected.
+    #[arg(long, default_value_t = 0.0)]
+    min_crap: f64,
+
+    /// Hide entries with cyclomatic complexity below N
+    #[arg(long)]
+    min_cc: Option<u32>,
+
+    /// Hide entries with cyclomatic complexity above N
+    #[arg(long)]
+    max_cc: Option<u32>,
+
+    /// Hide entries whose coverage is above this percentage, regardless of CRAP.
+    /// Entries with no coverage data (uninstrumented files) are unaffected.
+    #[arg(long)]
+    max_cov: Option<f64>,
+
+    /// Score at or above which a function counts as crappy (coverage debt, exports)
+    #[arg(long, default_value_t = crap::CRAPPY_THRESHOLD)]
+    threshold: f64,
+
+    /// Fail when a function in the crate's public API scores at or above N,
+    /// usually stricter than --threshold; adds the `api` column
+    #[arg(long, value_name = "N")]
+    public_threshold: Option<f64>,
+
+    /// Show only the top N entries after sorting and filtering.
+    #[arg(long)]
+    top: Option<usize>,
+
+    /// Fail instead of writing a report with more than N entries, which would
+    /// be too big to read or even open; 0 lifts the limit
+    #[arg(long, value_name = "N", default_value_t = MAX_ENTRIES)]
+    max_entries: usize,
+
+    /// Keep only entries matching an expression, e.g. `crap>30 && module~"core"`.
+    /// Fields: name, module, cc, cov, crap. Operators: > >= < <= == != ~ !~ && || ! ( ).
+    #[arg(long, value_parser = Selector::parse)]
+    select: Option<Selector>,
+
+    /// Table layout for the text report
+    #[arg(long, value_enum, default_value_t = TableStyle::Ascii)]
+    table_style: TableStyle,
+
+    /// Columns of the text report, in order, e.g. `name,cc,crap,file`
+    /// [default: name,module,cc,cov,crap,file, plus branch with BRDA data]
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "COLUMNS")]
+    columns: Vec<Column>,
+
+    /// Color report rows by risk: red at or above --threshold, yellow from
+    /// half of it, green below
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// CI system to adapt to: no color, GitHub annotations and job summary,
+    /// Buildkite annotation. `auto` detects it from the environment, `off`
+    /// behaves as on a developer machine
+    #[arg(long, value_enum, default_value_t = CiMode::Auto)]
+    ci: CiMode,
+
+    /// Only annotate functions whose lines intersect the pull request's diff
+    /// against REF [default: `origin/$GITHUB_BASE_REF`], so a legacy file
+    /// touched by a rename doesn't fill the PR with warnings
+    #[arg(long, value_name = "REF", num_args = 0..=1, default_missing_value = "")]
+    annotate_changed_only: Option<String>,
+
+    /// Print only a one-line `key=value` summary instead of the report, e.g.
+    /// `functions=42 crap_total=412.3 worst=87.2 over_threshold=5`
+    #[arg(long, conflicts_with = "format")]
+    quiet: bool,
+
+    /// Fit the text table into N characters, cutting long function names and
+    /// module paths short with `…`. Defaults to the terminal width when
+    /// printing to one; 0 never truncates
+    #[arg(long, value_name = "N")]
+    width: Option<usize>,
+
+    /// Which part of a long name or module path --width cuts out: `tail`
+    /// keeps the start, `head` the end, `middle` both ends
+    #[arg(long, value_enum, default_value_t = TruncateStyle::Tail)]
+    truncate_style: TruncateStyle,
+
+    /// Never cut function names to fit --width; only module paths are
+    #[arg(long)]
+    full_names: bool,
+
+    /// Text report meant for snapshot tests and diffs: ASCII table with fixed
+    /// column widths and never colored, whatever the terminal or environment
+    #[arg(long, conflicts_with_all = ["table_style", "color", "format", "number_format", "width"])]
+    stable_report: bool,
+
+    /// How scores and percentages are written in text and markdown reports,
+    /// as a sample: `0.0` (default), `0,00` for a decimal comma and two
+    /// digits, `0` for whole numbers
+    #[arg(long, value_name = "SAMPLE", default_value = "0.0", value_parser = NumberFormat::parse)]
+    number_format: NumberFormat,
+
+    /// Group the text report and rank groups by aggregate CRAP
+    #[arg(long, value_enum)]
+    group_by: Option<GroupBy>,
+
+    /// How groups are ranked with --group-by or --scan-root
+    #[arg(long, value_enum, default_value_t = Aggregate::Sum, requires = "grouping")]
+    group_sort: Aggregate,
+
+    /// Output format. `parquet` writes binary data; use --output or redirect stdout to a file.
+    #[arg(long, default_value = "text")]
+    format: OutputFormat,
+
+    /// Write the report to FILE instead of stdout, creating parent directories.
+    /// Progress, notes and warnings still go to stderr
+    #[arg(long, short = 'o', value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Additionally export the reported entries and a run summary, e.g. `sqlite:results.db`
+    #[arg(long, value_parser = parse_export)]
+    export: Option<Export>,
+
+    /// Show score and coverage changes against an earlier `--format json`
+    /// report (local path or http(s) URL) in text and markdown, marking
+    /// functions it doesn't have as new
+    #[arg(long, value_name = "FILE|URL")]
+    compare: Option<PathBuf>,
+
+    /// HTTP header sent when --compare or a `baseline` or `merge` subcommand
+    /// fetches a report from an http(s) URL, e.g. `Authorization: Bearer
+    /// $TOKEN` (repeatable)
+    #[arg(long = "baseline-header", value_name = "HEADER", global = true)]
+    baseline_headers: Vec<String>,
+
+    /// Report only functions that are new since the --compare report or
+    /// whose score went up, the ones a reviewer has to look at
+    #[arg(long, requires = "compare")]
+    only_regressions: bool,
+
+    /// Analyze every Cargo project found under DIR (skipping `target/`) and
+    /// report them together, grouped and ranked by workspace
+    #[arg(long, value_name = "DIR", conflicts_with = "project_dir")]
+    scan_root: Option<PathBuf>,
+
+    /// Fail unless the modules matching a pattern reach a line coverage, e.g.
+    /// `core::** >= 80%` (repeatable). `*` matches one module segment, `**` any
+    #[arg(long = "coverage-floor", value_name = "RULE", value_parser = CoverageFloor::parse)]
+    coverage_floors: Vec<CoverageFloor>,
+
+    /// Organization policy (local path or http(s) URL) with default thresholds
+    /// and exclusion rules; the project's craprs.toml and explicit flags override it
+    #[arg(long, value_name = "PATH|URL")]
+    policy: Option<String>,
+
+    /// After the text report, print refactoring hints for reported functions
+    /// with cyclomatic complexity at or above N
+    #[arg(long, value_name = "N")]
+    hints: Option<u32>,
+
+    /// After the text report, list reported functions that call through
+    /// `dyn Trait` receivers, with the number of such calls
+    #[arg(long)]
+    dyn_dispatch: bool,
+
+    /// After the text report, list the uncovered line numbers of each
+    /// reported function
+    #[arg(long)]
+    show_uncovered: bool,
+
+    /// After the text report, list the N functions where each newly covered
+    /// line lowers the score most
+    #[arg(long, value_name = "N")]
+    suggest_tests: Option<usize>,
+
+    /// After the text report, chart how many functions fall into the score
+    /// buckets 1-5, 5-15, 15-30 and 30+
+    #[arg(long)]
+    histogram: bool,
+
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Print a README badge snippet for the project's CRAP state, or rewrite it in place
+    Badge(BadgeArgs),
+    /// List non-trivial functions with 0% coverage, grouped by module
+    Untested(UntestedArgs),
+    /// Run both tarpaulin and llvm-cov and list functions whose CRAP scores disagree
+    CompareTools(CompareToolsArgs),
+    /// Print a `#[cfg(test)]` skeleton with a stub test per uncovered function in a module
+    ScaffoldTests(ScaffoldTestsArgs),
+    /// Work with `--format json` reports kept as baselines
+    #[command(subcommand)]
+    Baseline(BaselineCmd),
+    /// Combine `--format json` reports, e.g. one per repository, into a
+    /// summary per report and the worst functions across all of them
+    Merge(MergeArgs),
+    /// Check a report, baseline or policy file against craprs' built-in JSON Schemas
+    Validate(ValidateArgs),
+    /// Download a published crate from crates.io and report its complexity
+    Fetch(FetchArgs),
+    /// Summarize complexity hotspots in the project's registry dependencies
+    Deps(DepsArgs),
+    /// Show the report settings given on the command line, in policy files or
+    /// by the environment, and where each came from
+    Config(ConfigArgs),
+}
+
+#[derive(clap::Args)]
+struct DepsArgs {
+    /// Cyclomatic complexity at or above which a function is a hotspot
+    #[arg(long, default_value_t = 10)]
+    min_cc: u32,
+
+    /// Hotspots listed per dependency
+    #[arg(long, default_value_t = 3)]
+    top: usize,
+
+    /// Only the dependencies named in the project's own `[dependencies]`,
+    /// not everything in Cargo.lock
+    #[arg(long)]
+    direct: bool,
+
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+}
+
+#[derive(clap::Args)]
+struct FetchArgs {
+    /// Crate version to analyze, e.g. `serde_json@1.0.128`
+    #[arg(value_name = "CRATE@VERSION", value_parser = CrateSpec::parse)]
+    spec: CrateSpec,
+
+    /// Also run the crate's tests under the coverage tool and score CRAP;
+    /// by default only complexity is analyzed
+    #[arg(long)]
+    with_coverage: bool,
+
+    /// Where crates are downloaded and unpacked [default: craprs-crates in the
+    /// system temp dir]
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+}
+
+#[derive(Clone, Copy, clap::Args)]
+struct ConfigArgs {
+    /// Every report setting with its effective value, defaults included
+    #[arg(long)]
+    resolved: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ConfigFormat::Text)]
+    format: ConfigFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConfigFormat {
+    Text,
+    Json,
+}
+
+#[derive(clap::Args)]
+struct MergeArgs {
+    /// Reports to combine, each labelled by its path
+    #[arg(required = true, value_name = "REPORT")]
+    reports: Vec<PathBuf>,
+
+    /// Functions in the list of worst offenders
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// Score at or above which a function counts as crappy in the summaries
+    #[arg(long, default_value_t = crap::CRAPPY_THRESHOLD)]
+    threshold: f64,
+}
+
+#[derive(clap::Args)]
+struct ValidateArgs {
+    /// File to check
+    file: PathBuf,
+
+    /// Schema to use [default: `policy` for .toml files, `report` otherwise]
+    #[arg(long, value_enum)]
+    kind: Option<FileKind>,
+}
+
+#[derive(Subcommand)]
+enum BaselineCmd {
+    /// Merge two baselines, e.g. when reconciling a long-lived branch
+    Merge(BaselineMergeArgs),
+    /// List functions whose score changed between two baselines, and why
+    Diff(BaselineDiffArgs),
+}
+
+#[derive(clap::Args)]
+struct BaselineDiffArgs {
+    /// Earlier baseline, a path or http(s) URL
+    old: PathBuf,
+
+    /// Later baseline, a path or http(s) URL
+    new: PathBuf,
+
+    /// Smallest score change worth listing
+    #[arg(long, default_value_t = 0.1)]
+    min_delta: f64,
+
+    /// List the commits between the baselines that touched each function
+    /// whose score went up (or, with --most-improved, down). Run from the
+    /// project dir the reports describe
+    #[arg(long)]
+    blame: bool,
+
+    /// After the changes, list the N functions whose score dropped most,
+    /// with the authors of their commits when given --blame
+    #[arg(long, value_name = "N", conflicts_with = "format")]
+    most_improved: Option<usize>,
+
+    /// Output format; `html` is a standalone page with regressions in red
+    /// and improvements in green
+    #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+    format: DiffFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DiffFormat {
+    Text,
+    Html,
+}
+
+#[derive(clap::Args)]
+struct BaselineMergeArgs {
+    /// First baseline; wins ties
+    ours: PathBuf,
+
+    /// Second baseline
+    theirs: PathBuf,
+
+    /// Where to write the merged baseline
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Which score to keep for functions present in both
+    #[arg(long, value_enum, default_value_t = MergeStrategy::Max)]
+    strategy: MergeStrategy,
+}
+
+#[derive(Clone, clap::Args)]
+struct AnalysisArgs {
     /// Coverage tool to use
     #[arg(long, default_value = "tarpaulin")]
     coverage_tool: CoverageTool,
@@ -21,10 +417,96 @@ struct Cli {
     #[arg(long)]
     skip_coverage: bool,
 
+    /// Where coverage comes from: `lcov` (lcov.info), `cobertura[:PATH]` or
+    /// `coveralls[:PATH]`, which read an existing Cobertura XML report
+    /// (default cobertura.xml) or grcov Coveralls JSON (default
+    /// coveralls.json) instead of running a coverage tool, or `exec:<CMD>`,
+    /// which runs CMD in the project dir and reads
+    /// `{"files": {"<path>": {"<line>": <hits>}}}` JSON from its stdout
+    #[arg(long, value_name = "lcov|cobertura[:PATH]|coveralls[:PATH]|exec:CMD", value_parser = parse_coverage_format, default_value = "lcov")]
+    coverage_format: CoverageFormat,
+
+    /// If the coverage run fails but left an lcov.info behind, score it anyway
+    /// (with a warning) instead of aborting
+    #[arg(long, conflicts_with = "skip_coverage")]
+    use_partial_coverage: bool,
+
+    /// Kill the coverage run (including the test processes it spawned) after SECS seconds
+    #[arg(long, value_name = "SECS", conflicts_with = "skip_coverage")]
+    coverage_timeout: Option<u64>,
+
+    /// Re-run coverage up to N more times when it fails or times out
+    #[arg(long, value_name = "N", default_value_t = 0, conflicts_with = "skip_coverage")]
+    coverage_retries: u32,
+
+    /// With --skip-coverage, fail instead of warning when source files
+    /// changed after the reused lcov.info was written
+    #[arg(long, requires = "skip_coverage")]
+    strict_freshness: bool,
+
+    /// Fail instead of warning about data-quality problems: unparseable
+    /// sources, coverage for files that don't exist, files recorded twice in
+    /// lcov.info, stale reused coverage and coverage that doesn't line up
+    /// with the source
+    #[arg(long, conflicts_with = "use_partial_coverage")]
+    strict: bool,
+
+    /// `cargo public-api` listing (relative to the project dir) that decides
+    /// which functions are public API, instead of `pub` and `pub mod`
+    /// declarations; adds the `api` column
+    #[arg(long, value_name = "FILE")]
+    public_api: Option<PathBuf>,
+
+    /// Keep matching files out of the coverage run (repeatable). Forwarded as
+    /// `--ignore-filename-regex` to llvm-cov and `--exclude-files` to tarpaulin,
+    /// which treats the value as a glob.
+    #[arg(long = "coverage-ignore", value_name = "PATTERN")]
+    coverage_ignore: Vec<String>,
+
+    /// Run the coverage command in a container from IMAGE, with the project
+    /// mounted at /workspace, and map the paths in its lcov.info back
+    #[arg(long, value_name = "IMAGE")]
+    coverage_in_docker: Option<String>,
+
+    /// Bazel targets to run `bazel coverage` on with `--coverage-tool bazel` (repeatable)
+    #[arg(long = "bazel-target", value_name = "LABEL", default_value = "//...")]
+    bazel_targets: Vec<String>,
+
     /// Project directory (where Cargo.toml lives)
     #[arg(short = 'C', long)]
     project_dir: Option<PathBuf>,
 
+    /// Risk score to rank by: `crap` (CC² × (1 − cov)³ + CC) or the linear
+    /// `uncovered-cc` (CC × (1 − cov))
+    #[arg(long, value_enum, default_value_t = ScoreKind::Crap)]
+    score: ScoreKind,
+
+    /// Cyclomatic complexity added per `?` operator. 0 stops counting error
+    /// propagation as branching
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    try_weight: u32,
+
+    /// Also count or-pattern alternatives (`A | B => ...`) and match guards
+    /// (`x if cond => ...`) as decision points
+    #[arg(long)]
+    weigh_patterns: bool,
+
+    /// Start the coverage range of functions wrapped by attribute macros like
+    /// `#[tokio::main]` or `#[tracing::instrument]` at their first body line,
+    /// leaving out the setup code the macro attributes to the signature
+    #[arg(long)]
+    skip_macro_prologue: bool,
+
+    /// What to do with trivial functions: single-expression constructors,
+    /// `Default` impls, getters and setters
+    #[arg(long, value_enum, default_value_t = TrivialMode::Show)]
+    trivial: TrivialMode,
+
+    /// Run `cargo expand` on workspace members that use a proc-macro member
+    /// and report the complexity the macros generate there
+    #[arg(long)]
+    expand_macros: bool,
+
     /// Source directory (relative to project dir)
     #[arg(long, default_value = "src")]
     src: PathBuf,
@@ -33,130 +515,2277 @@ struct Cli {
     #[arg(short = 'p', long = "package")]
     packages: Vec<String>,
 
-    /// Hide entries with CRAP below this threshold.
-    /// Entries with no coverage data (uninstrumented files) are unaffected.
-    #[arg(long, default_value_t = 0.0)]
-    min_crap: f64,
-
-    /// Show only the top N entries after sorting and filtering.
-    #[arg(long)]
-    top: Option<usize>,
-
     /// Include entries for source files not present in lcov.info (shown with `—`).
     /// By default these are suppressed and summarized in a trailing note.
     #[arg(long)]
     include_uninstrumented: bool,
 
+    /// Also analyze `vendor/`, `third_party/`, and git submodule directories,
+    /// which are skipped during source discovery by default
+    #[arg(long)]
+    include_vendored: bool,
+
+    /// Skip source files excluded by .gitignore / .ignore (ripgrep-style discovery)
+    #[arg(long)]
+    respect_gitignore: bool,
+
+    /// Print an empty report instead of failing when no functions were found
+    /// or lcov.info matches none of the analyzed source files
+    #[arg(long)]
+    allow_empty: bool,
+
+    /// Instead of failing on an empty analysis, return it with
+    /// [`Analysis::empty`] set. `--scan-root` skips such workspaces.
+    #[arg(skip)]
+    skip_empty: bool,
+
     /// Module name fragments to filter by
     module_filters: Vec<String>,
 }
 
-#[derive(Clone, clap::ValueEnum)]
+#[derive(clap::Args)]
+struct BadgeArgs {
+    /// Rewrite the marked badge region in the README instead of printing the snippet
+    #[arg(long)]
+    update_readme: bool,
+
+    /// README to update (relative to the project dir)
+    #[arg(long, default_value = "README.md")]
+    readme: PathBuf,
+
+    /// CRAP score at or above which a function counts as crappy
+    #[arg(long, default_value_t = crap::CRAPPY_THRESHOLD)]
+    threshold: f64,
+
+    /// What the badge shows: the crappy function count or the total CRAP load
+    #[arg(long, value_enum, default_value_t = BadgeMetric::Crappy)]
+    metric: BadgeMetric,
+
+    /// Render the badge as an SVG file (relative to the project dir) instead
+    /// of printing a shields.io snippet
+    #[arg(long, value_name = "FILE", conflicts_with = "update_readme")]
+    svg: Option<PathBuf>,
+
+    /// Render one SVG badge per top-level module into DIR (relative to the
+    /// project dir), named `<module>.svg`
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["update_readme", "svg"])]
+    per_module: Option<PathBuf>,
+
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+}
+
+#[derive(clap::Args)]
+struct UntestedArgs {
+    /// Only list functions with at least this cyclomatic complexity
+    #[arg(long, default_value_t = 2)]
+    min_cc: u32,
+
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+}
+
+#[derive(clap::Args)]
+struct CompareToolsArgs {
+    /// Minimum absolute CRAP difference worth reporting
+    #[arg(long, default_value_t = 5.0)]
+    min_delta: f64,
+
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+}
+
+#[derive(clap::Args)]
+struct ScaffoldTestsArgs {
+    /// Module path to scaffold, as shown in the report (e.g. `coverage` or `mycrate::parse`)
+    module: String,
+
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Text,
+    Parquet,
+    Json,
+    Html,
+    Markdown,
+    Junit,
+    Checkstyle,
+    Prometheus,
+}
+
+/// Destination for `--export`, written alongside the regular report.
+#[derive(Clone)]
+enum Export {
+    Sqlite(PathBuf),
+}
+
+fn parse_export(s: &str) -> Result<Export, String> {
+    match s.split_once(':') {
+        Some(("sqlite", path)) if !path.is_empty() => Ok(Export::Sqlite(PathBuf::from(path))),
+        _ => Err(format!("expected `sqlite:<path>`, got `{s}`")),
+    }
+}
+
+/// Where coverage data comes from: `lcov.info`, written by the coverage tool
+/// run, a Cobertura XML or Coveralls JSON report kept from elsewhere, or the
+/// JSON an external converter prints.
+#[derive(Clone, PartialEq, Eq)]
+enum CoverageFormat {
+    Lcov,
+    Cobertura(PathBuf),
+    Coveralls(PathBuf),
+    Exec(String),
+}
+
+impl CoverageFormat {
+    /// Whether the coverage tool runs to produce the data.
+    fn runs_tool(&self) -> bool {
+        *self == CoverageFormat::Lcov
+    }
+}
+
+fn parse_coverage_format(s: &str) -> Result<CoverageFormat, String> {
+    match s.split_once(':') {
+        None if s == "lcov" => Ok(CoverageFormat::Lcov),
+        None if s == "cobertura" => Ok(CoverageFormat::Cobertura(PathBuf::from("cobertura.xml"))),
+        Some(("cobertura", path)) if !path.is_empty() => {
+            Ok(CoverageFormat::Cobertura(PathBuf::from(path)))
+        }
+        None if s == "coveralls" => Ok(CoverageFormat::Coveralls(PathBuf::from("coveralls.json"))),
+        Some(("coveralls", path)) if !path.is_empty() => {
+            Ok(CoverageFormat::Coveralls(PathBuf::from(path)))
+        }
+        Some(("exec", command)) if !command.trim().is_empty() => {
+            Ok(CoverageFormat::Exec(command.trim().to_string()))
+        }
+        _ => Err(format!(
+            "expected `lcov`, `cobertura[:<path>]`, `coveralls[:<path>]` or `exec:<command>`, \
+             got `{s}`"
+        )),
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, clap::ValueEnum)]
 enum CoverageTool {
     Tarpaulin,
     LlvmCov,
+    /// `bazel coverage` with a combined lcov report, for Rust built with Bazel
+    Bazel,
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+impl CoverageTool {
+    fn label(&self) -> &'static str {
+        match self {
+            CoverageTool::Tarpaulin => "tarpaulin",
+            CoverageTool::LlvmCov => "llvm-cov",
+            CoverageTool::Bazel => "bazel",
+        }
+    }
 
-    if let Some(ref dir) = cli.project_dir {
-        std::env::set_current_dir(dir)
-            .with_context(|| format!("failed to cd into {}", dir.display()))?;
-        if !Path::new("Cargo.toml").exists() {
-            if Path::new("../Cargo.toml").exists() {
-                if let Some(parent) = dir.parent() {
-                    bail!(
-                        "no Cargo.toml in {} — did you mean {}?",
-                        dir.display(),
-                        parent.display()
-                    );
-                }
+    fn program(&self) -> &'static str {
+        match self {
+            CoverageTool::Tarpaulin | CoverageTool::LlvmCov => "cargo",
+            CoverageTool::Bazel => "bazel",
+        }
+    }
+
+    /// Arguments to [`program`](Self::program) that print the tool's version.
+    fn version_args(&self) -> &'static [&'static str] {
+        match self {
+            CoverageTool::Tarpaulin => &["tarpaulin", "--version"],
+            CoverageTool::LlvmCov => &["llvm-cov", "--version"],
+            CoverageTool::Bazel => &["--version"],
+        }
+    }
+}
+
+/// What an LCOV report records besides line hits, keyed by file like line
+/// coverage. Empty for other formats.
+#[derive(Default)]
+struct LcovRecords {
+    branches: HashMap<String, BranchCoverage>,
+    functions: HashMap<String, Vec<FunctionRecord>>,
+    /// Sources as they were when the coverage was recorded, keyed like
+    /// [`CrapEntry::file`], see [`coverage_time_sources`].
+    sources: HashMap<PathBuf, String>,
+}
+
+/// Scored entries for every analyzed function, before display filters are applied.
+#[derive(Default)]
+struct Analysis {
+    entries: Vec<CrapEntry>,
+    /// Functions extracted from the sources, before any are dropped.
+    functions: usize,
+    /// Source files with functions but no entry in lcov.info.
+    uninstrumented_files: Vec<PathBuf>,
+    /// Every source file that was read for analysis.
+    sources: Vec<PathBuf>,
+    /// Non-`pub` functions only called from test code but not gated by
+    /// `#[cfg(test)]`. Kept out of `entries` so they don't skew production risk.
+    test_only: Vec<CrapEntry>,
+    /// Functions generated by workspace proc-macros, with `--expand-macros`.
+    expansions: Vec<Expansion>,
+    /// Line counts per module over whole instrumented files, for coverage floors.
+    module_lines: ModuleLines,
+    /// Coverage of every instrumented source, keyed like [`CrapEntry::file`].
+    line_coverage: HashMap<PathBuf, LineCoverage>,
+    /// Functions whose coverage doesn't line up with their source span.
+    span_mismatches: Vec<CrapEntry>,
+    /// Why the analysis came up empty, with `skip_empty`, see [`check_not_empty`].
+    empty: Option<anyhow::Error>,
+}
+
+fn main() -> Result<()> {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches)?;
+    match cli.command {
+        Some(Cmd::Badge(ref args)) => run_badge(args),
+        Some(Cmd::Untested(ref args)) => {
+            let analysis = analyze(&args.analysis)?;
+            print!("{}", untested::format_untested(&analysis.entries, args.min_cc));
+            Ok(())
+        }
+        Some(Cmd::CompareTools(ref args)) => run_compare_tools(args),
+        Some(Cmd::ScaffoldTests(ref args)) => {
+            let analysis = analyze(&args.analysis)?;
+            match scaffold::scaffold_tests(&analysis.entries, &args.module) {
+                Some(skeleton) => print!("{skeleton}"),
+                None => bail!("no uncovered functions with coverage data in `{}`", args.module),
             }
-            bail!("no Cargo.toml found in {}", dir.display());
+            Ok(())
+        }
+        Some(Cmd::Baseline(BaselineCmd::Merge(ref args))) => {
+            run_baseline_merge(args, &cli.baseline_headers)
+        }
+        Some(Cmd::Baseline(BaselineCmd::Diff(ref args))) => {
+            run_baseline_diff(args, &cli.baseline_headers)
+        }
+        Some(Cmd::Merge(ref args)) => run_merge(args, &cli.baseline_headers),
+        Some(Cmd::Validate(ref args)) => run_validate(args),
+        Some(Cmd::Fetch(ref args)) => run_fetch(args),
+        Some(Cmd::Deps(ref args)) => run_deps(args),
+        Some(Cmd::Config(args)) => run_config(&cli, &matches, args),
+        None => {
+            let policy = resolve_policy(&mut cli, &matches)?;
+            run_report(&cli, &policy)
         }
     }
+}
 
-    let resolved = workspace::resolve_targets(Path::new("."), &cli.src, &cli.packages)?;
+fn use_color(mode: ColorMode, ci: Option<Provider>) -> bool {
+    match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            ci.is_none()
+                && std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none()
+        }
+    }
+}
 
-    if !cli.skip_coverage {
-        delete_stale_coverage();
-        run_coverage(&cli.coverage_tool, resolved.is_workspace, &cli.packages)?;
+/// Columns of the terminal stdout is attached to, if it is one.
+fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+            && size.ws_col > 0
+        {
+            return Some(size.ws_col.into());
+        }
     }
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
 
-    let lcov_content = std::fs::read_to_string("lcov.info")
-        .context("failed to read lcov.info — did coverage run succeed?")?;
-    let file_coverage = coverage::parse_lcov(&lcov_content);
+/// The policy files a report reads, lowest precedence first: the `--policy`
+/// file, then the project's craprs.toml, each with where it was read from.
+fn policy_layers(cli: &Cli) -> Result<Vec<(Source, String, Policy)>> {
+    let mut layers = Vec::new();
+    if let Some(ref source) = cli.policy {
+        let policy = Policy::load(source)?;
+        if !policy.module_paths.is_empty() {
+            bail!(
+                "module_paths describes one project's layout; set it in its craprs.toml, \
+                 not --policy"
+            );
+        }
+        if !policy.tags.is_empty() {
+            bail!("tags describes one project's layout; set it in its craprs.toml, not --policy");
+        }
+        layers.push((Source::Policy, source.clone(), policy));
+    }
+    let project_dir = cli
+        .analysis
+        .project_dir
+        .as_deref()
+        .unwrap_or(Path::new("."));
+    let project_file = project_dir.join(policy::PROJECT_POLICY);
+    if project_file.is_file() {
+        let path = project_file.to_string_lossy().into_owned();
+        let policy = Policy::load(&path)?;
+        layers.push((Source::ProjectPolicy, path, policy));
+    }
+    Ok(layers)
+}
 
-    let mut all_entries = Vec::new();
-    let mut uninstrumented_files: u64 = 0;
-    for target in &resolved.targets {
-        let sources = find_rust_sources(&target.src_dir)?;
-        let sources = filter_sources(sources, &cli.module_filters);
+/// Layer the `--policy` file and the project's craprs.toml, and fill in
+/// thresholds that weren't given on the command line.
+fn resolve_policy(cli: &mut Cli, matches: &ArgMatches) -> Result<Policy> {
+    let policy = policy_layers(cli)?
+        .into_iter()
+        .fold(Policy::default(), |policy, (_, _, over)| {
+            policy.layered(over)
+        });
+    let from_flag = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    if let (Some(threshold), false) = (policy.threshold, from_flag("threshold")) {
+        cli.threshold = threshold;
+    }
+    if let (Some(min_crap), false) = (policy.min_crap, from_flag("min_crap")) {
+        cli.min_crap = min_crap;
+    }
+    Ok(policy)
+}
 
-        for source_path in &sources {
-            let source = std::fs::read_to_string(source_path)
-                .with_context(|| format!("failed to read {}", source_path.display()))?;
-            let fns = complexity::extract_functions(&source);
-            if fns.is_empty() {
+fn run_fetch(args: &FetchArgs) -> Result<()> {
+    let cache = match args.cache_dir {
+        Some(ref dir) => std::path::absolute(dir)?,
+        None => std::env::temp_dir().join("craprs-crates"),
+    };
+    eprintln!("fetching {}@{}", args.spec.name, args.spec.version);
+    let root = fetch::fetch_crate(&args.spec, &cache)?;
+    let mut analysis = args.analysis.clone();
+    analysis.project_dir = Some(root);
+    let entries = if args.with_coverage {
+        analyze(&analysis)?.entries
+    } else {
+        let resolved = enter_project(&analysis)?;
+        analysis.include_uninstrumented = true;
+        let mut entries = score_targets(
+            &analysis,
+            &resolved,
+            &HashMap::new(),
+            &LcovRecords::default(),
+        )?
+        .entries;
+        // Nothing is scored, so rank by complexity instead.
+        entries.sort_by_key(|e| std::cmp::Reverse(e.complexity));
+        entries
+    };
+    let table = TableOptions {
+        score_label: analysis.score.label(),
+        ..Default::default()
+    };
+    print!("{}", crap::format_report_with(&entries, &table));
+    if !args.with_coverage {
+        println!(
+            "note: complexity only — pass --with-coverage to run the crate's tests and score CRAP"
+        );
+    }
+    Ok(())
+}
+
+/// Static complexity analysis of every registry crate in the project's
+/// Cargo.lock that cargo has already unpacked; nothing is downloaded.
+fn run_deps(args: &DepsArgs) -> Result<()> {
+    let resolved = enter_project(&args.analysis)?;
+    let lock = std::fs::read_to_string("Cargo.lock")
+        .context("failed to read Cargo.lock — run `cargo generate-lockfile` first")?;
+    let mut crates = deps::registry_crates(&lock)?;
+    if args.direct {
+        let direct: HashSet<&str> = resolved
+            .targets
+            .iter()
+            .flat_map(|t| t.dependencies.iter().map(String::as_str))
+            .collect();
+        crates.retain(|c| direct.contains(c.name.replace('-', "_").as_str()));
+    }
+
+    let cargo_home = match std::env::var_os("CARGO_HOME") {
+        Some(home) => PathBuf::from(home),
+        None => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".cargo"),
+    };
+    let registry_dirs = deps::registry_src_dirs(&cargo_home);
+    let mut risks = Vec::new();
+    let mut missing = Vec::new();
+    for krate in crates {
+        let Some(root) = deps::locate(&registry_dirs, &krate) else {
+            missing.push(krate);
+            continue;
+        };
+        let mut analysis = args.analysis.clone();
+        analysis.project_dir = Some(root);
+        analysis.include_uninstrumented = true;
+        let entries = enter_project(&analysis).and_then(|resolved| {
+            score_targets(
+                &analysis,
+                &resolved,
+                &HashMap::new(),
+                &LcovRecords::default(),
+            )
+        });
+        match entries {
+            Ok(a) => risks.push(deps::dep_risk(krate, a.entries, args.min_cc)),
+            Err(err) => eprintln!(
+                "warning: skipping {} {}: {err:#}",
+                krate.name, krate.version
+            ),
+        }
+    }
+    deps::rank(&mut risks);
+    print!(
+        "{}",
+        deps::format_deps(&risks, args.min_cc, args.top, &missing)
+    );
+    Ok(())
+}
+
+/// `craprs config`: the report settings with where each came from, resolved
+/// the way a report run with the same flags would.
+fn run_config(cli: &Cli, matches: &ArgMatches, args: ConfigArgs) -> Result<()> {
+    let layers = policy_layers(cli)?;
+    let mut resolved = config::Resolved {
+        policy_files: layers.iter().map(|(_, path, _)| path.clone()).collect(),
+        settings: Vec::new(),
+    };
+    for arg in Cli::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        if matches!(id, "help" | "version") {
+            continue;
+        }
+        let raw: Vec<String> = matches
+            .get_raw(id)
+            .map(|values| values.map(|v| v.to_string_lossy().into_owned()).collect())
+            .unwrap_or_default();
+        let value = match arg.get_action() {
+            ArgAction::SetTrue | ArgAction::SetFalse => {
+                Value::Bool(raw.first().is_some_and(|v| v == "true"))
+            }
+            ArgAction::Append => raw.into(),
+            _ => raw.into_iter().next().map_or(Value::Null, Value::from),
+        };
+        let source = match matches.value_source(id) {
+            Some(ValueSource::CommandLine) => Source::CommandLine,
+            Some(ValueSource::EnvVariable) => Source::Environment,
+            _ => Source::Default,
+        };
+        resolved.settings.push(Setting {
+            name: arg
+                .get_long()
+                .map_or_else(|| id.replace('_', "-"), str::to_string),
+            value,
+            source,
+            origin: None,
+        });
+    }
+    // Thresholds the policy files fill in, later files winning.
+    for setting in resolved.settings.iter_mut() {
+        for (source, path, policy) in &layers {
+            let value = match setting.name.as_str() {
+                "threshold" => policy.threshold,
+                "min-crap" => policy.min_crap,
+                _ => None,
+            };
+            if let (Some(value), false) = (value, setting.source == Source::CommandLine) {
+                setting.value = value.to_string().into();
+                setting.source = *source;
+                setting.origin = Some(path.clone());
+            }
+        }
+    }
+    for key in ["exclude", "module_paths", "tags", "tiers"] {
+        let mut setting = Setting {
+            name: key.to_string(),
+            value: Value::Array(Vec::new()),
+            source: Source::Default,
+            origin: None,
+        };
+        let mut items = Vec::new();
+        let mut origins = Vec::new();
+        for (source, path, policy) in &layers {
+            let list = match key {
+                "exclude" => &policy.exclude,
+                "module_paths" => &policy.module_paths,
+                "tags" => &policy.tags,
+                _ => &policy.tiers,
+            };
+            if list.is_empty() {
                 continue;
             }
-            let module_path = coverage::source_to_module_path(source_path, &target.src_dir);
-            let module_path = match &target.crate_name {
-                Some(name) if !module_path.is_empty() => format!("{name}::{module_path}"),
-                Some(name) => name.clone(),
-                None => module_path,
+            // Exclusion rules from every file apply; other lists are replaced.
+            if key != "exclude" {
+                items.clear();
+                origins.clear();
+            }
+            items.extend(list.iter().cloned());
+            origins.push(path.as_str());
+            setting.source = *source;
+        }
+        if !origins.is_empty() {
+            setting.value = items.into();
+            setting.origin = Some(origins.join(", "));
+        }
+        resolved.settings.push(setting);
+    }
+    resolved.apply_environment(|name| std::env::var(name).ok());
+    if !args.resolved {
+        resolved = resolved.overrides();
+    }
+    match args.format {
+        ConfigFormat::Text => print!("{}", config::format_text(&resolved)),
+        ConfigFormat::Json => println!("{}", config::to_json(&resolved)),
+    }
+    Ok(())
+}
+
+fn run_validate(args: &ValidateArgs) -> Result<()> {
+    let kind = args.kind.unwrap_or_else(|| FileKind::detect(&args.file));
+    let content = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("failed to read {}", args.file.display()))?;
+    let errors = validate::validate(kind, &content)?;
+    if errors.is_empty() {
+        println!("{}: valid", args.file.display());
+        return Ok(());
+    }
+    for err in &errors {
+        eprintln!("{}: {err}", args.file.display());
+    }
+    bail!("{} schema violation(s) in {}", errors.len(), args.file.display())
+}
+
+/// A baseline from a local path or an `http(s)://` URL, fetched with `curl`
+/// and sent `headers` (`--baseline-header`), e.g. for a token.
+fn read_baseline(path: &Path, headers: &[String]) -> Result<String> {
+    let source = path.to_string_lossy();
+    if !(source.starts_with("https://") || source.starts_with("http://")) {
+        return std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()));
+    }
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(headers.iter().flat_map(|header| ["-H", header]))
+        .arg(&*source)
+        .output()
+        .context("failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "failed to fetch {source}: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("{source} is not UTF-8"))
+}
+
+fn run_baseline_diff(args: &BaselineDiffArgs, headers: &[String]) -> Result<()> {
+    let old = read_baseline(&args.old, headers)?;
+    let new = read_baseline(&args.new, headers)?;
+    let mut deltas = baseline::diff(&old, &new, args.min_delta)?;
+    if args.blame {
+        blame_deltas(&mut deltas, &old, &new, args.most_improved.is_some())?;
+    }
+    match args.format {
+        DiffFormat::Text => {
+            print!("{}", baseline::format_diff(&deltas));
+            if let Some(n) = args.most_improved {
+                print!("{}", baseline::format_most_improved(&deltas, n));
+            }
+        }
+        DiffFormat::Html => print!(
+            "{}",
+            html_export::diff_html(
+                &deltas,
+                &args.old.display().to_string(),
+                &args.new.display().to_string()
+            )
+        ),
+    }
+    Ok(())
+}
+
+/// Fill in the commits behind each regression, and each improvement with
+/// `improvements`: those between the old report's commit and the new one's
+/// (or `HEAD`) that touched the function's lines. Report paths are relative
+/// to the project dir, so this runs from it.
+fn blame_deltas(deltas: &mut [Delta], old: &str, new: &str, improvements: bool) -> Result<()> {
+    let Some(since) = baseline::report_commit(old) else {
+        bail!(
+            "--blame needs the git commit the old baseline was written at, and it has none \
+             (written by an older craprs or outside a git checkout)"
+        );
+    };
+    let until = baseline::report_commit(new).unwrap_or_else(|| "HEAD".to_string());
+    let dir = std::env::current_dir()?;
+    let blamed = |d: &Delta| d.is_regression() || (improvements && d.is_improvement());
+    for delta in deltas.iter_mut().filter(|d| blamed(d)) {
+        let Some((ref file, start, end)) = delta.location else {
+            continue;
+        };
+        match blame::commits_touching(&dir, &since, &until, file, start, end) {
+            Ok(commits) => delta.commits = commits,
+            Err(err) => eprintln!("warning: {err:#}"),
+        }
+    }
+    Ok(())
+}
+
+fn run_merge(args: &MergeArgs, headers: &[String]) -> Result<()> {
+    let mut reports = Vec::new();
+    for path in &args.reports {
+        let entries = baseline::report_entries(&read_baseline(path, headers)?)
+            .with_context(|| format!("invalid report {}", path.display()))?;
+        reports.push((path.display().to_string(), entries));
+    }
+    print!(
+        "{}",
+        baseline::format_aggregate(&reports, args.threshold, args.top)
+    );
+    Ok(())
+}
+
+fn run_baseline_merge(args: &BaselineMergeArgs, headers: &[String]) -> Result<()> {
+    let merged = baseline::merge(
+        &read_baseline(&args.ours, headers)?,
+        &read_baseline(&args.theirs, headers)?,
+        args.strategy,
+    )?;
+    std::fs::write(&args.output, merged + "\n")
+        .with_context(|| format!("failed to write {}", args.output.display()))
+}
+
+fn run_report(cli: &Cli, policy: &Policy) -> Result<()> {
+    let args = &cli.analysis;
+    // `analyze` changes the working directory; --output and --export paths
+    // are relative to the original one.
+    let output = cli.output.as_deref().map(std::path::absolute).transpose()?;
+    let sqlite = match cli.export {
+        Some(Export::Sqlite(ref path)) => Some(std::path::absolute(path)?),
+        None => None,
+    };
+    // Read before `analyze` changes into the project dir.
+    let previous = match cli.compare {
+        Some(ref path) => Some(
+            baseline::previous_scores(&read_baseline(path, &cli.baseline_headers)?)
+                .with_context(|| format!("invalid --compare report {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let uses_deltas = cli
+        .columns
+        .iter()
+        .any(|c| matches!(c, Column::DeltaCrap | Column::DeltaCov));
+    if uses_deltas && previous.is_none() {
+        bail!("the delta-crap and delta-cov columns need --compare");
+    }
+    let runs = match cli.scan_root {
+        Some(ref root) => scan_workspaces(root, args)?,
+        None => vec![(String::new(), analyze(args)?)],
+    };
+    let filters = Filters {
+        min_crap: cli.min_crap,
+        min_cc: cli.min_cc,
+        max_cc: cli.max_cc,
+        max_cov: cli.max_cov,
+        top: cli.top,
+    };
+    let mut debt = crap::CoverageDebt::default();
+    let mut uninstrumented = 0;
+    let mut test_only_sections = Vec::new();
+    let mut expansions = Vec::new();
+    let mut module_lines = ModuleLines::default();
+    let mut line_coverage = HashMap::new();
+    let mut span_mismatches = Vec::new();
+    let mut sections = Vec::new();
+    let mut omitted = 0;
+    let mut below_min_crap = 0;
+    let mut analyzed = Vec::new();
+    // Function coverage of each workspace before the report filters apply.
+    let mut workspace_coverage = HashMap::new();
+    for (label, mut analysis) in runs {
+        analysis.entries.retain(|e| !policy.excludes(e));
+        analyzed.extend(analysis.entries.iter().cloned());
+        if let Some(pct) = group::function_coverage(&analysis.entries) {
+            workspace_coverage.insert(label.clone(), pct);
+        }
+        let run_debt = crap::coverage_debt(&analysis.entries, cli.threshold);
+        debt.lines += run_debt.lines;
+        debt.functions += run_debt.functions;
+        uninstrumented += analysis.uninstrumented_files.len();
+        test_only_sections.push((label.clone(), analysis.test_only));
+        expansions.extend(analysis.expansions);
+        module_lines.extend(analysis.module_lines);
+        line_coverage.extend(analysis.line_coverage);
+        span_mismatches.extend(analysis.span_mismatches);
+        if let Some(ref selector) = cli.select {
+            analysis.entries.retain(|e| selector.matches(e));
+        }
+        if cli.only_regressions
+            && let Some(ref before) = previous
+        {
+            analysis.entries.retain(|e| crap::is_regression(e, before));
+        }
+        below_min_crap += analysis
+            .entries
+            .iter()
+            .filter(|e| e.crap.is_some_and(|s| s < cli.min_crap))
+            .count();
+        let (kept, cut) = filter_entries(analysis.entries, &filters);
+        omitted += cut;
+        sections.push((label, kept));
+    }
+    let filtered = flatten_sections(&sections);
+    if !cli.quiet {
+        check_entry_limit(filtered.len(), cli.max_entries)?;
+    }
+    let tiers = policy.risk_tiers();
+    let metadata = run_metadata(args);
+    let ci = Provider::resolve(cli.ci, |name| std::env::var(name).ok());
+    let table = TableOptions {
+        style: cli.table_style,
+        score_label: args.score.label(),
+        highlight: (!cli.stable_report && use_color(cli.color, ci)).then_some(cli.threshold),
+        numbers: cli.number_format,
+        columns: if cli.columns.is_empty() {
+            let branches = sections
+                .iter()
+                .any(|(_, entries)| entries.iter().any(|e| e.branch_coverage.is_some()));
+            let api = cli.public_threshold.is_some() || args.public_api.is_some();
+            crap::default_columns(branches, api, previous.is_some())
+        } else {
+            cli.columns.clone()
+        },
+        previous: previous.clone(),
+        max_width: match cli.width {
+            Some(0) => None,
+            Some(width) => Some(width),
+            None if cli.stable_report || output.is_some() => None,
+            None => terminal_width(),
+        },
+        truncate: cli.truncate_style,
+        full_names: cli.full_names,
+        fixed_widths: cli.stable_report,
+    };
+    // Everything meant for stdout goes to --output instead when given. The
+    // exporters stream into it; --quiet swaps the report for one line at the end.
+    let mut dest = open_output(output.as_deref())?;
+    let mut out: Box<dyn Write> = if cli.quiet {
+        Box::new(std::io::sink())
+    } else {
+        Box::new(&mut dest)
+    };
+    match cli.format {
+        OutputFormat::Text if cli.scan_root.is_some() => {
+            let groups = group::rank_groups(sections, &workspace_coverage, cli.group_sort);
+            write!(
+                out,
+                "{}",
+                group::format_grouped(&groups, "workspace", cli.group_sort, &table)
+            )
+        }
+        OutputFormat::Text => match cli.group_by {
+            Some(by) => {
+                let groups = group::group_entries(filtered.clone(), &analyzed, by, cli.group_sort);
+                write!(
+                    out,
+                    "{}",
+                    group::format_grouped(&groups, by.label(), cli.group_sort, &table)
+                )
+            }
+            None => write!(out, "{}", crap::format_report_with(&filtered, &table)),
+        },
+        OutputFormat::Parquet => {
+            if output.is_none() && std::io::stdout().is_terminal() {
+                bail!(
+                    "refusing to write Parquet to a terminal — pass --output or redirect stdout to a file"
+                );
+            }
+            out.write_all(&parquet_export::to_parquet(&filtered, &metadata)?)
+        }
+        OutputFormat::Json => {
+            json_export::write_json(&mut out, &filtered, &metadata)?;
+            writeln!(out)
+        }
+        OutputFormat::Html => {
+            let sources = html_sources(&filtered, &line_coverage);
+            html_export::write_html(
+                &mut out,
+                &filtered,
+                &sources,
+                args.score.label(),
+                cli.threshold,
+                &metadata,
+            )
+        }
+        OutputFormat::Junit => write!(
+            out,
+            "{}",
+            junit_export::to_junit(&filtered, args.score.label(), cli.threshold, &metadata)
+        ),
+        OutputFormat::Checkstyle => write!(
+            out,
+            "{}",
+            checkstyle_export::to_checkstyle(
+                &filtered,
+                args.score.label(),
+                cli.threshold,
+                tiers.as_ref(),
+                &metadata
+            )
+        ),
+        OutputFormat::Prometheus => write!(
+            out,
+            "{}",
+            prometheus_export::to_prometheus(
+                &filtered,
+                args.score.label(),
+                cli.threshold,
+                &metadata
+            )
+        ),
+        OutputFormat::Markdown => write!(
+            out,
+            "{}",
+            markdown::format_markdown(
+                &filtered,
+                args.score.label(),
+                cli.threshold,
+                cli.top.unwrap_or(markdown::DEFAULT_ROWS),
+                cli.number_format,
+                previous.as_ref()
+            )
+        ),
+    }?;
+
+    if omitted > 0 && cli.format == OutputFormat::Text {
+        writeln!(
+            out,
+            "{omitted} more function(s) omitted by --top {}",
+            cli.top.unwrap_or_default()
+        )?;
+    }
+
+    if below_min_crap > 0 && cli.format == OutputFormat::Text {
+        writeln!(
+            out,
+            "{below_min_crap} function(s) with {} below {} hidden by --min-crap",
+            args.score.label(),
+            cli.min_crap
+        )?;
+    }
+
+    if let (Some(min_cc), OutputFormat::Text) = (cli.hints, cli.format) {
+        write!(out, "{}", hints::format_hints(&filtered, min_cc))?;
+    }
+
+    if cli.dyn_dispatch && cli.format == OutputFormat::Text {
+        write!(out, "{}", dispatch::format_dispatch(&filtered))?;
+    }
+
+    if let (Some(previous), Some(path), OutputFormat::Text) = (&previous, &cli.compare, cli.format)
+    {
+        // Against every analyzed function, so filtered ones aren't "removed".
+        write!(
+            out,
+            "{}",
+            baseline::format_comparison(&analyzed, previous, &path.display().to_string())
+        )?;
+    }
+
+    if cli.show_uncovered && cli.format == OutputFormat::Text {
+        write!(out, "{}", coverage::format_uncovered(&filtered))?;
+    }
+
+    if let (Some(n), OutputFormat::Text) = (cli.suggest_tests, cli.format) {
+        let suggestions = suggest::suggest_tests(&analyzed, args.score, n);
+        write!(
+            out,
+            "{}",
+            suggest::format_suggestions(&suggestions, args.score.label())
+        )?;
+    }
+
+    if cli.histogram && cli.format == OutputFormat::Text {
+        write!(
+            out,
+            "{}",
+            histogram::format_histogram(&analyzed, args.score.label())
+        )?;
+    }
+
+    if let Some(ref path) = sqlite {
+        sqlite_export::write_sqlite(path, &filtered, cli.threshold, &metadata)?;
+    }
+
+    let debt_line = format!(
+        "coverage debt: {} uncovered line(s) in {} function(s) with {} >= {}",
+        debt.lines,
+        debt.functions,
+        args.score.label(),
+        cli.threshold
+    );
+
+    if uninstrumented > 0 && !args.include_uninstrumented {
+        let note = format!(
+            "note: {uninstrumented} source file(s) had no coverage data (not reached by the \
+             executed test set). Pass --include-uninstrumented to list them."
+        );
+        // Keep machine-readable stdout clean.
+        if cli.format == OutputFormat::Text {
+            writeln!(out, "{note}")?;
+        } else {
+            eprintln!("{note}");
+        }
+    }
+    let test_only_note = test_only::format_test_only(&flatten_sections(&test_only_sections));
+    let expansion_note = expand::format_expansions(&expansions);
+    let mismatch_warning = coverage::format_span_mismatches(&span_mismatches);
+    if cli.format == OutputFormat::Text {
+        write!(out, "{test_only_note}{expansion_note}{mismatch_warning}")?;
+        let summary = crap::summarize(&analyzed, cli.threshold);
+        write!(
+            out,
+            "{}",
+            crap::format_summary(
+                &summary,
+                args.score.label(),
+                cli.threshold,
+                cli.number_format
+            )
+        )?;
+        if let Some(ref tiers) = tiers {
+            write!(out, "{}", tiers::format_tier_counts(tiers, &analyzed))?;
+        }
+        writeln!(out, "{debt_line}")?;
+    } else {
+        eprint!("{test_only_note}{expansion_note}{mismatch_warning}");
+        eprintln!("{debt_line}");
+    }
+
+    let violations = floors::check_floors(&cli.coverage_floors, &module_lines);
+    eprint!("{}", floors::format_violations(&violations));
+    let exposed = match cli.public_threshold {
+        Some(limit) => public_api::over_threshold(&analyzed, limit),
+        None => Vec::new(),
+    };
+    if let Some(limit) = cli.public_threshold {
+        eprint!(
+            "{}",
+            public_api::format_exposed(&exposed, args.score.label(), limit)
+        );
+    }
+
+    let verdict = crap::verdict_line(&analyzed, cli.threshold);
+    if cli.format == OutputFormat::Text {
+        writeln!(out, "{verdict}")?;
+    } else {
+        eprintln!("{verdict}");
+    }
+
+    drop(out);
+    if cli.quiet {
+        writeln!(dest, "{}", crap::quiet_line(&analyzed, cli.threshold))?;
+    }
+    dest.flush()?;
+
+    if let Some(provider) = ci {
+        let summary = markdown::format_markdown(
+            &filtered,
+            args.score.label(),
+            cli.threshold,
+            cli.top.unwrap_or(markdown::DEFAULT_ROWS),
+            cli.number_format,
+            previous.as_ref(),
+        );
+        publish_to_ci(
+            provider,
+            &filtered,
+            &summary,
+            cli.threshold,
+            args.score.label(),
+            cli.annotate_changed_only.as_deref(),
+        );
+    }
+
+    if !violations.is_empty() {
+        bail!("{} coverage floor(s) not met", violations.len());
+    }
+    if let (false, Some(limit)) = (exposed.is_empty(), cli.public_threshold) {
+        bail!(
+            "{} public function(s) at or above --public-threshold {limit}",
+            exposed.len()
+        );
+    }
+    Ok(())
+}
+
+/// Surface the report where `provider` shows it: on GitHub Actions, warning
+/// annotations for crappy functions and the markdown `summary` on the job
+/// page; on Buildkite, `summary` as a build annotation. With `changed_only`
+/// (a diff base, empty for the PR's), only functions the diff touches are
+/// annotated. Failures here only warn, the report itself is already written.
+fn publish_to_ci(
+    provider: Provider,
+    entries: &[CrapEntry],
+    summary: &str,
+    threshold: f64,
+    score_label: &str,
+    changed_only: Option<&str>,
+) {
+    let result = match provider {
+        Provider::GithubActions => {
+            let root = std::env::var_os("GITHUB_WORKSPACE").map(PathBuf::from);
+            let annotated = match changed_only.map(pr_changed_lines) {
+                Some(Ok(changed)) => Cow::Owned(
+                    entries
+                        .iter()
+                        .filter(|e| ci::touches_changes(e, &changed))
+                        .cloned()
+                        .collect(),
+                ),
+                Some(Err(err)) => {
+                    eprintln!("warning: {err:#}; annotating every function");
+                    Cow::Borrowed(entries)
+                }
+                None => Cow::Borrowed(entries),
+            };
+            // The runner reads workflow commands from stderr as well as stdout.
+            eprint!(
+                "{}",
+                ci::github_annotations(&annotated, threshold, score_label, root.as_deref())
+            );
+            match std::env::var_os("GITHUB_STEP_SUMMARY") {
+                Some(path) => append_to(Path::new(&path), summary),
+                None => Ok(()),
+            }
+        }
+        Provider::Buildkite => {
+            buildkite_annotate(summary, badge::crappy_count(entries, threshold) > 0)
+        }
+        Provider::GitlabCi | Provider::Jenkins => Ok(()),
+    };
+    if let Err(err) = result {
+        eprintln!("warning: {}: {err:#}", provider.label());
+    }
+}
+
+/// The lines changed since `base`, or since the pull request's base branch
+/// when it's empty. Runs in the project dir, like the analysis.
+fn pr_changed_lines(base: &str) -> Result<ci::ChangedLines> {
+    let base = match base {
+        "" => match std::env::var("GITHUB_BASE_REF") {
+            Ok(branch) if !branch.is_empty() => format!("origin/{branch}"),
+            _ => bail!("--annotate-changed-only needs a REF outside pull request builds"),
+        },
+        base => base.to_string(),
+    };
+    ci::changed_lines(Path::new("."), &base)
+}
+
+fn append_to(path: &Path, text: &str) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{text}").with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// `buildkite-agent annotate` the build with `markdown`, styled as a warning
+/// when there are crappy functions.
+fn buildkite_annotate(markdown: &str, crappy: bool) -> Result<()> {
+    let style = if crappy { "warning" } else { "info" };
+    let mut child = Command::new("buildkite-agent")
+        .args(["annotate", "--style", style, "--context", "craprs"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("failed to run buildkite-agent")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(markdown.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("buildkite-agent annotate failed ({status})");
+    }
+    Ok(())
+}
+
+/// Default `--max-entries`: a hundred thousand functions already make a
+/// text report nobody reads and an HTML page of hundreds of megabytes.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Fail when the report would list more than `max` entries (0 = no limit).
+fn check_entry_limit(entries: usize, max: usize) -> Result<()> {
+    if max == 0 || entries <= max {
+        return Ok(());
+    }
+    bail!(
+        "the report would list {entries} functions, more than --max-entries {max}\n\
+         hint: narrow it with --top, --min-crap, --min-cc, --select or a module filter\n\
+         hint: pass --max-entries 0 to write it anyway"
+    );
+}
+
+/// Metadata for the run `args` describe, gathered from the project dir.
+fn run_metadata(args: &AnalysisArgs) -> RunMetadata {
+    let mut metadata = RunMetadata::new();
+    metadata.git_commit = std::env::current_dir()
+        .ok()
+        .and_then(|dir| metadata::git_commit(&dir));
+    match args.coverage_format {
+        CoverageFormat::Exec(ref command) => {
+            metadata.coverage_tool = Some(format!("exec:{command}"));
+        }
+        CoverageFormat::Cobertura(_) => metadata.coverage_tool = Some("cobertura".to_string()),
+        CoverageFormat::Coveralls(_) => metadata.coverage_tool = Some("coveralls".to_string()),
+        CoverageFormat::Lcov if !args.skip_coverage => {
+            let tool = &args.coverage_tool;
+            metadata.coverage_tool = Some(tool.label().to_string());
+            // The host's tool says nothing about the one in the image.
+            if args.coverage_in_docker.is_none() {
+                metadata.coverage_tool_version =
+                    metadata::tool_version(tool.program(), tool.version_args());
+            }
+        }
+        CoverageFormat::Lcov => {}
+    }
+    metadata.flags = analysis_flags(args);
+    metadata
+}
+
+/// The options in `args` that change what is scored or how, in the form
+/// they're given on the command line. Defaults are left out.
+fn analysis_flags(args: &AnalysisArgs) -> Vec<String> {
+    let value = |v: Option<clap::builder::PossibleValue>| {
+        v.map(|p| p.get_name().to_string()).unwrap_or_default()
+    };
+    let mut flags = Vec::new();
+    if args.score != ScoreKind::Crap {
+        flags.push(format!("--score {}", value(args.score.to_possible_value())));
+    }
+    if args.try_weight != 1 {
+        flags.push(format!("--try-weight {}", args.try_weight));
+    }
+    if args.trivial != TrivialMode::Show {
+        flags.push(format!(
+            "--trivial {}",
+            value(args.trivial.to_possible_value())
+        ));
+    }
+    for (set, flag) in [
+        (args.weigh_patterns, "--weigh-patterns"),
+        (args.skip_macro_prologue, "--skip-macro-prologue"),
+        (args.expand_macros, "--expand-macros"),
+        (args.include_uninstrumented, "--include-uninstrumented"),
+        (args.include_vendored, "--include-vendored"),
+        (args.respect_gitignore, "--respect-gitignore"),
+        (args.skip_coverage, "--skip-coverage"),
+        (args.use_partial_coverage, "--use-partial-coverage"),
+    ] {
+        if set {
+            flags.push(flag.to_string());
+        }
+    }
+    if let Some(ref image) = args.coverage_in_docker {
+        flags.push(format!("--coverage-in-docker {image}"));
+    }
+    for package in &args.packages {
+        flags.push(format!("--package {package}"));
+    }
+    flags
+}
+
+/// The readable source files of `entries`, in path order, for the HTML
+/// heatmap. Files that can't be read are left out rather than failing the report.
+fn html_sources(
+    entries: &[CrapEntry],
+    line_coverage: &HashMap<PathBuf, LineCoverage>,
+) -> Vec<html_export::SourceFile> {
+    let files: BTreeSet<&PathBuf> = entries.iter().map(|e| &e.file).collect();
+    files
+        .into_iter()
+        .filter_map(|path| {
+            Some(html_export::SourceFile {
+                path: path.clone(),
+                content: std::fs::read_to_string(path).ok()?,
+                coverage: line_coverage.get(path).cloned(),
+            })
+        })
+        .collect()
+}
+
+/// Write the report to `path`, creating missing parent directories.
+fn write_output(path: &Path, report: &[u8]) -> Result<()> {
+    let mut file = open_output(Some(path))?;
+    file.write_all(report)?;
+    Ok(file.flush()?)
+}
+
+/// A buffered writer to `path`, creating missing parent directories, or to
+/// stdout without one.
+fn open_output(path: Option<&Path>) -> Result<BufWriter<Box<dyn Write>>> {
+    let Some(path) = path else {
+        return Ok(BufWriter::new(Box::new(std::io::stdout().lock())));
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(BufWriter::new(Box::new(file)))
+}
+
+/// Analyze every Cargo project under `root`, each labelled by its path
+/// relative to `root` (or its directory name when `root` is itself a project).
+fn scan_workspaces(root: &Path, args: &AnalysisArgs) -> Result<Vec<(String, Analysis)>> {
+    // `analyze` changes the working directory, so pin everything to absolute paths first.
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", root.display()))?;
+    let dirs = workspace::discover_workspaces(&root)?;
+    if dirs.is_empty() {
+        bail!("no Cargo.toml found under {}", root.display());
+    }
+    let mut runs = Vec::new();
+    let mut empty = 0;
+    for dir in dirs {
+        let label = match dir.strip_prefix(&root) {
+            Ok(rel) if !rel.as_os_str().is_empty() => rel.display().to_string(),
+            _ => dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| dir.display().to_string()),
+        };
+        let mut ws_args = args.clone();
+        ws_args.project_dir = Some(dir);
+        ws_args.skip_empty = true;
+        let analysis =
+            analyze(&ws_args).with_context(|| format!("failed to analyze workspace {label}"))?;
+        // One empty project shouldn't sink the scan of all the others.
+        if let Some(ref err) = analysis.empty {
+            eprintln!("warning: skipping workspace {label}: {err}");
+            empty += 1;
+            continue;
+        }
+        runs.push((label, analysis));
+    }
+    if runs.is_empty() {
+        bail!(
+            "all {empty} workspace(s) under {} came up empty\n\
+             hint: pass --allow-empty to print empty reports anyway",
+            root.display()
+        );
+    }
+    Ok(runs)
+}
+
+/// All section entries in one CRAP-sorted list. Entries from labelled
+/// sections (`--scan-root`) get the label as a module path prefix so they
+/// stay distinguishable in flat outputs.
+pub fn flatten_sections(sections: &[(String, Vec<CrapEntry>)]) -> Vec<CrapEntry> {
+    let mut entries = Vec::new();
+    for (label, section) in sections {
+        for e in section {
+            let mut e = e.clone();
+            if !label.is_empty() {
+                e.module_path = if e.module_path.is_empty() {
+                    label.clone()
+                } else {
+                    format!("{label}::{}", e.module_path)
+                };
+            }
+            entries.push(e);
+        }
+    }
+    if sections.len() > 1 {
+        crap::sort_entries(&mut entries);
+    }
+    entries
+}
+
+fn run_badge(args: &BadgeArgs) -> Result<()> {
+    let analysis = analyze(&args.analysis)?;
+    if let Some(ref path) = args.svg {
+        let svg = badge::render_svg(&analysis.entries, args.threshold, args.metric);
+        write_output(path, svg.as_bytes())?;
+        println!("wrote {}", path.display());
+        return Ok(());
+    }
+    if let Some(ref dir) = args.per_module {
+        let badges = badge::render_module_svgs(&analysis.entries, args.threshold, args.metric);
+        for (module, svg) in badges {
+            let path = dir.join(format!("{module}.svg"));
+            write_output(&path, svg.as_bytes())?;
+            println!("wrote {}", path.display());
+        }
+        return Ok(());
+    }
+    let snippet = badge::readme_snippet(&analysis.entries, args.threshold, args.metric);
+
+    if !args.update_readme {
+        println!("{snippet}");
+        return Ok(());
+    }
+
+    let readme = std::fs::read_to_string(&args.readme)
+        .with_context(|| format!("failed to read {}", args.readme.display()))?;
+    let updated = badge::update_readme(&readme, &snippet)
+        .with_context(|| format!("failed to update {}", args.readme.display()))?;
+    if updated != readme {
+        std::fs::write(&args.readme, updated)
+            .with_context(|| format!("failed to write {}", args.readme.display()))?;
+    }
+    println!("updated {}: {snippet}", args.readme.display());
+    Ok(())
+}
+
+/// Score the project once per coverage tool and report where they disagree.
+/// Each tool's lcov output is kept under `target/craprs/compare/` so
+/// `--skip-coverage` can re-score without re-running both.
+fn run_compare_tools(args: &CompareToolsArgs) -> Result<()> {
+    let analysis = &args.analysis;
+    let resolved = enter_project(analysis)?;
+    let dir = Path::new("target/craprs/compare");
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let mut runs = Vec::new();
+    for tool in [CoverageTool::Tarpaulin, CoverageTool::LlvmCov] {
+        let saved = dir.join(format!("{}.info", tool.label()));
+        if !analysis.skip_coverage {
+            delete_stale_coverage();
+            run_coverage(&tool, resolved.is_workspace, analysis)?;
+            std::fs::rename("lcov.info", &saved)
+                .with_context(|| format!("failed to move lcov.info to {}", saved.display()))?;
+        }
+        let lcov_content = std::fs::read_to_string(&saved).with_context(|| {
+            format!("failed to read {} — run without --skip-coverage first", saved.display())
+        })?;
+        let file_coverage = coverage::parse_lcov(&lcov_content);
+        let records = LcovRecords {
+            sources: if analysis.skip_coverage {
+                coverage_time_sources(&saved)
+            } else {
+                HashMap::new()
+            },
+            ..LcovRecords::default()
+        };
+        let run = score_targets(analysis, &resolved, &file_coverage, &records)?;
+        if !analysis.skip_coverage {
+            record_sources(&saved, &run.sources)?;
+        } else {
+            check_freshness(
+                &saved,
+                &run.sources,
+                analysis.strict_freshness || analysis.strict,
+            )?;
+        }
+        runs.push(run.entries);
+    }
+
+    let found = discrepancy::discrepancies(&runs[0], &runs[1], args.min_delta);
+    print!(
+        "{}",
+        discrepancy::format_discrepancies(
+            &found,
+            "tarpaulin",
+            "llvm-cov",
+            args.min_delta,
+            runs[0].len()
+        )
+    );
+    Ok(())
+}
+
+/// Enter the project dir, run coverage (unless skipped), and score every function
+/// in the resolved targets. Entries come back sorted by CRAP.
+fn analyze(args: &AnalysisArgs) -> Result<Analysis> {
+    let resolved = enter_project(args)?;
+
+    let runs_tool = args.coverage_format.runs_tool();
+    if !runs_tool
+        && (args.coverage_in_docker.is_some() || args.coverage_tool == CoverageTool::Bazel)
+    {
+        bail!(
+            "--coverage-format cobertura, coveralls and exec run no coverage tool, so they can't be \
+             combined with --coverage-in-docker or --coverage-tool bazel"
+        );
+    }
+
+    let mut partial = false;
+    if !args.skip_coverage && runs_tool {
+        delete_stale_coverage();
+        let result = run_coverage(&args.coverage_tool, resolved.is_workspace, args);
+        if let Err(err) = result {
+            if !Path::new("lcov.info").exists() {
+                return Err(err);
+            }
+            if !args.use_partial_coverage {
+                return Err(err.context(
+                    "a partial lcov.info was written — pass --use-partial-coverage to score it anyway",
+                ));
+            }
+            eprintln!(
+                "warning: {err:#}\n\
+                 warning: continuing with partial lcov.info (--use-partial-coverage); \
+                 coverage and CRAP scores below may be incomplete"
+            );
+            partial = true;
+        }
+    }
+
+    let mut lcov = LcovRecords::default();
+    let mut file_coverage = match args.coverage_format {
+        CoverageFormat::Exec(ref command) => run_converter(command)?,
+        CoverageFormat::Cobertura(ref path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            coverage::parse_cobertura(&content)
+                .map_err(|err| anyhow!("{} is not a Cobertura report: {err}", path.display()))?
+        }
+        CoverageFormat::Coveralls(ref path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            coverage::parse_coveralls(&content)
+                .map_err(|err| anyhow!("{} is not a Coveralls report: {err}", path.display()))?
+        }
+        CoverageFormat::Lcov => {
+            let lcov_content = std::fs::read_to_string("lcov.info")
+                .context("failed to read lcov.info — did coverage run succeed?")?;
+            let duplicates = coverage::duplicate_records(&lcov_content);
+            if !duplicates.is_empty() {
+                data_warning(
+                    args.strict,
+                    format!(
+                        "{} file(s) recorded more than once in lcov.info, hits summed:\n  {}",
+                        duplicates.len(),
+                        duplicates.join("\n  ")
+                    ),
+                )?;
+            }
+            lcov.branches = coverage::parse_lcov_branches(&lcov_content);
+            lcov.functions = coverage::parse_lcov_functions(&lcov_content);
+            if args.skip_coverage {
+                lcov.sources = coverage_time_sources(Path::new("lcov.info"));
+            }
+            coverage::parse_lcov(&lcov_content)
+        }
+    };
+    if args.coverage_tool == CoverageTool::Bazel {
+        let cwd = std::env::current_dir()?;
+        let Some(root) = workspace::bazel_root(&cwd) else {
+            bail!(
+                "no MODULE.bazel or WORKSPACE file in {} or above — needed to map Bazel's \
+                 coverage paths",
+                cwd.display()
+            );
+        };
+        file_coverage = coverage::remap_bazel_paths(file_coverage, &root);
+        lcov.branches = coverage::remap_bazel_paths(lcov.branches, &root);
+        lcov.functions = coverage::remap_bazel_paths(lcov.functions, &root);
+    }
+    if args.coverage_in_docker.is_some() {
+        let host_dir = std::env::current_dir()?;
+        file_coverage = coverage::remap_prefix(file_coverage, CONTAINER_PROJECT_DIR, &host_dir);
+        lcov.branches = coverage::remap_prefix(lcov.branches, CONTAINER_PROJECT_DIR, &host_dir);
+        lcov.functions = coverage::remap_prefix(lcov.functions, CONTAINER_PROJECT_DIR, &host_dir);
+    }
+    let missing = coverage::missing_files(&file_coverage);
+    if !missing.is_empty() {
+        data_warning(
+            args.strict,
+            format!(
+                "coverage recorded for {} file(s) that don't exist:\n  {}",
+                missing.len(),
+                missing.join("\n  ")
+            ),
+        )?;
+    }
+    let mut analysis = score_targets(args, &resolved, &file_coverage, &lcov)?;
+    if !args.skip_coverage && runs_tool {
+        record_sources(Path::new("lcov.info"), &analysis.sources)?;
+    }
+    if args.strict && !analysis.span_mismatches.is_empty() {
+        let msg = coverage::span_mismatch_message(&analysis.span_mismatches);
+        data_warning(true, msg.trim_end().to_string())?;
+    }
+    if !args.allow_empty {
+        let source = match args.coverage_format {
+            CoverageFormat::Lcov => "lcov.info".to_string(),
+            CoverageFormat::Cobertura(ref path) | CoverageFormat::Coveralls(ref path) => {
+                path.display().to_string()
+            }
+            CoverageFormat::Exec(ref command) => format!("`{command}`"),
+        };
+        if let Err(err) = check_not_empty(&analysis, &file_coverage, &args.module_filters, &source)
+        {
+            if !args.skip_empty {
+                return Err(err);
+            }
+            analysis.empty = Some(err);
+            return Ok(analysis);
+        }
+    }
+    if args.expand_macros {
+        analysis.expansions = expand_macros(args, &resolved)?;
+    }
+    match args.coverage_format {
+        CoverageFormat::Lcov if args.skip_coverage => {
+            check_freshness(
+                Path::new("lcov.info"),
+                &analysis.sources,
+                args.strict_freshness || args.strict,
+            )?;
+        }
+        CoverageFormat::Cobertura(ref path) | CoverageFormat::Coveralls(ref path) => {
+            check_freshness(
+                path,
+                &analysis.sources,
+                args.strict_freshness || args.strict,
+            )?;
+        }
+        _ => {}
+    }
+    if partial && !analysis.uninstrumented_files.is_empty() {
+        eprintln!(
+            "warning: {} source file(s) missing from the partial coverage data:",
+            analysis.uninstrumented_files.len()
+        );
+        for path in &analysis.uninstrumented_files {
+            eprintln!("  {}", path.display());
+        }
+    }
+    Ok(analysis)
+}
+
+/// A data-quality problem: printed as a warning, or with `--strict` an error.
+fn data_warning(strict: bool, msg: String) -> Result<()> {
+    if strict {
+        bail!("{msg}\nhint: --strict turns data-quality warnings into errors");
+    }
+    eprintln!("warning: {msg}");
+    Ok(())
+}
+
+/// Fail when the analysis came up empty: no functions in the source dirs, or
+/// coverage data that matches none of them. Either almost always means a
+/// wrong `--src`, filter or path mapping rather than a project with nothing
+/// to report, so say which and where to look.
+fn check_not_empty(
+    analysis: &Analysis,
+    file_coverage: &HashMap<String, LineCoverage>,
+    module_filters: &[String],
+    coverage_source: &str,
+) -> Result<()> {
+    if analysis.functions == 0 {
+        let filters = match module_filters {
+            [] => String::new(),
+            filters => format!(" matching `{}`", filters.join("`, `")),
+        };
+        bail!(
+            "no functions found in {} source file(s){filters}\n\
+             hint: check --src (the source dir of each crate, default `src`), --package and \
+             the module filters\n\
+             hint: pass --allow-empty to print an empty report anyway",
+            analysis.sources.len()
+        );
+    }
+    if analysis.line_coverage.is_empty() {
+        let found = match file_coverage.keys().min() {
+            Some(path) => format!(
+                "{coverage_source} has coverage for {} file(s), e.g. {path}, but none of them match",
+                file_coverage.len()
+            ),
+            None => format!("{coverage_source} has no coverage records for"),
+        };
+        bail!(
+            "{found} the {} analyzed source file(s)\n\
+             hint: coverage paths must resolve to the sources relative to the project dir; \
+             with coverage from a container or another checkout, use --coverage-in-docker or \
+             re-run without --skip-coverage\n\
+             hint: pass --allow-empty to print an empty report anyway",
+            analysis.sources.len()
+        );
+    }
+    Ok(())
+}
+
+/// The file next to coverage data `lcov` that records the content hashes of
+/// the sources it was generated for.
+fn hashes_path(lcov: &Path) -> PathBuf {
+    let mut path = lcov.as_os_str().to_owned();
+    path.push(".hashes");
+    PathBuf::from(path)
+}
+
+/// The directory next to coverage data `lcov` that keeps a copy of each
+/// source it was generated for, named by its content hash.
+fn snapshots_path(lcov: &Path) -> PathBuf {
+    let mut path = lcov.as_os_str().to_owned();
+    path.push(".sources");
+    PathBuf::from(path)
+}
+
+/// Record the content hash of each of `sources` next to freshly generated
+/// `lcov`, one `<hash> <path>` line per file, for [`stale_sources`], and keep
+/// a copy of their content for [`coverage_time_sources`].
+fn record_sources(lcov: &Path, sources: &[PathBuf]) -> Result<()> {
+    let snapshots = snapshots_path(lcov);
+    let _ = std::fs::remove_dir_all(&snapshots);
+    let mut hashes = String::new();
+    for path in sources {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            let hash = baseline::content_hash(&content);
+            let snapshot = snapshots.join(&hash);
+            if !snapshot.exists() {
+                write_output(&snapshot, content.as_bytes())?;
+            }
+            hashes.push_str(&format!("{hash} {}\n", path.display()));
+        }
+    }
+    write_output(&hashes_path(lcov), hashes.as_bytes())
+}
+
+/// The content hash of each source recorded next to `lcov`, by path.
+fn recorded_hashes(lcov: &Path) -> HashMap<PathBuf, String> {
+    std::fs::read_to_string(hashes_path(lcov))
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .map(|(hash, path)| (PathBuf::from(path), hash.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The sources recorded next to `lcov` that changed since, as they were
+/// when it was written, so [`score_targets`] can map its line numbers
+/// through the edits. Files without a kept copy are left out.
+fn coverage_time_sources(lcov: &Path) -> HashMap<PathBuf, String> {
+    recorded_hashes(lcov)
+        .into_iter()
+        .filter(|(path, hash)| {
+            std::fs::read_to_string(path).is_ok_and(|now| baseline::content_hash(&now) != *hash)
+        })
+        .filter_map(|(path, hash)| {
+            let then = std::fs::read_to_string(snapshots_path(lcov).join(hash)).ok()?;
+            Some((path, then))
+        })
+        .collect()
+}
+
+/// Analyzed sources changed since `lcov` was written: those whose content no
+/// longer matches the hash recorded next to it and, for files without one
+/// (coverage from another tool or an older craprs), those modified after it.
+/// Files that can't be read are ignored.
+fn stale_sources(lcov: &Path, sources: &[PathBuf]) -> Vec<PathBuf> {
+    let recorded = recorded_hashes(lcov);
+    let written = std::fs::metadata(lcov).and_then(|m| m.modified()).ok();
+    sources
+        .iter()
+        .filter(|path| match recorded.get(*path) {
+            Some(hash) => std::fs::read_to_string(path)
+                .is_ok_and(|content| baseline::content_hash(&content) != *hash),
+            None => written.is_some_and(|written| {
+                std::fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|modified| modified > written)
+            }),
+        })
+        .cloned()
+        .collect()
+}
+
+/// Warn (or fail with `strict`) when reused coverage data is older than the
+/// sources it is scored against — stale line numbers silently land hits on
+/// the wrong functions.
+fn check_freshness(lcov: &Path, sources: &[PathBuf], strict: bool) -> Result<()> {
+    let stale = stale_sources(lcov, sources);
+    if stale.is_empty() {
+        return Ok(());
+    }
+    let mut msg = format!(
+        "{} source file(s) changed after {} was written; coverage may be mapped to the wrong lines:",
+        stale.len(),
+        lcov.display()
+    );
+    let mapped = coverage_time_sources(lcov);
+    for path in &stale {
+        msg.push_str(&format!("\n  {}", path.display()));
+        if mapped.contains_key(path) {
+            msg.push_str(" (line numbers mapped through the edits)");
+        }
+    }
+    if strict {
+        bail!("{msg}\nre-run without --skip-coverage to regenerate it");
+    }
+    eprintln!("warning: {msg}");
+    Ok(())
+}
+
+/// `cd` into `--project-dir` (if given) and resolve the analysis targets there.
+fn enter_project(args: &AnalysisArgs) -> Result<workspace::ResolvedWorkspace> {
+    if let Some(ref dir) = args.project_dir {
+        std::env::set_current_dir(dir)
+            .with_context(|| format!("failed to cd into {}", dir.display()))?;
+        if !Path::new("Cargo.toml").exists() {
+            if Path::new("../Cargo.toml").exists()
+                && let Some(parent) = dir.parent()
+            {
+                bail!(
+                    "no Cargo.toml in {} — did you mean {}?",
+                    dir.display(),
+                    parent.display()
+                );
+            }
+            bail!("no Cargo.toml found in {}", dir.display());
+        }
+    }
+
+    workspace::resolve_targets(Path::new("."), &args.src, &args.packages)
+}
+
+/// The project's `craprs.toml`, or an empty policy without one. Runs after
+/// [`enter_project`], so the file is looked up in the project dir.
+fn project_policy() -> Result<Policy> {
+    let path = Path::new(policy::PROJECT_POLICY);
+    if !path.is_file() {
+        return Ok(Policy::default());
+    }
+    Policy::load(&path.to_string_lossy())
+}
+
+/// Extract and score every function in `resolved` against parsed lcov data,
+/// with branch coverage and function records from `lcov` where the report
+/// had any.
+fn score_targets(
+    args: &AnalysisArgs,
+    resolved: &workspace::ResolvedWorkspace,
+    file_coverage: &HashMap<String, LineCoverage>,
+    lcov: &LcovRecords,
+) -> Result<Analysis> {
+    let mut all_entries = Vec::new();
+    let mut test_only_entries = Vec::new();
+    let mut trivial_entries = Vec::new();
+    let mut uninstrumented_files = Vec::new();
+    let mut analyzed = Vec::new();
+    let mut module_lines = ModuleLines::default();
+    let mut line_coverage = HashMap::new();
+    let mut span_mismatches = Vec::new();
+    let mut functions = 0;
+    let rules = complexity::ComplexityOptions {
+        try_weight: args.try_weight,
+        weigh_patterns: args.weigh_patterns,
+    };
+    let discovery = Discovery::from_args(args);
+    let project = project_policy()?;
+    let module_rules = project.module_rules();
+    let tag_rules = project.tag_rules();
+    let listing = match args.public_api {
+        Some(ref path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let listing = public_api::ApiListing::parse(&content);
+            if listing.is_empty() {
+                data_warning(
+                    args.strict,
+                    format!(
+                        "{} lists no public functions; is it `cargo public-api` output?",
+                        path.display()
+                    ),
+                )?;
+            }
+            Some(listing)
+        }
+        None => None,
+    };
+    for target in &resolved.targets {
+        let sources = find_rust_sources(&target.src_dir, &discovery)?;
+        let public_files = match target.proc_macro || listing.is_some() {
+            true => HashSet::new(),
+            false => public_api::public_files(&target.src_dir),
+        };
+        // Test-only detection needs references from the whole crate, not just
+        // the files that pass the module filters.
+        let mut contents = HashMap::new();
+        for source_path in &sources {
+            let source = std::fs::read_to_string(source_path)
+                .with_context(|| format!("failed to read {}", source_path.display()))?;
+            contents.insert(source_path.clone(), source);
+        }
+        let test_files = test_only::test_files(&contents);
+        let mut refs = test_only::References::default();
+        for (source_path, source) in &contents {
+            refs.add_source(source, test_files.contains(source_path));
+        }
+        let sources = filter_sources(sources, &args.module_filters);
+
+        for source_path in &sources {
+            let source = &contents[source_path];
+            analyzed.push(source_path.clone());
+            let fns = match complexity::try_extract_functions_with(source, rules) {
+                Ok(fns) => fns,
+                Err(err) => {
+                    let line = err.span().start().line;
+                    data_warning(
+                        args.strict,
+                        format!(
+                            "{} is not valid Rust (line {line}: {err}); its functions are \
+                             left out",
+                            source_path.display()
+                        ),
+                    )?;
+                    continue;
+                }
+            };
+            if fns.is_empty() {
+                continue;
+            }
+            functions += fns.len();
+            let module_path = match module_map::map_module(&module_rules, source_path) {
+                Some(mapped) => mapped,
+                None => {
+                    let module_path = target.module_path(source_path);
+                    match &target.crate_name {
+                        Some(name) if !module_path.is_empty() => format!("{name}::{module_path}"),
+                        Some(name) => name.clone(),
+                        None => module_path,
+                    }
+                }
+            };
+            let line_cov = coverage::find_coverage_for_file(source_path, file_coverage);
+            let branches = match lcov.branches.is_empty() {
+                true => None,
+                false => coverage::find_coverage_for_file(source_path, &lcov.branches),
+            };
+            let records = match lcov.functions.is_empty() {
+                true => None,
+                false => coverage::find_coverage_for_file(source_path, &lcov.functions),
+            };
+            // Reused coverage from before the file was edited: move its line
+            // numbers to where those lines are now, dropping edited ones.
+            let (line_cov, branches, records) = match lcov.sources.get(source_path) {
+                Some(then) => {
+                    let mapping = coverage::line_mapping(then, source);
+                    (
+                        line_cov.map(|lc| coverage::remap_lines(&lc, &mapping)),
+                        branches.map(|b| coverage::remap_branches(&b, &mapping)),
+                        records.map(|r| coverage::remap_functions(&r, &mapping)),
+                    )
+                }
+                None => (line_cov, branches, records),
             };
-            let line_cov = find_coverage_for_file(source_path, &file_coverage);
+            if let Some(ref lc) = line_cov {
+                module_lines.add_file(&module_path, lc);
+                line_coverage.insert(source_path.clone(), lc.clone());
+            }
 
             if line_cov.is_none() {
-                uninstrumented_files += 1;
-                if !cli.include_uninstrumented {
+                uninstrumented_files.push(source_path.clone());
+                if !args.include_uninstrumented {
                     continue;
                 }
             }
 
-            for f in &fns {
-                let (cov, score) = match &line_cov {
+            let file_hash = baseline::content_hash(source);
+            let code = coverage::code_lines(source);
+            // Functions in a test file are test code already, not helpers to flag.
+            let test_only_lines: HashSet<usize> = if test_files.contains(source_path) {
+                HashSet::new()
+            } else {
+                test_only::private_functions(source)
+                    .into_iter()
+                    .filter(|(name, _)| refs.test_only(name))
+                    .map(|(_, line)| line)
+                    .collect()
+            };
+
+            let starts: Vec<(&str, usize)> = fns
+                .iter()
+                .map(|f| (f.name.as_str(), f.start_line))
+                .collect();
+            let matched = match records {
+                Some(ref records) => coverage::match_functions(records, &starts),
+                None => vec![None; fns.len()],
+            };
+
+            for (f, record) in fns.iter().zip(matched) {
+                if f.trivial && args.trivial == TrivialMode::Hide {
+                    continue;
+                }
+                let cov_start = match f.wrapper {
+                    Some(_) if args.skip_macro_prologue => f.body_line,
+                    _ => f.start_line,
+                };
+                // Coverage recorded the function `shift` lines away from where
+                // it is now when the file was edited since. Wrapper macros'
+                // records start at the attribute, so they aren't moved.
+                let shift = match (record, &f.wrapper) {
+                    (Some(r), None) => r.line as isize - f.start_line as isize,
+                    _ => 0,
+                };
+                let line_cov = match (&line_cov, shift) {
+                    (Some(lc), 0) => Some(Cow::Borrowed(lc)),
+                    (Some(lc), _) => Some(Cow::Owned(coverage::shift_lines(
+                        lc, cov_start, f.end_line, shift,
+                    ))),
+                    (None, _) => None,
+                };
+                let (cov, score, uncovered) = match &line_cov {
+                    // Never entered: any hits in its lines belong to other code.
+                    Some(lc) if record.is_some_and(|r| r.hits == 0) => {
+                        let lines: Vec<usize> = (cov_start..=f.end_line)
+                            .filter(|ln| lc.contains_key(ln))
+                            .collect();
+                        (Some(0.0), args.score.score(f.complexity, Some(0.0)), lines)
+                    }
                     Some(lc) => {
-                        let c = coverage::coverage_for_range(lc, f.start_line, f.end_line);
-                        (Some(c), crap::crap_score(f.complexity, Some(c)))
+                        let c = coverage::coverage_for_range(lc, cov_start, f.end_line);
+                        let uncovered = coverage::uncovered_lines(lc, cov_start, f.end_line);
+                        (Some(c), args.score.score(f.complexity, Some(c)), uncovered)
                     }
-                    None => (None, None),
+                    None => (None, None, Vec::new()),
+                };
+                let branches = match (&branches, shift) {
+                    (Some(b), 0) => Some(Cow::Borrowed(b)),
+                    (Some(b), _) => Some(Cow::Owned(coverage::shift_branches(
+                        b, cov_start, f.end_line, shift,
+                    ))),
+                    (None, _) => None,
                 };
-                all_entries.push(CrapEntry {
+                let entry = CrapEntry {
                     name: f.name.clone(),
                     module_path: module_path.clone(),
                     complexity: f.complexity,
                     coverage: cov,
                     crap: score,
-                });
+                    uncovered_lines: uncovered,
+                    hints: hints::refactoring_hints(&f.shape),
+                    decision_points: f.decision_points.clone(),
+                    file: source_path.clone(),
+                    start_line: f.start_line,
+                    end_line: f.end_line,
+                    file_hash: file_hash.clone(),
+                    fingerprint: f.fingerprint.clone(),
+                    dyn_calls: f.dyn_calls,
+                    branch_coverage: branches.as_ref().and_then(|b| {
+                        coverage::branch_coverage_for_range(b, cov_start, f.end_line)
+                    }),
+                    tags: tags::tags_for(&tag_rules, source_path, &f.tags),
+                    public: match listing {
+                        Some(ref listing) => f.public && listing.contains(&f.name),
+                        None => f.public && !f.in_private_mod && public_files.contains(source_path),
+                    },
+                };
+                if let Some(ref lc) = line_cov
+                    && coverage::span_mismatch(lc, &code, cov_start, f.end_line)
+                {
+                    span_mismatches.push(entry.clone());
+                }
+                if test_only_lines.contains(&f.start_line) {
+                    test_only_entries.push(entry);
+                } else if f.trivial && args.trivial == TrivialMode::Demote {
+                    trivial_entries.push(entry);
+                } else {
+                    all_entries.push(entry);
+                }
             }
         }
     }
 
     crap::sort_entries(&mut all_entries);
-    let filtered = apply_filters(all_entries, cli.min_crap, cli.top);
-    print!("{}", crap::format_report(&filtered));
+    crap::sort_entries(&mut trivial_entries);
+    all_entries.append(&mut trivial_entries);
+    crap::sort_entries(&mut test_only_entries);
+    Ok(Analysis {
+        entries: all_entries,
+        functions,
+        uninstrumented_files,
+        sources: analyzed,
+        test_only: test_only_entries,
+        expansions: Vec::new(),
+        module_lines,
+        line_coverage,
+        span_mismatches,
+        empty: None,
+    })
+}
 
-    if uninstrumented_files > 0 && !cli.include_uninstrumented {
-        println!(
-            "note: {uninstrumented_files} source file(s) had no coverage data (not reached by the \
-             executed test set). Pass --include-uninstrumented to list them."
-        );
+/// `cargo expand` every member that depends on a proc-macro member and
+/// attribute the functions that appear only in the expansion to the macros
+/// that generated them.
+fn expand_macros(
+    args: &AnalysisArgs,
+    resolved: &workspace::ResolvedWorkspace,
+) -> Result<Vec<Expansion>> {
+    let rules = complexity::ComplexityOptions {
+        try_weight: args.try_weight,
+        weigh_patterns: args.weigh_patterns,
+    };
+    let discovery = Discovery::from_args(args);
+    let mut macro_crates = Vec::new();
+    // Every macro the proc-macro members define, by name.
+    let mut macros = HashMap::new();
+    for target in resolved.targets.iter().filter(|t| t.proc_macro) {
+        let Some(ref name) = target.crate_name else {
+            continue;
+        };
+        macro_crates.push(name.as_str());
+        for path in find_rust_sources(&target.src_dir, &discovery)? {
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            match syn::parse_file(&source) {
+                Ok(syntax) => {
+                    for defined in expand::defined_macros(&syntax) {
+                        macros.insert(defined.clone(), format!("{name}::{defined}"));
+                    }
+                }
+                Err(err) => data_warning(
+                    args.strict,
+                    format!(
+                        "{} is not valid Rust (line {}: {err}); code from its macros is \
+                         attributed to {name} as a whole",
+                        path.display(),
+                        err.span().start().line
+                    ),
+                )?,
+            }
+        }
     }
-
-    Ok(())
+    let mut expansions = Vec::new();
+    'targets: for target in resolved.targets.iter().filter(|t| !t.proc_macro) {
+        let used: Vec<&str> = macro_crates
+            .iter()
+            .copied()
+            .filter(|m| target.dependencies.iter().any(|d| d == m))
+            .collect();
+        let (Some(package), Some(consumer)) = (&target.package, &target.crate_name) else {
+            continue;
+        };
+        if used.is_empty() {
+            continue;
+        }
+        let mut cmd = Command::new("cargo");
+        cmd.args(["expand", "--package", package]);
+        if target.src_dir.join("lib.rs").is_file() {
+            cmd.arg("--lib");
+        }
+        let output = cmd.output().context("failed to run cargo expand")?;
+        if !output.status.success() {
+            bail!(
+                "cargo expand failed for {package} (is cargo-expand installed?):\n{}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+        // Without every source file, hand-written functions would look generated.
+        let mut written = Vec::new();
+        let mut uses: HashMap<String, Vec<String>> = HashMap::new();
+        for path in find_rust_sources(&target.src_dir, &discovery)? {
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let parsed = complexity::try_extract_functions_with(&source, rules)
+                .and_then(|functions| Ok((functions, syn::parse_file(&source)?)));
+            match parsed {
+                Ok((functions, syntax)) => {
+                    for (item, applied) in expand::macro_uses(&syntax, &macros) {
+                        let entry = uses.entry(item).or_default();
+                        for m in applied {
+                            if !entry.contains(&m) {
+                                entry.push(m);
+                            }
+                        }
+                    }
+                    written.extend(functions);
+                }
+                Err(err) => {
+                    data_warning(
+                        args.strict,
+                        format!(
+                            "{} is not valid Rust (line {}: {err}); code generated in \
+                             {consumer} is left out",
+                            path.display(),
+                            err.span().start().line
+                        ),
+                    )?;
+                    continue 'targets;
+                }
+            }
+        }
+        let expanded = String::from_utf8_lossy(&output.stdout);
+        let expanded = match complexity::try_extract_functions_with(&expanded, rules) {
+            Ok(functions) => functions,
+            Err(err) => {
+                data_warning(
+                    args.strict,
+                    format!(
+                        "cargo expand output for {package} is not valid Rust (line {}: {err}); \
+                         code generated in {consumer} is left out",
+                        err.span().start().line
+                    ),
+                )?;
+                continue;
+            }
+        };
+        let generated = expand::generated_functions(&written, expanded);
+        for (origin, functions) in expand::attribute(generated, &uses, &used.join("+")) {
+            expansions.push(Expansion {
+                origin,
+                consumer: consumer.clone(),
+                functions,
+            });
+        }
+    }
+    Ok(expansions)
 }
 
 fn delete_stale_coverage() {
     let _ = std::fs::remove_file("lcov.info");
+    let _ = std::fs::remove_file(hashes_path(Path::new("lcov.info")));
+    let _ = std::fs::remove_dir_all(snapshots_path(Path::new("lcov.info")));
+}
+
+/// Where the coverage tool's output goes, one file per tool and attempt.
+const COVERAGE_LOG_DIR: &str = "target/craprs/logs";
+/// Log lines repeated in the error when the coverage run fails.
+const LOG_TAIL_LINES: usize = 20;
+
+/// Run the coverage tool, retrying up to `--coverage-retries` times when it
+/// fails or exceeds `--coverage-timeout`. Its output is captured in
+/// [`COVERAGE_LOG_DIR`] and the tail is included in the error on failure.
+fn run_coverage(tool: &CoverageTool, is_workspace: bool, args: &AnalysisArgs) -> Result<()> {
+    let cmd_args = match tool {
+        CoverageTool::Bazel => bazel_coverage_args(&args.bazel_targets),
+        _ => coverage_args(tool, is_workspace, &args.packages, &args.coverage_ignore),
+    };
+    let container = format!("craprs-coverage-{}", std::process::id());
+    let (program, cmd_args) = match args.coverage_in_docker {
+        Some(_) if *tool == CoverageTool::Bazel => {
+            bail!("--coverage-in-docker can't be combined with --coverage-tool bazel")
+        }
+        Some(ref image) => {
+            let host_dir = std::env::current_dir()?;
+            let wrapped = docker_args(image, &container, &host_dir, tool.program(), cmd_args);
+            ("docker", wrapped)
+        }
+        None => (tool.program(), cmd_args),
+    };
+    let timeout = args.coverage_timeout.map(Duration::from_secs);
+    let attempts = args.coverage_retries + 1;
+    std::fs::create_dir_all(COVERAGE_LOG_DIR)
+        .with_context(|| format!("failed to create {COVERAGE_LOG_DIR}"))?;
+
+    for attempt in 1..=attempts {
+        let log_path = Path::new(COVERAGE_LOG_DIR).join(format!("{}-{attempt}.log", tool.label()));
+        let log = std::fs::File::create(&log_path)
+            .with_context(|| format!("failed to create {}", log_path.display()))?;
+        eprintln!(
+            "running {program} {} (output: {})",
+            cmd_args.join(" "),
+            log_path.display()
+        );
+        let mut cmd = Command::new(program);
+        cmd.args(&cmd_args).stdout(log.try_clone()?).stderr(log);
+        // Own process group, so a timeout also takes down the test binaries cargo spawned.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        let child = cmd
+            .spawn()
+            .with_context(|| format!("failed to run {program} {}", cmd_args.join(" ")))?;
+
+        let failure = match wait_with_timeout(child, timeout)? {
+            Some(status) if status.success() && *tool == CoverageTool::Bazel => {
+                return copy_bazel_report();
+            }
+            Some(status) if status.success() => return Ok(()),
+            Some(status) => format!("coverage command failed (exit {})", status.code().unwrap_or(-1)),
+            None => {
+                // Killing the docker client leaves the container running.
+                if args.coverage_in_docker.is_some() {
+                    let _ = Command::new("docker").args(["kill", &container]).output();
+                }
+                format!(
+                    "coverage command timed out after {}s",
+                    args.coverage_timeout.unwrap_or_default()
+                )
+            }
+        };
+        if attempt == attempts {
+            bail!(
+                "{failure}; last lines of {}:\n{}",
+                log_path.display(),
+                log_tail(&log_path, LOG_TAIL_LINES)
+            );
+        }
+        eprintln!(
+            "warning: {failure} (attempt {attempt}/{attempts}, see {}), retrying",
+            log_path.display()
+        );
+        delete_stale_coverage();
+    }
+    unreachable!("the last attempt returns or bails")
+}
+
+/// The last `lines` lines of a log file, or a note when it can't be read.
+fn log_tail(path: &Path, lines: usize) -> String {
+    let Ok(bytes) = std::fs::read(path) else {
+        return format!("(could not read {})", path.display());
+    };
+    let content = String::from_utf8_lossy(&bytes);
+    let all: Vec<&str> = content.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// Wait for `child`, killing its whole process group once `timeout` passes.
+/// `None` means it timed out.
+fn wait_with_timeout(mut child: Child, timeout: Option<Duration>) -> Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return Ok(Some(child.wait()?));
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill_tree(&mut child);
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
 }
 
-fn run_coverage(tool: &CoverageTool, is_workspace: bool, packages: &[String]) -> Result<()> {
-    let (program, mut args): (&str, Vec<String>) = match tool {
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) {
+    // A negative pid signals the process group started by `process_group(0)`.
+    let group = -(child.id() as libc::pid_t);
+    // SAFETY: kill(2) has no memory-safety preconditions.
+    if unsafe { libc::kill(group, libc::SIGKILL) } != 0 {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_tree(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Arguments for `cargo <tool>` that write `lcov.info` into the current dir.
+fn coverage_args(
+    tool: &CoverageTool,
+    is_workspace: bool,
+    packages: &[String],
+    ignore: &[String],
+) -> Vec<String> {
+    let (mut args, ignore_flag): (Vec<String>, &str) = match tool {
         CoverageTool::Tarpaulin => (
-            "cargo",
             vec![
                 "tarpaulin".into(),
                 "--out".into(),
@@ -164,16 +2793,18 @@ fn run_coverage(tool: &CoverageTool, is_workspace: bool, packages: &[String]) ->
                 "--output-dir".into(),
                 ".".into(),
             ],
+            "--exclude-files",
         ),
         CoverageTool::LlvmCov => (
-            "cargo",
             vec![
                 "llvm-cov".into(),
                 "--lcov".into(),
                 "--output-path".into(),
                 "lcov.info".into(),
             ],
+            "--ignore-filename-regex",
         ),
+        CoverageTool::Bazel => unreachable!("bazel runs with bazel_coverage_args"),
     };
 
     // Scope coverage to match analysis scope.
@@ -188,28 +2819,172 @@ fn run_coverage(tool: &CoverageTool, is_workspace: bool, packages: &[String]) ->
         args.push("--workspace".into());
     }
 
-    let status = Command::new(program)
-        .args(&args)
-        .status()
-        .with_context(|| format!("failed to run {program} {}", args.join(" ")))?;
+    for pattern in ignore {
+        args.push(ignore_flag.into());
+        args.push(pattern.clone());
+    }
+    args
+}
+
+/// Where `--coverage-in-docker` mounts the project inside the container.
+const CONTAINER_PROJECT_DIR: &str = "/workspace";
 
-    if !status.success() {
+/// `docker run` arguments that run `program args` in `image`, with `host_dir`
+/// mounted at [`CONTAINER_PROJECT_DIR`] as the working directory, so the
+/// lcov.info written there lands in `host_dir`.
+fn docker_args(
+    image: &str,
+    container: &str,
+    host_dir: &Path,
+    program: &str,
+    args: Vec<String>,
+) -> Vec<String> {
+    let mut docker = vec![
+        "run".to_string(),
+        "--rm".to_string(),
+        "--name".to_string(),
+        container.to_string(),
+        "--volume".to_string(),
+        format!("{}:{CONTAINER_PROJECT_DIR}", host_dir.display()),
+        "--workdir".to_string(),
+        CONTAINER_PROJECT_DIR.to_string(),
+        image.to_string(),
+        program.to_string(),
+    ];
+    docker.extend(args);
+    docker
+}
+
+/// Arguments for `bazel coverage`. `--package` and `--coverage-ignore` don't
+/// apply; pick what runs with `--bazel-target`.
+fn bazel_coverage_args(targets: &[String]) -> Vec<String> {
+    let mut args = vec!["coverage".to_string(), "--combined_report=lcov".to_string()];
+    args.extend(targets.iter().cloned());
+    args
+}
+
+/// Copy the combined report `bazel coverage` left in its output tree to `lcov.info`.
+fn copy_bazel_report() -> Result<()> {
+    let output = Command::new("bazel")
+        .args(["info", "output_path"])
+        .output()
+        .context("failed to run bazel info output_path")?;
+    if !output.status.success() {
         bail!(
-            "coverage command failed (exit {})",
-            status.code().unwrap_or(-1)
+            "bazel info output_path failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
         );
     }
+    let output_path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let report = Path::new(&output_path).join("_coverage/_coverage_report.dat");
+    std::fs::copy(&report, "lcov.info")
+        .with_context(|| format!("failed to copy {} to lcov.info", report.display()))?;
     Ok(())
 }
 
-fn find_rust_sources(src_dir: &Path) -> Result<Vec<PathBuf>> {
+/// Run a `--coverage-format exec:` converter in the project dir and parse the
+/// JSON coverage it prints. The command is split on whitespace, without
+/// shell quoting; wrap anything fancier in a script.
+fn run_converter(command: &str) -> Result<HashMap<String, LineCoverage>> {
+    let mut words = command.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    eprintln!("running {command}");
+    let output = Command::new(program)
+        .args(words)
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run coverage converter `{command}`"))?;
+    if !output.status.success() {
+        bail!("coverage converter `{command}` failed ({})", output.status);
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| format!("coverage converter `{command}` printed invalid UTF-8"))?;
+    coverage::parse_coverage_json(&stdout)
+        .map_err(|err| anyhow!("coverage converter `{command}` printed unexpected output: {err}"))
+}
+
+/// Directory names skipped during source discovery unless `--include-vendored`.
+const VENDORED_DIRS: &[&str] = &["vendor", "third_party"];
+
+/// How `find_rust_sources` walks a source dir.
+struct Discovery {
+    include_vendored: bool,
+    respect_gitignore: bool,
+    /// Git submodule paths, skipped unless `include_vendored`.
+    submodules: Vec<PathBuf>,
+}
+
+impl Discovery {
+    fn from_args(args: &AnalysisArgs) -> Self {
+        Discovery {
+            include_vendored: args.include_vendored,
+            respect_gitignore: args.respect_gitignore,
+            submodules: if args.include_vendored {
+                Vec::new()
+            } else {
+                git_submodule_paths()
+            },
+        }
+    }
+
+    fn skips(&self, dir: &Path) -> bool {
+        !self.include_vendored && is_vendored_dir(dir, &self.submodules)
+    }
+}
+
+/// Collect `.rs` files under `src_dir`. Subdirectories named in
+/// [`VENDORED_DIRS`] or listed as submodules are not descended into unless
+/// `include_vendored` is set; `src_dir` itself is always walked.
+fn find_rust_sources(src_dir: &Path, discovery: &Discovery) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
-    collect_rs_files(src_dir, &mut files)?;
+    if discovery.respect_gitignore {
+        collect_rs_files_ignoring(src_dir, &mut files, discovery)?;
+    } else {
+        collect_rs_files(src_dir, &mut files, &|dir: &Path| discovery.skips(dir))?;
+    }
     files.sort();
     Ok(files)
 }
 
-fn collect_rs_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+/// Like [`collect_rs_files`], but honours .gitignore, .ignore and hidden-file
+/// rules the way ripgrep does.
+fn collect_rs_files_ignoring(
+    src_dir: &Path,
+    files: &mut Vec<PathBuf>,
+    discovery: &Discovery,
+) -> Result<()> {
+    if !src_dir.is_dir() {
+        return Ok(());
+    }
+    let root = src_dir.to_path_buf();
+    let include_vendored = discovery.include_vendored;
+    let submodules = discovery.submodules.clone();
+    let walker = ignore::WalkBuilder::new(src_dir)
+        .filter_entry(move |entry| {
+            let path = entry.path();
+            path == root
+                || !entry.file_type().is_some_and(|t| t.is_dir())
+                || include_vendored
+                || !is_vendored_dir(path, &submodules)
+        })
+        .build();
+    for entry in walker {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type().is_some_and(|t| t.is_file())
+            && path.extension().is_some_and(|ext| ext == "rs")
+        {
+            files.push(path.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn collect_rs_files(
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+    skip: &dyn Fn(&Path) -> bool,
+) -> Result<()> {
     if !dir.is_dir() {
         return Ok(());
     }
@@ -217,7 +2992,9 @@ fn collect_rs_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
-            collect_rs_files(&path, files)?;
+            if !skip(&path) {
+                collect_rs_files(&path, files, skip)?;
+            }
         } else if path.extension().is_some_and(|ext| ext == "rs") {
             files.push(path);
         }
@@ -225,6 +3002,34 @@ fn collect_rs_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
     Ok(())
 }
 
+pub fn is_vendored_dir(dir: &Path, submodules: &[PathBuf]) -> bool {
+    if dir
+        .file_name()
+        .is_some_and(|name| VENDORED_DIRS.iter().any(|v| name == *v))
+    {
+        return true;
+    }
+    let dir = dir.strip_prefix(".").unwrap_or(dir);
+    submodules.iter().any(|m| dir == m)
+}
+
+/// Submodule paths from `.gitmodules` in the current dir (the project root).
+fn git_submodule_paths() -> Vec<PathBuf> {
+    std::fs::read_to_string(".gitmodules")
+        .map(|content| parse_gitmodules(&content))
+        .unwrap_or_default()
+}
+
+pub fn parse_gitmodules(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "path").then(|| PathBuf::from(value.trim()))
+        })
+        .collect()
+}
+
 pub fn filter_sources(files: Vec<PathBuf>, filters: &[String]) -> Vec<PathBuf> {
     if filters.is_empty() {
         return files;
@@ -238,69 +3043,83 @@ pub fn filter_sources(files: Vec<PathBuf>, filters: &[String]) -> Vec<PathBuf> {
         .collect()
 }
 
-/// Find coverage data for a source file. Tries canonical match first, then
-/// literal match, then suffix match against a normalized form that strips any
-/// leading `./`. Returns `None` when the file has no entry in lcov.info —
-/// distinct from an entry that exists but has zero hits.
-pub fn find_coverage_for_file(
-    source_path: &Path,
-    file_coverage: &HashMap<String, LineCoverage>,
-) -> Option<LineCoverage> {
-    // Best signal: canonical absolute path (tarpaulin emits absolutes).
-    let canonical = source_path
-        .canonicalize()
-        .ok()
-        .map(|p| p.to_string_lossy().into_owned());
-    if let Some(ref c) = canonical {
-        if let Some(cov) = file_coverage.get(c) {
-            return Some(cov.clone());
-        }
-    }
-
-    // Fall back to the literal string as we were given it.
-    let source_str = source_path.to_string_lossy();
-    if let Some(cov) = file_coverage.get(source_str.as_ref()) {
-        return Some(cov.clone());
-    }
-
-    // Suffix match using a normalized form that strips leading `./`. Without
-    // this, `./src/foo.rs` never matches `/abs/path/src/foo.rs` in lcov.
-    let normalized = source_str.strip_prefix("./").unwrap_or(&source_str);
-    for (lcov_path, cov) in file_coverage {
-        if lcov_path.ends_with(normalized) || normalized.ends_with(lcov_path.as_str()) {
-            return Some(cov.clone());
-        }
-    }
+/// Display filters applied to the sorted entry list, in order: the range
+/// filters, then `--top`.
+#[derive(Default)]
+pub struct Filters {
+    pub min_crap: f64,
+    pub min_cc: Option<u32>,
+    pub max_cc: Option<u32>,
+    pub max_cov: Option<f64>,
+    pub top: Option<usize>,
+}
 
-    None
+/// Apply `--min-crap`, `--min-cc` / `--max-cc`, `--max-cov` and `--top` to a
+/// sorted entry list. Entries with no CRAP score (uninstrumented) pass through
+/// the min-crap and max-cov filters untouched so they can still be displayed;
+/// they're already sunk to the bottom by `sort_entries`. Complexity is known for
+/// every entry, so the CC range applies to all of them.
+pub fn apply_filters(entries: Vec<CrapEntry>, filters: &Filters) -> Vec<CrapEntry> {
+    filter_entries(entries, filters).0
 }
 
-/// Apply `--min-crap` and `--top` to a sorted entry list.
-/// Entries with no CRAP score (uninstrumented) pass through the min-crap filter
-/// untouched so they can still be displayed; they're already sunk to the bottom
-/// by `sort_entries`.
-pub fn apply_filters(
-    entries: Vec<CrapEntry>,
-    min_crap: f64,
-    top: Option<usize>,
-) -> Vec<CrapEntry> {
+/// [`apply_filters`], also returning how many entries passed the other
+/// filters but were cut by `--top`.
+pub fn filter_entries(entries: Vec<CrapEntry>, filters: &Filters) -> (Vec<CrapEntry>, usize) {
     let mut kept: Vec<CrapEntry> = entries
         .into_iter()
         .filter(|e| match e.crap {
-            Some(s) => s >= min_crap,
+            Some(s) => s >= filters.min_crap,
             None => true,
         })
+        .filter(|e| filters.min_cc.is_none_or(|min| e.complexity >= min))
+        .filter(|e| filters.max_cc.is_none_or(|max| e.complexity <= max))
+        .filter(|e| match (filters.max_cov, e.coverage) {
+            (Some(max), Some(cov)) => cov <= max,
+            _ => true,
+        })
         .collect();
-    if let Some(n) = top {
+    let mut omitted = 0;
+    if let Some(n) = filters.top {
+        omitted = kept.len().saturating_sub(n);
         kept.truncate(n);
     }
-    kept
+    (kept, omitted)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_gitmodules_reads_paths() {
+        let content = r#"
+[submodule "libfoo"]
+	path = external/libfoo
+	url = https://example.com/libfoo.git
+[submodule "bar"]
+	path=crates/bar/deps
+	url = ../bar.git
+"#;
+        assert_eq!(
+            parse_gitmodules(content),
+            vec![
+                PathBuf::from("external/libfoo"),
+                PathBuf::from("crates/bar/deps")
+            ]
+        );
+    }
+
+    #[test]
+    fn vendored_dirs_by_name_and_submodule() {
+        let subs = vec![PathBuf::from("src/external")];
+        assert!(is_vendored_dir(Path::new("./src/vendor"), &[]));
+        assert!(is_vendored_dir(Path::new("crates/a/src/third_party"), &[]));
+        assert!(is_vendored_dir(Path::new("./src/external"), &subs));
+        assert!(!is_vendored_dir(Path::new("./src/vendoring"), &subs));
+        assert!(!is_vendored_dir(Path::new("./src/external/inner"), &[]));
+    }
+
     #[test]
     fn filter_sources_no_filter() {
         let files = vec![PathBuf::from("src/foo.rs"), PathBuf::from("src/bar.rs")];
@@ -329,74 +3148,13 @@ mod tests {
         assert_eq!(result, vec![PathBuf::from("src/foo/bar.rs")]);
     }
 
-    #[test]
-    fn find_coverage_exact_match() {
-        let mut file_cov = HashMap::new();
-        let mut line_cov = LineCoverage::new();
-        line_cov.insert(1, 5);
-        file_cov.insert("src/main.rs".to_string(), line_cov);
-
-        let result = find_coverage_for_file(Path::new("src/main.rs"), &file_cov);
-        let cov = result.expect("expected Some");
-        assert_eq!(cov.get(&1), Some(&5));
-    }
-
-    #[test]
-    fn find_coverage_suffix_match() {
-        let mut file_cov = HashMap::new();
-        let mut line_cov = LineCoverage::new();
-        line_cov.insert(1, 3);
-        file_cov.insert("/home/user/project/src/main.rs".to_string(), line_cov);
-
-        let result = find_coverage_for_file(Path::new("src/main.rs"), &file_cov);
-        let cov = result.expect("expected Some via suffix match");
-        assert_eq!(cov.get(&1), Some(&3));
-    }
-
-    #[test]
-    fn find_coverage_no_match_returns_none() {
-        let file_cov = HashMap::new();
-        let result = find_coverage_for_file(Path::new("src/main.rs"), &file_cov);
-        assert!(result.is_none(), "absent file must be None, not Some(empty)");
-    }
-
-    #[test]
-    fn find_coverage_present_but_empty_returns_some_empty() {
-        // Regression: tarpaulin emits SF: for files with no executable lines.
-        // That is instrumented-but-empty — distinct from "not in the build at all".
-        let mut file_cov = HashMap::new();
-        file_cov.insert("src/empty.rs".to_string(), LineCoverage::new());
-
-        let result = find_coverage_for_file(Path::new("src/empty.rs"), &file_cov);
-        let cov = result.expect("entry exists, must be Some");
-        assert!(cov.is_empty());
-    }
-
-    #[test]
-    fn find_coverage_dot_slash_prefix_matches_absolute() {
-        // Regression: `resolve_targets(Path::new("."), ...)` produces source paths
-        // like `./src/foo.rs`, while tarpaulin writes absolute paths into lcov.info.
-        // The suffix-match fallback must strip the leading `./` so the two forms align.
-        let mut file_cov = HashMap::new();
-        let mut line_cov = LineCoverage::new();
-        line_cov.insert(1, 7);
-        file_cov.insert(
-            "/Users/dev/project/src/foo.rs".to_string(),
-            line_cov,
-        );
-
-        let result = find_coverage_for_file(Path::new("./src/foo.rs"), &file_cov);
-        let cov = result.expect("dot-slash prefix must still suffix-match");
-        assert_eq!(cov.get(&1), Some(&7));
-    }
-
     fn entry(name: &str, crap: Option<f64>) -> CrapEntry {
         CrapEntry {
             name: name.into(),
-            module_path: String::new(),
             complexity: 1,
             coverage: crap.map(|_| 0.0),
             crap,
+            ..Default::default()
         }
     }
 
@@ -407,7 +3165,13 @@ mod tests {
             entry("b", Some(20.0)),
             entry("c", Some(5.0)),
         ];
-        let kept = apply_filters(entries, 10.0, None);
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                min_crap: 10.0,
+                ..Default::default()
+            },
+        );
         let names: Vec<&str> = kept.iter().map(|e| e.name.as_str()).collect();
         assert_eq!(names, vec!["a", "b"]);
     }
@@ -419,11 +3183,36 @@ mod tests {
             entry("b", Some(20.0)),
             entry("c", Some(5.0)),
         ];
-        let kept = apply_filters(entries, 0.0, Some(1));
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                top: Some(1),
+                ..Default::default()
+            },
+        );
         assert_eq!(kept.len(), 1);
         assert_eq!(kept[0].name, "a");
     }
 
+    #[test]
+    fn filter_counts_entries_cut_by_top() {
+        let entries = vec![
+            entry("a", Some(50.0)),
+            entry("b", Some(20.0)),
+            entry("c", Some(5.0)),
+        ];
+        let filters = Filters {
+            min_crap: 10.0,
+            top: Some(1),
+            ..Default::default()
+        };
+        let (kept, omitted) = filter_entries(entries.clone(), &filters);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(omitted, 1);
+        let (_, omitted) = filter_entries(entries, &Filters::default());
+        assert_eq!(omitted, 0);
+    }
+
     #[test]
     fn filter_combined() {
         let entries = vec![
@@ -431,7 +3220,14 @@ mod tests {
             entry("b", Some(20.0)),
             entry("c", Some(5.0)),
         ];
-        let kept = apply_filters(entries, 10.0, Some(10));
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                min_crap: 10.0,
+                top: Some(10),
+                ..Default::default()
+            },
+        );
         let names: Vec<&str> = kept.iter().map(|e| e.name.as_str()).collect();
         assert_eq!(names, vec!["a", "b"]);
     }
@@ -443,21 +3239,33 @@ mod tests {
             entry("b", Some(20.0)),
             entry("c", Some(5.0)),
         ];
-        let kept = apply_filters(entries, 0.0, None);
+        let kept = apply_filters(entries, &Filters::default());
         assert_eq!(kept.len(), 3);
     }
 
     #[test]
     fn filter_top_zero_empties_body() {
         let entries = vec![entry("a", Some(50.0)), entry("b", Some(20.0))];
-        let kept = apply_filters(entries, 0.0, Some(0));
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                top: Some(0),
+                ..Default::default()
+            },
+        );
         assert!(kept.is_empty());
     }
 
     #[test]
     fn filter_top_larger_than_entries_returns_all() {
         let entries = vec![entry("a", Some(50.0)), entry("b", Some(20.0))];
-        let kept = apply_filters(entries, 0.0, Some(100));
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                top: Some(100),
+                ..Default::default()
+            },
+        );
         assert_eq!(kept.len(), 2);
     }
 
@@ -470,7 +3278,13 @@ mod tests {
             entry("uninstrumented", None),
             entry("low", Some(2.0)),
         ];
-        let kept = apply_filters(entries, 10.0, None);
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                min_crap: 10.0,
+                ..Default::default()
+            },
+        );
         let names: Vec<&str> = kept.iter().map(|e| e.name.as_str()).collect();
         assert_eq!(names, vec!["covered", "uninstrumented"]);
     }
@@ -483,8 +3297,299 @@ mod tests {
             entry("b", Some(20.0)),
             entry("u", None),
         ];
-        let kept = apply_filters(entries, 0.0, Some(2));
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                top: Some(2),
+                ..Default::default()
+            },
+        );
         let names: Vec<&str> = kept.iter().map(|e| e.name.as_str()).collect();
         assert_eq!(names, vec!["a", "b"]);
     }
+
+    #[test]
+    fn log_tail_keeps_the_last_lines() {
+        let path = std::env::temp_dir().join(format!("craprs_log_{}.log", std::process::id()));
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        assert_eq!(log_tail(&path, 2), "two\nthree");
+        assert_eq!(log_tail(&path, 10), "one\ntwo\nthree");
+        let _ = std::fs::remove_file(&path);
+        assert!(log_tail(&path, 2).starts_with("(could not read "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_with_timeout_kills_the_process_group() {
+        let spawn = |script: &str| {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", script]);
+            std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+            cmd.spawn().unwrap()
+        };
+        let started = Instant::now();
+        let hung = spawn("sleep 30 & sleep 30");
+        assert!(wait_with_timeout(hung, Some(Duration::from_millis(200))).unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let done = wait_with_timeout(spawn("exit 3"), Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(done.and_then(|s| s.code()), Some(3));
+    }
+
+    #[test]
+    fn stale_sources_changed_after_lcov() {
+        use std::time::{Duration, SystemTime};
+
+        let dir = std::env::temp_dir().join(format!("craprs_fresh_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let lcov = dir.join("lcov.info");
+        let old = dir.join("old.rs");
+        let new = dir.join("new.rs");
+        let now = SystemTime::now();
+        for (path, age) in [(&old, 60), (&lcov, 30), (&new, 0)] {
+            let file = std::fs::File::create(path).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        assert_eq!(stale_sources(&lcov, &[old.clone(), new.clone()]), vec![new.clone()]);
+        assert!(stale_sources(&dir.join("missing.info"), std::slice::from_ref(&new)).is_empty());
+
+        // Recorded hashes win over mtimes: a touched file is still fresh, an
+        // edited one is stale whenever it was written.
+        record_sources(&lcov, &[old.clone(), new.clone()]).unwrap();
+        assert!(stale_sources(&lcov, &[old.clone(), new.clone()]).is_empty());
+        assert!(coverage_time_sources(&lcov).is_empty());
+        std::fs::write(&old, "fn edited() {}").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(now - Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(stale_sources(&lcov, &[old.clone(), new]), vec![old.clone()]);
+        // The copy kept at coverage time is what line numbers are mapped from.
+        assert_eq!(
+            coverage_time_sources(&lcov),
+            HashMap::from([(old, String::new())])
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_output_creates_parent_directories() {
+        let dir = std::env::temp_dir().join(format!("craprs_output_{}", std::process::id()));
+        let path = dir.join("reports/ci/crap.txt");
+        write_output(&path, b"CRAP Report\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "CRAP Report\n");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn flatten_sections_prefixes_labels_and_resorts() {
+        let mut api = entry("handler", Some(10.0));
+        api.module_path = "routes".into();
+        let sections = vec![
+            ("services/api".to_string(), vec![api]),
+            ("tools/cli".to_string(), vec![entry("main", Some(40.0))]),
+        ];
+        let flat = flatten_sections(&sections);
+        let modules: Vec<&str> = flat.iter().map(|e| e.module_path.as_str()).collect();
+        assert_eq!(modules, vec!["tools/cli", "services/api::routes"]);
+
+        let single = vec![(String::new(), vec![entry("a", Some(1.0))])];
+        assert_eq!(flatten_sections(&single)[0].module_path, "");
+    }
+
+    fn cc_entry(name: &str, complexity: u32, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            complexity,
+            ..entry(name, crap)
+        }
+    }
+
+    #[test]
+    fn filter_cc_range_is_inclusive() {
+        let entries = vec![
+            cc_entry("tiny", 2, Some(2.0)),
+            cc_entry("low", 5, Some(30.0)),
+            cc_entry("mid", 10, Some(60.0)),
+            cc_entry("high", 15, Some(90.0)),
+            cc_entry("huge", 40, Some(1600.0)),
+        ];
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                min_cc: Some(5),
+                max_cc: Some(15),
+                ..Default::default()
+            },
+        );
+        let names: Vec<&str> = kept.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["low", "mid", "high"]);
+    }
+
+    #[test]
+    fn filter_cc_range_applies_to_uninstrumented() {
+        let entries = vec![cc_entry("u_small", 1, None), cc_entry("u_big", 9, None)];
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                min_cc: Some(5),
+                ..Default::default()
+            },
+        );
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].name, "u_big");
+    }
+
+    #[test]
+    fn filter_top_applies_after_cc_range() {
+        let entries = vec![
+            cc_entry("huge", 40, Some(1600.0)),
+            cc_entry("mid", 10, Some(60.0)),
+            cc_entry("low", 5, Some(30.0)),
+        ];
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                max_cc: Some(15),
+                top: Some(1),
+                ..Default::default()
+            },
+        );
+        assert_eq!(kept[0].name, "mid");
+    }
+
+    fn cov_entry(name: &str, coverage: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            coverage,
+            ..entry(name, coverage.map(|_| 1.0))
+        }
+    }
+
+    #[test]
+    fn filter_max_cov_ignores_crap_and_keeps_uninstrumented() {
+        let entries = vec![
+            cov_entry("untested", Some(0.0)),
+            cov_entry("half", Some(50.0)),
+            cov_entry("mostly", Some(80.0)),
+            cov_entry("unknown", None),
+        ];
+        let kept = apply_filters(
+            entries,
+            &Filters {
+                max_cov: Some(50.0),
+                ..Default::default()
+            },
+        );
+        let names: Vec<&str> = kept.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["untested", "half", "unknown"]);
+    }
+
+    #[test]
+    fn coverage_args_forward_ignore_patterns_per_tool() {
+        let ignore = vec!["vendor/".to_string(), "generated".to_string()];
+        let llvm = coverage_args(&CoverageTool::LlvmCov, false, &[], &ignore);
+        assert_eq!(
+            llvm[llvm.len() - 4..],
+            [
+                "--ignore-filename-regex",
+                "vendor/",
+                "--ignore-filename-regex",
+                "generated"
+            ]
+        );
+        let tarp = coverage_args(&CoverageTool::Tarpaulin, false, &[], &ignore);
+        assert_eq!(
+            tarp[tarp.len() - 4..],
+            ["--exclude-files", "vendor/", "--exclude-files", "generated"]
+        );
+    }
+
+    #[test]
+    fn docker_args_mount_the_project_and_wrap_the_command() {
+        let args = docker_args(
+            "rust:1.85",
+            "craprs-coverage-7",
+            Path::new("/home/me/app"),
+            "cargo",
+            vec!["llvm-cov".to_string(), "--lcov".to_string()],
+        );
+        assert_eq!(
+            args,
+            [
+                "run",
+                "--rm",
+                "--name",
+                "craprs-coverage-7",
+                "--volume",
+                "/home/me/app:/workspace",
+                "--workdir",
+                "/workspace",
+                "rust:1.85",
+                "cargo",
+                "llvm-cov",
+                "--lcov"
+            ]
+        );
+    }
+
+    #[test]
+    fn bazel_coverage_writes_a_combined_lcov_report() {
+        assert_eq!(
+            bazel_coverage_args(&["//crates/...".to_string(), "//tools:lint".to_string()]),
+            ["coverage", "--combined_report=lcov", "//crates/...", "//tools:lint"]
+        );
+    }
+
+    #[test]
+    fn coverage_args_scope_packages_before_workspace() {
+        let args = coverage_args(&CoverageTool::Tarpaulin, true, &["a".to_string()], &[]);
+        assert!(args.ends_with(&["-p".to_string(), "a".to_string()]));
+        assert!(!args.contains(&"--workspace".to_string()));
+        let args = coverage_args(&CoverageTool::LlvmCov, true, &[], &[]);
+        assert_eq!(args.last().map(String::as_str), Some("--workspace"));
+    }
+
+    #[test]
+    fn empty_analysis_fails_with_hints() {
+        let mut analysis = Analysis {
+            sources: vec![PathBuf::from("src/lib.rs")],
+            ..Analysis::default()
+        };
+        let lcov = HashMap::from([("/ci/app/src/lib.rs".to_string(), LineCoverage::new())]);
+        let err = check_not_empty(&analysis, &lcov, &["parser".to_string()], "lcov.info").unwrap_err();
+        assert!(err.to_string().starts_with(
+            "no functions found in 1 source file(s) matching `parser`\nhint: check --src"
+        ));
+
+        analysis.functions = 3;
+        let err = check_not_empty(&analysis, &lcov, &[], "lcov.info").unwrap_err();
+        assert!(err.to_string().starts_with(
+            "lcov.info has coverage for 1 file(s), e.g. /ci/app/src/lib.rs, but none of them match \
+             the 1 analyzed source file(s)\n"
+        ));
+        let err = check_not_empty(&analysis, &HashMap::new(), &[], "lcov.info").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("lcov.info has no coverage records for the 1")
+        );
+
+        analysis
+            .line_coverage
+            .insert(PathBuf::from("src/lib.rs"), LineCoverage::new());
+        assert!(check_not_empty(&analysis, &lcov, &[], "lcov.info").is_ok());
+    }
+
+    #[test]
+    fn entry_limit_fails_past_the_maximum_unless_lifted() {
+        assert!(check_entry_limit(10, 10).is_ok());
+        assert!(check_entry_limit(200_000, 0).is_ok());
+        let err = check_entry_limit(11, 10).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("the report would list 11 functions, more than --max-entries 10\n")
+        );
+    }
 }
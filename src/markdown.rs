@@ -0,0 +1,107 @@
+use crate::badge::crappy_count;
+use crate::crap::{self, CrapEntry, NumberFormat, PreviousScores, TableOptions, TableStyle};
+
+/// Rows shown when `--top` isn't given; PR comments get unreadable beyond that.
+pub const DEFAULT_ROWS: usize = 10;
+
+/// GitHub-flavored markdown for a pull request comment: a heading, a one-line
+/// summary and a table of the first `rows` entries (worst first), with
+/// delta columns against `previous` scores when given.
+pub fn format_markdown(
+    entries: &[CrapEntry],
+    score_label: &'static str,
+    threshold: f64,
+    rows: usize,
+    numbers: NumberFormat,
+    previous: Option<&PreviousScores>,
+) -> String {
+    let scored = entries.iter().filter(|e| e.crap.is_some()).count();
+    let crappy = crappy_count(entries, threshold);
+    let mut out = String::from("### CRAP Report\n\n");
+    out.push_str(&format!(
+        "**{}** function(s), {scored} with coverage data, **{crappy}** with {score_label} ≥ {threshold}",
+        entries.len()
+    ));
+    if let Some(worst) = entries.first().filter(|e| e.crap.is_some()) {
+        out.push_str(&format!(
+            " — worst: `{}` ({})",
+            worst.name,
+            numbers.format(worst.crap.unwrap_or(0.0))
+        ));
+    }
+    out.push_str("\n\n");
+    if entries.is_empty() {
+        return out;
+    }
+
+    let opts = TableOptions {
+        style: TableStyle::Markdown,
+        score_label,
+        highlight: None,
+        numbers,
+        columns: crap::default_columns(
+            entries.iter().any(|e| e.branch_coverage.is_some()),
+            false,
+            previous.is_some(),
+        ),
+        previous: previous.cloned(),
+        ..Default::default()
+    };
+    let shown = &entries[..rows.min(entries.len())];
+    out.push_str(&crap::format_table(shown, &opts));
+    if entries.len() > shown.len() {
+        out.push_str(&format!("\n_…and {} more._\n", entries.len() - shown.len()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "core".into(),
+            complexity: 6,
+            coverage: crap.map(|_| 10.0),
+            crap,
+            file: PathBuf::from("src/core.rs"),
+            start_line: 12,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn summary_and_truncated_table() {
+        let entries = vec![
+            entry("parse", Some(42.0)),
+            entry("load", Some(12.0)),
+            entry("idle", None),
+        ];
+        let out = format_markdown(&entries, "CRAP", 30.0, 2, NumberFormat::default(), None);
+        assert!(out.starts_with("### CRAP Report\n\n"));
+        assert!(out.contains(
+            "**3** function(s), 2 with coverage data, **1** with CRAP ≥ 30 — worst: `parse` (42.0)\n"
+        ));
+        assert!(out.contains("| Function | Module | CC | Cov% | CRAP | Location |\n"));
+        assert!(out.contains("| parse | core | 6 | 10.0% | 42.0 | src/core.rs:12 |\n"));
+        assert!(!out.contains("| idle |"));
+        assert!(out.ends_with("_…and 1 more._\n"));
+    }
+
+    #[test]
+    fn empty_report_has_no_table() {
+        let out = format_markdown(
+            &[],
+            "CRAP",
+            30.0,
+            DEFAULT_ROWS,
+            NumberFormat::default(),
+            None,
+        );
+        assert!(out.contains("**0** function(s)"));
+        assert!(!out.contains('|'));
+    }
+}
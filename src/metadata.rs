@@ -0,0 +1,162 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Serializer};
+
+/// Target triple craprs was built for, e.g. `x86_64-unknown-linux-gnu`.
+pub const HOST: &str = env!("CRAPRS_HOST");
+
+/// What produced a report, embedded in machine-readable outputs so archived
+/// reports describe themselves and can be compared like for like.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunMetadata {
+    pub craprs_version: String,
+    /// Unix seconds, written as RFC 3339 in JSON.
+    #[serde(serialize_with = "serialize_rfc3339")]
+    pub timestamp: u64,
+    /// `HEAD` of the analyzed project, when it's a git checkout.
+    pub git_commit: Option<String>,
+    /// `None` when an existing lcov.info was scored with `--skip-coverage`.
+    pub coverage_tool: Option<String>,
+    pub coverage_tool_version: Option<String>,
+    /// Options that change what is scored or how, as given on the command line.
+    pub flags: Vec<String>,
+    pub host: String,
+}
+
+impl RunMetadata {
+    /// Metadata for a run happening now, with this build's version and host.
+    pub fn new() -> RunMetadata {
+        RunMetadata {
+            craprs_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: now(),
+            host: HOST.to_string(),
+            ..RunMetadata::default()
+        }
+    }
+
+    /// Every set field as a `(key, value)` pair, for formats that only hold
+    /// strings. `flags` are joined with spaces.
+    pub fn pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![
+            ("craprs_version", self.craprs_version.clone()),
+            ("timestamp", rfc3339(self.timestamp)),
+        ];
+        let optional = [
+            ("git_commit", &self.git_commit),
+            ("coverage_tool", &self.coverage_tool),
+            ("coverage_tool_version", &self.coverage_tool_version),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                pairs.push((key, value.clone()));
+            }
+        }
+        if !self.flags.is_empty() {
+            pairs.push(("flags", self.flags.join(" ")));
+        }
+        pairs.push(("host", self.host.clone()));
+        pairs
+    }
+}
+
+/// Current Unix time, or `SOURCE_DATE_EPOCH` when set so reports can be
+/// reproduced byte for byte.
+pub fn now() -> u64 {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+    {
+        return epoch;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `secs` since the Unix epoch as `2024-05-01T12:00:00Z`.
+pub fn rfc3339(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn serialize_rfc3339<S: Serializer>(secs: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(*secs))
+}
+
+/// `git rev-parse HEAD` in `dir`, if it's inside a git checkout.
+pub fn git_commit(dir: &Path) -> Option<String> {
+    first_line(
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(dir),
+    )
+}
+
+/// First line of what `program args` prints, e.g. `cargo-tarpaulin 0.31.2`
+/// for `cargo tarpaulin --version`. `None` when it can't be run or fails.
+pub fn tool_version(program: &str, args: &[&str]) -> Option<String> {
+    first_line(Command::new(program).args(args))
+}
+
+fn first_line(cmd: &mut Command) -> Option<String> {
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next()?.trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_timestamps_as_rfc3339() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_714_565_045), "2024-05-01T12:04:05Z");
+    }
+
+    #[test]
+    fn pairs_skip_unset_fields() {
+        let meta = RunMetadata {
+            craprs_version: "0.2.0".into(),
+            timestamp: 0,
+            git_commit: Some("abc123".into()),
+            coverage_tool: None,
+            coverage_tool_version: None,
+            flags: vec!["--score uncovered-cc".into(), "--weigh-patterns".into()],
+            host: "x86_64-unknown-linux-gnu".into(),
+        };
+        let keys: Vec<&str> = meta.pairs().iter().map(|(k, _)| *k).collect();
+        assert_eq!(
+            keys,
+            vec!["craprs_version", "timestamp", "git_commit", "flags", "host"]
+        );
+        assert_eq!(meta.pairs()[3].1, "--score uncovered-cc --weigh-patterns");
+        let json = serde_json::to_value(&meta).unwrap();
+        assert_eq!(json["timestamp"], "1970-01-01T00:00:00Z");
+        assert_eq!(json["coverage_tool"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::coverage::source_to_module_path;
+
+/// Maps source files matching a path pattern to a module path prefix, e.g.
+/// `gen/**/*.rs => generated::`, for layouts that don't mirror the module
+/// tree. Paths are relative to the project dir; `*` matches within one
+/// directory, `**` across any number.
+#[derive(Debug, Clone)]
+pub struct ModuleRule {
+    pub rule: String,
+    pattern: Pattern,
+    /// Literal directory the pattern starts with; the rest of a matching
+    /// path becomes module segments under `prefix`.
+    base: PathBuf,
+    prefix: String,
+}
+
+impl ModuleRule {
+    pub fn parse(s: &str) -> Result<ModuleRule, String> {
+        let Some((pattern, prefix)) = s.split_once("=>") else {
+            return Err(format!(
+                "expected `<path pattern> => <module prefix>`, got `{s}`"
+            ));
+        };
+        let pattern = pattern.trim().trim_start_matches("./");
+        let prefix = prefix.trim().trim_end_matches("::");
+        if pattern.is_empty() {
+            return Err(format!("empty path pattern in `{s}`"));
+        }
+        let compiled = Pattern::new(pattern)
+            .map_err(|err| format!("invalid path pattern `{pattern}`: {err}"))?;
+        let base: PathBuf = Path::new(pattern)
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+        Ok(ModuleRule {
+            rule: format!("{pattern} => {prefix}::"),
+            pattern: compiled,
+            base,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    /// The module path for `path` under this rule, or `None` if it doesn't match.
+    pub fn map(&self, path: &Path) -> Option<String> {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        if !self.pattern.matches_path_with(path, options) {
+            return None;
+        }
+        let rest = source_to_module_path(path, &self.base);
+        Some(match (self.prefix.is_empty(), rest.is_empty()) {
+            (_, true) => self.prefix.clone(),
+            (true, false) => rest,
+            (false, false) => format!("{}::{rest}", self.prefix),
+        })
+    }
+}
+
+/// Module path from the first rule matching `path`.
+pub fn map_module(rules: &[ModuleRule], path: &Path) -> Option<String> {
+    rules.iter().find_map(|rule| rule.map(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules() {
+        let rule = ModuleRule::parse("./gen/**/*.rs => generated").unwrap();
+        assert_eq!(rule.rule, "gen/**/*.rs => generated::");
+        assert_eq!(rule.base, PathBuf::from("gen"));
+        assert!(ModuleRule::parse("gen/**/*.rs generated::").is_err());
+        assert!(ModuleRule::parse(" => generated::").is_err());
+        assert!(ModuleRule::parse("gen/[.rs => generated::").is_err());
+    }
+
+    #[test]
+    fn maps_the_rest_of_the_path_under_the_prefix() {
+        let rules = vec![
+            ModuleRule::parse("src/platform/linux.rs => sys").unwrap(),
+            ModuleRule::parse("gen/**/*.rs => generated::").unwrap(),
+            ModuleRule::parse("vendored/*/lib.rs => ").unwrap(),
+        ];
+        let map = |p: &str| map_module(&rules, Path::new(p));
+        assert_eq!(
+            map("./gen/proto/user.rs").as_deref(),
+            Some("generated::proto::user")
+        );
+        assert_eq!(map("gen/api/mod.rs").as_deref(), Some("generated::api"));
+        assert_eq!(map("src/platform/linux.rs").as_deref(), Some("sys"));
+        assert_eq!(map("vendored/zlib/lib.rs").as_deref(), Some("zlib::lib"));
+        assert_eq!(map("vendored/zlib/src/lib.rs"), None);
+        assert_eq!(map("src/main.rs"), None);
+    }
+}
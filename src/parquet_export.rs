@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int32Type};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+use crate::crap::CrapEntry;
+use crate::metadata::RunMetadata;
+
+/// Parquet schema for exported entries. `coverage` and `crap` are optional so
+/// uninstrumented entries round-trip as nulls rather than fake zeros.
+/// `decision_points` lists what makes up `complexity`, as `(kind, line)`.
+pub const SCHEMA: &str = "
+message crap_entry {
+  REQUIRED BYTE_ARRAY name (UTF8);
+  REQUIRED BYTE_ARRAY module (UTF8);
+  REQUIRED INT32 complexity;
+  OPTIONAL DOUBLE coverage;
+  OPTIONAL DOUBLE crap;
+  REQUIRED group decision_points (LIST) {
+    REPEATED group list {
+      REQUIRED group element {
+        REQUIRED BYTE_ARRAY kind (UTF8);
+        REQUIRED INT32 line;
+      }
+    }
+  }
+}
+";
+
+/// Encode entries as a single-row-group, uncompressed Parquet file, with the
+/// run `metadata` as `craprs.*` key-value metadata in the footer.
+pub fn to_parquet(entries: &[CrapEntry], metadata: &RunMetadata) -> Result<Vec<u8>> {
+    let schema = Arc::new(parse_message_type(SCHEMA).context("invalid parquet schema")?);
+    let key_values = metadata
+        .pairs()
+        .into_iter()
+        .map(|(key, value)| KeyValue::new(format!("craprs.{key}"), value))
+        .collect();
+    let props = WriterProperties::builder()
+        .set_key_value_metadata(Some(key_values))
+        .build();
+    let mut buf = Vec::new();
+    let mut writer = SerializedFileWriter::new(&mut buf, schema, Arc::new(props))?;
+    let mut row_group = writer.next_row_group()?;
+
+    let names: Vec<ByteArray> = entries.iter().map(|e| e.name.as_str().into()).collect();
+    let modules: Vec<ByteArray> = entries
+        .iter()
+        .map(|e| e.module_path.as_str().into())
+        .collect();
+    let complexities: Vec<i32> = entries.iter().map(|e| e.complexity as i32).collect();
+    let (coverage, coverage_def) = optional_column(entries.iter().map(|e| e.coverage));
+    let (crap, crap_def) = optional_column(entries.iter().map(|e| e.crap));
+    let points = PointColumns::new(entries);
+
+    let mut col = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match col {
+            0 => column.typed::<ByteArrayType>().write_batch(&names, None, None)?,
+            1 => column.typed::<ByteArrayType>().write_batch(&modules, None, None)?,
+            2 => column.typed::<Int32Type>().write_batch(&complexities, None, None)?,
+            3 => column
+                .typed::<DoubleType>()
+                .write_batch(&coverage, Some(&coverage_def), None)?,
+            4 => column
+                .typed::<DoubleType>()
+                .write_batch(&crap, Some(&crap_def), None)?,
+            5 => column.typed::<ByteArrayType>().write_batch(
+                &points.kinds,
+                Some(&points.def_levels),
+                Some(&points.rep_levels),
+            )?,
+            _ => column.typed::<Int32Type>().write_batch(
+                &points.lines,
+                Some(&points.def_levels),
+                Some(&points.rep_levels),
+            )?,
+        };
+        column.close()?;
+        col += 1;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(buf)
+}
+
+/// Split an optional column into dense values plus definition levels
+/// (1 = present, 0 = null), as Parquet stores them.
+fn optional_column(values: impl Iterator<Item = Option<f64>>) -> (Vec<f64>, Vec<i16>) {
+    let mut dense = Vec::new();
+    let mut def_levels = Vec::new();
+    for v in values {
+        match v {
+            Some(x) => {
+                dense.push(x);
+                def_levels.push(1);
+            }
+            None => def_levels.push(0),
+        }
+    }
+    (dense, def_levels)
+}
+
+/// The two leaf columns of `decision_points` plus their shared levels. An
+/// empty list is a single slot at definition level 0; each point is a slot at
+/// level 1, with repetition level 0 starting a new entry's list.
+struct PointColumns {
+    kinds: Vec<ByteArray>,
+    lines: Vec<i32>,
+    def_levels: Vec<i16>,
+    rep_levels: Vec<i16>,
+}
+
+impl PointColumns {
+    fn new(entries: &[CrapEntry]) -> Self {
+        let mut cols = PointColumns {
+            kinds: Vec::new(),
+            lines: Vec::new(),
+            def_levels: Vec::new(),
+            rep_levels: Vec::new(),
+        };
+        for e in entries {
+            if e.decision_points.is_empty() {
+                cols.def_levels.push(0);
+                cols.rep_levels.push(0);
+            }
+            for (i, p) in e.decision_points.iter().enumerate() {
+                cols.kinds.push(p.kind.label().into());
+                cols.lines.push(p.line as i32);
+                cols.def_levels.push(1);
+                cols.rep_levels.push(if i == 0 { 0 } else { 1 });
+            }
+        }
+        cols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::{Field, RowAccessor};
+
+    use crate::complexity::{DecisionKind, DecisionPoint};
+
+    #[test]
+    fn optional_column_splits_nulls() {
+        let (dense, def) = optional_column([Some(1.0), None, Some(3.0)].into_iter());
+        assert_eq!(dense, vec![1.0, 3.0]);
+        assert_eq!(def, vec![1, 0, 1]);
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let entries = vec![
+            CrapEntry {
+                name: "parse".into(),
+                module_path: "core::parser".into(),
+                complexity: 9,
+                coverage: Some(50.0),
+                crap: Some(19.125),
+                decision_points: vec![
+                    DecisionPoint {
+                        kind: DecisionKind::If,
+                        line: 3,
+                    },
+                    DecisionPoint {
+                        kind: DecisionKind::MatchArm,
+                        line: 5,
+                    },
+                ],
+                ..Default::default()
+            },
+            CrapEntry {
+                name: "untouched".into(),
+                module_path: "core::io".into(),
+                complexity: 2,
+                ..Default::default()
+            },
+        ];
+        let meta = RunMetadata {
+            craprs_version: "0.2.0".into(),
+            ..RunMetadata::default()
+        };
+        let bytes = to_parquet(&entries, &meta).unwrap();
+        assert_eq!(&bytes[..4], b"PAR1");
+
+        let path = std::env::temp_dir().join(format!("craprs_parquet_{}.parquet", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        let key_values = reader
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap();
+        assert!(key_values.contains(&KeyValue::new(
+            "craprs.craprs_version".to_string(),
+            "0.2.0".to_string()
+        )));
+
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let first: Vec<_> = rows[0].get_column_iter().map(|(_, f)| f.clone()).collect();
+        assert_eq!(first[0], Field::Str("parse".into()));
+        assert_eq!(first[2], Field::Int(9));
+        assert_eq!(first[4], Field::Double(19.125));
+        let second: Vec<_> = rows[1].get_column_iter().map(|(_, f)| f.clone()).collect();
+        assert_eq!(second[3], Field::Null);
+        assert_eq!(second[4], Field::Null);
+
+        let Field::ListInternal(ref points) = first[5] else {
+            panic!("expected a list, got {:?}", first[5]);
+        };
+        assert_eq!(points.len(), 2);
+        let Field::Group(ref arm) = points.elements()[1] else {
+            panic!("expected a group");
+        };
+        assert_eq!(arm.get_string(0).unwrap(), "match_arm");
+        assert_eq!(arm.get_int(1).unwrap(), 5);
+        let Field::ListInternal(ref none) = second[5] else {
+            panic!("expected a list, got {:?}", second[5]);
+        };
+        assert_eq!(none.len(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,199 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::crap::CrapEntry;
+use crate::module_map::ModuleRule;
+use crate::tags::TagRule;
+use crate::tiers::Tiers;
+
+/// Project-level policy file, read from the project dir when present.
+pub const PROJECT_POLICY: &str = "craprs.toml";
+
+/// Thresholds and exclusion rules from a policy file. Every field is
+/// optional so an organization policy and a project's `craprs.toml` can be
+/// layered; command-line flags win over both.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Default for `--threshold`.
+    pub threshold: Option<f64>,
+    /// Default for `--min-crap`.
+    pub min_crap: Option<f64>,
+    /// Glob patterns over `module::function` paths; matching entries are
+    /// dropped from the report.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// [`ModuleRule`]s like `gen/**/*.rs => generated::`, first match wins.
+    /// Only honored in the project's own `craprs.toml`.
+    #[serde(default)]
+    pub module_paths: Vec<String>,
+    /// [`TagRule`]s like `src/ffi/**/*.rs => ffi`, every match applies. Only
+    /// honored in the project's own `craprs.toml`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Risk [`Tiers`] like `["ok < 15", "warn < 30", "fail >= 30"]`.
+    #[serde(default)]
+    pub tiers: Vec<String>,
+}
+
+impl Policy {
+    pub fn parse(content: &str) -> Result<Policy> {
+        let policy: Policy = toml::from_str(content)?;
+        for pattern in &policy.exclude {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("invalid exclude pattern `{pattern}`"))?;
+        }
+        for rule in &policy.module_paths {
+            ModuleRule::parse(rule).map_err(anyhow::Error::msg)?;
+        }
+        for rule in &policy.tags {
+            TagRule::parse(rule).map_err(anyhow::Error::msg)?;
+        }
+        if !policy.tiers.is_empty() {
+            Tiers::parse(&policy.tiers).map_err(anyhow::Error::msg)?;
+        }
+        Ok(policy)
+    }
+
+    /// Read a policy from a local path or an `http(s)://` URL (fetched with `curl`).
+    pub fn load(source: &str) -> Result<Policy> {
+        let content = if source.starts_with("https://") || source.starts_with("http://") {
+            let output = Command::new("curl")
+                .args(["--fail", "--silent", "--show-error", "--location", source])
+                .output()
+                .context("failed to run curl")?;
+            if !output.status.success() {
+                bail!(
+                    "failed to fetch {source}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim_end()
+                );
+            }
+            String::from_utf8(output.stdout).with_context(|| format!("{source} is not UTF-8"))?
+        } else {
+            std::fs::read_to_string(Path::new(source))
+                .with_context(|| format!("failed to read {source}"))?
+        };
+        Policy::parse(&content).with_context(|| format!("invalid policy {source}"))
+    }
+
+    /// `self` with `over` layered on top: set values in `over` win, exclusion
+    /// rules from both apply. Tiers are replaced as a whole.
+    pub fn layered(mut self, over: Policy) -> Policy {
+        self.threshold = over.threshold.or(self.threshold);
+        self.min_crap = over.min_crap.or(self.min_crap);
+        self.exclude.extend(over.exclude);
+        if !over.tiers.is_empty() {
+            self.tiers = over.tiers;
+        }
+        self
+    }
+
+    /// Parsed `tiers`, if any; [`Policy::parse`] has already rejected invalid ones.
+    pub fn risk_tiers(&self) -> Option<Tiers> {
+        Tiers::parse(&self.tiers).ok()
+    }
+
+    /// Parsed `module_paths`; [`Policy::parse`] has already rejected invalid ones.
+    pub fn module_rules(&self) -> Vec<ModuleRule> {
+        self.module_paths
+            .iter()
+            .filter_map(|rule| ModuleRule::parse(rule).ok())
+            .collect()
+    }
+
+    /// Parsed `tags`; [`Policy::parse`] has already rejected invalid ones.
+    pub fn tag_rules(&self) -> Vec<TagRule> {
+        self.tags
+            .iter()
+            .filter_map(|rule| TagRule::parse(rule).ok())
+            .collect()
+    }
+
+    /// True when an exclusion rule matches the entry's module or `module::name` path.
+    pub fn excludes(&self, entry: &CrapEntry) -> bool {
+        let full = format!("{}::{}", entry.module_path, entry.name);
+        self.exclude.iter().any(|pattern| {
+            glob::Pattern::new(pattern)
+                .is_ok_and(|p| p.matches(&entry.module_path) || p.matches(&full))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(module: &str, name: &str) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: module.into(),
+            complexity: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn project_values_override_org_and_exclusions_add_up() {
+        let org =
+            Policy::parse("threshold = 20.0\nmin_crap = 5.0\nexclude = [\"*::generated*\"]\n")
+                .unwrap();
+        let project = Policy::parse("threshold = 40\nexclude = [\"legacy\"]").unwrap();
+        let policy = org.layered(project);
+        assert_eq!(policy.threshold, Some(40.0));
+        assert_eq!(policy.min_crap, Some(5.0));
+        assert!(policy.excludes(&entry("app::generated", "parse")));
+        assert!(policy.excludes(&entry("legacy", "run")));
+        assert!(!policy.excludes(&entry("app::core", "run")));
+    }
+
+    #[test]
+    fn function_paths_can_be_excluded() {
+        let policy = Policy::parse("exclude = [\"app::*::new\"]").unwrap();
+        assert!(policy.excludes(&entry("app::config", "new")));
+        assert!(!policy.excludes(&entry("app::config", "load")));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bad_patterns() {
+        assert!(Policy::parse("treshold = 30").is_err());
+        assert!(Policy::parse("exclude = [\"[\"]").is_err());
+    }
+
+    #[test]
+    fn module_path_rules_are_validated() {
+        let policy = Policy::parse("module_paths = [\"gen/**/*.rs => generated::\"]").unwrap();
+        assert_eq!(policy.module_rules()[0].rule, "gen/**/*.rs => generated::");
+        assert!(Policy::parse("module_paths = [\"gen/**/*.rs\"]").is_err());
+    }
+
+    #[test]
+    fn tag_rules_are_validated() {
+        let policy = Policy::parse("tags = [\"src/ffi/** => ffi\"]").unwrap();
+        assert_eq!(policy.tag_rules()[0].tag, "ffi");
+        assert!(Policy::parse("tags = [\"src/ffi/**\"]").is_err());
+    }
+
+    #[test]
+    fn project_tiers_replace_org_tiers() {
+        let org = Policy::parse("tiers = [\"ok < 30\", \"fail >= 30\"]").unwrap();
+        let project =
+            Policy::parse("tiers = [\"ok < 15\", \"warn < 30\", \"fail >= 30\"]").unwrap();
+        let tiers = org.layered(project).risk_tiers().unwrap();
+        assert_eq!(tiers.tiers.len(), 3);
+        assert!(Policy::default().risk_tiers().is_none());
+        assert!(Policy::parse("tiers = [\"ok < 30\"]").is_err());
+    }
+
+    #[test]
+    fn load_reads_local_files() {
+        let path = std::env::temp_dir().join(format!("craprs-policy-{}.toml", std::process::id()));
+        std::fs::write(&path, "threshold = 15").unwrap();
+        let policy = Policy::load(&path.to_string_lossy()).unwrap();
+        assert_eq!(policy.threshold, Some(15.0));
+        let _ = std::fs::remove_file(&path);
+        assert!(Policy::load("/nonexistent/policy.toml").is_err());
+    }
+}
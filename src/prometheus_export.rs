@@ -0,0 +1,189 @@
+use crate::badge::crappy_count;
+use crate::crap::{self, CrapEntry};
+use crate::metadata::RunMetadata;
+
+/// Prometheus / OpenMetrics text exposition: per-function score, complexity
+/// and coverage gauges, plus project-level aggregates. The score gauge is
+/// named after the score, e.g. `craprs_function_crap`. Functions without
+/// coverage data only get a complexity sample. The run `metadata` becomes a
+/// `craprs_run_info` sample with the details as labels.
+pub fn to_prometheus(
+    entries: &[CrapEntry],
+    score_label: &str,
+    threshold: f64,
+    metadata: &RunMetadata,
+) -> String {
+    let score = score_label.to_lowercase();
+    let scored: Vec<(&CrapEntry, f64)> = entries
+        .iter()
+        .filter_map(|e| e.crap.map(|s| (e, s)))
+        .collect();
+    let mut out = String::new();
+
+    gauge(
+        &mut out,
+        &format!("craprs_function_{score}"),
+        &format!("{score_label} score per function."),
+        scored.iter().map(|&(e, s)| (labels(e), s)),
+    );
+    gauge(
+        &mut out,
+        "craprs_function_complexity",
+        "Cyclomatic complexity per function.",
+        entries.iter().map(|e| (labels(e), f64::from(e.complexity))),
+    );
+    gauge(
+        &mut out,
+        "craprs_function_coverage_percent",
+        "Line coverage per function, in percent.",
+        entries
+            .iter()
+            .filter_map(|e| e.coverage.map(|c| (labels(e), c))),
+    );
+
+    let total = crap::total_score(entries);
+    let max = scored.iter().map(|&(_, s)| s).fold(0.0, f64::max);
+    gauge(
+        &mut out,
+        "craprs_functions",
+        "Functions in the report.",
+        [(String::new(), entries.len() as f64)],
+    );
+    gauge(
+        &mut out,
+        "craprs_functions_scored",
+        "Functions with coverage data.",
+        [(String::new(), scored.len() as f64)],
+    );
+    gauge(
+        &mut out,
+        "craprs_functions_crappy",
+        &format!("Functions with {score_label} at or above the threshold."),
+        [(
+            format!("{{threshold=\"{threshold}\"}}"),
+            crappy_count(entries, threshold) as f64,
+        )],
+    );
+    gauge(
+        &mut out,
+        &format!("craprs_{score}_sum"),
+        &format!("Sum of {score_label} over scored functions."),
+        [(String::new(), total)],
+    );
+    gauge(
+        &mut out,
+        &format!("craprs_{score}_max"),
+        &format!("Highest {score_label} of any function."),
+        [(String::new(), max)],
+    );
+    let info: Vec<String> = metadata
+        .pairs()
+        .into_iter()
+        .filter(|(key, _)| *key != "timestamp")
+        .map(|(key, value)| format!("{key}=\"{}\"", escape(&value)))
+        .collect();
+    gauge(
+        &mut out,
+        "craprs_run_info",
+        "Details of the run that produced these metrics.",
+        [(format!("{{{}}}", info.join(",")), 1.0)],
+    );
+    gauge(
+        &mut out,
+        "craprs_run_timestamp_seconds",
+        "When the run happened, in Unix seconds.",
+        [(String::new(), metadata.timestamp as f64)],
+    );
+    out.push_str("# EOF\n");
+    out
+}
+
+fn gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} gauge\n"));
+    for (labels, value) in samples {
+        out.push_str(&format!("{name}{labels} {value}\n"));
+    }
+}
+
+fn labels(e: &CrapEntry) -> String {
+    format!(
+        "{{module=\"{}\",function=\"{}\"}}",
+        escape(&e.module_path),
+        escape(&e.name)
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "core".into(),
+            complexity: 6,
+            coverage: crap.map(|_| 25.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn per_function_gauges_and_aggregates() {
+        let entries = vec![
+            entry("parse \"fast\"", Some(42.5)),
+            entry("load", Some(8.0)),
+            entry("idle", None),
+        ];
+        let meta = RunMetadata {
+            craprs_version: "0.2.0".into(),
+            timestamp: 1_714_565_045,
+            host: "x86_64-unknown-linux-gnu".into(),
+            ..RunMetadata::default()
+        };
+        let out = to_prometheus(&entries, "CRAP", 30.0, &meta);
+        assert!(out.starts_with(
+            "# HELP craprs_function_crap CRAP score per function.\n\
+             # TYPE craprs_function_crap gauge\n\
+             craprs_function_crap{module=\"core\",function=\"parse \\\"fast\\\"\"} 42.5\n"
+        ));
+        assert!(!out.contains("craprs_function_crap{module=\"core\",function=\"idle\"}"));
+        assert!(out.contains("craprs_function_complexity{module=\"core\",function=\"idle\"} 6\n"));
+        assert!(
+            out.contains(
+                "craprs_function_coverage_percent{module=\"core\",function=\"load\"} 25\n"
+            )
+        );
+        assert!(out.contains("\ncraprs_functions 3\n"));
+        assert!(out.contains("\ncraprs_functions_scored 2\n"));
+        assert!(out.contains("\ncraprs_functions_crappy{threshold=\"30\"} 1\n"));
+        assert!(out.contains("\ncraprs_crap_sum 50.5\n"));
+        assert!(out.contains("\ncraprs_crap_max 42.5\n"));
+        assert!(out.contains(
+            "\ncraprs_run_info{craprs_version=\"0.2.0\",host=\"x86_64-unknown-linux-gnu\"} 1\n"
+        ));
+        assert!(out.contains("\ncraprs_run_timestamp_seconds 1714565045\n"));
+        assert!(out.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn score_kind_names_the_score_metrics() {
+        let meta = RunMetadata::default();
+        let out = to_prometheus(&[entry("load", Some(3.0))], "UncovCC", 30.0, &meta);
+        assert!(out.contains("# TYPE craprs_function_uncovcc gauge\n"));
+        assert!(out.contains("\ncraprs_uncovcc_sum 3\n"));
+        let empty = to_prometheus(&[], "CRAP", 30.0, &meta);
+        assert!(empty.contains("\ncraprs_crap_sum 0\n"));
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use syn::{Item, Visibility};
+
+use crate::complexity::plain_name;
+use crate::crap::CrapEntry;
+
+/// Source files of the library crate in `src_dir` whose items are reachable
+/// from outside: `lib.rs` and every file declared through an unbroken chain
+/// of `pub mod x;` from it. Files only a binary (`main.rs`) or a private
+/// `mod` declares are internal. `#[path]` attributes aren't followed.
+pub fn public_files(src_dir: &Path) -> HashSet<PathBuf> {
+    let mut public = HashSet::new();
+    let mut pending = vec![src_dir.join("lib.rs")];
+    while let Some(file) = pending.pop() {
+        let Ok(source) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let Ok(syntax) = syn::parse_file(&source) else {
+            continue;
+        };
+        let dir = child_dir(&file);
+        for item in syntax.items {
+            let Item::Mod(m) = item else { continue };
+            if m.content.is_some() || !matches!(m.vis, Visibility::Public(_)) {
+                continue;
+            }
+            let name = m.ident.to_string();
+            let flat = dir.join(format!("{name}.rs"));
+            let nested = dir.join(&name).join("mod.rs");
+            if let Some(child) = [flat, nested].into_iter().find(|p| p.is_file()) {
+                pending.push(child);
+            }
+        }
+        public.insert(file);
+    }
+    public
+}
+
+/// Directory holding the files `mod x;` declares in `file`: its own
+/// directory for `lib.rs`, `main.rs` and `mod.rs`, `foo/` for `foo.rs`.
+fn child_dir(file: &Path) -> PathBuf {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    match file.file_stem().and_then(|s| s.to_str()) {
+        Some("lib" | "main" | "mod") | None => parent.to_path_buf(),
+        Some(stem) => parent.join(stem),
+    }
+}
+
+/// The functions listed in `cargo public-api` output, which sees the crate
+/// the way its users do: re-exports from private modules included.
+#[derive(Debug, Clone, Default)]
+pub struct ApiListing {
+    /// Paths like `mycrate::parser::Parser::parse`, without generics.
+    paths: HashSet<String>,
+}
+
+impl ApiListing {
+    /// Read the `pub fn` lines of a `cargo public-api` listing; every other
+    /// kind of item is ignored.
+    pub fn parse(content: &str) -> ApiListing {
+        ApiListing {
+            paths: content.lines().filter_map(fn_path).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// True when a listed function's path ends with the report `name`, e.g.
+    /// `mycrate::Parser::parse` for `Parser::parse`. Names are matched rather
+    /// than module paths because a re-export changes the path users see.
+    pub fn contains(&self, name: &str) -> bool {
+        let name = plain_name(name);
+        let suffix = format!("::{name}");
+        self.paths
+            .iter()
+            .any(|path| path == &name || path.ends_with(&suffix))
+    }
+}
+
+/// The path of a `pub fn` line without generics, e.g. `mycrate::Map::get`
+/// for `pub fn mycrate::Map<K, V>::get<Q: ?Sized>(&self, k: &Q) -> Option<&V>`.
+fn fn_path(line: &str) -> Option<String> {
+    let (head, rest) = line.trim().split_once("fn ")?;
+    // Only qualifiers may come before `fn`, not a field or alias of fn type.
+    let mut words = head.split_whitespace();
+    if words.next() != Some("pub")
+        || !words
+            .all(|w| matches!(w, "const" | "async" | "unsafe" | "extern") || w.starts_with('"'))
+    {
+        return None;
+    }
+    let mut path = String::new();
+    let mut depth = 0usize;
+    for c in rest.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            '(' if depth == 0 => return Some(path),
+            _ if depth == 0 => path.push(c),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Public functions scoring at or above `threshold`, worst first as `entries` are.
+pub fn over_threshold(entries: &[CrapEntry], threshold: f64) -> Vec<&CrapEntry> {
+    entries
+        .iter()
+        .filter(|e| e.public && e.crap.is_some_and(|s| s >= threshold))
+        .collect()
+}
+
+/// One line per public function over `--public-threshold`, e.g.
+/// `public API: Parser::parse (parser) src/parser.rs:42 — CRAP 35.9 >= 20`.
+pub fn format_exposed(exposed: &[&CrapEntry], score_label: &str, threshold: f64) -> String {
+    let mut out = String::new();
+    for e in exposed {
+        out.push_str(&format!(
+            "public API: {} ({}) {} — {score_label} {:.1} >= {threshold}\n",
+            e.name,
+            e.module_path,
+            e.location(),
+            e.crap.unwrap_or(0.0)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_pub_mod_chains_from_lib_rs() {
+        let dir = std::env::temp_dir().join(format!("craprs-public-{}", std::process::id()));
+        let src = dir.join("src");
+        std::fs::create_dir_all(src.join("api")).unwrap();
+        std::fs::create_dir_all(src.join("internal")).unwrap();
+        let files = [
+            (
+                "lib.rs",
+                "pub mod api;\nmod internal;\npub(crate) mod util;\n",
+            ),
+            ("api/mod.rs", "pub mod types;\nmod private;\n"),
+            ("api/types.rs", ""),
+            ("api/private.rs", ""),
+            ("internal/mod.rs", "pub mod leaked;\n"),
+            ("internal/leaked.rs", ""),
+            ("util.rs", ""),
+            ("main.rs", "pub mod cli;\n"),
+            ("cli.rs", ""),
+        ];
+        for (path, content) in files {
+            std::fs::write(src.join(path), content).unwrap();
+        }
+        let public = public_files(&src);
+        let mut names: Vec<String> = public
+            .iter()
+            .map(|p| p.strip_prefix(&src).unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["api/mod.rs", "api/types.rs", "lib.rs"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reads_functions_from_a_public_api_listing() {
+        let listing = ApiListing::parse(
+            "pub mod mycrate\n\
+             pub fn mycrate::parse<R: std::io::Read>(r: R) -> mycrate::Result<()>\n\
+             pub struct mycrate::Map<K, V>\n\
+             impl<K, V> mycrate::Map<K, V>\n\
+             pub fn mycrate::Map<K, V>::get(&self, k: &K) -> Option<&V>\n\
+             pub const unsafe extern \"C\" fn mycrate::raw::from_ptr(p: *const u8) -> u8\n\
+             impl core::fmt::Display for mycrate::Map<K, V>\n\
+             pub fn mycrate::Map<K, V>::fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result\n",
+        );
+        assert!(listing.contains("parse"));
+        assert!(listing.contains("Map::get"));
+        assert!(listing.contains("from_ptr"));
+        assert!(listing.contains("<Map<K, V> as Display>::fmt"));
+        assert!(listing.contains("parse [cfg(unix)]"));
+        assert!(!listing.contains("Map::insert"));
+        assert!(
+            ApiListing::parse(
+                "pub struct mycrate::Map\n\
+                 pub mycrate::Hooks::on_error: fn(u8)\n\
+                 pub type mycrate::Handler = fn(u8)\n"
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::num::NonZeroUsize;
+use std::thread;
+
+/// Render `items` into one string per chunk, in order, spreading the chunks
+/// over the available cores. No chunk is smaller than `min_chunk` items, so
+/// short inputs stay on the calling thread where spawning would cost more
+/// than it saves. Concatenating the chunks gives the same text as
+/// rendering every item in sequence, so callers can write them out one by one
+/// instead of building the whole report in a single buffer.
+pub fn render_chunks<T: Sync>(
+    items: &[T],
+    min_chunk: usize,
+    render: impl Fn(&T, &mut String) + Sync,
+) -> Vec<String> {
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let size = items.len().div_ceil(threads).max(min_chunk.max(1));
+    let render_chunk = |chunk: &[T]| {
+        let mut out = String::new();
+        for item in chunk {
+            render(item, &mut out);
+        }
+        out
+    };
+    if items.len() <= size {
+        return vec![render_chunk(items)];
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(size)
+            .map(|chunk| scope.spawn(|| render_chunk(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("render thread panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    #[test]
+    fn chunks_concatenate_in_order() {
+        let items: Vec<usize> = (0..5000).collect();
+        let chunks = render_chunks(&items, 100, |i, out| writeln!(out, "{i}").unwrap());
+        let expected: String = items.iter().map(|i| format!("{i}\n")).collect();
+        assert_eq!(chunks.concat(), expected);
+        assert_eq!(
+            render_chunks(&[] as &[usize], 100, |_, _| {}),
+            vec![String::new()]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use crate::coverage::line_ranges;
+use crate::crap::CrapEntry;
+
+/// Entries in `module` with coverage data and at least one uncovered line.
+/// Uninstrumented entries are skipped — we don't know what they miss.
+pub fn uncovered_in_module<'a>(entries: &'a [CrapEntry], module: &str) -> Vec<&'a CrapEntry> {
+    entries
+        .iter()
+        .filter(|e| e.module_path == module && e.coverage.is_some())
+        .filter(|e| !e.uncovered_lines.is_empty())
+        .collect()
+}
+
+/// `#[cfg(test)]` module skeleton for `module` with one `todo!()` test per
+/// uncovered function, riskiest first. `None` when nothing in the module is uncovered.
+pub fn scaffold_tests(entries: &[CrapEntry], module: &str) -> Option<String> {
+    let found = uncovered_in_module(entries, module);
+    if found.is_empty() {
+        return None;
+    }
+
+    let mut out = String::from("#[cfg(test)]\nmod tests {\n    use super::*;\n");
+    let mut used = HashSet::new();
+    for e in found {
+        let base = test_name(&e.name);
+        let mut name = base.clone();
+        let mut n = 2;
+        while !used.insert(name.clone()) {
+            name = format!("{base}_{n}");
+            n += 1;
+        }
+        let cov = e.coverage.unwrap_or(0.0);
+        out.push_str(&format!(
+            "\n    // {} — CC {}, {cov:.1}% covered\n",
+            e.name, e.complexity
+        ));
+        out.push_str(&format!(
+            "    // uncovered lines: {}\n",
+            line_ranges(&e.uncovered_lines)
+        ));
+        out.push_str(&format!(
+            "    #[test]\n    fn {name}() {{\n        todo!()\n    }}\n"
+        ));
+    }
+    out.push_str("}\n");
+    Some(out)
+}
+
+/// snake_case test function name for an entry name such as `MyType::parse`.
+fn test_name(name: &str) -> String {
+    let mut out = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && prev_lower {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+            prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        } else {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            prev_lower = false;
+        }
+    }
+    let out = out.trim_end_matches('_');
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("test_{out}")
+    } else {
+        out.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, module: &str, coverage: Option<f64>, uncovered: &[usize]) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: module.into(),
+            complexity: 3,
+            coverage,
+            crap: coverage.map(|_| 1.0),
+            uncovered_lines: uncovered.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_names_are_snake_case() {
+        assert_eq!(test_name("parse_lcov"), "parse_lcov");
+        assert_eq!(
+            test_name("LineCoverage::from_str"),
+            "line_coverage_from_str"
+        );
+        assert_eq!(test_name("Vec<u8>::len"), "vec_u8_len");
+    }
+
+    #[test]
+    fn scaffold_only_uncovered_functions_in_module() {
+        let entries = vec![
+            entry("Parser::next", "parse", Some(40.0), &[10, 11, 14]),
+            entry("covered", "parse", Some(100.0), &[]),
+            entry("unknown", "parse", None, &[]),
+            entry("elsewhere", "other", Some(0.0), &[3]),
+        ];
+        let out = scaffold_tests(&entries, "parse").unwrap();
+        assert!(out.starts_with("#[cfg(test)]\nmod tests {\n    use super::*;\n"));
+        assert!(out.contains("// uncovered lines: 10-11, 14"));
+        assert!(out.contains("fn parser_next() {\n        todo!()\n    }"));
+        assert!(!out.contains("covered()"));
+        assert!(!out.contains("unknown"));
+        assert!(!out.contains("elsewhere"));
+        assert!(scaffold_tests(&entries, "missing").is_none());
+    }
+
+    #[test]
+    fn colliding_names_get_suffixes() {
+        let entries = vec![
+            entry("Foo::bar", "m", Some(0.0), &[1]),
+            entry("foo::bar", "m", Some(0.0), &[5]),
+        ];
+        let out = scaffold_tests(&entries, "m").unwrap();
+        assert!(out.contains("fn foo_bar()"));
+        assert!(out.contains("fn foo_bar_2()"));
+    }
+}
@@ -0,0 +1,390 @@
+use anyhow::{Result, bail};
+
+use crate::crap::CrapEntry;
+
+/// A parsed `--select` expression, e.g. `crap>30 && module~"core"`.
+///
+/// Grammar (loosest binding first):
+///
+/// ```text
+/// expr    := and ("||" and)*
+/// and     := unary ("&&" unary)*
+/// unary   := "!" unary | "(" expr ")" | compare
+/// compare := field op value
+/// field   := name | module | cc | complexity | cov | coverage | crap
+/// op      := > | >= | < | <= | == | != | ~ | !~      (`~` = substring match)
+/// value   := number | "quoted string" | bare-word
+/// ```
+///
+/// Comparisons against a missing coverage/CRAP value (uninstrumented entries)
+/// are always false, so `!(crap > 30)` still selects them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    Or(Box<Selector>, Box<Selector>),
+    And(Box<Selector>, Box<Selector>),
+    Not(Box<Selector>),
+    Compare(Field, Op, Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Module,
+    Complexity,
+    Coverage,
+    Crap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Contains,
+    NotContains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Num(f64),
+    Str(String),
+}
+
+impl Selector {
+    pub fn parse(input: &str) -> Result<Selector> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let sel = parser.expr()?;
+        if let Some(tok) = parser.tokens.get(parser.pos) {
+            bail!("unexpected {} at column {}", tok.kind, tok.col);
+        }
+        Ok(sel)
+    }
+
+    pub fn matches(&self, e: &CrapEntry) -> bool {
+        match self {
+            Selector::Or(a, b) => a.matches(e) || b.matches(e),
+            Selector::And(a, b) => a.matches(e) && b.matches(e),
+            Selector::Not(a) => !a.matches(e),
+            Selector::Compare(field, op, value) => compare(e, *field, *op, value),
+        }
+    }
+}
+
+fn compare(e: &CrapEntry, field: Field, op: Op, value: &Value) -> bool {
+    match field {
+        Field::Name => compare_str(&e.name, op, value),
+        Field::Module => compare_str(&e.module_path, op, value),
+        Field::Complexity => compare_num(Some(e.complexity as f64), op, value),
+        Field::Coverage => compare_num(e.coverage, op, value),
+        Field::Crap => compare_num(e.crap, op, value),
+    }
+}
+
+fn compare_str(actual: &str, op: Op, value: &Value) -> bool {
+    let expected = match value {
+        Value::Str(s) => s.as_str(),
+        Value::Num(_) => return false,
+    };
+    match op {
+        Op::Eq => actual == expected,
+        Op::Ne => actual != expected,
+        Op::Contains => actual.contains(expected),
+        Op::NotContains => !actual.contains(expected),
+        Op::Gt => actual > expected,
+        Op::Ge => actual >= expected,
+        Op::Lt => actual < expected,
+        Op::Le => actual <= expected,
+    }
+}
+
+fn compare_num(actual: Option<f64>, op: Op, value: &Value) -> bool {
+    let (Some(actual), Value::Num(expected)) = (actual, value) else {
+        return false;
+    };
+    let expected = *expected;
+    match op {
+        Op::Gt => actual > expected,
+        Op::Ge => actual >= expected,
+        Op::Lt => actual < expected,
+        Op::Le => actual <= expected,
+        Op::Eq => actual == expected,
+        Op::Ne => actual != expected,
+        Op::Contains | Op::NotContains => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Num(f64),
+    Str(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Ident(s) => write!(f, "`{s}`"),
+            TokenKind::Num(n) => write!(f, "`{n}`"),
+            TokenKind::Str(s) => write!(f, "\"{s}\""),
+            TokenKind::Op(op) => write!(f, "operator {op:?}"),
+            TokenKind::And => write!(f, "`&&`"),
+            TokenKind::Or => write!(f, "`||`"),
+            TokenKind::Not => write!(f, "`!`"),
+            TokenKind::LParen => write!(f, "`(`"),
+            TokenKind::RParen => write!(f, "`)`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// 1-based column of the token's first character, for error messages.
+    col: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i + 1;
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let (kind, len) = match (c, two.as_str()) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            (_, "&&") => (TokenKind::And, 2),
+            (_, "||") => (TokenKind::Or, 2),
+            (_, ">=") => (TokenKind::Op(Op::Ge), 2),
+            (_, "<=") => (TokenKind::Op(Op::Le), 2),
+            (_, "==") => (TokenKind::Op(Op::Eq), 2),
+            (_, "!=") => (TokenKind::Op(Op::Ne), 2),
+            (_, "!~") => (TokenKind::Op(Op::NotContains), 2),
+            ('>', _) => (TokenKind::Op(Op::Gt), 1),
+            ('<', _) => (TokenKind::Op(Op::Lt), 1),
+            ('~', _) => (TokenKind::Op(Op::Contains), 1),
+            ('!', _) => (TokenKind::Not, 1),
+            ('(', _) => (TokenKind::LParen, 1),
+            (')', _) => (TokenKind::RParen, 1),
+            ('"', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&ch| ch == '"')
+                    .map(|p| i + 1 + p);
+                let Some(end) = end else {
+                    bail!("unterminated string starting at column {col}");
+                };
+                let s: String = chars[i + 1..end].iter().collect();
+                (TokenKind::Str(s), end + 1 - i)
+            }
+            (c, _) if c.is_ascii_digit() || c == '.' || c == '-' => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .take_while(|ch| ch.is_ascii_digit() || **ch == '.')
+                    .count()
+                    + 1;
+                let text: String = chars[i..i + len].iter().collect();
+                let Ok(n) = text.parse::<f64>() else {
+                    bail!("invalid number `{text}` at column {col}");
+                };
+                (TokenKind::Num(n), len)
+            }
+            (c, _) if is_word_char(c) => {
+                let len = chars[i..].iter().take_while(|ch| is_word_char(**ch)).count();
+                (TokenKind::Ident(chars[i..i + len].iter().collect()), len)
+            }
+            (c, _) => bail!("unexpected character `{c}` at column {col}"),
+        };
+        tokens.push(Token { kind, col });
+        i += len;
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == ':'
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let Some(tok) = self.tokens.get(self.pos).cloned() else {
+            bail!("unexpected end of expression");
+        };
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn expr(&mut self) -> Result<Selector> {
+        let mut lhs = self.and()?;
+        while self.peek() == Some(&TokenKind::Or) {
+            self.pos += 1;
+            lhs = Selector::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Selector> {
+        let mut lhs = self.unary()?;
+        while self.peek() == Some(&TokenKind::And) {
+            self.pos += 1;
+            lhs = Selector::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Selector> {
+        match self.peek() {
+            Some(TokenKind::Not) => {
+                self.pos += 1;
+                Ok(Selector::Not(Box::new(self.unary()?)))
+            }
+            Some(TokenKind::LParen) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                let close = self.next()?;
+                if close.kind != TokenKind::RParen {
+                    bail!("expected `)` at column {}, found {}", close.col, close.kind);
+                }
+                Ok(inner)
+            }
+            _ => self.compare(),
+        }
+    }
+
+    fn compare(&mut self) -> Result<Selector> {
+        let tok = self.next()?;
+        let field = match &tok.kind {
+            TokenKind::Ident(name) => match name.as_str() {
+                "name" => Field::Name,
+                "module" => Field::Module,
+                "cc" | "complexity" => Field::Complexity,
+                "cov" | "coverage" => Field::Coverage,
+                "crap" => Field::Crap,
+                other => bail!(
+                    "unknown field `{other}` at column {} (expected name, module, cc, cov or crap)",
+                    tok.col
+                ),
+            },
+            other => bail!("expected a field at column {}, found {other}", tok.col),
+        };
+        let tok = self.next()?;
+        let TokenKind::Op(op) = tok.kind else {
+            bail!("expected a comparison operator at column {}, found {}", tok.col, tok.kind);
+        };
+        let tok = self.next()?;
+        let value = match tok.kind {
+            TokenKind::Num(n) => Value::Num(n),
+            TokenKind::Str(s) | TokenKind::Ident(s) => Value::Str(s),
+            other => bail!("expected a value at column {}, found {other}", tok.col),
+        };
+        let numeric = matches!(field, Field::Complexity | Field::Coverage | Field::Crap);
+        match (&value, numeric) {
+            (Value::Str(_), true) => bail!("field at column {} needs a numeric value", tok.col),
+            (Value::Num(_), false) => {
+                bail!("field at column {} needs a string value (quote it)", tok.col)
+            }
+            _ if numeric && matches!(op, Op::Contains | Op::NotContains) => {
+                bail!("`~` / `!~` only apply to name and module")
+            }
+            _ => {}
+        }
+        Ok(Selector::Compare(field, op, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, module: &str, cc: u32, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: module.into(),
+            complexity: cc,
+            coverage: crap.map(|_| 40.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    fn sel(s: &str) -> Selector {
+        Selector::parse(s).unwrap()
+    }
+
+    #[test]
+    fn numeric_and_substring() {
+        let s = sel(r#"crap>30 && module~"core""#);
+        assert!(s.matches(&entry("f", "app::core::x", 5, Some(31.0))));
+        assert!(!s.matches(&entry("f", "app::core::x", 5, Some(30.0))));
+        assert!(!s.matches(&entry("f", "app::cli", 5, Some(99.0))));
+    }
+
+    #[test]
+    fn or_binds_looser_than_and() {
+        let s = sel("cc >= 10 || crap > 30 && cov < 50");
+        assert!(s.matches(&entry("f", "m", 12, Some(1.0))));
+        assert!(!s.matches(&entry("f", "m", 2, Some(1.0))));
+    }
+
+    #[test]
+    fn parentheses_and_negation() {
+        let s = sel(r#"!(name == "main" || module !~ parser)"#);
+        assert!(s.matches(&entry("parse", "core::parser", 1, Some(1.0))));
+        assert!(!s.matches(&entry("main", "core::parser", 1, Some(1.0))));
+        assert!(!s.matches(&entry("run", "cli", 1, Some(1.0))));
+    }
+
+    #[test]
+    fn missing_scores_never_compare_true() {
+        let e = entry("f", "m", 3, None);
+        assert!(!sel("crap > 0").matches(&e));
+        assert!(!sel("crap <= 0").matches(&e));
+        assert!(sel("!(crap > 30)").matches(&e));
+    }
+
+    #[test]
+    fn errors_point_at_column() {
+        let err = Selector::parse("crap > 30 && bogus < 1").unwrap_err().to_string();
+        assert!(err.contains("unknown field `bogus` at column 14"), "{err}");
+        let err = Selector::parse("crap > ").unwrap_err().to_string();
+        assert!(err.contains("unexpected end"), "{err}");
+        let err = Selector::parse("(crap > 1").unwrap_err().to_string();
+        assert!(err.contains("unexpected end"), "{err}");
+        let err = Selector::parse(r#"module ~ "core"#).unwrap_err().to_string();
+        assert!(err.contains("unterminated string"), "{err}");
+    }
+
+    #[test]
+    fn type_mismatches_are_rejected() {
+        assert!(Selector::parse(r#"crap > "high""#).is_err());
+        assert!(Selector::parse("module == 3").is_err());
+        assert!(Selector::parse("cc ~ 3").is_err());
+    }
+
+    #[test]
+    fn trailing_tokens_are_rejected() {
+        assert!(Selector::parse("crap > 1 crap").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+
+use crate::crap::{self, CrapEntry};
+use crate::metadata::RunMetadata;
+
+/// Tables written by [`write_sqlite`]. Existing tables are dropped first so a
+/// database always describes exactly one run.
+pub const SCHEMA: &str = "
+DROP TABLE IF EXISTS entries;
+DROP TABLE IF EXISTS summary;
+DROP TABLE IF EXISTS decision_points;
+DROP TABLE IF EXISTS metadata;
+CREATE TABLE entries (
+    name        TEXT    NOT NULL,
+    module      TEXT    NOT NULL,
+    complexity  INTEGER NOT NULL,
+    coverage    REAL,             -- NULL when the file was not instrumented
+    crap        REAL              -- NULL when coverage is NULL
+);
+CREATE TABLE decision_points (
+    entry       INTEGER NOT NULL, -- rowid in entries
+    kind        TEXT    NOT NULL, -- see DecisionKind::label
+    line        INTEGER NOT NULL
+);
+CREATE TABLE summary (
+    created_at      INTEGER NOT NULL, -- unix seconds
+    craprs_version  TEXT    NOT NULL,
+    functions       INTEGER NOT NULL,
+    scored          INTEGER NOT NULL,
+    crappy          INTEGER NOT NULL,
+    threshold       REAL    NOT NULL,
+    total_crap      REAL    NOT NULL,
+    max_crap        REAL
+);
+CREATE TABLE metadata (
+    key     TEXT NOT NULL, -- see RunMetadata::pairs
+    value   TEXT NOT NULL
+);
+";
+
+/// Write `entries`, a one-row summary and the run `metadata` into the SQLite
+/// database at `path`, creating it if needed. `threshold` decides what
+/// counts as crappy.
+pub fn write_sqlite(
+    path: &Path,
+    entries: &[CrapEntry],
+    threshold: f64,
+    metadata: &RunMetadata,
+) -> Result<()> {
+    let mut conn = Connection::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO entries (name, module, complexity, coverage, crap) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut insert_point =
+            tx.prepare("INSERT INTO decision_points (entry, kind, line) VALUES (?1, ?2, ?3)")?;
+        for e in entries {
+            let entry = insert.insert(params![
+                e.name,
+                e.module_path,
+                e.complexity,
+                e.coverage,
+                e.crap
+            ])?;
+            for p in &e.decision_points {
+                insert_point.execute(params![entry, p.kind.label(), p.line as i64])?;
+            }
+        }
+    }
+
+    let scores: Vec<f64> = entries.iter().filter_map(|e| e.crap).collect();
+    let crappy = scores.iter().filter(|&&s| s >= threshold).count();
+    let total = crap::total_score(entries);
+    let max = scores.iter().copied().reduce(f64::max);
+    tx.execute(
+        "INSERT INTO summary VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            metadata.timestamp as i64,
+            metadata.craprs_version,
+            entries.len() as i64,
+            scores.len() as i64,
+            crappy as i64,
+            threshold,
+            total,
+            max
+        ],
+    )?;
+    for (key, value) in metadata.pairs() {
+        tx.execute(
+            "INSERT INTO metadata (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complexity::{DecisionKind, DecisionPoint};
+
+    fn entry(name: &str, crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "m".into(),
+            complexity: 3,
+            coverage: crap.map(|_| 50.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn writes_entries_and_summary_and_replaces_previous_run() {
+        let path = std::env::temp_dir().join(format!("craprs_sqlite_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let meta = RunMetadata {
+            craprs_version: "0.2.0".into(),
+            timestamp: 1_714_565_045,
+            ..RunMetadata::default()
+        };
+        write_sqlite(&path, &[entry("old", Some(1.0))], 30.0, &meta).unwrap();
+        let mut a = entry("a", Some(45.0));
+        a.decision_points = vec![
+            DecisionPoint {
+                kind: DecisionKind::If,
+                line: 4,
+            },
+            DecisionPoint {
+                kind: DecisionKind::Try,
+                line: 7,
+            },
+        ];
+        let entries = vec![a, entry("b", Some(2.0)), entry("c", None)];
+        write_sqlite(&path, &entries, 30.0, &meta).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let names: Vec<String> = conn
+            .prepare("SELECT name FROM entries ORDER BY name")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+
+        let null_crap: i64 = conn
+            .query_row("SELECT count(*) FROM entries WHERE crap IS NULL", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(null_crap, 1);
+
+        let points: Vec<(String, String, i64)> = conn
+            .prepare(
+                "SELECT e.name, d.kind, d.line FROM decision_points d \
+                 JOIN entries e ON e.rowid = d.entry ORDER BY d.line",
+            )
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            points,
+            vec![
+                ("a".to_string(), "if".to_string(), 4),
+                ("a".to_string(), "try".to_string(), 7),
+            ]
+        );
+
+        let (functions, scored, crappy, total, max): (i64, i64, i64, f64, f64) = conn
+            .query_row(
+                "SELECT functions, scored, crappy, total_crap, max_crap FROM summary",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!((functions, scored, crappy), (3, 2, 1));
+        assert_eq!(total, 47.0);
+        assert_eq!(max, 45.0);
+
+        let timestamp: String = conn
+            .query_row(
+                "SELECT value FROM metadata WHERE key = 'timestamp'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(timestamp, "2024-05-01T12:04:05Z");
+        let rows: i64 = conn
+            .query_row("SELECT count(*) FROM metadata", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(rows, 3);
+
+        write_sqlite(&path, &[], 30.0, &meta).unwrap();
+        let conn = Connection::open(&path).unwrap();
+        let (total, max): (f64, Option<f64>) = conn
+            .query_row("SELECT total_crap, max_crap FROM summary", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert!(total == 0.0 && total.is_sign_positive());
+        assert_eq!(max, None);
+        let _ = std::fs::remove_file(&path);
+    }
+}
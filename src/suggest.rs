@@ -0,0 +1,137 @@
+use crate::crap::{CrapEntry, ScoreKind};
+
+/// A function worth writing tests for, with what covering it buys.
+#[derive(Debug, Clone)]
+pub struct Suggestion<'a> {
+    pub entry: &'a CrapEntry,
+    /// Score reduction from covering one more line, at the current coverage,
+    /// capped at `total`: the linear estimate overshoots on short functions.
+    pub per_line: f64,
+    /// Score reduction from covering every uncovered line.
+    pub total: f64,
+}
+
+/// Score reduction per newly covered line: the derivative of the score with
+/// respect to coverage, scaled to one line. A function with `u` of `n`
+/// instrumented lines uncovered has `1 - cov = u / n`, so for CRAP
+/// (`CC² × (1 − cov)³ + CC`) that is `3 × CC² × (1 − cov)² / n`, and for
+/// uncovered CC (`CC × (1 − cov)`) it is `CC / n`. `None` without coverage
+/// data or uncovered lines.
+pub fn gain_per_line(entry: &CrapEntry, kind: ScoreKind) -> Option<f64> {
+    let uncovered = entry.uncovered_lines.len() as f64;
+    let missed = 1.0 - entry.coverage? / 100.0;
+    if uncovered == 0.0 || missed <= 0.0 {
+        return None;
+    }
+    let lines = uncovered / missed;
+    let cc = entry.complexity as f64;
+    Some(match kind {
+        ScoreKind::Crap => 3.0 * cc * cc * missed * missed / lines,
+        ScoreKind::UncoveredCc => cc / lines,
+    })
+}
+
+/// The `n` functions where each new covered line lowers the score most,
+/// best first; ties go to the larger total reduction.
+pub fn suggest_tests(entries: &[CrapEntry], kind: ScoreKind, n: usize) -> Vec<Suggestion<'_>> {
+    let mut suggestions: Vec<Suggestion> = entries
+        .iter()
+        .filter_map(|entry| {
+            let covered = kind.score(entry.complexity, Some(100.0))?;
+            let total = entry.crap? - covered;
+            Some(Suggestion {
+                entry,
+                per_line: gain_per_line(entry, kind)?.min(total),
+                total,
+            })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.per_line
+            .total_cmp(&a.per_line)
+            .then(b.total.total_cmp(&a.total))
+    });
+    suggestions.truncate(n);
+    suggestions
+}
+
+/// Numbered list of [`suggest_tests`], e.g.
+/// `   1. parse (core) src/parse.rs:12 — 6 uncovered line(s), -4.2 CRAP per line, -18.0 in total`.
+pub fn format_suggestions(suggestions: &[Suggestion], score_label: &str) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("Best places to add tests ({score_label} reduction per covered line):\n");
+    for (i, s) in suggestions.iter().enumerate() {
+        out.push_str(&format!(
+            "  {:>2}. {} ({}) {} — {} uncovered line(s), -{:.1} {score_label} per line, -{:.1} in total\n",
+            i + 1,
+            s.entry.name,
+            s.entry.module_path,
+            s.entry.location(),
+            s.entry.uncovered_lines.len(),
+            s.per_line,
+            s.total
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, complexity: u32, coverage: f64, uncovered: usize) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: "m".into(),
+            complexity,
+            coverage: Some(coverage),
+            crap: ScoreKind::Crap.score(complexity, Some(coverage)),
+            uncovered_lines: (1..=uncovered).collect(),
+            file: PathBuf::from("src/m.rs"),
+            start_line: 1,
+            end_line: 20,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn gain_is_the_scaled_derivative() {
+        // CC 4, 2 of 8 lines covered: 3 × 16 × 0.75² / 8.
+        let e = entry("f", 4, 25.0, 6);
+        assert!((gain_per_line(&e, ScoreKind::Crap).unwrap() - 3.375).abs() < 1e-9);
+        assert!((gain_per_line(&e, ScoreKind::UncoveredCc).unwrap() - 0.5).abs() < 1e-9);
+        assert_eq!(
+            gain_per_line(&entry("done", 4, 100.0, 0), ScoreKind::Crap),
+            None
+        );
+        let unknown = CrapEntry {
+            coverage: None,
+            ..entry("unknown", 4, 0.0, 6)
+        };
+        assert_eq!(gain_per_line(&unknown, ScoreKind::Crap), None);
+    }
+
+    #[test]
+    fn ranks_by_gain_per_line() {
+        let entries = vec![
+            // Same score as `short`, but spread over many more lines.
+            entry("long", 6, 0.0, 60),
+            entry("short", 6, 0.0, 4),
+            entry("covered", 9, 100.0, 0),
+            entry("mild", 2, 50.0, 2),
+        ];
+        let picked = suggest_tests(&entries, ScoreKind::Crap, 2);
+        let names: Vec<&str> = picked.iter().map(|s| s.entry.name.as_str()).collect();
+        assert_eq!(names, vec!["short", "long"]);
+        assert_eq!(picked[0].total, 36.0);
+        assert_eq!(
+            format_suggestions(&picked[..1], "CRAP"),
+            "Best places to add tests (CRAP reduction per covered line):\n\
+             \x20  1. short (m) src/m.rs:1 — 4 uncovered line(s), -27.0 CRAP per line, -36.0 in total\n"
+        );
+        assert_eq!(format_suggestions(&[], "CRAP"), "");
+    }
+}
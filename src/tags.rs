@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+
+/// Tags every function in source files matching a path pattern, e.g.
+/// `src/ffi/**/*.rs => ffi`, so reports can be grouped by architectural
+/// concern with `--group-by tag`. Paths are relative to the project dir and
+/// matched like [`ModuleRule`](crate::module_map::ModuleRule)s.
+#[derive(Debug, Clone)]
+pub struct TagRule {
+    pub rule: String,
+    pattern: Pattern,
+    pub tag: String,
+}
+
+impl TagRule {
+    pub fn parse(s: &str) -> Result<TagRule, String> {
+        let Some((pattern, tag)) = s.split_once("=>") else {
+            return Err(format!("expected `<path pattern> => <tag>`, got `{s}`"));
+        };
+        let pattern = pattern.trim().trim_start_matches("./");
+        let tag = tag.trim();
+        if pattern.is_empty() {
+            return Err(format!("empty path pattern in `{s}`"));
+        }
+        if tag.is_empty() {
+            return Err(format!("empty tag in `{s}`"));
+        }
+        let compiled = Pattern::new(pattern)
+            .map_err(|err| format!("invalid path pattern `{pattern}`: {err}"))?;
+        Ok(TagRule {
+            rule: format!("{pattern} => {tag}"),
+            pattern: compiled,
+            tag: tag.to_string(),
+        })
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.pattern.matches_path_with(path, options)
+    }
+}
+
+/// A function's tags: `own` (from its attributes) followed by the tags of
+/// every rule matching `path`, without duplicates. Unlike module rules, all
+/// matching rules apply.
+pub fn tags_for(rules: &[TagRule], path: &Path, own: &[String]) -> Vec<String> {
+    let mut tags = Vec::new();
+    let matched = rules.iter().filter(|r| r.matches(path)).map(|r| &r.tag);
+    for tag in own.iter().chain(matched) {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules() {
+        let rule = TagRule::parse("./src/ffi/** => ffi").unwrap();
+        assert_eq!(rule.rule, "src/ffi/** => ffi");
+        assert_eq!(rule.tag, "ffi");
+        assert!(TagRule::parse("src/ffi/** ffi").is_err());
+        assert!(TagRule::parse("src/ffi/** => ").is_err());
+        assert!(TagRule::parse(" => ffi").is_err());
+        assert!(TagRule::parse("src/[.rs => ffi").is_err());
+    }
+
+    #[test]
+    fn every_matching_rule_adds_its_tag() {
+        let rules = vec![
+            TagRule::parse("src/ffi/**/*.rs => ffi").unwrap(),
+            TagRule::parse("src/*/parse*.rs => parser").unwrap(),
+            TagRule::parse("src/**/*.rs => core").unwrap(),
+        ];
+        let tags = |p: &str, own: &[&str]| {
+            let own: Vec<String> = own.iter().map(|t| t.to_string()).collect();
+            tags_for(&rules, Path::new(p), &own)
+        };
+        assert_eq!(
+            tags("./src/ffi/parse_c.rs", &["unsafe"]),
+            vec!["unsafe", "ffi", "parser", "core"]
+        );
+        assert_eq!(tags("src/lib.rs", &["core"]), vec!["core"]);
+        assert!(tags("benches/main.rs", &[]).is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{TokenStream, TokenTree};
+use syn::visit::Visit;
+use syn::{
+    Expr, ExprLit, ExprMethodCall, ExprPath, ImplItem, Item, ItemMod, Lit, Macro, Visibility,
+};
+
+use crate::complexity::{has_cfg_test_attr, has_test_attr};
+use crate::crap::CrapEntry;
+
+/// Names referenced from production code and from test code (`#[test]`
+/// functions and `#[cfg(test)]` items) across a crate. Matching is by bare
+/// identifier, so it errs toward "used in production".
+#[derive(Debug, Default)]
+pub struct References {
+    prod: HashSet<String>,
+    test: HashSet<String>,
+}
+
+impl References {
+    /// Record the references in one source file. Unparseable files are skipped.
+    /// `in_test` marks the whole file as test code, see [`test_files`].
+    pub fn add_source(&mut self, source: &str, in_test: bool) {
+        let Ok(file) = syn::parse_file(source) else {
+            return;
+        };
+        let mut visitor = ReferenceVisitor {
+            refs: self,
+            in_test,
+        };
+        visitor.visit_file(&file);
+    }
+
+    /// True when `name` is used from test code and nowhere else.
+    pub fn test_only(&self, name: &str) -> bool {
+        self.test.contains(name) && !self.prod.contains(name)
+    }
+}
+
+struct ReferenceVisitor<'a> {
+    refs: &'a mut References,
+    in_test: bool,
+}
+
+impl ReferenceVisitor<'_> {
+    fn add(&mut self, name: String) {
+        if self.in_test {
+            self.refs.test.insert(name);
+        } else {
+            self.refs.prod.insert(name);
+        }
+    }
+
+    /// Macro bodies aren't parsed, so every identifier inside counts as a reference.
+    fn add_tokens(&mut self, tokens: &TokenStream) {
+        for tt in tokens.clone() {
+            match tt {
+                TokenTree::Ident(ident) => self.add(ident.to_string()),
+                TokenTree::Group(group) => self.add_tokens(&group.stream()),
+                _ => {}
+            }
+        }
+    }
+
+    fn with_test<F: FnOnce(&mut Self)>(&mut self, is_test: bool, f: F) {
+        let prev = self.in_test;
+        self.in_test |= is_test;
+        f(self);
+        self.in_test = prev;
+    }
+}
+
+impl<'ast> Visit<'ast> for ReferenceVisitor<'_> {
+    fn visit_item(&mut self, node: &'ast Item) {
+        let is_test = match node {
+            Item::Mod(m) => has_cfg_test_attr(&m.attrs),
+            Item::Fn(f) => has_test_attr(&f.attrs) || has_cfg_test_attr(&f.attrs),
+            Item::Impl(i) => has_cfg_test_attr(&i.attrs),
+            _ => false,
+        };
+        self.with_test(is_test, |v| syn::visit::visit_item(v, node));
+    }
+
+    fn visit_impl_item(&mut self, node: &'ast ImplItem) {
+        let is_test = match node {
+            ImplItem::Fn(f) => has_test_attr(&f.attrs) || has_cfg_test_attr(&f.attrs),
+            _ => false,
+        };
+        self.with_test(is_test, |v| syn::visit::visit_impl_item(v, node));
+    }
+
+    fn visit_expr_path(&mut self, node: &'ast ExprPath) {
+        if let Some(last) = node.path.segments.last() {
+            self.add(last.ident.to_string());
+        }
+        syn::visit::visit_expr_path(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        self.add(node.method.to_string());
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        self.add_tokens(&node.tokens);
+    }
+}
+
+/// The files among `sources` that are test code as a whole: out-of-line
+/// modules declared under `#[cfg(test)]`, like `src/parser/tests.rs` for
+/// `#[cfg(test)] mod tests;` in `src/parser.rs`, and the modules those
+/// declare in turn. `sources` maps each file to its content.
+pub fn test_files(sources: &HashMap<PathBuf, String>) -> HashSet<PathBuf> {
+    let mut found = HashSet::new();
+    let mut pending: Vec<(&PathBuf, bool)> = sources.keys().map(|path| (path, false)).collect();
+    while let Some((path, in_test)) = pending.pop() {
+        let Ok(file) = syn::parse_file(&sources[path]) else {
+            continue;
+        };
+        let mut visitor = ModuleVisitor {
+            file_dir: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            dir: module_dir(path),
+            nested: false,
+            in_test,
+            files: Vec::new(),
+        };
+        visitor.visit_file(&file);
+        for file in visitor.files {
+            if let Some((path, _)) = sources.get_key_value(&file)
+                && found.insert(path.clone())
+            {
+                pending.push((path, true));
+            }
+        }
+    }
+    found
+}
+
+/// Directory the out-of-line modules declared in `path` live in: next to
+/// a crate root or `mod.rs`, in a directory named after the file otherwise.
+fn module_dir(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    match path.file_stem().and_then(|s| s.to_str()) {
+        Some("lib" | "main" | "mod") | None => parent.to_path_buf(),
+        Some(stem) => parent.join(stem),
+    }
+}
+
+/// Collects the files of out-of-line modules declared in test code.
+struct ModuleVisitor {
+    /// Directory of the file itself, which top-level `#[path]`s are relative to.
+    file_dir: PathBuf,
+    /// Where the modules declared at the current nesting level live.
+    dir: PathBuf,
+    /// Inside an inline `mod { ... }`.
+    nested: bool,
+    in_test: bool,
+    files: Vec<PathBuf>,
+}
+
+impl<'ast> Visit<'ast> for ModuleVisitor {
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        let in_test = self.in_test || has_cfg_test_attr(&node.attrs);
+        let name = node.ident.to_string();
+        if node.content.is_none() {
+            if in_test {
+                match path_attr(node) {
+                    Some(path) if self.nested => self.files.push(self.dir.join(path)),
+                    Some(path) => self.files.push(self.file_dir.join(path)),
+                    None => self.files.extend([
+                        self.dir.join(format!("{name}.rs")),
+                        self.dir.join(&name).join("mod.rs"),
+                    ]),
+                }
+            }
+            return;
+        }
+        let dir = self.dir.join(path_attr(node).unwrap_or(name));
+        let outer = std::mem::replace(&mut self.dir, dir);
+        let outer_test = std::mem::replace(&mut self.in_test, in_test);
+        let outer_nested = std::mem::replace(&mut self.nested, true);
+        syn::visit::visit_item_mod(self, node);
+        self.dir = outer;
+        self.in_test = outer_test;
+        self.nested = outer_nested;
+    }
+}
+
+/// The `#[path = "..."]` a module is declared with, if any.
+fn path_attr(node: &ItemMod) -> Option<String> {
+    node.attrs.iter().find_map(|a| match &a.meta {
+        syn::Meta::NameValue(nv) if nv.path.is_ident("path") => match &nv.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(s), ..
+            }) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Non-`pub` free functions and inherent methods outside test code, as
+/// `(identifier, line of the identifier)`. Trait impl methods are left out —
+/// they're reached through the trait, not by name.
+pub fn private_functions(source: &str) -> Vec<(String, usize)> {
+    let Ok(file) = syn::parse_file(source) else {
+        return Vec::new();
+    };
+    let mut visitor = DefinitionVisitor { found: Vec::new() };
+    visitor.visit_file(&file);
+    visitor.found
+}
+
+struct DefinitionVisitor {
+    found: Vec<(String, usize)>,
+}
+
+impl<'ast> Visit<'ast> for DefinitionVisitor {
+    fn visit_item(&mut self, node: &'ast Item) {
+        match node {
+            Item::Mod(m) if has_cfg_test_attr(&m.attrs) => {}
+            Item::Fn(f) if has_test_attr(&f.attrs) || has_cfg_test_attr(&f.attrs) => {}
+            Item::Impl(i) if i.trait_.is_some() || has_cfg_test_attr(&i.attrs) => {}
+            Item::Fn(f) => {
+                if !matches!(f.vis, Visibility::Public(_)) {
+                    let ident = &f.sig.ident;
+                    self.found
+                        .push((ident.to_string(), ident.span().start().line));
+                }
+                syn::visit::visit_item(self, node);
+            }
+            _ => syn::visit::visit_item(self, node),
+        }
+    }
+
+    fn visit_impl_item(&mut self, node: &'ast ImplItem) {
+        if let ImplItem::Fn(f) = node
+            && !matches!(f.vis, Visibility::Public(_))
+            && !has_test_attr(&f.attrs)
+            && !has_cfg_test_attr(&f.attrs)
+        {
+            let ident = &f.sig.ident;
+            self.found
+                .push((ident.to_string(), ident.span().start().line));
+        }
+    }
+}
+
+/// Note listing test-only helpers left out of the report; empty when there are none.
+pub fn format_test_only(entries: &[CrapEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    let mut out = format!(
+        "note: {} function(s) are only called from test code but not gated by \
+         #[cfg(test)]; left out of the report:\n",
+        entries.len()
+    );
+    for e in entries {
+        out.push_str(&format!("  {} ({})\n", e.name, e.module_path));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = r#"
+pub fn api() -> u8 { used() }
+fn used() -> u8 { 1 }
+fn fixture() -> u8 { 2 }
+pub(crate) fn builder() -> Foo { Foo }
+fn orphan() {}
+
+struct Foo;
+impl Foo {
+    fn probe(&self) -> u8 { 3 }
+}
+impl Default for Foo {
+    fn default() -> Self { Foo }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn local_helper() {}
+
+    #[test]
+    fn works() {
+        assert_eq!(fixture(), Foo.probe() - 1);
+        let _ = builder();
+        local_helper();
+    }
+}
+"#;
+
+    #[test]
+    fn flags_private_functions_only_called_from_tests() {
+        let mut refs = References::default();
+        refs.add_source(SRC, false);
+        let flagged: Vec<String> = private_functions(SRC)
+            .into_iter()
+            .filter(|(name, _)| refs.test_only(name))
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(flagged, vec!["fixture", "builder", "probe"]);
+    }
+
+    #[test]
+    fn production_use_in_another_file_clears_the_flag() {
+        let mut refs = References::default();
+        refs.add_source(SRC, false);
+        refs.add_source("pub fn run() { let _ = crate::fixture(); }", false);
+        assert!(!refs.test_only("fixture"));
+        assert!(refs.test_only("builder"));
+    }
+
+    #[test]
+    fn out_of_line_test_modules_are_test_code() {
+        let sources: HashMap<PathBuf, String> = [
+            (
+                "src/lib.rs",
+                "pub fn api() {}\nfn helper() {}\n#[cfg(test)]\nmod tests;\nmod parser;",
+            ),
+            (
+                "src/tests.rs",
+                "use super::*;\nmod support;\n#[test]\nfn t() { helper(); }",
+            ),
+            ("src/tests/support.rs", "pub fn setup() { crate::parse(); }"),
+            (
+                "src/parser.rs",
+                "pub fn parse() {}\nmod inner { #[cfg(test)] mod tests; }",
+            ),
+            (
+                "src/parser/inner/tests.rs",
+                "fn uses() { super::super::parse(); }",
+            ),
+        ]
+        .into_iter()
+        .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+        .collect();
+        let mut tests: Vec<PathBuf> = test_files(&sources).into_iter().collect();
+        tests.sort();
+        assert_eq!(
+            tests,
+            [
+                "src/parser/inner/tests.rs",
+                "src/tests/support.rs",
+                "src/tests.rs"
+            ]
+            .map(PathBuf::from)
+        );
+
+        let mut refs = References::default();
+        for (path, source) in &sources {
+            refs.add_source(source, tests.contains(path));
+        }
+        assert!(refs.test_only("helper"));
+        assert!(refs.test_only("parse"));
+    }
+
+    #[test]
+    fn format_lists_helpers_or_nothing() {
+        assert!(format_test_only(&[]).is_empty());
+        let entry = CrapEntry {
+            name: "fixture".into(),
+            module_path: "parse".into(),
+            complexity: 1,
+            coverage: Some(100.0),
+            crap: Some(1.0),
+            ..Default::default()
+        };
+        let out = format_test_only(&[entry]);
+        assert!(out.starts_with("note: 1 function(s) are only called from test code"));
+        assert!(out.contains("  fixture (parse)\n"));
+    }
+
+    #[test]
+    fn definitions_carry_their_line() {
+        let defs = private_functions(SRC);
+        assert_eq!(defs[0], ("used".to_string(), 3));
+        assert!(!defs.iter().any(|(n, _)| n == "api" || n == "default"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Test-support API for enforcing a CRAP policy from a crate's own tests.
+//!
+//! ```ignore
+//! #[test]
+//! fn no_crappy_functions() {
+//!     craprs::assert_crap!("target/lcov.info", 30.0);
+//! }
+//! ```
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::baseline;
+use crate::complexity;
+use crate::coverage;
+use crate::crap::{self, CrapEntry};
+use crate::hints;
+use crate::public_api;
+
+/// Score every function under `manifest_dir/src` against a pre-generated lcov
+/// file (relative paths are resolved against `manifest_dir`) and return those
+/// with CRAP at or above `threshold`, worst first. Files missing from the
+/// lcov data are skipped — they can't be scored.
+pub fn crappy_functions(
+    manifest_dir: &Path,
+    lcov: &Path,
+    threshold: f64,
+) -> Result<Vec<CrapEntry>> {
+    let lcov = manifest_dir.join(lcov);
+    let content = std::fs::read_to_string(&lcov)
+        .with_context(|| format!("failed to read {}", lcov.display()))?;
+    let file_coverage = coverage::parse_lcov(&content);
+    let file_branches = coverage::parse_lcov_branches(&content);
+
+    let src_dir = manifest_dir.join("src");
+    let public_files = public_api::public_files(&src_dir);
+    let pattern = format!("{}/**/*.rs", src_dir.display());
+    let mut entries = Vec::new();
+    for path in glob::glob(&pattern)? {
+        let path = path?;
+        let Some(line_cov) = coverage::find_coverage_for_file(&path, &file_coverage) else {
+            continue;
+        };
+        let source = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let module_path = coverage::source_to_module_path(&path, &src_dir);
+        let file_hash = baseline::content_hash(&source);
+        let branches = coverage::find_coverage_for_file(&path, &file_branches);
+        for f in complexity::extract_functions(&source) {
+            let cov = coverage::coverage_for_range(&line_cov, f.start_line, f.end_line);
+            let score = crap::crap_score(f.complexity, Some(cov));
+            if score.is_some_and(|s| s >= threshold) {
+                entries.push(CrapEntry {
+                    name: f.name,
+                    module_path: module_path.clone(),
+                    complexity: f.complexity,
+                    coverage: Some(cov),
+                    crap: score,
+                    uncovered_lines: coverage::uncovered_lines(&line_cov, f.start_line, f.end_line),
+                    hints: hints::refactoring_hints(&f.shape),
+                    decision_points: f.decision_points,
+                    file: path.clone(),
+                    start_line: f.start_line,
+                    end_line: f.end_line,
+                    file_hash: file_hash.clone(),
+                    fingerprint: f.fingerprint,
+                    dyn_calls: f.dyn_calls,
+                    branch_coverage: branches.as_ref().and_then(|b| {
+                        coverage::branch_coverage_for_range(b, f.start_line, f.end_line)
+                    }),
+                    tags: f.tags,
+                    public: f.public && !f.in_private_mod && public_files.contains(&path),
+                });
+            }
+        }
+    }
+    crap::sort_entries(&mut entries);
+    Ok(entries)
+}
+
+/// Panic message listing the functions returned by [`crappy_functions`].
+pub fn failure_message(entries: &[CrapEntry], threshold: f64) -> String {
+    let mut msg = format!("{} function(s) with CRAP >= {threshold}:\n", entries.len());
+    for e in entries {
+        msg.push_str(&format!(
+            "  {} ({}) — CRAP {:.1}, CC {}, {:.1}% covered\n",
+            e.name,
+            e.module_path,
+            e.crap.unwrap_or(0.0),
+            e.complexity,
+            e.coverage.unwrap_or(0.0)
+        ));
+    }
+    msg
+}
+
+/// Fail the calling test if any function in the calling crate's `src/` scores
+/// at or above the threshold (default [`crap::CRAPPY_THRESHOLD`]) against the
+/// given lcov file, which is resolved relative to the crate's manifest dir.
+#[macro_export]
+macro_rules! assert_crap {
+    ($lcov:expr) => {
+        $crate::assert_crap!($lcov, $crate::crap::CRAPPY_THRESHOLD)
+    };
+    ($lcov:expr, $threshold:expr) => {{
+        let threshold: f64 = $threshold;
+        let manifest_dir = ::std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        match $crate::testing::crappy_functions(
+            manifest_dir,
+            ::std::path::Path::new($lcov),
+            threshold,
+        ) {
+            Ok(entries) if entries.is_empty() => {}
+            Ok(entries) => panic!("{}", $crate::testing::failure_message(&entries, threshold)),
+            Err(err) => panic!("assert_crap!: {err:#}"),
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_only_functions_at_or_above_threshold() {
+        let dir = std::env::temp_dir().join(format!("craprs-testing-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("src/lib.rs"),
+            "fn simple() {}\n\nfn branchy(a: bool, b: bool) {\n    if a {}\n    if b {}\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("lcov.info"),
+            "SF:src/lib.rs\nDA:1,1\nDA:3,0\nDA:4,0\nDA:5,0\nend_of_record\n",
+        )
+        .unwrap();
+
+        let found = crappy_functions(&dir, Path::new("lcov.info"), 10.0).unwrap();
+        let names: Vec<&str> = found.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["branchy"]);
+        assert!(failure_message(&found, 10.0).contains("branchy (lib)"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::crap::CrapEntry;
+
+/// A named score band, e.g. `warn < 30`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tier {
+    pub name: String,
+    /// Scores below this belong to the tier; `None` for the last tier,
+    /// which takes everything from the previous cutoff up.
+    pub below: Option<f64>,
+}
+
+/// Risk tiers from a policy's `tiers = ["ok < 15", "warn < 30", "fail >= 30"]`,
+/// lowest first. Every tier but the last has an exclusive upper cutoff; the
+/// last repeats the previous cutoff as its lower bound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tiers {
+    pub tiers: Vec<Tier>,
+}
+
+impl Tiers {
+    pub fn parse(specs: &[String]) -> Result<Tiers, String> {
+        if specs.len() < 2 {
+            return Err(
+                "tiers need at least two entries, e.g. [\"ok < 30\", \"fail >= 30\"]".into(),
+            );
+        }
+        let mut tiers: Vec<Tier> = Vec::new();
+        let mut cutoff: Option<f64> = None;
+        for (i, spec) in specs.iter().enumerate() {
+            let last = i == specs.len() - 1;
+            let op = if last { ">=" } else { "<" };
+            let Some((name, bound)) = spec.split_once(op) else {
+                return Err(format!("expected `<name> {op} <score>`, got `{spec}`"));
+            };
+            let name = name.trim();
+            let bound: f64 = bound
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid score `{}` in `{spec}`", bound.trim()))?;
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(format!("invalid tier name `{name}` in `{spec}`"));
+            }
+            if tiers.iter().any(|t| t.name == name) {
+                return Err(format!("tier `{name}` given twice"));
+            }
+            match cutoff {
+                Some(prev) if last && bound != prev => {
+                    return Err(format!(
+                        "last tier must start at the previous cutoff {prev}, got `{spec}`"
+                    ));
+                }
+                Some(prev) if !last && bound <= prev => {
+                    return Err(format!(
+                        "tier cutoffs must increase, got `{spec}` after {prev}"
+                    ));
+                }
+                _ => {}
+            }
+            cutoff = Some(bound);
+            tiers.push(Tier {
+                name: name.to_string(),
+                below: (!last).then_some(bound),
+            });
+        }
+        Ok(Tiers { tiers })
+    }
+
+    /// Index of the tier `score` falls in.
+    pub fn classify(&self, score: f64) -> usize {
+        self.tiers
+            .iter()
+            .position(|t| t.below.is_some_and(|below| score < below))
+            .unwrap_or(self.tiers.len() - 1)
+    }
+
+    /// Scored entries per tier, in tier order; unscored entries aren't counted.
+    pub fn counts(&self, entries: &[CrapEntry]) -> Vec<usize> {
+        let mut counts = vec![0; self.tiers.len()];
+        for score in entries.iter().filter_map(|e| e.crap) {
+            counts[self.classify(score)] += 1;
+        }
+        counts
+    }
+}
+
+/// One line, e.g. `tiers: ok 40, warn 3, fail 1`.
+pub fn format_tier_counts(tiers: &Tiers, entries: &[CrapEntry]) -> String {
+    let counts: Vec<String> = tiers
+        .tiers
+        .iter()
+        .zip(tiers.counts(entries))
+        .map(|(t, n)| format!("{} {n}", t.name))
+        .collect();
+    format!("tiers: {}\n", counts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn entry(crap: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: "f".into(),
+            complexity: 1,
+            coverage: crap.map(|_| 0.0),
+            crap,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classifies_and_counts_scored_entries() {
+        let tiers = Tiers::parse(&specs(&["ok<15", "warn < 30", "fail>=30"])).unwrap();
+        assert_eq!(tiers.tiers[1].below, Some(30.0));
+        assert_eq!(tiers.tiers[2].below, None);
+        assert_eq!(tiers.classify(14.9), 0);
+        assert_eq!(tiers.classify(15.0), 1);
+        assert_eq!(tiers.classify(30.0), 2);
+        let entries: Vec<CrapEntry> = [Some(1.0), Some(2.0), Some(20.0), Some(99.0), None]
+            .into_iter()
+            .map(entry)
+            .collect();
+        assert_eq!(tiers.counts(&entries), vec![2, 1, 1]);
+        assert_eq!(
+            format_tier_counts(&tiers, &entries),
+            "tiers: ok 2, warn 1, fail 1\n"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_tiers() {
+        for bad in [
+            &["fail >= 30"][..],
+            &["ok < 30", "fail > 30"],
+            &["ok < 30", "fail >= 20"],
+            &["ok < 30", "warn < 15", "fail >= 15"],
+            &["ok < x", "fail >= 30"],
+            &["ok < 15", "ok >= 15"],
+            &["< 15", "fail >= 15"],
+        ] {
+            assert!(Tiers::parse(&specs(bad)).is_err(), "{bad:?}");
+        }
+    }
+}
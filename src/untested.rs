@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+use crate::crap::CrapEntry;
+
+/// Entries with CC at or above `min_cc` whose observed coverage is exactly 0%.
+/// Uninstrumented entries (no coverage data) are not included — we don't know
+/// that they're untested.
+pub fn untested(entries: &[CrapEntry], min_cc: u32) -> Vec<&CrapEntry> {
+    entries
+        .iter()
+        .filter(|e| e.complexity >= min_cc && e.coverage == Some(0.0))
+        .collect()
+}
+
+/// List untested functions grouped by module. Modules with the most untested
+/// functions come first; within a module, the most complex function leads.
+pub fn format_untested(entries: &[CrapEntry], min_cc: u32) -> String {
+    let found = untested(entries, min_cc);
+    let title = format!("Untested functions (0% coverage, CC >= {min_cc})");
+    let mut lines = vec![title.clone(), "=".repeat(title.chars().count())];
+
+    let mut by_module: BTreeMap<&str, Vec<&CrapEntry>> = BTreeMap::new();
+    for e in &found {
+        by_module.entry(e.module_path.as_str()).or_default().push(e);
+    }
+    let mut modules: Vec<(&str, Vec<&CrapEntry>)> = by_module.into_iter().collect();
+    modules.sort_by_key(|(_, fns)| std::cmp::Reverse(fns.len()));
+
+    for (module, mut fns) in modules {
+        fns.sort_by(|a, b| b.complexity.cmp(&a.complexity).then(a.name.cmp(&b.name)));
+        let module = if module.is_empty() { "(root)" } else { module };
+        lines.push(String::new());
+        lines.push(format!("{module} ({})", fns.len()));
+        for f in fns {
+            lines.push(format!("  {:<40} CC {:>3}", f.name, f.complexity));
+        }
+    }
+
+    lines.push(String::new());
+    let module_count = {
+        let mut m: Vec<&str> = found.iter().map(|e| e.module_path.as_str()).collect();
+        m.sort_unstable();
+        m.dedup();
+        m.len()
+    };
+    lines.push(format!(
+        "{} untested function(s) in {module_count} module(s)",
+        found.len()
+    ));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, module: &str, cc: u32, coverage: Option<f64>) -> CrapEntry {
+        CrapEntry {
+            name: name.into(),
+            module_path: module.into(),
+            complexity: cc,
+            coverage,
+            crap: coverage.map(|_| 1.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_zero_coverage_above_floor() {
+        let entries = vec![
+            entry("untested", "a", 4, Some(0.0)),
+            entry("trivial", "a", 1, Some(0.0)),
+            entry("partly", "a", 4, Some(10.0)),
+            entry("unknown", "a", 4, None),
+        ];
+        let names: Vec<&str> = untested(&entries, 2).iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["untested"]);
+    }
+
+    #[test]
+    fn format_groups_by_module_biggest_first() {
+        let entries = vec![
+            entry("solo", "small", 3, Some(0.0)),
+            entry("simple", "big", 2, Some(0.0)),
+            entry("gnarly", "big", 9, Some(0.0)),
+        ];
+        let out = format_untested(&entries, 2);
+        let big = out.find("big (2)").unwrap();
+        let small = out.find("small (1)").unwrap();
+        assert!(big < small);
+        assert!(out.find("gnarly").unwrap() < out.find("simple").unwrap());
+        assert!(out.contains("3 untested function(s) in 2 module(s)"));
+    }
+
+    #[test]
+    fn format_empty() {
+        let out = format_untested(&[], 2);
+        assert!(out.contains("0 untested function(s) in 0 module(s)"));
+    }
+}
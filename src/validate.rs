@@ -0,0 +1,133 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// JSON Schema for `--format json` reports and the baselines built from them.
+pub const REPORT_SCHEMA: &str = include_str!("../schemas/report.schema.json");
+/// JSON Schema for policy files, applied to the TOML after conversion to JSON.
+pub const POLICY_SCHEMA: &str = include_str!("../schemas/policy.schema.json");
+
+/// Which built-in schema a file is checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FileKind {
+    /// A `--format json` report or baseline
+    Report,
+    /// A `--policy` file or `craprs.toml`
+    Policy,
+}
+
+impl FileKind {
+    /// `.toml` files are policies; everything else is taken for a report.
+    pub fn detect(path: &Path) -> FileKind {
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            FileKind::Policy
+        } else {
+            FileKind::Report
+        }
+    }
+
+    fn schema(self) -> &'static str {
+        match self {
+            FileKind::Report => REPORT_SCHEMA,
+            FileKind::Policy => POLICY_SCHEMA,
+        }
+    }
+}
+
+/// Every schema violation in `content`, as `<JSON pointer>: <message>`.
+/// Syntax errors come back as a single entry with their line and column.
+pub fn validate(kind: FileKind, content: &str) -> Result<Vec<String>> {
+    let instance: Value = match kind {
+        FileKind::Report => match serde_json::from_str(content) {
+            Ok(value) => value,
+            Err(err) => return Ok(vec![format!("invalid JSON: {err}")]),
+        },
+        FileKind::Policy => match toml::from_str::<toml::Value>(content) {
+            Ok(value) => serde_json::to_value(value)?,
+            Err(err) => {
+                return Ok(vec![format!(
+                    "invalid TOML: {}",
+                    err.to_string().trim_end()
+                )]);
+            }
+        },
+    };
+    let schema: Value =
+        serde_json::from_str(kind.schema()).context("built-in schema is invalid")?;
+    let validator = jsonschema::validator_for(&schema)
+        .map_err(|err| anyhow::anyhow!("built-in schema is invalid: {err}"))?;
+    Ok(validator
+        .iter_errors(&instance)
+        .map(|err| {
+            let path = err.instance_path.to_string();
+            let path = if path.is_empty() {
+                "/".to_string()
+            } else {
+                path
+            };
+            format!("{path}: {err}")
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crap::CrapEntry;
+    use crate::json_export::to_json;
+    use crate::metadata::RunMetadata;
+    use std::path::PathBuf;
+
+    #[test]
+    fn exported_reports_pass_their_schema() {
+        let entry = CrapEntry {
+            name: "parse".into(),
+            module_path: "core".into(),
+            complexity: 2,
+            file: PathBuf::from("src/core.rs"),
+            start_line: 3,
+            end_line: 9,
+            file_hash: crate::baseline::content_hash("fn parse() {}"),
+            ..Default::default()
+        };
+        let json = to_json(&[entry], &RunMetadata::new()).unwrap();
+        assert_eq!(
+            validate(FileKind::Report, &json).unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_value() {
+        let errors = validate(
+            FileKind::Report,
+            r#"[{"name": "a", "module": "m", "complexity": 1, "coverage": 150, "crap": "high"}]"#,
+        )
+        .unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.starts_with("/0/coverage: ")));
+        assert!(errors.iter().any(|e| e.starts_with("/0/crap: ")));
+
+        let errors = validate(FileKind::Report, "[{\"name\": ").unwrap();
+        assert!(errors[0].starts_with("invalid JSON: "));
+        assert!(errors[0].contains("line 1"));
+    }
+
+    #[test]
+    fn policies_are_checked_as_toml() {
+        assert!(
+            validate(FileKind::Policy, "threshold = 20\nexclude = [\"gen\"]")
+                .unwrap()
+                .is_empty()
+        );
+        let errors = validate(FileKind::Policy, "treshold = 20").unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("/: "));
+        assert_eq!(FileKind::detect(Path::new("craprs.toml")), FileKind::Policy);
+        assert_eq!(
+            FileKind::detect(Path::new("baseline.json")),
+            FileKind::Report
+        );
+    }
+}
@@ -2,12 +2,53 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, bail};
 
+use crate::coverage::source_to_module_path;
+
 /// One analysis target — a single crate (or the whole project for non-workspaces).
 pub struct CrateTarget {
     /// Rust crate name (hyphens → underscores). `None` for single-crate projects.
     pub crate_name: Option<String>,
     /// Path to the crate's source directory, e.g. `pubky-common/src`.
     pub src_dir: PathBuf,
+    /// Binary targets other than the default `src/main.rs`.
+    pub bins: Vec<BinTarget>,
+    /// Package name as written in `Cargo.toml`, for `cargo -p`.
+    pub package: Option<String>,
+    /// True for `[lib] proc-macro = true` crates.
+    pub proc_macro: bool,
+    /// Crate names (hyphens → underscores) of the package's `[dependencies]`.
+    pub dependencies: Vec<String>,
+}
+
+/// A binary target whose sources get the target name as module prefix, so
+/// `src/bin/cli/main.rs` and `src/bin/server/main.rs` don't both become `main`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinTarget {
+    /// Target name (hyphens → underscores).
+    pub name: String,
+    /// The binary's root file.
+    pub main: PathBuf,
+    /// Directory holding the binary's own modules, for `<dir>/main.rs` layouts.
+    pub module_dir: Option<PathBuf>,
+}
+
+impl CrateTarget {
+    /// Module path of `path` within this target: prefixed with the binary name
+    /// for files belonging to a [`BinTarget`], plain otherwise.
+    pub fn module_path(&self, path: &Path) -> String {
+        for bin in &self.bins {
+            if path == bin.main {
+                return format!("{}::main", bin.name);
+            }
+            if let Some(ref dir) = bin.module_dir
+                && path.starts_with(dir)
+            {
+                let inner = source_to_module_path(path, dir);
+                return format!("{}::{inner}", bin.name);
+            }
+        }
+        source_to_module_path(path, &self.src_dir)
+    }
 }
 
 /// Result of workspace resolution: the analysis targets plus whether the root is a workspace.
@@ -42,6 +83,10 @@ pub fn resolve_targets(
                 targets: vec![CrateTarget {
                     crate_name: None,
                     src_dir: root.join(src_rel),
+                    bins: bin_targets(root, &doc),
+                    package: package_name(&doc),
+                    proc_macro: is_proc_macro(&doc),
+                    dependencies: dependency_names(&doc),
                 }],
                 is_workspace: false,
             });
@@ -81,7 +126,9 @@ pub fn resolve_targets(
 
     let mut targets = Vec::new();
     for dir in &member_dirs {
-        let pkg_name = read_package_name(dir)?;
+        let manifest = read_manifest(dir)?;
+        let pkg_name = package_name(&manifest)
+            .with_context(|| format!("no package.name in {}", dir.join("Cargo.toml").display()))?;
         let rust_name = pkg_name.replace('-', "_");
 
         // Apply --package filter (match against either form).
@@ -94,6 +141,10 @@ pub fn resolve_targets(
         targets.push(CrateTarget {
             crate_name: Some(rust_name),
             src_dir: dir.join(src_rel),
+            bins: bin_targets(dir, &manifest),
+            package: Some(pkg_name),
+            proc_macro: is_proc_macro(&manifest),
+            dependencies: dependency_names(&manifest),
         });
     }
 
@@ -110,6 +161,46 @@ pub fn resolve_targets(
     })
 }
 
+/// Files that mark the root of a Bazel workspace.
+const BAZEL_WORKSPACE_FILES: &[&str] = &["MODULE.bazel", "WORKSPACE.bazel", "WORKSPACE"];
+
+/// The nearest directory at or above `dir` that is a Bazel workspace root.
+pub fn bazel_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|d| BAZEL_WORKSPACE_FILES.iter().any(|f| d.join(f).is_file()))
+        .map(Path::to_path_buf)
+}
+
+/// Every independent Cargo project under `root`: directories with a
+/// `Cargo.toml`, not descending into one once found (its members belong to
+/// it). `target/` and hidden directories are skipped. Sorted.
+pub fn discover_workspaces(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    collect_workspaces(root, &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+fn collect_workspaces(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    if dir.join("Cargo.toml").is_file() {
+        found.push(dir.to_path_buf());
+        return Ok(());
+    }
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let skip = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n == "target" || n.starts_with('.'));
+        if path.is_dir() && !skip {
+            collect_workspaces(&path, found)?;
+        }
+    }
+    Ok(())
+}
+
 /// Expand glob patterns from `members`, then subtract `excludes`.
 fn expand_members(root: &Path, members: &[String], excludes: &[String]) -> Result<Vec<PathBuf>> {
     let mut dirs = Vec::new();
@@ -135,19 +226,113 @@ fn expand_members(root: &Path, members: &[String], excludes: &[String]) -> Resul
     Ok(dirs)
 }
 
-/// Read `package.name` from a crate's `Cargo.toml`.
-fn read_package_name(crate_dir: &Path) -> Result<String> {
+/// Parse a crate's `Cargo.toml`.
+fn read_manifest(crate_dir: &Path) -> Result<toml::Value> {
     let path = crate_dir.join("Cargo.toml");
     let content = std::fs::read_to_string(&path)
         .with_context(|| format!("failed to read {}", path.display()))?;
-    let doc: toml::Value = content
+    content
         .parse()
-        .with_context(|| format!("failed to parse {}", path.display()))?;
-    doc.get("package")
+        .with_context(|| format!("failed to parse {}", path.display()))
+}
+
+fn package_name(manifest: &toml::Value) -> Option<String> {
+    manifest
+        .get("package")
         .and_then(|p| p.get("name"))
         .and_then(|n| n.as_str())
         .map(String::from)
-        .with_context(|| format!("no package.name in {}", path.display()))
+}
+
+fn is_proc_macro(manifest: &toml::Value) -> bool {
+    manifest
+        .get("lib")
+        .and_then(|lib| lib.get("proc-macro").or_else(|| lib.get("proc_macro")))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Names of regular `[dependencies]`, resolving `package = "..."` renames to
+/// the real package so they can be matched against workspace members.
+fn dependency_names(manifest: &toml::Value) -> Vec<String> {
+    let Some(deps) = manifest.get("dependencies").and_then(|d| d.as_table()) else {
+        return Vec::new();
+    };
+    deps.iter()
+        .map(|(key, spec)| {
+            let name = spec
+                .get("package")
+                .and_then(|p| p.as_str())
+                .unwrap_or(key);
+            name.replace('-', "_")
+        })
+        .collect()
+}
+
+/// Binary targets of a crate: auto-discovered `src/bin/<name>.rs` and
+/// `src/bin/<name>/main.rs`, plus explicit `[[bin]]` entries (which win on a
+/// name clash). The default `src/main.rs` binary is left out so single-binary
+/// crates keep their plain `main` module.
+fn bin_targets(crate_dir: &Path, manifest: &toml::Value) -> Vec<BinTarget> {
+    let mut bins: Vec<BinTarget> = Vec::new();
+    let mut add = |name: &str, main: PathBuf| {
+        let name = name.replace('-', "_");
+        let is_main_rs = main.file_name().is_some_and(|f| f == "main.rs");
+        let module_dir = main
+            .parent()
+            .filter(|dir| is_main_rs && *dir != crate_dir.join("src"))
+            .map(Path::to_path_buf);
+        bins.retain(|b| b.name != name);
+        bins.push(BinTarget {
+            name,
+            main,
+            module_dir,
+        });
+    };
+
+    let bin_dir = crate_dir.join("src").join("bin");
+    if let Ok(read) = std::fs::read_dir(&bin_dir) {
+        let mut paths: Vec<PathBuf> = read.filter_map(|e| e.ok().map(|e| e.path())).collect();
+        paths.sort();
+        for path in paths {
+            if path.is_dir() && path.join("main.rs").is_file() {
+                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                    add(name, path.join("main.rs"));
+                }
+            } else if path.extension().is_some_and(|ext| ext == "rs")
+                && let Some(stem) = path.file_stem().and_then(|n| n.to_str())
+            {
+                add(stem, path.clone());
+            }
+        }
+    }
+
+    let explicit = manifest
+        .get("bin")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.as_slice())
+        .unwrap_or_default();
+    for bin in explicit {
+        let Some(name) = bin.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let main = match bin.get("path").and_then(|p| p.as_str()) {
+            Some(path) => crate_dir.join(path),
+            None => {
+                let file = bin_dir.join(format!("{name}.rs"));
+                if file.is_file() {
+                    file
+                } else {
+                    bin_dir.join(name).join("main.rs")
+                }
+            }
+        };
+        if main == crate_dir.join("src").join("main.rs") {
+            continue;
+        }
+        add(name, main);
+    }
+    bins
 }
 
 #[cfg(test)]
@@ -344,4 +529,100 @@ mod tests {
         assert!(resolved.is_workspace);
         assert!(resolved.targets.is_empty());
     }
+
+    #[test]
+    fn bin_targets_get_their_name_as_prefix() {
+        let tmp = tempdir();
+        write_single_crate(&tmp, "tool");
+        let src = tmp.join("src");
+        fs::write(src.join("main.rs"), "").unwrap();
+        fs::create_dir_all(src.join("bin/cli")).unwrap();
+        fs::create_dir_all(src.join("bin/server")).unwrap();
+        fs::write(src.join("bin/cli/main.rs"), "").unwrap();
+        fs::write(src.join("bin/cli/run.rs"), "").unwrap();
+        fs::write(src.join("bin/server/main.rs"), "").unwrap();
+        fs::write(src.join("bin/server/run.rs"), "").unwrap();
+        fs::write(src.join("bin/migrate-db.rs"), "").unwrap();
+
+        let resolved = resolve_targets(&tmp, Path::new("src"), &[]).unwrap();
+        let target = &resolved.targets[0];
+        let names: Vec<&str> = target.bins.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["cli", "migrate_db", "server"]);
+
+        assert_eq!(target.module_path(&src.join("bin/cli/main.rs")), "cli::main");
+        assert_eq!(target.module_path(&src.join("bin/cli/run.rs")), "cli::run");
+        assert_eq!(target.module_path(&src.join("bin/server/run.rs")), "server::run");
+        assert_eq!(target.module_path(&src.join("bin/migrate-db.rs")), "migrate_db::main");
+        // The default binary and library keep their plain paths.
+        assert_eq!(target.module_path(&src.join("main.rs")), "main");
+        assert_eq!(target.module_path(&src.join("lib.rs")), "lib");
+    }
+
+    #[test]
+    fn explicit_bin_path() {
+        let tmp = tempdir();
+        write_single_crate(&tmp, "tool");
+        let mut manifest = fs::read_to_string(tmp.join("Cargo.toml")).unwrap();
+        manifest.push_str("\n[[bin]]\nname = \"daemon\"\npath = \"src/daemon/main.rs\"\n");
+        fs::write(tmp.join("Cargo.toml"), manifest).unwrap();
+        fs::create_dir_all(tmp.join("src/daemon")).unwrap();
+
+        let resolved = resolve_targets(&tmp, Path::new("src"), &[]).unwrap();
+        let target = &resolved.targets[0];
+        assert_eq!(
+            target.module_path(&tmp.join("src/daemon/worker.rs")),
+            "daemon::worker"
+        );
+    }
+
+    #[test]
+    fn discover_workspaces_stops_at_first_manifest() {
+        let tmp = tempdir();
+        write_single_crate(&tmp.join("services/api"), "api");
+        write_single_crate(&tmp.join("services/api/nested"), "nested");
+        write_single_crate(&tmp.join("tools/cli"), "cli");
+        write_single_crate(&tmp.join("tools/cli/target/package/cli-0.1.0"), "cli");
+        write_single_crate(&tmp.join(".cache/junk"), "junk");
+
+        let found = discover_workspaces(&tmp).unwrap();
+        assert_eq!(found, vec![tmp.join("services/api"), tmp.join("tools/cli")]);
+        let solo = tmp.join("tools/cli");
+        assert_eq!(discover_workspaces(&solo).unwrap(), vec![solo.clone()]);
+    }
+
+    #[test]
+    fn proc_macro_members_and_dependencies() {
+        let tmp = tempdir();
+        fs::write(
+            tmp.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\", \"app-derive\"]\n",
+        )
+        .unwrap();
+        write_single_crate(&tmp.join("app"), "app");
+        write_single_crate(&tmp.join("app-derive"), "app-derive");
+        let mut manifest = fs::read_to_string(tmp.join("app-derive/Cargo.toml")).unwrap();
+        manifest.push_str("\n[lib]\nproc-macro = true\n");
+        fs::write(tmp.join("app-derive/Cargo.toml"), manifest).unwrap();
+        let mut manifest = fs::read_to_string(tmp.join("app/Cargo.toml")).unwrap();
+        manifest.push_str(
+            "\n[dependencies]\nderive = { package = \"app-derive\", path = \"../app-derive\" }\nserde = \"1\"\n",
+        );
+        fs::write(tmp.join("app/Cargo.toml"), manifest).unwrap();
+
+        let resolved = resolve_targets(&tmp, Path::new("src"), &[]).unwrap();
+        let app = &resolved.targets[0];
+        assert_eq!(app.package.as_deref(), Some("app"));
+        assert!(!app.proc_macro);
+        assert_eq!(app.dependencies, vec!["app_derive", "serde"]);
+        assert!(resolved.targets[1].proc_macro);
+    }
+
+    #[test]
+    fn bazel_root_is_the_nearest_workspace_marker() {
+        let tmp = tempdir();
+        write_single_crate(&tmp.join("crates/a"), "a");
+        assert_eq!(bazel_root(&tmp.join("crates/a")), None);
+        fs::write(tmp.join("MODULE.bazel"), "").unwrap();
+        assert_eq!(bazel_root(&tmp.join("crates/a")), Some(tmp.clone()));
+    }
 }
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use craprs::complexity;
 use craprs::coverage;
@@ -73,6 +73,18 @@ end_of_record
                 complexity: f.complexity,
                 coverage: Some(cov),
                 crap: score,
+                uncovered_lines: Vec::new(),
+                hints: Vec::new(),
+                decision_points: Vec::new(),
+                file: PathBuf::from("src/example.rs"),
+                start_line: f.start_line,
+                end_line: f.end_line,
+                file_hash: craprs::baseline::content_hash(source),
+                fingerprint: f.fingerprint.clone(),
+                dyn_calls: f.dyn_calls,
+                branch_coverage: None,
+                tags: f.tags.clone(),
+                public: f.public,
             }
         })
         .collect();